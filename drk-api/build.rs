@@ -0,0 +1,19 @@
+//! Records which rustc builds this copy of drk-api. Plugins report it
+//! through `declare_plugin!` so hosts can refuse same-compiler entry points
+//! built by another compiler.
+
+use std::process::Command;
+
+fn main() {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|version| version.trim().to_string())
+        .filter(|version| !version.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=DRK_RUSTC_VERSION={}", version);
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,717 @@
+//! A plugin interface that doesn't require host and plugin to be built with
+//! the same compiler.
+//!
+//! `*mut dyn Plugin` is only meaningful when both sides agree on Rust's
+//! (unstable) layout of trait objects, `String`, `HashMap` and friends. Here
+//! everything that crosses the boundary is `#[repr(C)]`: a table of
+//! `extern "C"` functions, byte buffers, and plain integers. Structured values
+//! travel as text (JSON for events and schemas, TOML for config and state) and
+//! each buffer is freed by the side that allocated it.
+//!
+//! `declare_plugin!` exports this table as `_plugin_vtable` next to the older
+//! entry points, so rebuilding a plugin against the current drk-api is the
+//! whole migration. Hosts prefer the table and fall back to
+//! `_plugin_create_v2`/`_plugin_create` for plugins built before it existed.
+//!
+//! One thing doesn't survive the trip: `Custom` event payloads are opaque
+//! `Any` values, so only `String`, `&'static str`, `Vec<u8>` and `Vec<String>`
+//! payloads are carried across; others arrive as `None`.
+
+use crate::{CancellationToken, CommandMatches, Context, ControlFlow, Plugin, SystemEvent};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Layout version of [`PluginVTable`] and the types it passes.
+///
+/// New entries are only appended to the table, so hosts accept any version
+/// from [`MIN_ABI_VERSION`] up to their own and skip the entries an older
+/// table doesn't have.
+pub const ABI_VERSION: u32 = 9;
+
+/// The oldest table layout hosts still load.
+pub const MIN_ABI_VERSION: u32 = 1;
+
+// --- Buffers ---
+
+/// Borrowed UTF-8 text; a null pointer stands for "absent".
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RStr {
+    ptr: *const u8,
+    len: usize,
+}
+
+impl RStr {
+    pub fn new(text: &str) -> Self {
+        Self {
+            ptr: text.as_ptr(),
+            len: text.len(),
+        }
+    }
+
+    pub fn none() -> Self {
+        Self {
+            ptr: std::ptr::null(),
+            len: 0,
+        }
+    }
+
+    /// # Safety
+    /// The text this was made from must still be alive for `'a`.
+    pub unsafe fn get<'a>(self) -> Option<&'a str> {
+        if self.ptr.is_null() {
+            return None;
+        }
+        std::str::from_utf8(std::slice::from_raw_parts(self.ptr, self.len)).ok()
+    }
+}
+
+/// Owned text handed to the other side, which reads it with
+/// [`RBuf::into_string`]. The allocating side's `free` releases it, so the two
+/// allocators never mix. A null pointer stands for "absent".
+#[repr(C)]
+pub struct RBuf {
+    ptr: *mut u8,
+    len: usize,
+    cap: usize,
+    free: Option<unsafe extern "C" fn(*mut u8, usize, usize)>,
+}
+
+impl RBuf {
+    pub fn new(text: String) -> Self {
+        let mut bytes = std::mem::ManuallyDrop::new(text.into_bytes());
+        Self {
+            ptr: bytes.as_mut_ptr(),
+            len: bytes.len(),
+            cap: bytes.capacity(),
+            free: Some(free_bytes),
+        }
+    }
+
+    pub fn none() -> Self {
+        Self {
+            ptr: std::ptr::null_mut(),
+            len: 0,
+            cap: 0,
+            free: None,
+        }
+    }
+
+    pub fn into_string(self) -> Option<String> {
+        if self.ptr.is_null() {
+            return None;
+        }
+        // SAFETY: `ptr`/`len` describe the bytes `new` leaked, freed on drop
+        let bytes = unsafe { std::slice::from_raw_parts(self.ptr, self.len) };
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+impl Drop for RBuf {
+    fn drop(&mut self) {
+        if let Some(free) = self.free {
+            // SAFETY: the triple came from the `Vec` leaked in `new`, on the side `free` belongs to
+            unsafe { free(self.ptr, self.len, self.cap) };
+        }
+    }
+}
+
+unsafe extern "C" fn free_bytes(ptr: *mut u8, len: usize, cap: usize) {
+    drop(Vec::from_raw_parts(ptr, len, cap));
+}
+
+fn error_buf(result: Result<()>) -> RBuf {
+    match result {
+        Ok(()) => RBuf::none(),
+        Err(e) => RBuf::new(format!("{:#}", e)),
+    }
+}
+
+// --- Events ---
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum WireEvent {
+    ConfigLoaded,
+    PluginsDiscovered {
+        libraries: Vec<String>,
+    },
+    PluginsLoaded {
+        plugins: Vec<String>,
+    },
+    Startup,
+    BuildingCli,
+    CliBuilt {
+        commands: Vec<String>,
+    },
+    PreCommand {
+        name: String,
+        args: Vec<String>,
+    },
+    PostCommand {
+        name: String,
+        success: bool,
+    },
+    ExecuteCommand {
+        plugin_name: String,
+        command_name: String,
+        /// Absent from hosts before nested commands
+        #[serde(default)]
+        path: Vec<String>,
+        args: HashMap<String, String>,
+    },
+    Custom {
+        source: String,
+        event: String,
+        payload: Option<WirePayload>,
+        /// Missing from plugins built before it existed
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        data: Option<serde_json::Value>,
+    },
+    Shutdown,
+    /// Sent by a newer host; plugins skip events they don't know
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize)]
+enum WirePayload {
+    Text(String),
+    Bytes(Vec<u8>),
+    List(Vec<String>),
+}
+
+impl WirePayload {
+    fn encode(payload: &(dyn Any + Send + Sync)) -> Option<Self> {
+        if let Some(s) = payload.downcast_ref::<String>() {
+            Some(WirePayload::Text(s.clone()))
+        } else if let Some(s) = payload.downcast_ref::<&'static str>() {
+            Some(WirePayload::Text(s.to_string()))
+        } else if let Some(bytes) = payload.downcast_ref::<Vec<u8>>() {
+            Some(WirePayload::Bytes(bytes.clone()))
+        } else {
+            payload
+                .downcast_ref::<Vec<String>>()
+                .map(|items| WirePayload::List(items.clone()))
+        }
+    }
+
+    fn decode(self) -> Arc<dyn Any + Send + Sync> {
+        match self {
+            WirePayload::Text(s) => Arc::new(s),
+            WirePayload::Bytes(bytes) => Arc::new(bytes),
+            WirePayload::List(items) => Arc::new(items),
+        }
+    }
+}
+
+/// Serializes `event` for the other side of the boundary.
+pub fn encode_event(event: &SystemEvent) -> String {
+    let wire = match event {
+        SystemEvent::ConfigLoaded => WireEvent::ConfigLoaded,
+        SystemEvent::PluginsDiscovered { libraries } => WireEvent::PluginsDiscovered {
+            libraries: libraries.clone(),
+        },
+        SystemEvent::PluginsLoaded { plugins } => WireEvent::PluginsLoaded {
+            plugins: plugins.clone(),
+        },
+        SystemEvent::Startup => WireEvent::Startup,
+        SystemEvent::BuildingCli => WireEvent::BuildingCli,
+        SystemEvent::CliBuilt { commands } => WireEvent::CliBuilt {
+            commands: commands.clone(),
+        },
+        SystemEvent::PreCommand { name, args } => WireEvent::PreCommand {
+            name: name.clone(),
+            args: args.clone(),
+        },
+        SystemEvent::PostCommand { name, success } => WireEvent::PostCommand {
+            name: name.clone(),
+            success: *success,
+        },
+        SystemEvent::ExecuteCommand {
+            plugin_name,
+            matches,
+        } => WireEvent::ExecuteCommand {
+            plugin_name: plugin_name.clone(),
+            command_name: matches.command_name.clone(),
+            path: matches.path.clone(),
+            args: matches.args.clone(),
+        },
+        SystemEvent::Custom {
+            source,
+            event,
+            payload,
+            data,
+        } => WireEvent::Custom {
+            source: source.clone(),
+            event: event.clone(),
+            payload: payload.as_deref().and_then(WirePayload::encode),
+            data: data.clone(),
+        },
+        SystemEvent::Shutdown => WireEvent::Shutdown,
+    };
+    serde_json::to_string(&wire).expect("events always serialize")
+}
+
+/// Reads an event from the other side; `None` for kinds this side doesn't know.
+pub fn decode_event(json: &str) -> Result<Option<SystemEvent>> {
+    Ok(Some(match serde_json::from_str(json)? {
+        WireEvent::ConfigLoaded => SystemEvent::ConfigLoaded,
+        WireEvent::PluginsDiscovered { libraries } => SystemEvent::PluginsDiscovered { libraries },
+        WireEvent::PluginsLoaded { plugins } => SystemEvent::PluginsLoaded { plugins },
+        WireEvent::Startup => SystemEvent::Startup,
+        WireEvent::BuildingCli => SystemEvent::BuildingCli,
+        WireEvent::CliBuilt { commands } => SystemEvent::CliBuilt { commands },
+        WireEvent::PreCommand { name, args } => SystemEvent::PreCommand { name, args },
+        WireEvent::PostCommand { name, success } => SystemEvent::PostCommand { name, success },
+        WireEvent::ExecuteCommand {
+            plugin_name,
+            command_name,
+            path,
+            args,
+        } => SystemEvent::ExecuteCommand {
+            plugin_name,
+            matches: CommandMatches {
+                command_name,
+                path,
+                args,
+            },
+        },
+        WireEvent::Custom {
+            source,
+            event,
+            payload,
+            data,
+        } => SystemEvent::Custom {
+            source,
+            event,
+            payload: payload.map(WirePayload::decode),
+            data,
+        },
+        WireEvent::Shutdown => SystemEvent::Shutdown,
+        WireEvent::Unknown => return Ok(None),
+    }))
+}
+
+// --- Host side of a call ---
+
+/// Cleanup a plugin registered with `ctx.on_abnormal_exit`. The host either
+/// runs it or drops it; both go back into the plugin's code.
+#[repr(C)]
+pub struct ExitCallback {
+    data: *mut c_void,
+    run: unsafe extern "C" fn(*mut c_void),
+    drop: unsafe extern "C" fn(*mut c_void),
+}
+
+// SAFETY: `data` is a boxed `FnOnce() + Send`
+unsafe impl Send for ExitCallback {}
+
+type BoxedCleanup = Box<dyn FnOnce() + Send>;
+
+impl ExitCallback {
+    fn new(cleanup: BoxedCleanup) -> Self {
+        unsafe extern "C" fn run(data: *mut c_void) {
+            let cleanup = Box::from_raw(data as *mut BoxedCleanup);
+            let _ = catch_unwind(AssertUnwindSafe(cleanup));
+        }
+        unsafe extern "C" fn drop(data: *mut c_void) {
+            std::mem::drop(Box::from_raw(data as *mut BoxedCleanup));
+        }
+        Self {
+            data: Box::into_raw(Box::new(cleanup)) as *mut c_void,
+            run,
+            drop,
+        }
+    }
+
+    pub fn run(self) {
+        let this = std::mem::ManuallyDrop::new(self);
+        // SAFETY: `data` is consumed exactly once, here or in `drop`
+        unsafe { (this.run)(this.data) }
+    }
+}
+
+impl Drop for ExitCallback {
+    fn drop(&mut self) {
+        // SAFETY: as in `run`
+        unsafe { (self.drop)(self.data) }
+    }
+}
+
+/// Everything a `handle_event` call gets from the host: the context's data
+/// as text, and callbacks for the parts of the context that act.
+#[repr(C)]
+pub struct HostCall {
+    /// Passed back to `emit` and `on_exit`
+    pub host: *mut c_void,
+    /// All config sections, as a TOML document
+    pub config: RStr,
+    /// The plugin's state table, as TOML
+    pub state: RStr,
+    /// Absent while the data directory is over its quota
+    pub data_dir: RStr,
+    pub emit: unsafe extern "C" fn(host: *mut c_void, event: RStr),
+    /// Separate from `host` because it's polled from another thread
+    pub cancel: *const c_void,
+    pub cancelled: unsafe extern "C" fn(cancel: *const c_void) -> bool,
+    pub on_exit: unsafe extern "C" fn(host: *mut c_void, cleanup: ExitCallback),
+    /// Since v6. Takes a [`crate::Capability::key`]; true if it's granted
+    pub request_permission: unsafe extern "C" fn(host: *mut c_void, capability: RStr) -> bool,
+}
+
+/// What `handle_event` hands back. Absent buffers mean success / unchanged.
+#[repr(C)]
+pub struct EventOutcome {
+    pub error: RBuf,
+    /// The whole config as TOML, if the plugin changed it
+    pub config: RBuf,
+    /// The plugin's state as TOML, if it changed
+    pub state: RBuf,
+}
+
+/// `migrate_config`'s result: the migrated section as TOML, or an error.
+#[repr(C)]
+pub struct MigrateOutcome {
+    pub section: RBuf,
+    pub error: RBuf,
+}
+
+// --- The table ---
+
+/// The functions a plugin exports, all taking the instance `create` returned.
+#[repr(C)]
+pub struct PluginVTable {
+    /// First so any host can check it before reading further
+    pub abi_version: u32,
+    pub api_version: u32,
+    pub create: extern "C" fn() -> *mut c_void,
+    pub destroy: unsafe extern "C" fn(*mut c_void),
+    /// `PluginMetadata` as JSON
+    pub metadata: unsafe extern "C" fn(*const c_void) -> RBuf,
+    /// `Vec<PluginCommand>` as JSON
+    pub commands: unsafe extern "C" fn(*const c_void) -> RBuf,
+    pub config_version: unsafe extern "C" fn(*const c_void) -> u32,
+    pub migrate_config: unsafe extern "C" fn(*const c_void, u32, RStr) -> MigrateOutcome,
+    /// These return an error message, or an absent buffer on success
+    pub on_load: unsafe extern "C" fn(*mut c_void) -> RBuf,
+    pub on_unload: unsafe extern "C" fn(*mut c_void) -> RBuf,
+    pub on_cancel: unsafe extern "C" fn(*mut c_void) -> RBuf,
+    pub handle_event: unsafe extern "C" fn(*mut c_void, RStr, *const HostCall) -> EventOutcome,
+    /// Since v2. Writes `Vec<PluginCommand>` as JSON to the out buffer
+    pub contribute_commands:
+        unsafe extern "C" fn(*mut c_void, *const HostCall, *mut RBuf) -> EventOutcome,
+    /// Since v3
+    pub on_install: unsafe extern "C" fn(*mut c_void, *const HostCall) -> EventOutcome,
+    /// Since v4. Takes the argv as a JSON array and sets the out flag if the
+    /// plugin handled it
+    pub handle_unknown_command:
+        unsafe extern "C" fn(*mut c_void, RStr, *const HostCall, *mut bool) -> EventOutcome,
+    /// Since v5. `Vec<ConfigKey>` as JSON
+    pub config_schema: unsafe extern "C" fn(*const c_void) -> RBuf,
+    /// Since v7. `Vec<Tip>` as JSON
+    pub tips: unsafe extern "C" fn(*const c_void) -> RBuf,
+    /// Since v8. Takes the command's name and args as a JSON `[name, args]`
+    /// and writes the reason to the out buffer if the plugin cancels it
+    pub on_pre_command:
+        unsafe extern "C" fn(*mut c_void, RStr, *const HostCall, *mut RBuf) -> EventOutcome,
+    /// Since v9. `Vec<EventKind>` as JSON
+    pub subscriptions: unsafe extern "C" fn(*const c_void) -> RBuf,
+}
+
+impl PluginVTable {
+    /// The table for plugins made by `create`, which should return
+    /// [`into_instance`]'s result. Used by `declare_plugin!`.
+    pub const fn new(create: extern "C" fn() -> *mut c_void) -> Self {
+        Self {
+            abi_version: ABI_VERSION,
+            api_version: crate::API_VERSION,
+            create,
+            destroy,
+            metadata,
+            commands,
+            config_version,
+            migrate_config,
+            on_load,
+            on_unload,
+            on_cancel,
+            handle_event,
+            contribute_commands,
+            on_install,
+            handle_unknown_command,
+            config_schema,
+            tips,
+            on_pre_command,
+            subscriptions,
+        }
+    }
+}
+
+/// Turns a plugin into the opaque instance pointer the table works with.
+pub fn into_instance(plugin: Box<dyn Plugin>) -> *mut c_void {
+    Box::into_raw(Box::new(plugin)) as *mut c_void
+}
+
+/// Makes an instance with `create` for a table's `create` entry, or returns
+/// null if it panics, which the host reports as a failed load.
+pub fn create_instance(create: impl FnOnce() -> Box<dyn Plugin>) -> *mut c_void {
+    guarded(|| Ok(into_instance(create()))).unwrap_or(std::ptr::null_mut())
+}
+
+unsafe fn plugin<'a>(instance: *const c_void) -> &'a dyn Plugin {
+    &**(instance as *const Box<dyn Plugin>)
+}
+
+unsafe fn plugin_mut<'a>(instance: *mut c_void) -> &'a mut dyn Plugin {
+    &mut **(instance as *mut Box<dyn Plugin>)
+}
+
+/// Unwinding out of an `extern "C"` function aborts, so panics become errors.
+fn guarded<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Err(anyhow!("plugin panicked: {}", message))
+    })
+}
+
+/// What `f` returns as JSON, or an absent buffer if it panics, which the
+/// host treats like a plugin that returned nothing.
+fn json_buf<T: Serialize>(f: impl FnOnce() -> T) -> RBuf {
+    guarded(|| Ok(serde_json::to_string(&f())?)).map_or_else(|_| RBuf::none(), RBuf::new)
+}
+
+unsafe extern "C" fn destroy(instance: *mut c_void) {
+    // A panicking destructor leaks the rest of the instance instead
+    let _ = guarded(|| {
+        drop(Box::from_raw(instance as *mut Box<dyn Plugin>));
+        Ok(())
+    });
+}
+
+unsafe extern "C" fn metadata(instance: *const c_void) -> RBuf {
+    json_buf(|| plugin(instance).metadata())
+}
+
+unsafe extern "C" fn commands(instance: *const c_void) -> RBuf {
+    json_buf(|| plugin(instance).get_commands())
+}
+
+unsafe extern "C" fn config_schema(instance: *const c_void) -> RBuf {
+    json_buf(|| plugin(instance).config_schema())
+}
+
+unsafe extern "C" fn tips(instance: *const c_void) -> RBuf {
+    json_buf(|| plugin(instance).tips())
+}
+
+unsafe extern "C" fn subscriptions(instance: *const c_void) -> RBuf {
+    json_buf(|| plugin(instance).subscriptions())
+}
+
+/// Falls back to the trait's default, version 0, if the plugin panics.
+unsafe extern "C" fn config_version(instance: *const c_void) -> u32 {
+    guarded(|| Ok(plugin(instance).config_version())).unwrap_or(0)
+}
+
+unsafe extern "C" fn migrate_config(
+    instance: *const c_void,
+    from: u32,
+    section: RStr,
+) -> MigrateOutcome {
+    let result = guarded(|| {
+        let mut section: toml::Table = toml::from_str(section.get().unwrap_or_default())?;
+        plugin(instance).migrate_config(from, &mut section)?;
+        Ok(toml::to_string(&section)?)
+    });
+    match result {
+        Ok(section) => MigrateOutcome {
+            section: RBuf::new(section),
+            error: RBuf::none(),
+        },
+        Err(e) => MigrateOutcome {
+            section: RBuf::none(),
+            error: RBuf::new(format!("{:#}", e)),
+        },
+    }
+}
+
+unsafe extern "C" fn on_load(instance: *mut c_void) -> RBuf {
+    error_buf(guarded(|| plugin_mut(instance).on_load()))
+}
+
+unsafe extern "C" fn on_unload(instance: *mut c_void) -> RBuf {
+    error_buf(guarded(|| plugin_mut(instance).on_unload()))
+}
+
+unsafe extern "C" fn on_cancel(instance: *mut c_void) -> RBuf {
+    error_buf(guarded(|| plugin_mut(instance).on_cancel()))
+}
+
+unsafe extern "C" fn handle_event(
+    instance: *mut c_void,
+    event: RStr,
+    call: *const HostCall,
+) -> EventOutcome {
+    let call = &*call;
+    with_context(call, |ctx, cancel| {
+        let Some(event) = decode_event(event.get().unwrap_or_default())? else {
+            return Ok(());
+        };
+        let plugin = plugin_mut(instance);
+        if matches!(event, SystemEvent::ExecuteCommand { .. }) {
+            // Commands are what runs long enough to be interrupted, so only
+            // they get a thread mirroring the host's token into the local one
+            watch_cancellation(call, cancel, || plugin.handle_event(&event, ctx))
+        } else {
+            plugin.handle_event(&event, ctx)
+        }
+    })
+}
+
+unsafe extern "C" fn contribute_commands(
+    instance: *mut c_void,
+    call: *const HostCall,
+    commands: *mut RBuf,
+) -> EventOutcome {
+    with_context(&*call, |ctx, _| {
+        let contributed = plugin_mut(instance).contribute_commands(ctx)?;
+        *commands = RBuf::new(serde_json::to_string(&contributed)?);
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn on_install(instance: *mut c_void, call: *const HostCall) -> EventOutcome {
+    let call = &*call;
+    with_context(call, |ctx, cancel| {
+        // Setup may download assets, so it can be interrupted like a command
+        let plugin = plugin_mut(instance);
+        watch_cancellation(call, cancel, || plugin.on_install(ctx))
+    })
+}
+
+unsafe extern "C" fn handle_unknown_command(
+    instance: *mut c_void,
+    argv: RStr,
+    call: *const HostCall,
+    handled: *mut bool,
+) -> EventOutcome {
+    let call = &*call;
+    with_context(call, |ctx, cancel| {
+        let argv: Vec<String> = serde_json::from_str(argv.get().unwrap_or("[]"))?;
+        // It stands in for a command, so it can be interrupted like one
+        let plugin = plugin_mut(instance);
+        *handled = watch_cancellation(call, cancel, || plugin.handle_unknown_command(&argv, ctx))?;
+        Ok(())
+    })
+}
+
+unsafe extern "C" fn on_pre_command(
+    instance: *mut c_void,
+    command: RStr,
+    call: *const HostCall,
+    reason: *mut RBuf,
+) -> EventOutcome {
+    with_context(&*call, |ctx, _| {
+        let (name, args): (String, Vec<String>) =
+            serde_json::from_str(command.get().unwrap_or_default())?;
+        if let ControlFlow::Cancel(why) = plugin_mut(instance).on_pre_command(&name, &args, ctx)? {
+            *reason = RBuf::new(why);
+        }
+        Ok(())
+    })
+}
+
+/// Rebuilds a `Context` from what the host sent, runs `f` with it, and
+/// reports back the error and whatever config or state `f` changed.
+fn with_context(
+    call: &HostCall,
+    f: impl FnOnce(&mut Context, &CancellationToken) -> Result<()>,
+) -> EventOutcome {
+    let mut outcome = EventOutcome {
+        error: RBuf::none(),
+        config: RBuf::none(),
+        state: RBuf::none(),
+    };
+
+    let result = guarded(|| {
+        // SAFETY: the host keeps everything in `call` alive for the whole call
+        let (config, state, data_dir) =
+            unsafe { (call.config.get(), call.state.get(), call.data_dir.get()) };
+        let mut config: HashMap<String, toml::Value> = toml::from_str(config.unwrap_or_default())?;
+        let mut state: toml::Table = toml::from_str(state.unwrap_or_default())?;
+        let (config_before, state_before) = (config.clone(), state.clone());
+        let data_dir = data_dir.map(PathBuf::from);
+
+        let cancel = CancellationToken::new();
+        // SAFETY: as above; the host's `cancelled` is safe to call any time
+        if unsafe { (call.cancelled)(call.cancel) } {
+            cancel.cancel();
+        }
+        let mut ctx = Context {
+            config: &mut config,
+            // SAFETY: as above
+            event_sender: &mut |event| unsafe {
+                (call.emit)(call.host, RStr::new(&encode_event(&event)))
+            },
+            state: &mut state,
+            data_dir: data_dir.as_deref(),
+            cancel: &cancel,
+            // SAFETY: as above
+            on_exit: &mut |cleanup| unsafe {
+                (call.on_exit)(call.host, ExitCallback::new(cleanup))
+            },
+            // SAFETY: as above; hosts only load tables no newer than their
+            // own, so one loading this plugin has sent this field
+            permit: &mut |capability| unsafe {
+                (call.request_permission)(call.host, RStr::new(capability.key()))
+            },
+        };
+        let result = f(&mut ctx, &cancel);
+
+        if config != config_before {
+            let table: toml::Table = config.into_iter().collect();
+            outcome.config = RBuf::new(toml::to_string(&table)?);
+        }
+        if state != state_before {
+            outcome.state = RBuf::new(toml::to_string(&state)?);
+        }
+        result
+    });
+    outcome.error = error_buf(result);
+    outcome
+}
+
+/// Runs `f` while a helper thread cancels `cancel` once the host's token trips.
+fn watch_cancellation<T>(call: &HostCall, cancel: &CancellationToken, f: impl FnOnce() -> T) -> T {
+    // Raw pointers aren't `Send`; the host promises `cancelled` is thread-safe
+    let (host_cancel, cancelled) = (call.cancel as usize, call.cancelled);
+    let done = AtomicBool::new(false);
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                // SAFETY: the host keeps the token alive for the whole call
+                if unsafe { cancelled(host_cancel as *const c_void) } {
+                    cancel.cancel();
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        });
+        let result = f();
+        done.store(true, Ordering::Relaxed);
+        result
+    })
+}
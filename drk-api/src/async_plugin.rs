@@ -0,0 +1,221 @@
+//! Plugins whose event handlers are `async`, behind the `async` feature.
+//!
+//! Network-bound plugins can `await` HTTP calls (`http::Request::send_async`,
+//! with the `http` feature too) and run several at once with `tokio::join!`
+//! instead of blocking on each in turn.
+//!
+//! Each plugin library links its own copy of tokio, and a runtime's futures
+//! can't be polled from another library's copy, so [`Async`] keeps a runtime
+//! inside the plugin and drives the handler on it. To the host it is an
+//! ordinary [`Plugin`]. drk hands a broadcast event to all its subscribers
+//! at once, each on a thread of its own, so one plugin awaiting a slow
+//! response doesn't hold up the others.
+
+use crate::{
+    ConfigKey, Context, EventKind, Plugin, PluginCommand, PluginMetadata, SystemEvent, Tip,
+};
+use anyhow::Result;
+use std::future::Future;
+use std::pin::pin;
+use std::task::Poll;
+
+// Plugins spawn, join and sleep with the same tokio that runs them
+pub use tokio;
+
+/// Like [`Plugin`], but `handle_event` is async. Wrap it in [`Async`] to
+/// hand it to `declare_plugin!`.
+///
+/// # Example
+/// ```
+/// use drk_api::{declare_plugin, Async, AsyncPlugin, Context, PluginMetadata, SystemEvent};
+///
+/// #[derive(Default)]
+/// struct Fetcher;
+///
+/// impl AsyncPlugin for Fetcher {
+///     fn metadata(&self) -> PluginMetadata {
+///         PluginMetadata {
+///             name: "fetcher".to_string(),
+///             version: "0.1.0".to_string(),
+///             author: "You".to_string(),
+///             description: "Fetches things".to_string(),
+///             essential: false,
+///         }
+///     }
+///
+///     async fn handle_event(&mut self, event: &SystemEvent, _: &mut Context<'_>) -> anyhow::Result<()> {
+///         if let SystemEvent::Startup = event {
+///             // Both run at once; the handler finishes when the slower one does
+///             let (a, b) = drk_api::tokio::join!(async { 1 }, async { 2 });
+///             assert_eq!(a + b, 3);
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// fn constructor() -> Async<Fetcher> {
+///     Async::new(Fetcher)
+/// }
+///
+/// declare_plugin!(Async<Fetcher>, constructor);
+/// ```
+pub trait AsyncPlugin: Send + Sync + 'static {
+    fn metadata(&self) -> PluginMetadata;
+
+    fn get_commands(&self) -> Vec<PluginCommand> {
+        Vec::new()
+    }
+
+    fn contribute_commands(&mut self, _ctx: &mut Context) -> Result<Vec<PluginCommand>> {
+        Ok(Vec::new())
+    }
+
+    fn config_version(&self) -> u32 {
+        0
+    }
+
+    fn migrate_config(&self, _from: u32, _section: &mut toml::Table) -> Result<()> {
+        Ok(())
+    }
+
+    fn config_schema(&self) -> Vec<ConfigKey> {
+        Vec::new()
+    }
+
+    fn tips(&self) -> Vec<Tip> {
+        Vec::new()
+    }
+
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![EventKind::All]
+    }
+
+    fn on_load(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_install(&mut self, _ctx: &mut Context) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_unload(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_cancel(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn handle_unknown_command(&mut self, _argv: &[String], _ctx: &mut Context) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Runs until the returned future completes, or until the user presses
+    /// Ctrl-C: then the future is dropped mid-way and `on_cancel` follows.
+    fn handle_event(
+        &mut self,
+        event: &SystemEvent,
+        ctx: &mut Context<'_>,
+    ) -> impl Future<Output = Result<()>>;
+}
+
+/// Runs an [`AsyncPlugin`] as a [`Plugin`], on a single-threaded runtime of
+/// its own.
+pub struct Async<P> {
+    plugin: P,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl<P: AsyncPlugin> Async<P> {
+    pub fn new(plugin: P) -> Self {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("a single-threaded runtime always builds");
+        Self { plugin, runtime }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.plugin
+    }
+
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.plugin
+    }
+
+    /// Runs `future` to completion on this plugin's runtime, e.g. from one
+    /// of the synchronous hooks.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+}
+
+impl<P: AsyncPlugin> Plugin for Async<P> {
+    fn metadata(&self) -> PluginMetadata {
+        self.plugin.metadata()
+    }
+
+    fn get_commands(&self) -> Vec<PluginCommand> {
+        self.plugin.get_commands()
+    }
+
+    fn contribute_commands(&mut self, ctx: &mut Context) -> Result<Vec<PluginCommand>> {
+        self.plugin.contribute_commands(ctx)
+    }
+
+    fn config_version(&self) -> u32 {
+        self.plugin.config_version()
+    }
+
+    fn migrate_config(&self, from: u32, section: &mut toml::Table) -> Result<()> {
+        self.plugin.migrate_config(from, section)
+    }
+
+    fn config_schema(&self) -> Vec<ConfigKey> {
+        self.plugin.config_schema()
+    }
+
+    fn tips(&self) -> Vec<Tip> {
+        self.plugin.tips()
+    }
+
+    fn subscriptions(&self) -> Vec<EventKind> {
+        self.plugin.subscriptions()
+    }
+
+    fn on_load(&mut self) -> Result<()> {
+        self.plugin.on_load()
+    }
+
+    fn on_install(&mut self, ctx: &mut Context) -> Result<()> {
+        self.plugin.on_install(ctx)
+    }
+
+    fn on_unload(&mut self) -> Result<()> {
+        self.plugin.on_unload()
+    }
+
+    fn on_cancel(&mut self) -> Result<()> {
+        self.plugin.on_cancel()
+    }
+
+    fn handle_unknown_command(&mut self, argv: &[String], ctx: &mut Context) -> Result<bool> {
+        self.plugin.handle_unknown_command(argv, ctx)
+    }
+
+    fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> Result<()> {
+        let cancel = ctx.cancellation();
+        let mut cancelled = pin!(cancel.wait());
+        let mut handler = pin!(self.plugin.handle_event(event, ctx));
+        self.runtime.block_on(std::future::poll_fn(|cx| {
+            if let Poll::Ready(result) = handler.as_mut().poll(cx) {
+                return Poll::Ready(result);
+            }
+            // The host reports the interruption and calls `on_cancel`
+            match cancelled.as_mut().poll(cx) {
+                Poll::Ready(()) => Poll::Ready(Ok(())),
+                Poll::Pending => Poll::Pending,
+            }
+        }))
+    }
+}
@@ -0,0 +1,84 @@
+//! Cooperative cancellation.
+//!
+//! The host trips the token when the user presses Ctrl-C. Long-running plugin
+//! work should check `ctx.cancelled()` between steps, or await
+//! [`CancellationToken::wait`] alongside its own futures, and stop early.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+/// A shared flag saying the current command should stop. Cheap to clone and
+/// safe to move into worker threads.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trips the token and wakes every pending [`wait`](Self::wait).
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        let wakers = std::mem::take(&mut *self.inner.wakers.lock().unwrap());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// A future that completes once the token is cancelled.
+    ///
+    /// # Example
+    /// ```
+    /// # use drk_api::CancellationToken;
+    /// let token = CancellationToken::new();
+    /// let wait = token.wait();
+    /// token.cancel();
+    /// // `wait` is now ready; in async code: `select!` it against the real work
+    /// # drop(wait);
+    /// assert!(token.is_cancelled());
+    /// ```
+    pub fn wait(&self) -> impl Future<Output = ()> + Send + 'static {
+        Wait {
+            token: self.clone(),
+        }
+    }
+}
+
+struct Wait {
+    token: CancellationToken,
+}
+
+impl Future for Wait {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<()> {
+        if self.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+        let mut wakers = self.token.inner.wakers.lock().unwrap();
+        // Re-check under the lock so a concurrent `cancel` can't be missed
+        if self.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+        if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
@@ -0,0 +1,92 @@
+//! Line diffs, for showing what writing a file would change before it's
+//! written (dry runs, `diff` subcommands).
+//!
+//! ```
+//! use drk_api::diff::{lines, Change};
+//!
+//! let changes = lines("a\nb\nc\n", "a\nB\nc\n");
+//! assert_eq!(
+//!     changes,
+//!     [Change::Same("a"), Change::Removed("b"), Change::Added("B"), Change::Same("c")]
+//! );
+//! ```
+
+use crate::styling::{style_dim, style_error, style_success};
+
+/// Unchanged lines kept around each change by [`render`].
+const CONTEXT: usize = 2;
+
+/// One line of a diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// The changes turning `old` into `new`, line by line. Where a line is
+/// replaced its removal comes first.
+pub fn lines<'a>(old: &'a str, new: &'a str) -> Vec<Change<'a>> {
+    let (a, b): (Vec<&str>, Vec<&str>) = (old.lines().collect(), new.lines().collect());
+    // Longest common subsequence lengths of every pair of suffixes
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            out.push(Change::Same(a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push(Change::Removed(a[i]));
+            i += 1;
+        } else {
+            out.push(Change::Added(b[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+/// The diff from `old` to `new` as styled `-`/`+` lines, with a little
+/// unchanged context around each change and `...` where more is skipped.
+/// Empty if nothing changed.
+pub fn render(old: &str, new: &str) -> String {
+    let changes = lines(old, new);
+    let near_change = |at: usize| {
+        let from = at.saturating_sub(CONTEXT);
+        let to = (at + CONTEXT + 1).min(changes.len());
+        changes[from..to]
+            .iter()
+            .any(|c| !matches!(c, Change::Same(_)))
+    };
+
+    let mut out = Vec::new();
+    let mut skipped = false;
+    for (at, change) in changes.iter().enumerate() {
+        let line = match change {
+            Change::Same(_) if !near_change(at) => {
+                skipped = true;
+                continue;
+            }
+            Change::Same(line) => style_dim(&format!(" {}", line)).to_string(),
+            Change::Removed(line) => style_error(&format!("-{}", line)).to_string(),
+            Change::Added(line) => style_success(&format!("+{}", line)).to_string(),
+        };
+        if std::mem::take(&mut skipped) && !out.is_empty() {
+            out.push(style_dim("...").to_string());
+        }
+        out.push(line);
+    }
+    out.join("\n")
+}
@@ -0,0 +1,314 @@
+//! Fetching several files at once, for installers and scaffolding plugins.
+//! Needs the `download` feature.
+//!
+//! A [`DownloadPool`] runs a bounded number of transfers in parallel. On a
+//! terminal every file gets a progress line, redrawn in place; elsewhere each
+//! one prints a line when it finishes. Failures don't stop the other
+//! transfers: they are collected and reported together at the end.
+//!
+//! ```no_run
+//! use drk_api::download::{Download, DownloadPool};
+//!
+//! # fn demo(ctx: &drk_api::Context) -> anyhow::Result<()> {
+//! let dir = ctx.plugin_data_dir()?;
+//! DownloadPool::new(4).cancel_on(ctx.cancellation()).run(vec![
+//!     Download::new("https://example.com/flake.nix", dir.join("flake.nix")),
+//!     Download::new("https://example.com/.envrc", dir.join(".envrc")),
+//! ])?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::http::Client;
+use crate::progress::bar;
+use crate::styling::{icon_error, icon_success, style_dim, style_error, style_success};
+use crate::text::{pad, truncate, Align};
+use crate::CancellationToken;
+use anyhow::{anyhow, bail, Context as _, Result};
+use console::Term;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// One file to fetch.
+#[derive(Debug, Clone)]
+pub struct Download {
+    pub url: String,
+    pub dest: PathBuf,
+}
+
+impl Download {
+    pub fn new(url: impl Into<String>, dest: impl Into<PathBuf>) -> Self {
+        Self {
+            url: url.into(),
+            dest: dest.into(),
+        }
+    }
+
+    /// The last path segment of the URL, for display.
+    fn label(&self) -> &str {
+        let path = self.url.split(['?', '#']).next().unwrap_or(&self.url);
+        path.rsplit('/').find(|s| !s.is_empty()).unwrap_or(path)
+    }
+}
+
+#[derive(Clone)]
+enum State {
+    Queued,
+    Running,
+    Done,
+    Failed(String),
+}
+
+#[derive(Clone)]
+struct Progress {
+    label: String,
+    received: u64,
+    total: Option<u64>,
+    state: State,
+}
+
+pub struct DownloadPool {
+    concurrency: usize,
+    cancel: Option<CancellationToken>,
+}
+
+impl DownloadPool {
+    /// A pool running at most `concurrency` transfers at a time.
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            concurrency: concurrency.max(1),
+            cancel: None,
+        }
+    }
+
+    /// Stops starting and continuing transfers once `token` trips.
+    pub fn cancel_on(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Fetches every download, returning their destinations in order. Each
+    /// file is written next to its destination and renamed into place, so a
+    /// failed transfer never leaves a partial file behind. Fails with every
+    /// failure listed if any transfer failed.
+    pub fn run(&self, downloads: Vec<Download>) -> Result<Vec<PathBuf>> {
+        let progress: Arc<Mutex<Vec<Progress>>> = Arc::new(Mutex::new(
+            downloads
+                .iter()
+                .map(|d| Progress {
+                    label: d.label().to_string(),
+                    received: 0,
+                    total: None,
+                    state: State::Queued,
+                })
+                .collect(),
+        ));
+        let downloads = Arc::new(downloads);
+        let next = Arc::new(AtomicUsize::new(0));
+        let client = Client::new()?;
+
+        let workers: Vec<_> = (0..self.concurrency.min(downloads.len()))
+            .map(|_| {
+                let (downloads, next, progress, client, cancel) = (
+                    downloads.clone(),
+                    next.clone(),
+                    progress.clone(),
+                    client.clone(),
+                    self.cancel.clone(),
+                );
+                std::thread::spawn(move || loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(download) = downloads.get(i) else {
+                        break;
+                    };
+                    set(&progress, i, |p| p.state = State::Running);
+                    let result = fetch(&client, download, cancel.as_ref(), |received, total| {
+                        set(&progress, i, |p| {
+                            p.received = received;
+                            p.total = total;
+                        })
+                    });
+                    set(&progress, i, |p| {
+                        p.state = match result {
+                            Ok(()) => State::Done,
+                            Err(e) => State::Failed(format!("{:#}", e)),
+                        }
+                    });
+                })
+            })
+            .collect();
+
+        let mut display = Display::new();
+        while !workers.iter().all(|w| w.is_finished()) {
+            display.update(&snapshot(&progress));
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        for worker in workers {
+            let _ = worker.join();
+        }
+        let finished = snapshot(&progress);
+        display.update(&finished);
+
+        let failures: Vec<String> = finished
+            .iter()
+            .zip(downloads.iter())
+            .filter_map(|(p, d)| match &p.state {
+                State::Failed(e) => Some(format!("  {}: {}", d.url, e)),
+                _ => None,
+            })
+            .collect();
+        if !failures.is_empty() {
+            bail!(
+                "{} of {} downloads failed:\n{}",
+                failures.len(),
+                downloads.len(),
+                failures.join("\n")
+            );
+        }
+        Ok(downloads.iter().map(|d| d.dest.clone()).collect())
+    }
+}
+
+fn set(progress: &Mutex<Vec<Progress>>, i: usize, f: impl FnOnce(&mut Progress)) {
+    f(&mut progress.lock().unwrap_or_else(|e| e.into_inner())[i]);
+}
+
+fn snapshot(progress: &Mutex<Vec<Progress>>) -> Vec<Progress> {
+    progress.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+fn fetch(
+    client: &Client,
+    download: &Download,
+    cancel: Option<&CancellationToken>,
+    mut report: impl FnMut(u64, Option<u64>),
+) -> Result<()> {
+    let cancelled = || cancel.is_some_and(|c| c.is_cancelled());
+    if cancelled() {
+        bail!("cancelled");
+    }
+    let mut response = client.get(&download.url).send()?.error_for_status()?;
+    let total = response.content_length();
+
+    if let Some(parent) = download.dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let name = download
+        .dest
+        .file_name()
+        .ok_or_else(|| anyhow!("{:?} has no file name", download.dest))?;
+    let partial = download
+        .dest
+        .with_file_name(format!(".{}.partial", name.to_string_lossy()));
+    let result = (|| -> Result<()> {
+        let mut file = fs::File::create(&partial)
+            .with_context(|| format!("Could not create {:?}", partial))?;
+        let mut buf = vec![0; 64 * 1024];
+        let mut received = 0;
+        loop {
+            if cancelled() {
+                bail!("cancelled");
+            }
+            let n = response.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            file.write_all(&buf[..n])?;
+            received += n as u64;
+            report(received, total);
+        }
+        fs::rename(&partial, &download.dest)
+            .with_context(|| format!("Could not write {:?}", download.dest))
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+/// Draws the progress of every transfer.
+struct Display {
+    term: Term,
+    live: bool,
+    drawn: usize,
+    /// Transfers already announced (plain mode)
+    reported: Vec<bool>,
+}
+
+impl Display {
+    fn new() -> Self {
+        let term = Term::stdout();
+        Self {
+            live: term.features().is_attended(),
+            term,
+            drawn: 0,
+            reported: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, progress: &[Progress]) {
+        if !self.live {
+            self.reported.resize(progress.len(), false);
+            for (p, reported) in progress.iter().zip(self.reported.iter_mut()) {
+                if *reported {
+                    continue;
+                }
+                match &p.state {
+                    State::Done => println!("downloaded {} ({})", p.label, bytes(p.received)),
+                    State::Failed(e) => println!("failed {}: {}", p.label, e),
+                    _ => continue,
+                }
+                *reported = true;
+            }
+            return;
+        }
+
+        let width = (self.term.size().1 as usize).saturating_sub(1).max(20);
+        let lines: Vec<String> = progress.iter().map(line).collect();
+        if self.drawn > 0 {
+            let _ = self.term.clear_last_lines(self.drawn);
+        }
+        for l in &lines {
+            let _ = self.term.write_line(&truncate(l, width));
+        }
+        self.drawn = lines.len();
+    }
+}
+
+fn line(p: &Progress) -> String {
+    let label = pad(&p.label, 24, Align::Left);
+    match &p.state {
+        State::Queued => format!("  {} {}", label, style_dim("queued")),
+        State::Running => {
+            let amount = match p.total {
+                Some(total) => format!("{} / {}", bytes(p.received), bytes(total)),
+                None => bytes(p.received),
+            };
+            format!(
+                "  {} {} {}",
+                label,
+                bar(p.received, p.total),
+                style_dim(&amount)
+            )
+        }
+        State::Done => format!(
+            "{} {} {}",
+            style_success(icon_success()),
+            label,
+            style_dim(&bytes(p.received))
+        ),
+        State::Failed(e) => format!("{} {} {}", style_error(icon_error()), label, style_error(e)),
+    }
+}
+
+fn bytes(n: u64) -> String {
+    match n {
+        n if n < 1024 => format!("{} B", n),
+        n if n < 1024 * 1024 => format!("{:.1} KiB", n as f64 / 1024.0),
+        n => format!("{:.1} MiB", n as f64 / (1024.0 * 1024.0)),
+    }
+}
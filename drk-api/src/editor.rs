@@ -0,0 +1,102 @@
+//! Letting the user write or change longer text in their own editor, through
+//! `ctx.edit()` and `ctx.edit_file()`: notes, messages, config files.
+//!
+//! The editor is `$VISUAL`, else `$EDITOR`, else the first of `nano`, `vim`
+//! and `vi` that's installed (`notepad` on Windows). Both may carry
+//! arguments, like `code --wait`. Without a terminal there's nobody to edit,
+//! so these fail; offer another way in, like piped input.
+//!
+//! ```no_run
+//! # fn demo(ctx: &mut drk_api::Context) -> anyhow::Result<()> {
+//! let message = ctx.edit("\n# Describe the release; lines starting with # are dropped\n")?;
+//! let message: Vec<&str> = message.lines().filter(|l| !l.starts_with('#')).collect();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::tools::which;
+use crate::ui::Ui;
+use anyhow::{bail, Context as _, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The editor command to run, if one is set or installed.
+pub fn editor() -> Option<String> {
+    let set = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|e| !e.trim().is_empty());
+    let fallbacks: &[&str] = if cfg!(windows) {
+        &["notepad"]
+    } else {
+        &["nano", "vim", "vi"]
+    };
+    set.or_else(|| {
+        fallbacks
+            .iter()
+            .find(|e| which(e).is_some())
+            .map(|e| e.to_string())
+    })
+}
+
+/// Opens `path` in the editor and waits for it to be closed.
+pub fn edit_file(path: &Path) -> Result<()> {
+    if !Ui::new().is_interactive() {
+        bail!("Opening an editor needs a terminal");
+    }
+    let Some(editor) = editor() else {
+        bail!("No editor found; set $EDITOR");
+    };
+    let mut words = editor.split_whitespace();
+    let program = words.next().expect("editor() skips blank values");
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .with_context(|| format!("Could not start the editor '{}'", editor))?;
+    if !status.success() {
+        bail!("The editor '{}' exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+/// A path in the temp directory for a scratch file ending in `.<extension>`,
+/// not taken by another drk run.
+pub fn scratch_file(extension: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    std::env::temp_dir().join(format!(
+        "drk-edit-{}-{:08x}.{}",
+        std::process::id(),
+        nanos,
+        extension
+    ))
+}
+
+/// Opens `text` in the editor, by way of the file `scratch`, and returns
+/// what was saved. `scratch` is readable only by the user and removed
+/// afterwards; its extension picks the editor's syntax highlighting.
+pub fn edit_text(text: &str, scratch: &Path) -> Result<String> {
+    // Editors expect a file to end in a newline
+    let text = if text.is_empty() || text.ends_with('\n') {
+        text.to_string()
+    } else {
+        format!("{}\n", text)
+    };
+    write_private(scratch, &text)?;
+    let edited = edit_file(scratch).and_then(|()| Ok(std::fs::read_to_string(scratch)?));
+    let _ = std::fs::remove_file(scratch);
+    edited
+}
+
+fn write_private(path: &Path, text: &str) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    // Never through a file or link someone else put there
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    std::io::Write::write_all(&mut options.open(path)?, text.as_bytes())?;
+    Ok(())
+}
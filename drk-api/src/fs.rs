@@ -0,0 +1,195 @@
+//! Changing several files as one step: either all of them change, or, if
+//! one fails, the ones already done are put back as they were.
+//!
+//! ```no_run
+//! use drk_api::fs::Transaction;
+//!
+//! # fn demo() -> anyhow::Result<()> {
+//! let mut txn = Transaction::new().keep_backups(".drk-backup");
+//! txn.write("/home/me/.gitconfig", "[user]\n  name = Me\n");
+//! txn.symlink("/home/me/dotfiles/vimrc", "/home/me/.vimrc");
+//! txn.commit()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{Context as _, Result};
+use std::path::{Path, PathBuf};
+
+/// Staged file changes, applied in order by [`Transaction::commit`].
+#[derive(Debug, Default)]
+pub struct Transaction {
+    ops: Vec<Op>,
+    backup_suffix: Option<String>,
+}
+
+#[derive(Debug)]
+enum Op {
+    Write { path: PathBuf, contents: Vec<u8> },
+    Symlink { original: PathBuf, link: PathBuf },
+}
+
+impl Op {
+    fn path(&self) -> &Path {
+        match self {
+            Op::Write { path, .. } => path,
+            Op::Symlink { link, .. } => link,
+        }
+    }
+}
+
+/// What to do to take back one applied change.
+struct Undo {
+    path: PathBuf,
+    /// Where the file that was there before is kept
+    backup: Option<PathBuf>,
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps every file the transaction replaces next to it, named with
+    /// `suffix` added. Without this they're deleted once it's committed.
+    pub fn keep_backups(mut self, suffix: &str) -> Self {
+        self.backup_suffix = Some(suffix.to_string());
+        self
+    }
+
+    /// Writes `contents` to `path`, replacing what's there and creating
+    /// missing parent directories.
+    pub fn write(&mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) {
+        self.ops.push(Op::Write {
+            path: path.into(),
+            contents: contents.into(),
+        });
+    }
+
+    /// Makes `link` a symlink to `original`, replacing what's there.
+    pub fn symlink(&mut self, original: impl Into<PathBuf>, link: impl Into<PathBuf>) {
+        self.ops.push(Op::Symlink {
+            original: original.into(),
+            link: link.into(),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// The paths it changes, in order.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.ops.iter().map(Op::path)
+    }
+
+    /// Applies every change. If one fails, those already applied are rolled
+    /// back and the error says which path it failed on.
+    ///
+    /// ```
+    /// use drk_api::fs::Transaction;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("drk-txn-doc-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// std::fs::write(dir.join("a"), "old").unwrap();
+    /// std::fs::write(dir.join("file"), "").unwrap();
+    ///
+    /// let mut txn = Transaction::new();
+    /// txn.write(dir.join("a"), "new");
+    /// txn.write(dir.join("file/b"), "a file can't hold another");
+    /// assert!(txn.commit().is_err());
+    /// assert_eq!(std::fs::read_to_string(dir.join("a")).unwrap(), "old");
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn commit(self) -> Result<()> {
+        let mut done: Vec<Undo> = Vec::new();
+        for op in &self.ops {
+            match self.apply(op) {
+                Ok(undo) => done.push(undo),
+                Err(e) => {
+                    for undo in done.into_iter().rev() {
+                        let _ = rollback(undo);
+                    }
+                    return Err(e.context(format!("Could not change {:?}", op.path())));
+                }
+            }
+        }
+        if self.backup_suffix.is_none() {
+            for backup in done.into_iter().filter_map(|undo| undo.backup) {
+                let _ = std::fs::remove_file(backup);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&self, op: &Op) -> Result<Undo> {
+        let path = op.path();
+        // `exists` follows symlinks; a dangling one is still in the way
+        let backup = match path.symlink_metadata() {
+            Ok(_) => {
+                let suffix = self
+                    .backup_suffix
+                    .clone()
+                    .unwrap_or_else(|| format!(".drk-txn-{}", std::process::id()));
+                let backup = PathBuf::from(format!("{}{}", path.display(), suffix));
+                std::fs::rename(path, &backup).context("Could not move it aside")?;
+                Some(backup)
+            }
+            Err(_) => None,
+        };
+        let undo = Undo {
+            path: path.to_path_buf(),
+            backup,
+        };
+
+        let result = (|| -> Result<()> {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            match op {
+                Op::Write { contents, .. } => std::fs::write(path, contents)?,
+                Op::Symlink { original, .. } => symlink(original, path)?,
+            }
+            Ok(())
+        })();
+        match result {
+            Ok(()) => Ok(undo),
+            Err(e) => {
+                let _ = rollback(undo);
+                Err(e)
+            }
+        }
+    }
+}
+
+fn rollback(undo: Undo) -> std::io::Result<()> {
+    if undo.path.symlink_metadata().is_ok() {
+        std::fs::remove_file(&undo.path)?;
+    }
+    if let Some(backup) = undo.backup {
+        std::fs::rename(backup, &undo.path)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    if original.is_dir() {
+        std::os::windows::fs::symlink_dir(original, link)
+    } else {
+        std::os::windows::fs::symlink_file(original, link)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink(_: &Path, _: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "symlinks aren't supported on this platform",
+    ))
+}
@@ -0,0 +1,402 @@
+//! The HTTP client drk and its plugins share. Needs the `http` feature
+//! (`download` includes it).
+//!
+//! Besides going to the network, a [`Client`] can record what it receives to
+//! a cassette file and later replay it, so tests of code that fetches things
+//! (the GitHub template list, a registry index) run offline and get the same
+//! answers every time:
+//!
+//! ```no_run
+//! use drk_api::http::{Cassette, Client};
+//!
+//! # fn demo() -> anyhow::Result<()> {
+//! let cassette = Cassette::open(concat!(
+//!     env!("CARGO_MANIFEST_DIR"),
+//!     "/tests/cassettes/templates.json"
+//! ))?;
+//! let client = Client::with_cassette(cassette)?;
+//! let body = client.get("https://api.github.com/repos/o/r/contents").send()?.text()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A missing cassette is recorded on the first run and replayed from then
+//! on; `DRK_HTTP_RECORD=1` records it again. A replayed client never touches
+//! the network: a request the cassette doesn't hold is an error.
+//!
+//! [`Client::new`] uses the cassette named by `DRK_HTTP_CASSETTE`, so a test
+//! running the `drk` binary covers the plugins it loads as well.
+//!
+//! Offline (`drk --offline`, `[http] offline = true` or `DRK_OFFLINE=1`)
+//! every request that would go to the network fails at once; cassettes still
+//! replay. Check [`is_offline`] to fall back to something saved instead.
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Interaction {
+    method: String,
+    url: String,
+    status: u16,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// The body, when it is text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    /// The body, when it isn't
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body_bytes: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Tape {
+    interactions: Vec<Interaction>,
+}
+
+/// Responses recorded to a JSON file, one file per test.
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    recording: bool,
+    tape: Tape,
+    /// Which interactions have been replayed already
+    played: Vec<bool>,
+}
+
+impl Cassette {
+    /// Replays the cassette at `path`, or records it if there is none yet
+    /// (or `DRK_HTTP_RECORD` is set).
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let recording = !path.exists()
+            || std::env::var("DRK_HTTP_RECORD").is_ok_and(|v| !v.is_empty() && v != "0");
+        let tape = if recording {
+            Tape::default()
+        } else {
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("Could not read cassette {:?}", path))?;
+            serde_json::from_str(&text).with_context(|| format!("{:?} is not a cassette", path))?
+        };
+        Ok(Self {
+            played: vec![false; tape.interactions.len()],
+            path,
+            recording,
+            tape,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// The first unplayed interaction for `method` and `url`, so a request
+    /// made twice gets both recorded answers in order.
+    fn replay(&mut self, method: &str, url: &str) -> Result<Interaction> {
+        let found = self
+            .tape
+            .interactions
+            .iter()
+            .zip(self.played.iter_mut())
+            .find(|(i, played)| !**played && i.method == method && i.url == url);
+        match found {
+            Some((interaction, played)) => {
+                *played = true;
+                Ok(interaction.clone())
+            }
+            None => bail!(
+                "Cassette {:?} has no response for {} {}; record it again with DRK_HTTP_RECORD=1",
+                self.path,
+                method,
+                url
+            ),
+        }
+    }
+
+    fn record(&mut self, interaction: Interaction) -> Result<()> {
+        self.tape.interactions.push(interaction);
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&self.tape)?)
+            .with_context(|| format!("Could not write cassette {:?}", self.path))
+    }
+}
+
+/// An HTTP client sending drk's user agent, blocking or (with the `async`
+/// feature) async. Cheap to clone; clones share the connection pool and the
+/// cassette.
+#[derive(Clone, Default)]
+pub struct Client {
+    // Built on first use: a blocking client can't even be created on an async runtime
+    blocking: Arc<OnceLock<reqwest::blocking::Client>>,
+    #[cfg(feature = "async")]
+    asynchronous: Arc<OnceLock<reqwest::Client>>,
+    cassette: Option<Arc<Mutex<Cassette>>>,
+}
+
+impl Client {
+    /// A client for the network, or for the cassette in `DRK_HTTP_CASSETTE`.
+    pub fn new() -> Result<Self> {
+        match std::env::var_os("DRK_HTTP_CASSETTE").filter(|p| !p.is_empty()) {
+            Some(path) => Self::with_cassette(Cassette::open(path)?),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// A client that records to or replays from `cassette`.
+    pub fn with_cassette(cassette: Cassette) -> Result<Self> {
+        Ok(Self {
+            cassette: Some(Arc::new(Mutex::new(cassette))),
+            ..Self::default()
+        })
+    }
+
+    pub fn get(&self, url: impl Into<String>) -> Request<'_> {
+        Request {
+            client: self,
+            method: "GET",
+            url: url.into(),
+            headers: Vec::new(),
+        }
+    }
+}
+
+const USER_AGENT: &str = concat!("drk/", env!("CARGO_PKG_VERSION"));
+
+/// Set to anything but `0` to keep drk and its plugins off the network.
+pub const OFFLINE_ENV: &str = "DRK_OFFLINE";
+
+/// Whether requests to the network are refused, see [`OFFLINE_ENV`].
+pub fn is_offline() -> bool {
+    std::env::var(OFFLINE_ENV).is_ok_and(|v| !v.is_empty() && v != "0")
+}
+
+fn check_online(url: &str) -> Result<()> {
+    if is_offline() {
+        bail!(
+            "Not fetching {}: drk is offline ({} is set)",
+            url,
+            OFFLINE_ENV
+        );
+    }
+    Ok(())
+}
+
+/// The client in `cell`, built by `build` the first time.
+fn get_or_build<T>(cell: &OnceLock<T>, build: impl FnOnce() -> reqwest::Result<T>) -> Result<&T> {
+    if let Some(client) = cell.get() {
+        return Ok(client);
+    }
+    let _ = cell.set(build()?);
+    Ok(cell.get().expect("just set"))
+}
+
+pub struct Request<'a> {
+    client: &'a Client,
+    method: &'static str,
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Request<'_> {
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn send(self) -> Result<Response> {
+        let Some(cassette) = &self.client.cassette else {
+            return Ok(Response::live(self.network()?));
+        };
+        let mut cassette = cassette.lock().unwrap_or_else(|e| e.into_inner());
+        if !cassette.is_recording() {
+            return Ok(Response::recorded(cassette.replay(self.method, &self.url)?));
+        }
+
+        let mut live = self.network()?;
+        let mut bytes = Vec::new();
+        live.read_to_end(&mut bytes)?;
+        let (body, body_bytes) = match String::from_utf8(bytes) {
+            Ok(text) => (Some(text), None),
+            Err(e) => (None, Some(e.into_bytes())),
+        };
+        let interaction = Interaction {
+            method: self.method.to_string(),
+            url: self.url.clone(),
+            status: live.status().as_u16(),
+            headers: headers(live.headers()),
+            body,
+            body_bytes,
+        };
+        cassette.record(interaction.clone())?;
+        Ok(Response::recorded(interaction))
+    }
+
+    /// Like [`send`](Self::send), for async code such as an
+    /// [`AsyncPlugin`](crate::AsyncPlugin). The whole body is read before it
+    /// returns.
+    #[cfg(feature = "async")]
+    pub async fn send_async(self) -> Result<Response> {
+        let cassette = self.client.cassette.clone();
+        if let Some(cassette) = &cassette {
+            let mut cassette = cassette.lock().unwrap_or_else(|e| e.into_inner());
+            if !cassette.is_recording() {
+                return Ok(Response::recorded(cassette.replay(self.method, &self.url)?));
+            }
+        }
+
+        check_online(&self.url)?;
+        let method = reqwest::Method::from_bytes(self.method.as_bytes())?;
+        let client = get_or_build(&self.client.asynchronous, || {
+            reqwest::Client::builder().user_agent(USER_AGENT).build()
+        })?;
+        let mut request = client.request(method, &self.url);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        let live = request.send().await?;
+        let status = live.status().as_u16();
+        let headers = headers(live.headers());
+        let bytes = live.bytes().await?.to_vec();
+        let (body, body_bytes) = match String::from_utf8(bytes) {
+            Ok(text) => (Some(text), None),
+            Err(e) => (None, Some(e.into_bytes())),
+        };
+        let interaction = Interaction {
+            method: self.method.to_string(),
+            url: self.url.clone(),
+            status,
+            headers,
+            body,
+            body_bytes,
+        };
+        if let Some(cassette) = &cassette {
+            let mut cassette = cassette.lock().unwrap_or_else(|e| e.into_inner());
+            cassette.record(interaction.clone())?;
+        }
+        Ok(Response::recorded(interaction))
+    }
+
+    fn network(&self) -> Result<reqwest::blocking::Response> {
+        check_online(&self.url)?;
+        let method = reqwest::Method::from_bytes(self.method.as_bytes())?;
+        let client = get_or_build(&self.client.blocking, || {
+            reqwest::blocking::Client::builder()
+                .user_agent(USER_AGENT)
+                .build()
+        })?;
+        let mut request = client.request(method, &self.url);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        Ok(request.send()?)
+    }
+}
+
+/// The headers that are text; names come lowercase.
+fn headers(map: &reqwest::header::HeaderMap) -> BTreeMap<String, String> {
+    map.iter()
+        .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_string())))
+        .collect()
+}
+
+enum Body {
+    Live(reqwest::blocking::Response),
+    Recorded(Cursor<Vec<u8>>),
+}
+
+/// A response, read as it arrives from the network or from a cassette.
+pub struct Response {
+    url: String,
+    status: u16,
+    headers: BTreeMap<String, String>,
+    body: Body,
+}
+
+impl Response {
+    fn live(response: reqwest::blocking::Response) -> Self {
+        Self {
+            url: response.url().to_string(),
+            status: response.status().as_u16(),
+            headers: headers(response.headers()),
+            body: Body::Live(response),
+        }
+    }
+
+    fn recorded(interaction: Interaction) -> Self {
+        let body = match (interaction.body, interaction.body_bytes) {
+            (Some(text), _) => text.into_bytes(),
+            (None, bytes) => bytes.unwrap_or_default(),
+        };
+        Self {
+            url: interaction.url,
+            status: interaction.status,
+            headers: interaction.headers,
+            body: Body::Recorded(Cursor::new(body)),
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// A header's value; `name` is lowercase.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        match &self.body {
+            Body::Live(response) => response.content_length(),
+            Body::Recorded(body) => Some(body.get_ref().len() as u64),
+        }
+    }
+
+    /// Fails for a status outside 2xx.
+    pub fn error_for_status(self) -> Result<Self> {
+        if self.is_success() {
+            Ok(self)
+        } else {
+            Err(anyhow!("HTTP status {} for {}", self.status, self.url))
+        }
+    }
+
+    pub fn bytes(mut self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn text(self) -> Result<String> {
+        Ok(String::from_utf8(self.bytes()?)?)
+    }
+
+    pub fn json<T: serde::de::DeserializeOwned>(self) -> Result<T> {
+        let url = self.url.clone();
+        serde_json::from_slice(&self.bytes()?)
+            .with_context(|| format!("{} did not return the expected JSON", url))
+    }
+}
+
+impl Read for Response {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.body {
+            Body::Live(response) => response.read(buf),
+            Body::Recorded(body) => body.read(buf),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! `{config.*}` / `{env.*}` interpolation for user-defined invocations.
+//!
+//! Bare `{name}` placeholders are filled from caller-supplied values, as used
+//! by saved command templates.
+//!
+//! The host resolves placeholders before dispatch, so plugins only ever see
+//! final values. Plugins rendering their own templates (dotfiles, config
+//! snippets) use the same syntax through these functions. Use `{{` and `}}`
+//! for literal braces. A placeholder pointing at a missing key is an error
+//! rather than an empty string.
+
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+/// Resolves every placeholder in `input`.
+///
+/// # Example
+/// ```
+/// # use std::collections::HashMap;
+/// # use drk_api::interpolate::interpolate;
+/// let mut config = HashMap::new();
+/// config.insert("nix".to_string(), toml::toml! { default_template = "rust" }.into());
+///
+/// let out = interpolate("--template {config.nix.default_template}", &config).unwrap();
+/// assert_eq!(out, "--template rust");
+/// assert_eq!(interpolate("{{literal}}", &config).unwrap(), "{literal}");
+/// assert!(interpolate("{config.nix.missing}", &config).is_err());
+/// ```
+pub fn interpolate(input: &str, config: &HashMap<String, toml::Value>) -> Result<String> {
+    interpolate_with(input, config, &HashMap::new())
+}
+
+/// Like [`interpolate`], but bare `{name}` placeholders are filled from `vars`.
+pub fn interpolate_with(
+    input: &str,
+    config: &HashMap<String, toml::Value>,
+    vars: &HashMap<String, String>,
+) -> Result<String> {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut key = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => key.push(ch),
+                        None => bail!("Unterminated placeholder '{{{}' in '{}'", key, input),
+                    }
+                }
+                out.push_str(&resolve(key.trim(), config, vars)?);
+            }
+            '}' => bail!(
+                "Unmatched '}}' in '{}' (use '}}}}' for a literal brace)",
+                input
+            ),
+            _ => out.push(c),
+        }
+    }
+
+    Ok(out)
+}
+
+fn resolve(
+    key: &str,
+    config: &HashMap<String, toml::Value>,
+    vars: &HashMap<String, String>,
+) -> Result<String> {
+    if let Some(name) = key.strip_prefix("env.") {
+        return match std::env::var(name) {
+            Ok(value) => Ok(value),
+            Err(_) => bail!("Environment variable '{}' is not set", name),
+        };
+    }
+
+    if let Some(path) = key.strip_prefix("config.") {
+        let mut parts = path.split('.');
+        let mut value = parts.next().and_then(|section| config.get(section));
+        for part in parts {
+            value = value.and_then(|v| v.get(part));
+        }
+        return match value {
+            Some(toml::Value::String(s)) => Ok(s.clone()),
+            Some(toml::Value::Table(_)) | Some(toml::Value::Array(_)) => {
+                bail!("Config key '{}' is not a plain value", path)
+            }
+            Some(other) => Ok(other.to_string()),
+            None => bail!("Config key '{}' is not set", path),
+        };
+    }
+
+    if let Some(value) = vars.get(key) {
+        return Ok(value.clone());
+    }
+
+    bail!("No value for placeholder '{{{}}}' (pass {}=...)", key, key)
+}
+
+/// The bare `{name}` placeholders in `input`, each once, in order: the
+/// values [`interpolate_with`] needs in `vars`.
+///
+/// ```
+/// # use drk_api::interpolate::placeholders;
+/// let names = placeholders("Hi {name}, from {env.USER} to {name} {{x}}");
+/// assert_eq!(names, ["name"]);
+/// ```
+pub fn placeholders(input: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+            }
+            '{' => {
+                let key: String = chars.by_ref().take_while(|&ch| ch != '}').collect();
+                let key = key.trim();
+                let qualified = key.starts_with("env.") || key.starts_with("config.");
+                if !qualified && !names.iter().any(|name| name == key) {
+                    names.push(key.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> HashMap<String, toml::Value> {
+        toml::from_str("[build]\njobs = 4\nrelease = true\ntargets = [\"x86\"]\n").unwrap()
+    }
+
+    #[test]
+    fn plain_config_values_are_written_out() {
+        let config = config();
+        assert_eq!(
+            interpolate("-j{config.build.jobs}", &config).unwrap(),
+            "-j4"
+        );
+        assert_eq!(
+            interpolate("{ config.build.release }", &config).unwrap(),
+            "true"
+        );
+        assert!(interpolate("{config.build.targets}", &config).is_err());
+        assert!(interpolate("{config.build}", &config).is_err());
+    }
+
+    #[test]
+    fn env_placeholders_read_the_environment() {
+        let path = std::env::var("PATH").unwrap();
+        assert_eq!(interpolate("{env.PATH}", &HashMap::new()).unwrap(), path);
+        assert!(interpolate("{env.DRK_SURELY_UNSET}", &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn stray_braces_are_errors() {
+        assert!(interpolate("{config.build.jobs", &config()).is_err());
+        assert!(interpolate("jobs}", &config()).is_err());
+        assert_eq!(interpolate("{{}}", &config()).unwrap(), "{}");
+    }
+
+    #[test]
+    fn bare_names_come_from_vars() {
+        let vars = HashMap::from([("name".to_string(), "World".to_string())]);
+        assert_eq!(
+            interpolate_with("Hi {name}", &HashMap::new(), &vars).unwrap(),
+            "Hi World"
+        );
+        assert!(interpolate("Hi {name}", &HashMap::new()).is_err());
+    }
+}
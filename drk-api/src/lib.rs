@@ -2,68 +2,410 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::any::Any;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
+pub mod abi;
+#[cfg(feature = "async")]
+pub mod async_plugin;
+pub mod cancel;
+pub mod diff;
+#[cfg(feature = "download")]
+pub mod download;
+pub mod editor;
+pub mod fs;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod interpolate;
+pub mod markdown;
+pub mod notify;
+pub mod output;
+pub mod progress;
+pub mod report;
+pub mod secrets;
 pub mod styling;
+pub mod text;
+pub mod time;
+pub mod tools;
+pub mod ui;
 
+// Plugins name config types (`toml::Table`) without depending on toml themselves
+pub use toml;
+// ...and build `CommandOutput` data without depending on serde_json
+pub use serde_json;
+// ...and read `ArgType::DateTime` arguments without depending on chrono
+pub use chrono;
+
+#[cfg(feature = "async")]
+pub use async_plugin::{tokio, Async, AsyncPlugin};
+pub use cancel::CancellationToken;
+pub use output::{CommandOutput, OUTPUT_EVENT};
+pub use progress::Progress;
+pub use report::Reporter;
+pub use secrets::Secrets;
 pub use styling::{
+    box_chars,
+    emoji_level,
+    glyph_profile,
     icon_error,
     icon_info,
     icon_success,
     icon_warning,
     // TODO: icon_question,
+    progress_chars,
+    spinner_frames,
     style_dim,
     style_error,
     style_primary,
     style_success,
     style_warning,
+    theme,
+    BoxChars,
+    EmojiLevel,
+    GlyphProfile,
+    Theme,
 };
+pub use text::{fit_to_terminal, pad, truncate, truncate_middle, Align};
+pub use tools::{ToolStatus, Version};
+pub use ui::Ui;
 
 // --- 1. COMMAND SCHEMA SYSTEM ---
 // A serializable representation of a CLI command that can cross FFI boundaries.
 
 /// Represents an argument for a command
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CommandArg {
     pub name: String,
     pub description: String,
     pub required: bool,
     pub arg_type: ArgType,
+    /// For a [`ArgType::Boolean`], also adds `--no-<name>`. The last of the
+    /// two given wins and the argument is passed on as `true` or `false`;
+    /// it's absent when neither is given
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub negatable: bool,
+    /// Secrets such as tokens: the plugin gets the value, but the host masks
+    /// it in `PreCommand`, history, the audit log and traces
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sensitive: bool,
+    /// For a `String`, `Integer`, `Float` or `Choice` flag: it may be given
+    /// more than once, `--tag a --tag b`, and every value is passed on. Read
+    /// them with [`CommandMatches::values`]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub multiple: bool,
+    /// The value the plugin gets when the argument isn't given, shown in
+    /// `--help`; the plugin no longer needs its own fallback
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// An environment variable to take the value from when the argument
+    /// isn't given; it wins over `default`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<String>,
 }
 
 /// The type of argument
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub enum ArgType {
+    #[default]
     String,
     Integer,
     Float,
     Boolean,
-    /// A positional argument
+    /// A positional argument. Positionals are filled in the order the
+    /// command declares them
     Positional,
+    /// Every remaining positional value, e.g. the files in
+    /// `drk fmt <style> <files>...`. Only the last positional may be
+    /// variadic; read the values with [`CommandMatches::values`]
+    Variadic,
+    /// A flag counted each time it's given, e.g. `-v -vv` or `--verbose
+    /// --verbose`, passed on as the number of times. It also gets a short
+    /// form: the first letter of its name, unless that's `h` or taken by
+    /// an earlier counted flag of the same command
+    Count,
+    /// A flag that may carry a value, like `--color` or `--color=never`. A
+    /// bare flag is passed on as an empty string; without the flag the
+    /// argument is absent. The value needs the `=`
+    OptionalValue,
+    /// A `--name value` flag taking one of the listed values, e.g. a
+    /// template name. Anything else is rejected before the plugin runs, and
+    /// shell completions offer the values
+    Choice(Vec<String>),
+    /// A `--name 2h30m` flag taking a duration. It's passed on in the
+    /// canonical form of [`time::format_duration`]; read it with
+    /// [`CommandMatches::duration`]
+    Duration,
+    /// A `--name <when>` flag taking a point in time, as RFC 3339 or
+    /// something like `tomorrow 9:00` (see [`time::parse_datetime`]). It's
+    /// passed on as RFC 3339; read it with [`CommandMatches::datetime`]
+    DateTime,
+}
+
+impl CommandArg {
+    /// Whether the argument can have several values: it's
+    /// [`ArgType::Variadic`] or declared `multiple`.
+    pub fn takes_many(&self) -> bool {
+        self.multiple || self.arg_type == ArgType::Variadic
+    }
+
+    /// The `no-<name>` flag of a negatable boolean argument.
+    pub fn negated_name(&self) -> Option<String> {
+        (self.negatable && self.arg_type == ArgType::Boolean).then(|| format!("no-{}", self.name))
+    }
+
+    /// The short flag of a [`ArgType::Count`] argument, given the shorts
+    /// already used by the command's earlier arguments.
+    pub fn count_short(&self, taken: &[char]) -> Option<char> {
+        let short = self.name.chars().next()?;
+        (self.arg_type == ArgType::Count
+            && short.is_ascii_alphanumeric()
+            && short != 'h'
+            && !taken.contains(&short))
+        .then_some(short)
+    }
 }
 
 /// Represents a command that a plugin can handle
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PluginCommand {
     pub name: String,
     pub description: String,
     pub args: Vec<CommandArg>,
+    /// Other names that run the command, e.g. `g` for `greet`. They're shown
+    /// in help and completed by the shell; one taken by a built-in or
+    /// another command is dropped
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// What the environment must provide for the command to be offered. The
+    /// host checks these before building the CLI; a command with an unmet
+    /// requirement is hidden and prints a hint instead of running.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<Requirement>,
+    /// Read-only commands can let the host reuse their output: a run with
+    /// the same args and `cache_inputs` within `[cache] max_age_secs` prints
+    /// the previous output instead of calling the plugin. `--no-cache` skips it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cacheable: bool,
+    /// What a cacheable command's output depends on besides its args.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cache_inputs: Vec<CacheInput>,
+    /// Commands nested under this one, e.g. `init` for `drk nix init`. A
+    /// command with subcommands only runs through one of them; `requires`
+    /// applies to all of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subcommands: Vec<PluginCommand>,
+    /// Destructive commands: the host asks for confirmation, or `--yes`,
+    /// before running them. Covers the nested commands too.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dangerous: bool,
+    /// What a dangerous command puts at risk, shown when asking, e.g.
+    /// "Deletes every cached build"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+impl PluginCommand {
+    /// Whether `name` is the command's name or one of its aliases.
+    pub fn is_called(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|alias| alias == name)
+    }
+
+    /// The nested command called `name`, or aliased so.
+    pub fn subcommand(&self, name: &str) -> Option<&PluginCommand> {
+        self.subcommands.iter().find(|c| c.is_called(name))
+    }
+}
+
+/// An availability predicate for a [`PluginCommand`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "kebab-case")]
+pub enum Requirement {
+    /// An executable that must be on `PATH`, e.g. `"nix"`
+    Binary(String),
+    /// The OS drk runs on, as in `std::env::consts::OS` (`"linux"`, `"macos"`, `"windows"`)
+    Os(String),
+    /// A file marking the project type, e.g. `"flake.nix"` or `"Cargo.toml"`,
+    /// looked for from the working directory up to the project root
+    ProjectFile(String),
+}
+
+/// Something a cached result is invalidated by, see [`PluginCommand::cacheable`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "kebab-case")]
+pub enum CacheInput {
+    /// A file, relative to the working directory; its size and modification
+    /// time are part of the key
+    File(String),
+    /// An environment variable's value
+    Env(String),
+    /// The directory the command runs in
+    WorkingDir,
+}
+
+/// One key of a plugin's `[<name>]` config section. Declared through
+/// [`Plugin::config_schema`]; `drk config docs` is generated from these.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigKey {
+    pub name: String,
+    pub description: String,
+    pub value_type: ConfigType,
+    /// What the plugin uses when the key isn't set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<toml::Value>,
+}
+
+/// A hint shown after a command once it has been used often enough, e.g.
+/// pointing at a config key or alias that would save typing. Declared
+/// through [`Plugin::tips`]; drk decides when one is shown and how often.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tip {
+    /// Unique within the plugin; `drk tips dismiss <plugin>.<id>` hides it
+    pub id: String,
+    /// The command the tip follows
+    pub command: String,
+    /// How many runs of `command` before the tip is due
+    pub min_runs: u64,
+    /// `{count}` is replaced with the number of runs so far
+    pub message: String,
+}
+
+/// The TOML type a [`ConfigKey`] takes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfigType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Array,
+    Table,
+}
+
+impl std::fmt::Display for ConfigType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ConfigType::String => "string",
+            ConfigType::Integer => "integer",
+            ConfigType::Float => "float",
+            ConfigType::Boolean => "boolean",
+            ConfigType::Array => "array",
+            ConfigType::Table => "table",
+        })
+    }
+}
+
+/// Something a plugin asks the user's permission for, see
+/// [`Context::request_permission`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    Network,
+    /// Writing files outside the current project
+    FsWrite,
+    ProcessSpawn,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 3] = [
+        Capability::Network,
+        Capability::FsWrite,
+        Capability::ProcessSpawn,
+    ];
+
+    /// The name used in config, e.g. `process-spawn`.
+    pub fn key(self) -> &'static str {
+        match self {
+            Capability::Network => "network",
+            Capability::FsWrite => "fs-write",
+            Capability::ProcessSpawn => "process-spawn",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.key() == key)
+    }
+
+    /// What it lets a plugin do, completing "wants to ...".
+    pub fn describe(self) -> &'static str {
+        match self {
+            Capability::Network => "access the network",
+            Capability::FsWrite => "write files outside the project",
+            Capability::ProcessSpawn => "run other programs",
+        }
+    }
 }
 
 /// Parsed arguments from a command execution
 #[derive(Debug, Clone)]
 pub struct CommandMatches {
     pub command_name: String,
+    /// The commands invoked, outermost first: `["nix", "init"]` for
+    /// `drk nix init`, `["greet"]` for a command without subcommands
+    pub path: Vec<String>,
+    /// The arguments of every command on the path
     pub args: HashMap<String, String>,
 }
 
+impl CommandMatches {
+    /// The path below `command_name`, e.g. `["init"]` for `drk nix init`.
+    pub fn subcommands(&self) -> &[String] {
+        self.path.get(1..).unwrap_or_default()
+    }
+
+    /// The values of a [`ArgType::Variadic`] or `multiple` argument, which
+    /// travel in `args` joined by newlines. Empty if none were given.
+    pub fn values(&self, name: &str) -> Vec<&str> {
+        self.args
+            .get(name)
+            .map(|joined| joined.split(VALUES_SEPARATOR).collect())
+            .unwrap_or_default()
+    }
+
+    /// The value of a [`ArgType::Duration`] argument, if it was given.
+    pub fn duration(&self, name: &str) -> Result<Option<std::time::Duration>> {
+        self.args
+            .get(name)
+            .map(|value| time::parse_duration(value))
+            .transpose()
+    }
+
+    /// The value of a [`ArgType::DateTime`] argument, if it was given.
+    pub fn datetime(&self, name: &str) -> Result<Option<chrono::DateTime<chrono::FixedOffset>>> {
+        self.args
+            .get(name)
+            .map(|value| time::parse_datetime(value))
+            .transpose()
+    }
+}
+
+/// What the values of a [`ArgType::Variadic`] or `multiple` argument are
+/// joined with in [`CommandMatches::args`].
+pub const VALUES_SEPARATOR: char = '\n';
+
 // --- 2. TYPE-SAFE EVENT SYSTEM ---
 // Instead of just Strings, we use an Enum to strictly define Core events.
 // Plugins can use `Custom` to pass data, but they should document their data payload.
+// Structured data belongs in `data`, which crosses the plugin boundary as JSON.
 
 #[derive(Debug, Clone)]
 pub enum SystemEvent {
-    /// Fired when CLI starts.
+    /// Startup phase: config has been read. Plugins may still adjust
+    /// `ctx.config` (e.g. add aliases) before anything else reads it.
+    ///
+    /// Config is read and libraries are found before any plugin exists, so
+    /// the first two phases are announced right after loading, in order.
+    ConfigLoaded,
+    /// Startup phase: the plugin libraries that were found.
+    PluginsDiscovered { libraries: Vec<String> },
+    /// Startup phase: the plugins that loaded, by name.
+    PluginsLoaded { plugins: Vec<String> },
+    /// Fired when CLI starts, after the phases above.
     Startup,
+    /// Startup phase: the command line parser is about to be built. Plugins
+    /// are asked for `contribute_commands` right after this.
+    BuildingCli,
+    /// Startup phase: the command line parser is built; the names of every
+    /// command it accepts. Not fired for `exec-json`, which has no parser.
+    CliBuilt { commands: Vec<String> },
     /// Fired before a command runs.
     PreCommand { name: String, args: Vec<String> },
     /// Fired after a command runs.
@@ -73,15 +415,132 @@ pub enum SystemEvent {
         plugin_name: String,
         matches: CommandMatches,
     },
+    /// Fired once when drk is done, before every plugin is unloaded. Flush
+    /// and close what the plugin keeps open here or in `on_unload`.
+    Shutdown,
     /// A custom hook from another plugin.
     /// Plugins should document: "I fire 'http:request' with payload 'HttpRequest'"
     Custom {
         source: String,
         event: String,
+        /// Only strings, bytes and string lists reach other plugins; any
+        /// other type is dropped at the plugin boundary.
         payload: Option<std::sync::Arc<dyn Any + Send + Sync>>,
+        /// Structured data, set with [`SystemEvent::with_data`] and read
+        /// back with [`SystemEvent::data_as`].
+        data: Option<serde_json::Value>,
     },
 }
 
+impl SystemEvent {
+    /// A `Custom` event without payload or data.
+    pub fn custom(source: impl Into<String>, event: impl Into<String>) -> Self {
+        SystemEvent::Custom {
+            source: source.into(),
+            event: event.into(),
+            payload: None,
+            data: None,
+        }
+    }
+
+    /// Attaches `value` to a `Custom` event as its data. Other events are
+    /// returned unchanged.
+    ///
+    /// ```
+    /// use drk_api::SystemEvent;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    /// struct Deployed { env: String, version: u32 }
+    ///
+    /// let deployed = Deployed { env: "staging".into(), version: 7 };
+    /// let event = SystemEvent::custom("deploy", "deploy:done").with_data(&deployed)?;
+    /// assert_eq!(event.data_as::<Deployed>().unwrap()?, deployed);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn with_data<T: Serialize + ?Sized>(mut self, value: &T) -> Result<Self> {
+        if let SystemEvent::Custom { data, .. } = &mut self {
+            *data = Some(serde_json::to_value(value)?);
+        }
+        Ok(self)
+    }
+
+    /// The data of a `Custom` event as a `T`; `None` when there is none,
+    /// an error when it doesn't have `T`'s shape.
+    pub fn data_as<T: serde::de::DeserializeOwned>(&self) -> Option<Result<T>> {
+        match self {
+            SystemEvent::Custom {
+                data: Some(data), ..
+            } => Some(T::deserialize(data).map_err(Into::into)),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a command goes ahead, as decided in [`Plugin::on_pre_command`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControlFlow {
+    Continue,
+    /// Don't run it, for the reason given
+    Cancel(String),
+}
+
+/// A kind of event a plugin subscribes to in [`Plugin::subscriptions`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventKind {
+    /// Every event, the default
+    All,
+    ConfigLoaded,
+    PluginsDiscovered,
+    PluginsLoaded,
+    Startup,
+    BuildingCli,
+    CliBuilt,
+    PreCommand,
+    PostCommand,
+    /// Commands of other plugins too; a plugin always gets its own
+    ExecuteCommand,
+    /// `Custom` events with this name, or with a name starting with what
+    /// comes before a trailing `*`, like `"tools:*"`
+    Custom(String),
+    Shutdown,
+}
+
+impl EventKind {
+    /// Whether `event` is of this kind.
+    ///
+    /// ```
+    /// use drk_api::{EventKind, SystemEvent};
+    ///
+    /// let missing = SystemEvent::custom("mise", "tools:missing");
+    /// assert!(EventKind::Custom("tools:*".into()).matches(&missing));
+    /// assert!(!EventKind::Custom("tools".into()).matches(&missing));
+    /// assert!(!EventKind::Startup.matches(&missing));
+    /// ```
+    pub fn matches(&self, event: &SystemEvent) -> bool {
+        match (self, event) {
+            (EventKind::All, _)
+            | (EventKind::ConfigLoaded, SystemEvent::ConfigLoaded)
+            | (EventKind::PluginsDiscovered, SystemEvent::PluginsDiscovered { .. })
+            | (EventKind::PluginsLoaded, SystemEvent::PluginsLoaded { .. })
+            | (EventKind::Startup, SystemEvent::Startup)
+            | (EventKind::BuildingCli, SystemEvent::BuildingCli)
+            | (EventKind::CliBuilt, SystemEvent::CliBuilt { .. })
+            | (EventKind::PreCommand, SystemEvent::PreCommand { .. })
+            | (EventKind::PostCommand, SystemEvent::PostCommand { .. })
+            | (EventKind::ExecuteCommand, SystemEvent::ExecuteCommand { .. })
+            | (EventKind::Shutdown, SystemEvent::Shutdown) => true,
+            (EventKind::Custom(pattern), SystemEvent::Custom { event, .. }) => {
+                match pattern.strip_suffix('*') {
+                    Some(prefix) => event.starts_with(prefix),
+                    None => event == pattern,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
 // --- 3. PLUGIN METADATA ---
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PluginMetadata {
@@ -100,11 +559,202 @@ pub struct Context<'a> {
     pub config: &'a mut HashMap<String, toml::Value>,
     // A way to fire events back to the manager
     pub event_sender: &'a mut dyn FnMut(SystemEvent),
+    // This plugin's persistent state; saved after the call if it changed
+    pub state: &'a mut toml::Table,
+    // This plugin's data directory; `None` while it is over its size quota
+    pub data_dir: Option<&'a Path>,
+    // Tripped by the host on Ctrl-C
+    pub cancel: &'a CancellationToken,
+    // Registers cleanup for abnormal exit; dropped once the call returns
+    pub on_exit: &'a mut dyn FnMut(Box<dyn FnOnce() + Send>),
+    // Asks the host for a capability; true if the user allows it
+    pub permit: &'a mut dyn FnMut(Capability) -> bool,
 }
 
+/// The `Custom` event [`Context::save_config`] sends. The host handles it
+/// itself; other plugins never see it.
+pub const SAVE_CONFIG_EVENT: &str = "drk:save-config";
+
+impl Context<'_> {
+    /// Writes `ctx.config` back to the config file once this call returns.
+    /// Otherwise changes to it only last for the current run.
+    pub fn save_config(&mut self) {
+        (self.event_sender)(SystemEvent::custom("drk", SAVE_CONFIG_EVENT));
+    }
+
+    /// Hands the result of the running command to drk, which prints its
+    /// text, or emits its data under `--output json`. Call it once, at the
+    /// end; a later call replaces the earlier output.
+    pub fn output(&mut self, output: CommandOutput) {
+        let json = serde_json::to_string(&output).expect("outputs always serialize");
+        (self.event_sender)(SystemEvent::Custom {
+            source: "drk".to_string(),
+            event: OUTPUT_EVENT.to_string(),
+            payload: Some(std::sync::Arc::new(json)),
+            data: None,
+        });
+    }
+
+    /// Renders Markdown (help topics, descriptions, changelogs) as styled terminal text.
+    pub fn render_markdown(&self, text: &str) -> String {
+        markdown::render(text)
+    }
+
+    /// True once the user asked to stop (Ctrl-C). Check it between steps of
+    /// long-running work and return early.
+    pub fn cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// A handle to the cancellation state that can be moved into threads or
+    /// awaited with [`CancellationToken::wait`].
+    pub fn cancellation(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Step-by-step progress output for long commands; see [`Reporter`].
+    pub fn reporter(&self) -> Reporter {
+        Reporter::new(self.cancel.clone())
+    }
+
+    /// A spinner that turns until it's finished, for waits of unknown
+    /// length; see [`Progress`].
+    pub fn spinner(&self, message: &str) -> Progress {
+        Progress::spinner(message)
+    }
+
+    /// A bar for `total` units of work, advanced with [`Progress::inc`].
+    pub fn progress_bar(&self, total: u64, message: &str) -> Progress {
+        Progress::bar(total, message)
+    }
+
+    /// Questions for the user: confirmations, text and picks from a list;
+    /// see [`Ui`].
+    pub fn ui(&self) -> Ui {
+        Ui::new()
+    }
+
+    /// Runs `cleanup` if drk is killed (Ctrl-C, panic) before this call
+    /// returns, e.g. to delete a half-written file or release a lock. Once
+    /// the call returns normally the handler is forgotten.
+    pub fn on_abnormal_exit(&mut self, cleanup: impl FnOnce() + Send + 'static) {
+        (self.on_exit)(Box::new(cleanup));
+    }
+
+    /// Asks the user whether this plugin may use `capability`. The first
+    /// request prompts (allow once, allow always, deny) and a lasting answer
+    /// is saved in config. Call it before the work it guards and pass the
+    /// error on if it's denied:
+    ///
+    /// ```ignore
+    /// ctx.request_permission(Capability::Network)?;
+    /// ```
+    pub fn request_permission(&mut self, capability: Capability) -> Result<()> {
+        if (self.permit)(capability) {
+            Ok(())
+        } else {
+            anyhow::bail!("Permission to {} was denied", capability.describe())
+        }
+    }
+
+    /// This plugin's private data directory, created on first use.
+    /// Fails while the directory is over its configured size quota.
+    pub fn plugin_data_dir(&self) -> Result<&Path> {
+        let dir = self.data_dir.ok_or_else(|| {
+            anyhow::anyhow!(
+                "This plugin's data directory is over its quota; free it with `drk plugin clean`"
+            )
+        })?;
+        std::fs::create_dir_all(dir)?;
+        Ok(dir)
+    }
+
+    /// This plugin's passwords, tokens and passphrases, kept in the system
+    /// keychain where there is one; see [`Secrets`].
+    pub fn secrets(&self) -> Result<Secrets> {
+        let dir = self.plugin_data_dir()?;
+        // The data directory is named after the plugin
+        let plugin = dir.file_name().unwrap_or_default().to_string_lossy();
+        Ok(Secrets::new(&plugin, dir))
+    }
+
+    /// Shows `message` under `title` as a desktop notification; see
+    /// [`notify`](crate::notify).
+    pub fn notify(&self, title: &str, message: &str) -> Result<()> {
+        notify::notify(title, message)
+    }
+
+    /// Opens `text` in the user's editor and returns what they saved; see
+    /// [`editor`]. The text goes through a Markdown file in the temp
+    /// directory, readable only by the user and removed afterwards, even if
+    /// drk is interrupted while the editor is open.
+    pub fn edit(&mut self, text: &str) -> Result<String> {
+        let scratch = editor::scratch_file("md");
+        let removed = scratch.clone();
+        self.on_abnormal_exit(move || {
+            let _ = std::fs::remove_file(removed);
+        });
+        editor::edit_text(text, &scratch)
+    }
+
+    /// Opens the file at `path` in the user's editor and returns its
+    /// contents once they close it.
+    pub fn edit_file(&self, path: &Path) -> Result<String> {
+        editor::edit_file(path)?;
+        Ok(std::fs::read_to_string(path)?)
+    }
+
+    /// A file the plugin's package ships in its assets directory, e.g.
+    /// `ctx.asset("templates/flake.nix")`. Fails if the package doesn't
+    /// include it.
+    pub fn asset(&self, path: &str) -> Result<PathBuf> {
+        let relative = Path::new(path);
+        if relative.is_absolute() || relative.components().any(|c| c.as_os_str() == "..") {
+            anyhow::bail!(
+                "Asset path '{}' must stay inside the assets directory",
+                path
+            );
+        }
+        let dir = self.plugin_data_dir()?.join(ASSETS_DIR);
+        let file = dir.join(relative);
+        if !file.exists() {
+            anyhow::bail!("This plugin's package has no asset '{}'", path);
+        }
+        Ok(file)
+    }
+
+    /// Where `tool` is installed, if it's on `PATH`. Cached per process.
+    pub fn which(&self, tool: &str) -> Option<PathBuf> {
+        tools::which(tool)
+    }
+
+    /// The installed version of `tool` if it satisfies `requirement`, e.g.
+    /// `ctx.tool_version("nix", ">=2.18")?`. The error says what's missing
+    /// and how to install it on this OS, ready to show the user.
+    pub fn tool_version(&self, tool: &str, requirement: &str) -> Result<Version> {
+        tools::require(tool, requirement)
+    }
+
+    /// The tool versions the current project pins in its `drk.toml`, e.g.
+    /// to warn when the installed `nix` drifts from them:
+    ///
+    /// ```ignore
+    /// if let Some(ToolStatus::Drift(installed)) = ctx.tool_catalog()?.check("nix") {
+    ///     report.log(&format!("nix {} isn't the version this project pins", installed));
+    /// }
+    /// ```
+    pub fn tool_catalog(&self) -> Result<tools::Catalog> {
+        tools::Catalog::for_dir(&std::env::current_dir()?)
+    }
+}
+
+/// Subdirectory of a plugin's data directory holding the assets its package
+/// ships, see [`Context::asset`].
+pub const ASSETS_DIR: &str = "assets";
+
 // --- 5. THE PLUGIN TRAIT ---
 // All dynamic plugins must implement this.
-pub trait Plugin: Send + Sync {
+pub trait Plugin: AsAny + Send + Sync {
     fn metadata(&self) -> PluginMetadata;
 
     /// Returns the commands this plugin provides.
@@ -113,23 +763,225 @@ pub trait Plugin: Send + Sync {
         Vec::new()
     }
 
+    /// Commands that depend on the environment, e.g. one per task found in
+    /// the project's `drk.toml`. Asked once per run while the CLI is built;
+    /// a contributed command replaces a `get_commands` one of the same name.
+    fn contribute_commands(&mut self, _ctx: &mut Context) -> Result<Vec<PluginCommand>> {
+        Ok(Vec::new())
+    }
+
+    /// Layout version of this plugin's `[<name>]` config section.
+    /// Bump it whenever `migrate_config` learns a new step.
+    fn config_version(&self) -> u32 {
+        0
+    }
+
+    /// Upgrades the config section from version `from` to `from + 1`, e.g. by
+    /// renaming keys. Called once per step until the section is current.
+    fn migrate_config(&self, _from: u32, _section: &mut toml::Table) -> Result<()> {
+        Ok(())
+    }
+
+    /// The keys this plugin reads from its `[<name>]` config section, for
+    /// `drk config docs`.
+    fn config_schema(&self) -> Vec<ConfigKey> {
+        Vec::new()
+    }
+
+    /// Hints about this plugin's commands, for `drk tips`.
+    fn tips(&self) -> Vec<Tip> {
+        Vec::new()
+    }
+
+    /// The events this plugin wants; drk doesn't call `handle_event` (or
+    /// `on_pre_command`) for others, except `ExecuteCommand` for its own
+    /// commands. Asked once, when the plugin loads.
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![EventKind::All]
+    }
+
     fn on_load(&mut self) -> Result<()> {
         Ok(())
     }
 
+    /// Called once, the first time the plugin is loaded while enabled, and
+    /// again on `drk plugin reset <name>`. Create default config, fetch
+    /// assets into the data directory, or print a quickstart here.
+    fn on_install(&mut self, _ctx: &mut Context) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called before the plugin is dropped, on shutdown or when the host
+    /// unloads it, if `on_load` ran. Remove temp files and close connections.
     fn on_unload(&mut self) -> Result<()> {
         Ok(())
     }
 
+    /// Called when the running command was interrupted with Ctrl-C, before
+    /// drk exits. Roll back partial work here.
+    fn on_cancel(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Gets the raw arguments (everything after `drk`) of an invocation no
+    /// command matched, when this plugin is the one named in `[cli] fallback`.
+    /// Return `false` to let drk report the unknown command as usual.
+    fn handle_unknown_command(&mut self, _argv: &[String], _ctx: &mut Context) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Gets `PreCommand` before the command `name` runs with `args`. Return
+    /// `ControlFlow::Cancel(reason)` to stop it, e.g. from a policy or
+    /// confirmation plugin: drk then skips the command, prints the reason and
+    /// exits non-zero, and plugins after this one aren't asked. By default
+    /// the event goes to `handle_event` and the command runs.
+    fn on_pre_command(
+        &mut self,
+        name: &str,
+        args: &[String],
+        ctx: &mut Context,
+    ) -> Result<ControlFlow> {
+        let event = SystemEvent::PreCommand {
+            name: name.to_string(),
+            args: args.to_vec(),
+        };
+        self.handle_event(&event, ctx)?;
+        Ok(ControlFlow::Continue)
+    }
+
     // The handler now takes the strict SystemEvent enum
     fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> Result<()>;
 }
 
+/// Gives `dyn Plugin` access to the concrete type behind it. Implemented for
+/// every type, so plugins never implement it themselves.
+pub trait AsAny: Any {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl dyn Plugin {
+    /// True if the plugin is a `T`.
+    pub fn is<T: Plugin>(&self) -> bool {
+        self.as_any().is::<T>()
+    }
+
+    /// The plugin as a `T`, if that's what it is. Meant for plugins compiled
+    /// into the same binary; a type from a dynamically loaded library never
+    /// matches the host's type of the same name.
+    ///
+    /// # Example
+    /// ```
+    /// # use drk_api::{Context, Plugin, PluginMetadata, SystemEvent};
+    /// struct Counter(u32);
+    ///
+    /// impl Plugin for Counter {
+    ///     fn metadata(&self) -> PluginMetadata {
+    ///         PluginMetadata {
+    ///             name: "counter".into(),
+    ///             version: "0.1.0".into(),
+    ///             author: String::new(),
+    ///             description: String::new(),
+    ///             essential: false,
+    ///         }
+    ///     }
+    ///
+    ///     fn handle_event(&mut self, _: &SystemEvent, _: &mut Context) -> anyhow::Result<()> {
+    ///         self.0 += 1;
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let plugin: Box<dyn Plugin> = Box::new(Counter(3));
+    /// assert_eq!(plugin.downcast_ref::<Counter>().map(|c| c.0), Some(3));
+    /// ```
+    pub fn downcast_ref<T: Plugin>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+
+    pub fn downcast_mut<T: Plugin>(&mut self) -> Option<&mut T> {
+        self.as_any_mut().downcast_mut()
+    }
+}
+
 // --- 6. FFI MACRO ---
 // Plugins will use this macro to export themselves safely.
+
+/// Version of the plugin interface this drk-api implements. Bumped whenever
+/// the `Plugin` trait or the types it passes change incompatibly.
+pub const API_VERSION: u32 = 22;
+
+/// The compiler that built this copy of drk-api (and so the plugin linking
+/// it), as `rustc --version` prints it, or "unknown".
+pub const RUSTC_VERSION: &str = env!("DRK_RUSTC_VERSION");
+
+#[doc(hidden)]
+pub const RUSTC_VERSION_NUL: &str = concat!(env!("DRK_RUSTC_VERSION"), "\0");
+
+/// What `_plugin_create_v2` hands the loader.
+///
+/// Fields are only ever appended, so a loader can read the ones it knows
+/// about from a declaration made by a newer drk-api, and check
+/// `api_version` before trusting the rest.
+#[repr(C)]
+pub struct PluginDeclaration {
+    pub api_version: u32,
+    /// Metadata without handing over an instance
+    pub metadata: fn() -> PluginMetadata,
+    pub create: fn() -> Box<dyn Plugin>,
+}
+
 #[macro_export]
 macro_rules! declare_plugin {
     ($plugin_type:ty, $constructor:path) => {
+        #[no_mangle]
+        pub extern "C" fn _plugin_vtable() -> *const $crate::abi::PluginVTable {
+            extern "C" fn create() -> *mut ::std::ffi::c_void {
+                let constructor: fn() -> $plugin_type = $constructor;
+                $crate::abi::create_instance(|| Box::new(constructor()))
+            }
+            static VTABLE: $crate::abi::PluginVTable = $crate::abi::PluginVTable::new(create);
+            &VTABLE
+        }
+
+        // What the same-compiler entry points below need the host to match
+        #[no_mangle]
+        pub extern "C" fn _plugin_api_version() -> u32 {
+            $crate::API_VERSION
+        }
+
+        #[no_mangle]
+        pub extern "C" fn _plugin_rustc_version() -> *const ::std::os::raw::c_char {
+            $crate::RUSTC_VERSION_NUL.as_ptr().cast()
+        }
+
+        // Same-compiler entry points, kept for hosts that predate `_plugin_vtable`
+        #[no_mangle]
+        pub extern "C" fn _plugin_create_v2() -> *const $crate::PluginDeclaration {
+            static DECLARATION: $crate::PluginDeclaration = $crate::PluginDeclaration {
+                api_version: $crate::API_VERSION,
+                metadata: || {
+                    let constructor: fn() -> $plugin_type = $constructor;
+                    $crate::Plugin::metadata(&constructor())
+                },
+                create: || {
+                    let constructor: fn() -> $plugin_type = $constructor;
+                    Box::new(constructor())
+                },
+            };
+            &DECLARATION
+        }
+
         #[no_mangle]
         pub extern "C" fn _plugin_create() -> *mut dyn $crate::Plugin {
             // Create the plugin and leak it into a raw pointer for the CLI to take
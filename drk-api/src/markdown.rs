@@ -0,0 +1,255 @@
+//! Markdown rendering for the terminal.
+//!
+//! Help topics, registry descriptions and changelogs are written in Markdown;
+//! [`render`] turns them into styled text with headings, lists, quotes and
+//! indented code blocks. Colors follow the usual `console` rules, so piped
+//! output stays plain.
+
+use crate::styling::{box_chars, glyph_profile, GlyphProfile};
+use console::Style;
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+/// Renders `text` as Markdown for display in a terminal.
+///
+/// # Example
+/// ```
+/// # use drk_api::markdown::render;
+/// let out = render("# Title\n\n- one\n- two");
+/// assert!(out.contains("Title"));
+/// assert!(out.contains("one"));
+/// ```
+pub fn render(text: &str) -> String {
+    let mut renderer = Renderer::default();
+    for event in Parser::new_ext(
+        text,
+        Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS,
+    ) {
+        renderer.event(event);
+    }
+    renderer.out.trim_end().to_string()
+}
+
+#[derive(Default)]
+struct Renderer {
+    out: String,
+    at_line_start: bool,
+    /// A blank line is owed before the next block.
+    blank_pending: bool,
+    bold: bool,
+    italic: bool,
+    strike: bool,
+    heading: Option<HeadingLevel>,
+    in_code_block: bool,
+    quote_depth: usize,
+    /// One entry per open list: the next number, or `None` for bullets.
+    lists: Vec<Option<u64>>,
+    /// Destination and text start of the open link.
+    link: Option<(String, usize)>,
+}
+
+impl Renderer {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) if self.in_code_block => {
+                for line in text.split_inclusive('\n') {
+                    let (content, newline) = match line.strip_suffix('\n') {
+                        Some(content) => (content, true),
+                        None => (line, false),
+                    };
+                    self.prefix();
+                    self.out.push_str("    ");
+                    self.out
+                        .push_str(&Style::new().yellow().apply_to(content).to_string());
+                    if newline {
+                        self.newline();
+                    }
+                }
+            }
+            Event::Text(text) => self.write(&text),
+            Event::Code(code) => {
+                self.prefix();
+                let styled = Style::new().yellow().apply_to(code.as_ref()).to_string();
+                self.out.push_str(&styled);
+            }
+            Event::InlineMath(text) | Event::DisplayMath(text) => self.write(&text),
+            Event::Html(html) | Event::InlineHtml(html) => {
+                self.prefix();
+                self.out.push_str(&html);
+            }
+            Event::FootnoteReference(name) => self.write(&format!("[{}]", name)),
+            Event::SoftBreak => self.write(" "),
+            Event::HardBreak => self.newline(),
+            Event::Rule => {
+                self.begin_block();
+                self.prefix();
+                let rule = box_chars().horizontal.repeat(40);
+                self.out
+                    .push_str(&Style::new().dim().apply_to(rule).to_string());
+                self.end_block();
+            }
+            Event::TaskListMarker(done) => self.write(if done { "[x] " } else { "[ ] " }),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph | Tag::HtmlBlock => self.begin_block(),
+            Tag::Heading { level, .. } => {
+                self.begin_block();
+                self.heading = Some(level);
+            }
+            Tag::BlockQuote(_) => {
+                self.begin_block();
+                self.quote_depth += 1;
+            }
+            Tag::CodeBlock(_) => {
+                self.begin_block();
+                self.in_code_block = true;
+            }
+            Tag::List(first) => {
+                if self.lists.is_empty() {
+                    self.begin_block();
+                } else if !self.at_line_start {
+                    self.newline();
+                }
+                self.lists.push(first);
+            }
+            Tag::Item => {
+                if !self.at_line_start {
+                    self.newline();
+                }
+                self.prefix();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => match glyph_profile() {
+                        GlyphProfile::Unicode => "• ".to_string(),
+                        GlyphProfile::Ascii => "- ".to_string(),
+                    },
+                };
+                self.out.push_str(&marker);
+            }
+            Tag::Emphasis => self.italic = true,
+            Tag::Strong => self.bold = true,
+            Tag::Strikethrough => self.strike = true,
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                self.link = Some((dest_url.to_string(), self.out.len()));
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph | TagEnd::HtmlBlock => self.end_block(),
+            TagEnd::Heading(_) => {
+                self.heading = None;
+                self.end_block();
+            }
+            TagEnd::BlockQuote(_) => {
+                self.quote_depth -= 1;
+                self.end_block();
+            }
+            TagEnd::CodeBlock => {
+                self.in_code_block = false;
+                self.end_block();
+            }
+            TagEnd::List(_) => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.end_block();
+                }
+            }
+            TagEnd::Item if !self.at_line_start => self.newline(),
+            TagEnd::Emphasis => self.italic = false,
+            TagEnd::Strong => self.bold = false,
+            TagEnd::Strikethrough => self.strike = false,
+            TagEnd::Link | TagEnd::Image => {
+                if let Some((url, start)) = self.link.take() {
+                    // Autolinks already show the address as their text
+                    let shown = console::strip_ansi_codes(&self.out[start..]).to_string();
+                    if shown != url {
+                        let styled = Style::new().dim().apply_to(format!(" ({})", url));
+                        self.out.push_str(&styled.to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        self.prefix();
+        let styled = self.style().apply_to(text).to_string();
+        self.out.push_str(&styled);
+    }
+
+    fn style(&self) -> Style {
+        let mut style = Style::new();
+        match self.heading {
+            Some(HeadingLevel::H1) => style = style.cyan().bold().underlined(),
+            Some(HeadingLevel::H2) => style = style.cyan().bold(),
+            Some(_) => style = style.bold(),
+            None => {}
+        }
+        if self.bold {
+            style = style.bold();
+        }
+        if self.italic {
+            style = style.italic();
+        }
+        if self.strike {
+            style = style.strikethrough();
+        }
+        if self.link.is_some() {
+            style = style.underlined();
+        }
+        style
+    }
+
+    /// Writes quote bars and list indentation at the start of a line.
+    fn prefix(&mut self) {
+        if !self.at_line_start {
+            return;
+        }
+        self.at_line_start = false;
+        let bar = format!("{} ", box_chars().vertical);
+        for _ in 0..self.quote_depth {
+            self.out
+                .push_str(&Style::new().dim().apply_to(&bar).to_string());
+        }
+        self.out
+            .push_str(&"  ".repeat(self.lists.len().saturating_sub(1)));
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.at_line_start = true;
+    }
+
+    fn begin_block(&mut self) {
+        if !self.at_line_start && !self.out.is_empty() {
+            self.newline();
+        }
+        // Blocks inside list items stay tight
+        if self.blank_pending && self.lists.is_empty() {
+            self.prefix();
+            self.newline();
+        }
+        self.blank_pending = false;
+        if self.out.is_empty() {
+            self.at_line_start = true;
+        }
+    }
+
+    fn end_block(&mut self) {
+        if !self.at_line_start {
+            self.newline();
+        }
+        self.blank_pending = true;
+    }
+}
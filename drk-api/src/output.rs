@@ -0,0 +1,54 @@
+//! A command's result as data plus the text a person reads, so the same
+//! command works in a terminal and under `drk --output json`.
+
+use serde::{Deserialize, Serialize};
+
+/// The `Custom` event [`crate::Context::output`] sends, with the output as
+/// JSON text in the payload. The host handles it itself; other plugins
+/// never see it.
+pub const OUTPUT_EVENT: &str = "drk:output";
+
+/// What a command hands back to drk instead of (or besides) printing.
+///
+/// drk prints `text` normally, or `data` pretty-printed if there's no text;
+/// with `--output json` it emits `data` as-is for scripts.
+///
+/// # Example
+/// ```
+/// use drk_api::CommandOutput;
+///
+/// let output = CommandOutput::new(serde_json::json!({ "greeted": "Bob" })).with_text("Hello Bob!");
+/// assert_eq!(output.render(), "Hello Bob!");
+/// assert_eq!(CommandOutput::new(3).render(), "3");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommandOutput {
+    /// Machine-readable result
+    pub data: serde_json::Value,
+    /// Human-readable result, already styled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+impl CommandOutput {
+    /// Output carrying `data`; anything that serializes to JSON.
+    pub fn new(data: impl Serialize) -> Self {
+        Self {
+            data: serde_json::to_value(data).unwrap_or(serde_json::Value::Null),
+            text: None,
+        }
+    }
+
+    pub fn with_text(mut self, text: impl Into<String>) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    /// What drk prints for this output in text mode.
+    pub fn render(&self) -> String {
+        match &self.text {
+            Some(text) => text.clone(),
+            None => serde_json::to_string_pretty(&self.data).unwrap_or_default(),
+        }
+    }
+}
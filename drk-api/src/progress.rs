@@ -0,0 +1,209 @@
+//! Spinners and progress bars, from `ctx.spinner()` and `ctx.progress_bar()`.
+//!
+//! On a terminal the line is redrawn in place from a thread of its own, so a
+//! spinner keeps turning while the plugin blocks on a network call. When
+//! stdout isn't a terminal (CI logs, pipes, `--output json`) nothing is
+//! drawn while it runs; `finish` and `fail` still print their one line.
+//!
+//! ```no_run
+//! # fn demo(ctx: &drk_api::Context, files: &[std::path::PathBuf]) -> anyhow::Result<()> {
+//! let spinner = ctx.spinner("Fetching the template list");
+//! // ... the request ...
+//! spinner.finish("Fetched 42 templates");
+//!
+//! let bar = ctx.progress_bar(files.len() as u64, "Copying");
+//! for file in files {
+//!     bar.set_message(&format!("Copying {}", file.display()));
+//!     // ... the copy ...
+//!     bar.inc(1);
+//! }
+//! bar.finish("Copied everything");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::styling::{
+    icon_error, icon_success, progress_chars, spinner_frames, style_dim, style_error,
+    style_primary, style_success,
+};
+use crate::text::truncate;
+use console::Term;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How often a live line is redrawn.
+const TICK: Duration = Duration::from_millis(100);
+
+/// A spinner, or a bar when the amount of work is known. Cheap to share
+/// between threads by reference; every method takes `&self`.
+pub struct Progress {
+    shared: Arc<Shared>,
+    ticker: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    term: Term,
+    live: bool,
+    stopped: AtomicBool,
+    state: Mutex<State>,
+}
+
+struct State {
+    message: String,
+    position: u64,
+    /// `None` for a spinner
+    total: Option<u64>,
+    started: Instant,
+    frame: usize,
+}
+
+impl Progress {
+    /// A spinner for work of unknown length.
+    pub fn spinner(message: &str) -> Self {
+        Self::start(message, None)
+    }
+
+    /// A bar filling up as the position goes from 0 to `total`.
+    pub fn bar(total: u64, message: &str) -> Self {
+        Self::start(message, Some(total))
+    }
+
+    fn start(message: &str, total: Option<u64>) -> Self {
+        let term = Term::stdout();
+        let shared = Arc::new(Shared {
+            live: term.features().is_attended(),
+            term,
+            stopped: AtomicBool::new(false),
+            state: Mutex::new(State {
+                message: message.to_string(),
+                position: 0,
+                total,
+                started: Instant::now(),
+                frame: 0,
+            }),
+        });
+        let ticker = shared.live.then(|| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                while !shared.stopped.load(Ordering::SeqCst) {
+                    shared.draw();
+                    std::thread::sleep(TICK);
+                }
+            })
+        });
+        Self { shared, ticker }
+    }
+
+    pub fn set_message(&self, message: &str) {
+        self.shared.state().message = message.to_string();
+    }
+
+    /// Moves a bar forward by `delta`.
+    pub fn inc(&self, delta: u64) {
+        let mut state = self.shared.state();
+        state.position = state.position.saturating_add(delta);
+    }
+
+    pub fn set_position(&self, position: u64) {
+        self.shared.state().position = position;
+    }
+
+    /// Replaces the line with `message` and a success icon.
+    pub fn finish(mut self, message: &str) {
+        self.stop();
+        let elapsed = self.shared.state().started.elapsed();
+        let _ = self.shared.term.write_line(&format!(
+            "{} {} {}",
+            style_success(icon_success()),
+            message,
+            style_dim(&seconds(elapsed))
+        ));
+    }
+
+    /// Replaces the line with `message` and an error icon.
+    pub fn fail(mut self, message: &str) {
+        self.stop();
+        let _ = self.shared.term.write_line(&format!(
+            "{} {}",
+            style_error(icon_error()),
+            style_error(message)
+        ));
+    }
+
+    /// Removes the line without a trace, e.g. when `ctx.reporter()` goes on
+    /// to say how it went. Dropping the progress does the same.
+    pub fn clear(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        if let Some(ticker) = self.ticker.take() {
+            let _ = ticker.join();
+            let _ = self.shared.term.clear_line();
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Shared {
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn draw(&self) {
+        let line = {
+            let mut state = self.state();
+            let frames = spinner_frames();
+            state.frame = (state.frame + 1) % frames.len();
+            let elapsed = style_dim(&seconds(state.started.elapsed())).to_string();
+            match state.total {
+                None => format!(
+                    "{} {} {}",
+                    style_primary(frames[state.frame]),
+                    state.message,
+                    elapsed
+                ),
+                Some(total) => format!(
+                    "{} {} {} {}",
+                    bar(state.position, Some(total)),
+                    state.message,
+                    style_dim(&format!("{}/{}", state.position.min(total), total)),
+                    elapsed
+                ),
+            }
+        };
+        // A wrapped line couldn't be cleared in place
+        let width = (self.term.size().1 as usize).saturating_sub(1).max(20);
+        let _ = self.term.clear_line();
+        let _ = self.term.write_str(&truncate(&line, width));
+    }
+}
+
+/// A 20-character bar for `position` out of `total`; empty if the total
+/// isn't known.
+pub(crate) fn bar(position: u64, total: Option<u64>) -> String {
+    const WIDTH: usize = 20;
+    let chars: Vec<char> = progress_chars().chars().collect();
+    let (full, empty) = (chars[0], chars[chars.len() - 1]);
+    let filled = match total {
+        Some(total) if total > 0 => ((position.min(total) * WIDTH as u64) / total) as usize,
+        _ => 0,
+    };
+    format!(
+        "[{}{}]",
+        full.to_string().repeat(filled),
+        empty.to_string().repeat(WIDTH - filled)
+    )
+}
+
+fn seconds(elapsed: Duration) -> String {
+    format!("{:.2}s", elapsed.as_secs_f64())
+}
@@ -0,0 +1,348 @@
+//! Progress output for long-running commands, from `ctx.reporter()`.
+//!
+//! On a terminal the running step is redrawn in place as a small tree, with
+//! the last few lines of output underneath (like `cargo` or `nix build`);
+//! each finished step collapses to one line with its duration. Anywhere else
+//! every event is a plain line stamped with the time since the start, which
+//! reads well in CI logs.
+//!
+//! ```no_run
+//! # fn demo(ctx: &drk_api::Context) -> anyhow::Result<()> {
+//! let mut report = ctx.reporter();
+//! report.step("Fetching templates");
+//! report.log("GET https://api.github.com/...");
+//! report.step("Initializing flake");
+//! report.exec(std::process::Command::new("nix").args(["flake", "init"]))?;
+//! report.finish();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::styling::{
+    box_chars, icon_error, icon_success, spinner_frames, style_dim, style_error, style_primary,
+    style_success,
+};
+use crate::text::truncate;
+use crate::CancellationToken;
+use anyhow::{anyhow, bail, Result};
+use console::Term;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Output lines kept under the running step on a terminal.
+const TAIL_LINES: usize = 5;
+
+struct Step {
+    title: String,
+    started: Instant,
+}
+
+/// A finished substep, kept to redraw the step's tree.
+struct DoneSubstep {
+    title: String,
+    elapsed: Duration,
+    ok: bool,
+}
+
+pub struct Reporter {
+    term: Term,
+    live: bool,
+    started: Instant,
+    cancel: CancellationToken,
+    step: Option<Step>,
+    substep: Option<Step>,
+    substeps_done: Vec<DoneSubstep>,
+    /// Recent output of the running step
+    tail: Vec<String>,
+    /// Lines of the running step currently on screen (live mode)
+    drawn: usize,
+    frame: usize,
+}
+
+impl Reporter {
+    /// A reporter that draws live when stdout is a terminal. Child processes
+    /// started with [`Reporter::exec`] are killed once `cancel` trips.
+    pub fn new(cancel: CancellationToken) -> Self {
+        let term = Term::stdout();
+        Self {
+            live: term.features().is_attended(),
+            term,
+            started: Instant::now(),
+            cancel,
+            step: None,
+            substep: None,
+            substeps_done: Vec::new(),
+            tail: Vec::new(),
+            drawn: 0,
+            frame: 0,
+        }
+    }
+
+    /// Completes the running step, if any, and starts a new one.
+    pub fn step(&mut self, title: &str) {
+        self.end_step(true, None);
+        self.plain(0, &format!("==> {}", title));
+        self.step = Some(Step {
+            title: title.to_string(),
+            started: Instant::now(),
+        });
+        self.redraw();
+    }
+
+    /// Completes the running substep, if any, and starts a new one under the
+    /// current step.
+    pub fn substep(&mut self, title: &str) {
+        if self.step.is_none() {
+            return self.step(title);
+        }
+        self.end_substep(true);
+        self.plain(1, &format!("-> {}", title));
+        self.substep = Some(Step {
+            title: title.to_string(),
+            started: Instant::now(),
+        });
+        self.redraw();
+    }
+
+    /// A line of detail under the running step.
+    pub fn log(&mut self, line: &str) {
+        let depth = if self.substep.is_some() { 3 } else { 2 };
+        self.plain(depth, line);
+        self.tail.push(line.to_string());
+        if self.tail.len() > TAIL_LINES {
+            self.tail.remove(0);
+        }
+        self.redraw();
+    }
+
+    /// Marks the running step as failed with `message`. The output that led
+    /// up to it stays on screen.
+    pub fn fail(&mut self, message: &str) {
+        self.end_step(false, Some(message));
+    }
+
+    /// Marks the running step as done. Dropping a reporter without calling
+    /// this marks the step as failed, so an early `?` doesn't look like success.
+    pub fn finish(mut self) {
+        self.end_step(true, None);
+    }
+
+    /// Runs `command`, logging each line it prints under the running step.
+    /// Fails if it can't start, exits unsuccessfully, or drk is interrupted
+    /// (which kills it).
+    pub fn exec(&mut self, command: &mut Command) -> Result<()> {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Could not start `{}`: {}", program, e))?;
+
+        let (lines, received) = mpsc::channel();
+        let readers: Vec<_> = [
+            child
+                .stdout
+                .take()
+                .map(|s| Box::new(s) as Box<dyn Read + Send>),
+            child
+                .stderr
+                .take()
+                .map(|s| Box::new(s) as Box<dyn Read + Send>),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|stream| {
+            let lines = lines.clone();
+            std::thread::spawn(move || {
+                for line in BufReader::new(stream).lines().map_while(|l| l.ok()) {
+                    let _ = lines.send(line);
+                }
+            })
+        })
+        .collect();
+        drop(lines);
+
+        loop {
+            match received.recv_timeout(Duration::from_millis(100)) {
+                Ok(line) => self.log(&line),
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if self.cancel.is_cancelled() {
+                        let _ = child.kill();
+                    }
+                    self.redraw();
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        for reader in readers {
+            let _ = reader.join();
+        }
+
+        let status = child.wait()?;
+        if self.cancel.is_cancelled() {
+            bail!("`{}` was interrupted", program);
+        }
+        if !status.success() {
+            bail!("`{}` failed ({})", program, status);
+        }
+        Ok(())
+    }
+
+    fn end_substep(&mut self, ok: bool) {
+        let Some(substep) = self.substep.take() else {
+            return;
+        };
+        let elapsed = substep.started.elapsed();
+        if !ok {
+            self.plain(1, &format!("failed: {}", substep.title));
+        }
+        self.substeps_done.push(DoneSubstep {
+            title: substep.title,
+            elapsed,
+            ok,
+        });
+        // A substep's output isn't interesting once it's done
+        if ok {
+            self.tail.clear();
+        }
+    }
+
+    fn end_step(&mut self, ok: bool, message: Option<&str>) {
+        self.end_substep(ok);
+        let Some(step) = self.step.take() else {
+            return;
+        };
+        let elapsed = step.started.elapsed();
+        let outcome = if ok { "ok" } else { "failed" };
+        self.plain(
+            0,
+            &format!("{}: {} ({})", outcome, step.title, seconds(elapsed)),
+        );
+        if let Some(message) = message {
+            self.plain(0, message);
+        }
+
+        if self.live {
+            self.clear();
+            let mut lines = vec![step_line(ok, &step.title, elapsed)];
+            lines.extend(self.substep_lines());
+            if !ok {
+                lines.extend(self.tail.iter().map(|l| format!("    {}", style_dim(l))));
+                if let Some(message) = message {
+                    lines.push(format!("  {}", style_error(message)));
+                }
+            }
+            for line in lines {
+                let _ = self.term.write_line(&self.fit(&line));
+            }
+        }
+        self.substeps_done.clear();
+        self.tail.clear();
+    }
+
+    /// Writes a timestamped line when not drawing live.
+    fn plain(&self, depth: usize, text: &str) {
+        if !self.live {
+            println!(
+                "[{:>8}] {}{}",
+                seconds(self.started.elapsed()),
+                " ".repeat(depth * 2),
+                text
+            );
+        }
+    }
+
+    fn substep_lines(&self) -> Vec<String> {
+        let chars = box_chars();
+        self.substeps_done
+            .iter()
+            .map(|done| {
+                let icon = if done.ok {
+                    style_success(icon_success()).to_string()
+                } else {
+                    style_error(icon_error()).to_string()
+                };
+                format!(
+                    "  {}{} {} {} {}",
+                    chars.tee,
+                    chars.horizontal,
+                    icon,
+                    done.title,
+                    style_dim(&seconds(done.elapsed))
+                )
+            })
+            .collect()
+    }
+
+    /// Replaces the running step's lines with its current state.
+    fn redraw(&mut self) {
+        if !self.live {
+            return;
+        }
+        let Some(step) = &self.step else {
+            return;
+        };
+        let frames = spinner_frames();
+        self.frame = (self.frame + 1) % frames.len();
+        let spinner = style_primary(frames[self.frame]).to_string();
+
+        let mut lines = vec![format!(
+            "{} {} {}",
+            spinner,
+            step.title,
+            style_dim(&seconds(step.started.elapsed()))
+        )];
+        lines.extend(self.substep_lines());
+        if let Some(substep) = &self.substep {
+            let chars = box_chars();
+            lines.push(format!(
+                "  {}{} {} {}",
+                chars.bottom_left, chars.horizontal, spinner, substep.title
+            ));
+        }
+        lines.extend(self.tail.iter().map(|l| format!("    {}", style_dim(l))));
+
+        self.clear();
+        for line in &lines {
+            let _ = self.term.write_line(&self.fit(line));
+        }
+        self.drawn = lines.len();
+    }
+
+    fn clear(&mut self) {
+        if self.drawn > 0 {
+            let _ = self.term.clear_last_lines(self.drawn);
+            self.drawn = 0;
+        }
+    }
+
+    /// Wrapped lines would throw off how many lines `clear` removes.
+    fn fit(&self, line: &str) -> String {
+        let width = self.term.size().1 as usize;
+        truncate(line, width.saturating_sub(1).max(10))
+    }
+}
+
+impl Drop for Reporter {
+    fn drop(&mut self) {
+        if self.step.is_some() {
+            self.end_step(false, None);
+        }
+    }
+}
+
+fn step_line(ok: bool, title: &str, elapsed: Duration) -> String {
+    let icon = if ok {
+        style_success(icon_success()).to_string()
+    } else {
+        style_error(icon_error()).to_string()
+    };
+    format!("{} {} {}", icon, title, style_dim(&seconds(elapsed)))
+}
+
+fn seconds(elapsed: Duration) -> String {
+    format!("{:.2}s", elapsed.as_secs_f64())
+}
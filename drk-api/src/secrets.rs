@@ -0,0 +1,217 @@
+//! Passwords, tokens and passphrases a plugin keeps between runs, through
+//! `ctx.secrets()`.
+//!
+//! They go to the system keychain when drk can reach one: the macOS login
+//! keychain through `security`, or the Secret Service (GNOME Keyring,
+//! KWallet) through `secret-tool`. Elsewhere they're kept in `secrets.toml`
+//! in the plugin's data directory, readable only by the user but not
+//! encrypted.
+//!
+//! ```no_run
+//! # fn demo(ctx: &drk_api::Context) -> anyhow::Result<()> {
+//! let secrets = ctx.secrets()?;
+//! if secrets.get("token")?.is_none() {
+//!     secrets.set("token", &ctx.ui().password("GitHub token")?)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::tools::which;
+use anyhow::{bail, Context as _, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The file secrets are kept in without a keychain.
+const SECRETS_FILE: &str = "secrets.toml";
+
+/// One plugin's secrets; see the module docs.
+pub struct Secrets {
+    /// What they're filed under in a keychain, `drk:<plugin>`
+    service: String,
+    backend: Backend,
+}
+
+enum Backend {
+    /// macOS, through `security`
+    Keychain,
+    /// Linux desktops, through `secret-tool`
+    SecretService,
+    File(PathBuf),
+}
+
+impl Secrets {
+    /// The secrets of `plugin`, kept in `dir` when there's no keychain.
+    pub fn new(plugin: &str, dir: &Path) -> Self {
+        let backend = if cfg!(target_os = "macos") && which("security").is_some() {
+            Backend::Keychain
+        } else if which("secret-tool").is_some()
+            && std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some()
+        {
+            Backend::SecretService
+        } else {
+            Backend::File(dir.join(SECRETS_FILE))
+        };
+        Self {
+            service: format!("drk:{}", plugin),
+            backend,
+        }
+    }
+
+    /// Whether they're kept in a system keychain rather than a plain file.
+    pub fn in_keychain(&self) -> bool {
+        !matches!(self.backend, Backend::File(_))
+    }
+
+    /// Where the secrets are kept, for telling the user.
+    pub fn location(&self) -> String {
+        match &self.backend {
+            Backend::Keychain => "the macOS keychain".to_string(),
+            Backend::SecretService => "the Secret Service keyring".to_string(),
+            Backend::File(path) => path.display().to_string(),
+        }
+    }
+
+    /// The secret saved as `name`, if there is one.
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        let output = match &self.backend {
+            Backend::Keychain => Command::new("security")
+                .args([
+                    "find-generic-password",
+                    "-s",
+                    &self.service,
+                    "-a",
+                    name,
+                    "-w",
+                ])
+                .output()?,
+            Backend::SecretService => Command::new("secret-tool")
+                .args(["lookup", "service", &self.service, "account", name])
+                .output()?,
+            Backend::File(path) => {
+                let table = read_file(path)?;
+                return Ok(table.get(name).and_then(|v| v.as_str()).map(str::to_string));
+            }
+        };
+        // Both tools fail when there's nothing saved under the name
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let secret = String::from_utf8(output.stdout).context("The saved secret isn't text")?;
+        Ok(Some(secret.trim_end_matches('\n').to_string()))
+    }
+
+    /// Saves `secret` as `name`, replacing what was saved before.
+    pub fn set(&self, name: &str, secret: &str) -> Result<()> {
+        match &self.backend {
+            // A trailing `-w` without a value makes `security` prompt for the
+            // password twice, which it reads from stdin
+            Backend::Keychain => run_with_input(
+                Command::new("security").args([
+                    "add-generic-password",
+                    "-U",
+                    "-s",
+                    &self.service,
+                    "-a",
+                    name,
+                    "-w",
+                ]),
+                &format!("{}\n{}\n", secret, secret),
+            ),
+            Backend::SecretService => {
+                let label = format!("{} {}", self.service, name);
+                run_with_input(
+                    Command::new("secret-tool")
+                        .args(["store", "--label", &label])
+                        .args(["service", &self.service, "account", name]),
+                    secret,
+                )
+            }
+            Backend::File(path) => {
+                let mut table = read_file(path)?;
+                table.insert(name.to_string(), secret.into());
+                write_file(path, &table)
+            }
+        }
+    }
+
+    /// Forgets the secret saved as `name`; nothing happens if there's none.
+    pub fn remove(&self, name: &str) -> Result<()> {
+        match &self.backend {
+            Backend::Keychain => {
+                // Fails when there's nothing to delete, which is fine
+                Command::new("security")
+                    .args(["delete-generic-password", "-s", &self.service, "-a", name])
+                    .output()?;
+                Ok(())
+            }
+            Backend::SecretService => run(Command::new("secret-tool").args([
+                "clear",
+                "service",
+                &self.service,
+                "account",
+                name,
+            ])),
+            Backend::File(path) => {
+                let mut table = read_file(path)?;
+                if table.remove(name).is_some() {
+                    write_file(path, &table)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    check(command.stdin(Stdio::null()).output()?)
+}
+
+/// Runs `command` with `input` on its stdin. Secrets go this way so they
+/// never show in the process list, as arguments would.
+fn run_with_input(command: &mut Command, input: &str) -> Result<()> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(input.as_bytes())?;
+    check(child.wait_with_output()?)
+}
+
+fn check(output: std::process::Output) -> Result<()> {
+    if !output.status.success() {
+        bail!(
+            "Could not save to the keychain: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn read_file(path: &Path) -> Result<toml::Table> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => text
+            .parse()
+            .with_context(|| format!("Could not read {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(toml::Table::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_file(path: &Path, table: &toml::Table) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Could not write {}", path.display()))?;
+    file.write_all(toml::to_string(table)?.as_bytes())?;
+    Ok(())
+}
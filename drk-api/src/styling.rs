@@ -5,83 +5,300 @@
 
 pub use console;
 
-use console::{style, Style};
+use console::Style;
+
+// --- Themes ---
+
+/// Built-in color themes. Every style role and icon below follows the active one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Default,
+    /// Blue/orange instead of green/red, safe for red-green color blindness
+    Deuteranopia,
+    /// Bright, bold colors and no dimmed text
+    HighContrast,
+    /// No colors; roles differ by weight and icons spell out their meaning
+    Monochrome,
+}
+
+impl Theme {
+    pub const NAMES: &'static [&'static str] =
+        &["default", "deuteranopia", "high-contrast", "monochrome"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::Default),
+            "deuteranopia" => Some(Self::Deuteranopia),
+            "high-contrast" => Some(Self::HighContrast),
+            "monochrome" => Some(Self::Monochrome),
+            _ => None,
+        }
+    }
+}
+
+/// Environment variable selecting the theme by name.
+///
+/// Like [`GLYPHS_ENV`], the host sets it from `--theme` or `[output] theme` so
+/// every plugin's copy of this crate agrees.
+pub const THEME_ENV: &str = "DRK_THEME";
+
+/// The active theme; unknown names fall back to the default.
+pub fn theme() -> Theme {
+    std::env::var(THEME_ENV)
+        .ok()
+        .and_then(|name| Theme::from_name(&name))
+        .unwrap_or(Theme::Default)
+}
 
 /// Style for primary/important text (cyan, bold)
 ///
 /// # Example
 /// ```
+/// # use drk_api::styling::style_primary;
 /// let text = style_primary("Hello");
 /// println!("{}", text);
 /// ```
 pub fn style_primary(text: &str) -> console::StyledObject<&str> {
-    style(text).cyan().bold()
+    let style = match theme() {
+        Theme::Default | Theme::Deuteranopia => Style::new().cyan().bold(),
+        Theme::HighContrast => Style::new().white().bright().bold(),
+        Theme::Monochrome => Style::new().bold(),
+    };
+    style.apply_to(text)
 }
 
 /// Style for success messages (green)
 pub fn style_success(text: &str) -> console::StyledObject<&str> {
-    style(text).green()
+    let style = match theme() {
+        Theme::Default => Style::new().green(),
+        Theme::Deuteranopia => Style::new().blue(),
+        Theme::HighContrast => Style::new().green().bright().bold(),
+        Theme::Monochrome => Style::new(),
+    };
+    style.apply_to(text)
 }
 
 /// Style for warnings (yellow)
 pub fn style_warning(text: &str) -> console::StyledObject<&str> {
-    style(text).yellow()
+    let style = match theme() {
+        Theme::Default | Theme::Deuteranopia => Style::new().yellow(),
+        Theme::HighContrast => Style::new().yellow().bright().bold(),
+        Theme::Monochrome => Style::new().bold(),
+    };
+    style.apply_to(text)
 }
 
 /// Style for errors (red, bold)
 pub fn style_error(text: &str) -> console::StyledObject<&str> {
-    style(text).red().bold()
+    let style = match theme() {
+        Theme::Default => Style::new().red().bold(),
+        // Orange reads as distinct from the blue success color
+        Theme::Deuteranopia => Style::new().color256(208).bold(),
+        Theme::HighContrast => Style::new().red().bright().bold(),
+        Theme::Monochrome => Style::new().bold().underlined(),
+    };
+    style.apply_to(text)
 }
 
 /// Style for dimmed/secondary text (dim white)
 pub fn style_dim(text: &str) -> console::StyledObject<&str> {
-    style(text).dim()
+    let style = match theme() {
+        Theme::HighContrast => Style::new(),
+        _ => Style::new().dim(),
+    };
+    style.apply_to(text)
 }
 
 /// Create a custom style
 ///
 /// # Example
 /// ```
+/// # use drk_api::styling::create_style;
 /// let my_style = create_style().magenta().italic();
 /// ```
 pub fn create_style() -> Style {
     Style::new()
 }
 
+// --- Glyph Profiles ---
+
+/// Which characters are safe to print on this terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphProfile {
+    Unicode,
+    Ascii,
+}
+
+/// Environment variable selecting the profile ("unicode", "ascii" or "auto").
+///
+/// The host sets it from `[output] glyphs` in config before loading plugins.
+/// Each plugin links its own copy of this crate, so the environment is the one
+/// place every copy reads from.
+pub const GLYPHS_ENV: &str = "DRK_GLYPHS";
+
+/// How much pictographic output is wanted, from `[output] emoji`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmojiLevel {
+    /// Every icon the terminal can show
+    Full,
+    /// Only plain single-width symbols (✓ ✗ ! i), never emoji-style ones
+    Minimal,
+    /// Strictly ASCII output, whatever the terminal supports
+    None,
+}
+
+/// Environment variable selecting the emoji level ("full", "minimal" or "none").
+pub const EMOJI_ENV: &str = "DRK_EMOJI";
+
+/// The active emoji level; defaults to [`EmojiLevel::Full`].
+pub fn emoji_level() -> EmojiLevel {
+    match std::env::var(EMOJI_ENV).as_deref() {
+        Ok("minimal") => EmojiLevel::Minimal,
+        Ok("none") => EmojiLevel::None,
+        _ => EmojiLevel::Full,
+    }
+}
+
+/// The active glyph profile: ASCII when emoji are turned off entirely,
+/// otherwise forced via `DRK_GLYPHS` or detected from the terminal type and
+/// locale.
+pub fn glyph_profile() -> GlyphProfile {
+    if emoji_level() == EmojiLevel::None {
+        return GlyphProfile::Ascii;
+    }
+
+    match std::env::var(GLYPHS_ENV).as_deref() {
+        Ok("ascii") => return GlyphProfile::Ascii,
+        Ok("unicode") => return GlyphProfile::Unicode,
+        _ => {}
+    }
+
+    if std::env::var("TERM").as_deref() == Ok("dumb") {
+        return GlyphProfile::Ascii;
+    }
+
+    // Legacy Windows consoles mangle anything outside the OEM code page;
+    // Windows Terminal and most third-party terminals announce themselves
+    if cfg!(windows) {
+        let modern =
+            std::env::var_os("WT_SESSION").is_some() || std::env::var_os("TERM_PROGRAM").is_some();
+        return if modern {
+            GlyphProfile::Unicode
+        } else {
+            GlyphProfile::Ascii
+        };
+    }
+
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_default()
+        .to_lowercase();
+    if locale.contains("utf-8") || locale.contains("utf8") {
+        GlyphProfile::Unicode
+    } else {
+        GlyphProfile::Ascii
+    }
+}
+
+/// True when stdout is a terminal that can show Unicode symbols.
+fn fancy_glyphs() -> bool {
+    console::Term::stdout().features().is_attended() && glyph_profile() == GlyphProfile::Unicode
+}
+
+/// Characters for drawing table borders and separators.
+#[derive(Debug, Clone, Copy)]
+pub struct BoxChars {
+    pub horizontal: &'static str,
+    pub vertical: &'static str,
+    pub top_left: &'static str,
+    pub top_right: &'static str,
+    pub bottom_left: &'static str,
+    pub bottom_right: &'static str,
+    pub cross: &'static str,
+    /// A vertical line with a branch to the right, for trees
+    pub tee: &'static str,
+}
+
+/// Box-drawing characters for the active glyph profile.
+pub fn box_chars() -> BoxChars {
+    match glyph_profile() {
+        GlyphProfile::Unicode => BoxChars {
+            horizontal: "─",
+            vertical: "│",
+            top_left: "┌",
+            top_right: "┐",
+            bottom_left: "└",
+            bottom_right: "┘",
+            cross: "┼",
+            tee: "├",
+        },
+        GlyphProfile::Ascii => BoxChars {
+            horizontal: "-",
+            vertical: "|",
+            top_left: "+",
+            top_right: "+",
+            bottom_left: "+",
+            bottom_right: "+",
+            cross: "+",
+            tee: "+",
+        },
+    }
+}
+
+/// Fill characters for progress bars, from full to empty.
+pub fn progress_chars() -> &'static str {
+    match glyph_profile() {
+        GlyphProfile::Unicode => "█▉▊▋▌▍▎▏ ",
+        GlyphProfile::Ascii => "#>-",
+    }
+}
+
+/// Animation frames for spinners.
+pub fn spinner_frames() -> &'static [&'static str] {
+    match glyph_profile() {
+        GlyphProfile::Unicode => &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
+        GlyphProfile::Ascii => &["|", "/", "-", "\\"],
+    }
+}
+
 // --- Advanced: Icon Support ---
 
+/// Picks the icon for the active theme, emoji level and terminal.
+fn themed_icon(
+    unicode: &'static str,
+    heavy: &'static str,
+    minimal: &'static str,
+    label: &'static str,
+) -> &'static str {
+    if !fancy_glyphs() {
+        return label;
+    }
+    match (theme(), emoji_level()) {
+        // Without color a lone symbol is easy to miss
+        (Theme::Monochrome, _) => label,
+        (_, EmojiLevel::Minimal) => minimal,
+        (Theme::HighContrast, _) => heavy,
+        _ => unicode,
+    }
+}
+
 /// Cross-platform checkmark icon
 pub fn icon_success() -> &'static str {
-    if console::Term::stdout().features().is_attended() {
-        "✓" // Unicode checkmark
-    } else {
-        "[OK]" // Fallback for non-terminal output
-    }
+    themed_icon("✓", "✔", "✓", "[OK]")
 }
 
-/// Cross-platform error icon  
+/// Cross-platform error icon
 pub fn icon_error() -> &'static str {
-    if console::Term::stdout().features().is_attended() {
-        "✗" // Unicode X
-    } else {
-        "[ERROR]"
-    }
+    themed_icon("✗", "✖", "✗", "[ERROR]")
 }
 
 /// Cross-platform warning icon
 pub fn icon_warning() -> &'static str {
-    if console::Term::stdout().features().is_attended() {
-        "⚠" // Unicode warning
-    } else {
-        "[WARN]"
-    }
+    themed_icon("⚠", "⚠", "!", "[WARN]")
 }
 
 /// Cross-platform info icon
 pub fn icon_info() -> &'static str {
-    if console::Term::stdout().features().is_attended() {
-        "ℹ" // Unicode info
-    } else {
-        "[INFO]"
-    }
+    themed_icon("ℹ", "ℹ", "i", "[INFO]")
 }
@@ -0,0 +1,116 @@
+//! Width-aware text helpers.
+//!
+//! Widths are measured in terminal columns, ignoring ANSI escape codes, so
+//! styled strings (see [`crate::styling`]) can be truncated and aligned safely.
+
+use crate::styling::{glyph_profile, GlyphProfile};
+
+/// Horizontal alignment for [`pad`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// The ellipsis for the active glyph profile.
+pub fn ellipsis() -> &'static str {
+    match glyph_profile() {
+        GlyphProfile::Unicode => "…",
+        GlyphProfile::Ascii => "...",
+    }
+}
+
+/// Display width of `text` in columns, not counting ANSI escape codes.
+pub fn width(text: &str) -> usize {
+    console::measure_text_width(text)
+}
+
+/// Width of the terminal, falling back to `$COLUMNS` and then 80.
+pub fn terminal_width() -> usize {
+    if let Some((_, cols)) = console::Term::stdout().size_checked() {
+        return cols as usize;
+    }
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .filter(|c| *c > 0)
+        .unwrap_or(80)
+}
+
+/// Cuts `text` to at most `max` columns, ending in an ellipsis when shortened.
+///
+/// Styling is preserved up to the cut.
+///
+/// # Example
+/// ```
+/// # use drk_api::text::{truncate, width};
+/// let cut = truncate("a very long line of output", 10);
+/// assert!(width(&cut) <= 10);
+/// assert_eq!(truncate("short", 10), "short");
+/// ```
+pub fn truncate(text: &str, max: usize) -> String {
+    if width(text) <= max {
+        return text.to_string();
+    }
+    let tail = ellipsis();
+    if max <= width(tail) {
+        return console::truncate_str(text, max, "").into_owned();
+    }
+    console::truncate_str(text, max, tail).into_owned()
+}
+
+/// Cuts the middle out of `text`, keeping both ends. Better than [`truncate`]
+/// for paths and URLs, where the end is usually the interesting part.
+///
+/// Styling is dropped from shortened text.
+pub fn truncate_middle(text: &str, max: usize) -> String {
+    if width(text) <= max {
+        return text.to_string();
+    }
+    let plain = console::strip_ansi_codes(text);
+    let tail = ellipsis();
+    let tail_width = width(tail);
+    if max <= tail_width {
+        return truncate(&plain, max);
+    }
+
+    let keep = max - tail_width;
+    let chars: Vec<char> = plain.chars().collect();
+    let head: String = take_columns(chars.iter().copied(), keep - keep / 2);
+    let end: String = take_columns(chars.iter().rev().copied(), keep / 2)
+        .chars()
+        .rev()
+        .collect();
+    format!("{}{}{}", head, tail, end)
+}
+
+/// Pads `text` with spaces to `columns`, truncating it first if it is wider.
+pub fn pad(text: &str, columns: usize, align: Align) -> String {
+    let text = truncate(text, columns);
+    let alignment = match align {
+        Align::Left => console::Alignment::Left,
+        Align::Center => console::Alignment::Center,
+        Align::Right => console::Alignment::Right,
+    };
+    console::pad_str(&text, columns, alignment, None).into_owned()
+}
+
+/// Truncates `line` to the terminal width, so long output never wraps.
+pub fn fit_to_terminal(line: &str) -> String {
+    truncate(line, terminal_width())
+}
+
+fn take_columns(chars: impl Iterator<Item = char>, columns: usize) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+    for c in chars {
+        let w = width(c.encode_utf8(&mut [0; 4]));
+        if used + w > columns {
+            break;
+        }
+        used += w;
+        out.push(c);
+    }
+    out
+}
@@ -0,0 +1,230 @@
+//! Durations and points in time as users type them, behind
+//! [`ArgType::Duration`](crate::ArgType::Duration) and
+//! [`ArgType::DateTime`](crate::ArgType::DateTime).
+//!
+//! drk checks these arguments before a plugin runs and passes them on in
+//! one canonical form, so plugins never see `90m` and `1h30m` as different
+//! values; read them back with [`CommandMatches::duration`] and
+//! [`CommandMatches::datetime`].
+//!
+//! ```
+//! use drk_api::time::{format_duration, parse_datetime, parse_duration};
+//!
+//! assert_eq!(format_duration(parse_duration("90m")?), "1h30m");
+//! assert_eq!(format_duration(parse_duration("2h 30m 0s")?), "2h30m");
+//! assert!(parse_duration("25").is_err());
+//!
+//! let start = parse_datetime("2026-03-01T09:30:00+01:00")?;
+//! assert_eq!(start.to_rfc3339(), "2026-03-01T09:30:00+01:00");
+//! assert!(parse_datetime("in 2h")? > parse_datetime("now")?);
+//! assert!(parse_datetime("tomorrow 9:00")? > parse_datetime("today")?);
+//! assert!(parse_datetime("2026-03-01 09:30").is_ok());
+//! assert!(parse_datetime("next week").is_err());
+//! assert!(parse_datetime("in 99999999d").is_err());
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! [`CommandMatches::duration`]: crate::CommandMatches::duration
+//! [`CommandMatches::datetime`]: crate::CommandMatches::datetime
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{
+    DateTime, Days, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat,
+    TimeZone,
+};
+use std::time::Duration;
+
+/// Units a duration may be written in, largest first
+const UNITS: [(char, u64); 4] = [('d', 86400), ('h', 3600), ('m', 60), ('s', 1)];
+
+/// Reads a duration like `2h30m`, `90s` or `1d 12h`. A number without a
+/// unit is rejected, since it could mean seconds as well as minutes.
+pub fn parse_duration(text: &str) -> Result<Duration> {
+    let invalid = || anyhow!("Expected a duration like 2h30m, 25m or 90s, got '{}'", text);
+    let trimmed = text.trim();
+    if trimmed.parse::<u64>().is_ok() {
+        bail!("'{}' needs a unit, like {}m or {}s", text, trimmed, trimmed);
+    }
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+    for c in trimmed.chars().filter(|c| !c.is_whitespace()) {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let (_, unit) = UNITS
+            .iter()
+            .find(|(name, _)| *name == c.to_ascii_lowercase())
+            .ok_or_else(invalid)?;
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+        seconds = value
+            .checked_mul(*unit)
+            .and_then(|value| seconds.checked_add(value))
+            .ok_or_else(invalid)?;
+        number.clear();
+    }
+    if !number.is_empty() || trimmed.is_empty() {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// The canonical form of a duration, like `1d2h30m`; `0s` when it's empty.
+/// Fractions of a second are dropped.
+pub fn format_duration(duration: Duration) -> String {
+    let mut left = duration.as_secs();
+    let mut out = String::new();
+    for (name, unit) in UNITS {
+        if left >= unit {
+            out.push_str(&format!("{}{}", left / unit, name));
+            left %= unit;
+        }
+    }
+    if out.is_empty() {
+        out.push_str("0s");
+    }
+    out
+}
+
+/// Reads a point in time. Besides RFC 3339 (`2026-03-01T09:30:00Z`) this
+/// takes local times: `2026-03-01 09:30`, `2026-03-01` (midnight), `09:30`
+/// (today), `now`, `today`, `tomorrow` and `yesterday` (optionally with a
+/// time, like `tomorrow 9:00`), `in 2h` and `3d ago`.
+pub fn parse_datetime(text: &str) -> Result<DateTime<FixedOffset>> {
+    parse_datetime_at(text, Local::now())
+}
+
+/// The canonical form of a point in time: RFC 3339 to the second, with the
+/// offset it was given in.
+pub fn format_datetime(datetime: &DateTime<FixedOffset>) -> String {
+    datetime.to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+fn parse_datetime_at(text: &str, now: DateTime<Local>) -> Result<DateTime<FixedOffset>> {
+    let invalid = || {
+        anyhow!(
+            "Expected a date and time like 2026-03-01 09:30, tomorrow 9:00 or in 2h, got '{}'",
+            text
+        )
+    };
+    let trimmed = text.trim();
+    let lower = trimmed.to_lowercase();
+
+    if lower == "now" {
+        return Ok(now.fixed_offset());
+    }
+    if let Some(after) = lower.strip_prefix("in ") {
+        let after = chrono::Duration::from_std(parse_duration(after)?).map_err(|_| invalid())?;
+        return now
+            .checked_add_signed(after)
+            .map(|datetime| datetime.fixed_offset())
+            .ok_or_else(invalid);
+    }
+    if let Some(before) = lower.strip_suffix(" ago") {
+        let before = chrono::Duration::from_std(parse_duration(before)?).map_err(|_| invalid())?;
+        return now
+            .checked_sub_signed(before)
+            .map(|datetime| datetime.fixed_offset())
+            .ok_or_else(invalid);
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(trimmed) {
+        return Ok(datetime);
+    }
+
+    // The rest are local: a day, a time of day, or both
+    let mut lower = lower;
+    if lower.get(10..11) == Some("t") {
+        lower.replace_range(10..11, " ");
+    }
+    let (day, time) = match lower.split_once(' ') {
+        Some((day, time)) if parse_day(day, now).is_some() => (day, Some(time)),
+        _ => (lower.as_str(), None),
+    };
+    let (date, time) = match (parse_day(day, now), time) {
+        (Some(date), Some(time)) => (date, parse_time(time).ok_or_else(invalid)?),
+        (Some(date), None) => (date, NaiveTime::MIN),
+        (None, _) => (now.date_naive(), parse_time(day).ok_or_else(invalid)?),
+    };
+    Local
+        .from_local_datetime(&NaiveDateTime::new(date, time))
+        .earliest()
+        .map(|datetime| datetime.fixed_offset())
+        .ok_or_else(|| anyhow!("'{}' doesn't exist in the local time zone", text))
+}
+
+/// `2026-03-01`, `today`, `tomorrow` or `yesterday`
+fn parse_day(text: &str, now: DateTime<Local>) -> Option<NaiveDate> {
+    let today = now.date_naive();
+    match text {
+        "today" => Some(today),
+        "tomorrow" => today.checked_add_days(Days::new(1)),
+        "yesterday" => today.checked_sub_days(Days::new(1)),
+        _ => NaiveDate::parse_from_str(text, "%Y-%m-%d").ok(),
+    }
+}
+
+/// `09:30`, `9:30` or `09:30:15`
+fn parse_time(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Once;
+
+    /// 10:00 on the Saturday before Berlin moves its clocks forward.
+    fn now() -> DateTime<Local> {
+        static TZ: Once = Once::new();
+        TZ.call_once(|| std::env::set_var("TZ", "CET-1CEST,M3.5.0,M10.5.0/3"));
+        Local.with_ymd_and_hms(2026, 3, 28, 10, 0, 0).unwrap()
+    }
+
+    fn parse(text: &str) -> Result<String> {
+        parse_datetime_at(text, now()).map(|datetime| format_datetime(&datetime))
+    }
+
+    #[test]
+    fn days_are_relative_to_now() {
+        assert_eq!(parse("tomorrow 9:00").unwrap(), "2026-03-29T09:00:00+02:00");
+        assert_eq!(parse("yesterday").unwrap(), "2026-03-27T00:00:00+01:00");
+        assert_eq!(
+            parse("today 18:30:15").unwrap(),
+            "2026-03-28T18:30:15+01:00"
+        );
+        assert_eq!(parse("11:00").unwrap(), "2026-03-28T11:00:00+01:00");
+    }
+
+    #[test]
+    fn date_and_time_may_be_joined_by_t() {
+        assert_eq!(
+            parse("2026-03-01T09:30").unwrap(),
+            "2026-03-01T09:30:00+01:00"
+        );
+        assert_eq!(
+            parse("2026-03-01 09:30").unwrap(),
+            "2026-03-01T09:30:00+01:00"
+        );
+    }
+
+    #[test]
+    fn offsets_count_from_now() {
+        assert_eq!(parse("3d ago").unwrap(), "2026-03-25T10:00:00+01:00");
+        assert_eq!(parse("in 1h30m").unwrap(), "2026-03-28T11:30:00+01:00");
+        assert_eq!(parse("now").unwrap(), "2026-03-28T10:00:00+01:00");
+    }
+
+    #[test]
+    fn times_skipped_by_dst_are_refused() {
+        let err = parse("2026-03-29 02:30").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("doesn't exist in the local time zone"));
+        assert_eq!(
+            parse("2026-03-29 03:30").unwrap(),
+            "2026-03-29T03:30:00+02:00"
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//! Finding the external tools plugins drive (nix, git, docker, ...) and
+//! checking their versions.
+//!
+//! Lookups are cached for the life of the process, so asking on every
+//! command costs one `PATH` scan and one `--version` run per tool.
+
+use anyhow::{anyhow, bail, Result};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// A dotted numeric version as tools print it (`2.18.1`, `24.0`). Missing
+/// trailing components compare as zero, so `2.18` equals `2.18.0`.
+#[derive(Debug, Clone)]
+pub struct Version {
+    parts: Vec<u64>,
+}
+
+impl Version {
+    /// Parses the leading `1.2.3` of `text`; anything after the numbers
+    /// (`-pre`, `+git`) is ignored.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().trim_start_matches('v');
+        let numeric = text
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .map_or(text, |end| &text[..end])
+            .trim_end_matches('.');
+        let parts = numeric
+            .split('.')
+            .map(|part| part.parse().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        Some(Self { parts })
+    }
+
+    /// The first version-looking word of a tool's `--version` output, e.g.
+    /// `2.18.1` from `nix (Nix) 2.18.1`.
+    pub fn from_output(output: &str) -> Option<Self> {
+        output
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| {
+                word.trim_start_matches('v')
+                    .starts_with(|c: char| c.is_ascii_digit())
+            })
+            .find_map(Self::parse)
+    }
+
+    /// Whether this version satisfies `requirement`: comma-separated
+    /// comparisons such as `>=2.18`, `>=1.0, <2`, or a bare `2.18` for an
+    /// exact match.
+    ///
+    /// ```
+    /// use drk_api::Version;
+    ///
+    /// let nix = Version::from_output("nix (Nix) 2.18.1").unwrap();
+    /// assert!(nix.satisfies(">=2.18").unwrap());
+    /// assert!(!nix.satisfies(">=2.4, <2.18").unwrap());
+    /// ```
+    pub fn satisfies(&self, requirement: &str) -> Result<bool> {
+        for clause in requirement.split(',').map(str::trim) {
+            let (op, version) = ["<=", ">=", "==", "<", ">", "="]
+                .iter()
+                .find_map(|op| clause.strip_prefix(op).map(|rest| (*op, rest)))
+                .unwrap_or(("=", clause));
+            let wanted = Version::parse(version)
+                .ok_or_else(|| anyhow!("Invalid version requirement '{}'", clause))?;
+            let ordering = self.cmp(&wanted);
+            let ok = match op {
+                "<=" => ordering != Ordering::Greater,
+                ">=" => ordering != Ordering::Less,
+                "<" => ordering == Ordering::Less,
+                ">" => ordering == Ordering::Greater,
+                _ => ordering == Ordering::Equal,
+            };
+            if !ok {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.parts.len().max(other.parts.len());
+        (0..len)
+            .map(|i| {
+                let a = self.parts.get(i).copied().unwrap_or(0);
+                let b = other.parts.get(i).copied().unwrap_or(0);
+                a.cmp(&b)
+            })
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self.parts.iter().map(u64::to_string).collect();
+        f.write_str(&parts.join("."))
+    }
+}
+
+fn path_cache() -> &'static Mutex<HashMap<String, Option<PathBuf>>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Option<PathBuf>>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+fn version_cache() -> &'static Mutex<HashMap<String, Option<Version>>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Option<Version>>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// The full path `name` resolves to through `PATH`, trying the `PATHEXT`
+/// extensions on Windows.
+pub fn which(name: &str) -> Option<PathBuf> {
+    let mut cache = path_cache().lock().unwrap_or_else(|e| e.into_inner());
+    cache
+        .entry(name.to_string())
+        .or_insert_with(|| search_path(name))
+        .clone()
+}
+
+fn search_path(name: &str) -> Option<PathBuf> {
+    let extensions: Vec<String> = if cfg!(windows) {
+        std::env::var("PATHEXT")
+            .unwrap_or_else(|_| ".EXE;.CMD;.BAT;.COM".to_string())
+            .split(';')
+            .map(str::to_string)
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{}{}", name, ext)))
+            .find(|candidate| is_executable(candidate))
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The installed version of `name`, read from `name --version`. `None` if
+/// it isn't installed or doesn't print a version.
+pub fn version(name: &str) -> Option<Version> {
+    let mut cache = version_cache().lock().unwrap_or_else(|e| e.into_inner());
+    cache
+        .entry(name.to_string())
+        .or_insert_with(|| {
+            let output = std::process::Command::new(which(name)?)
+                .arg("--version")
+                .output()
+                .ok()?;
+            // Some tools print their version on stderr
+            Version::from_output(&String::from_utf8_lossy(&output.stdout))
+                .or_else(|| Version::from_output(&String::from_utf8_lossy(&output.stderr)))
+        })
+        .clone()
+}
+
+/// The installed version of `name` if it satisfies `requirement` (see
+/// [`Version::satisfies`]); otherwise an error saying what's wrong and how
+/// to install a suitable one.
+pub fn require(name: &str, requirement: &str) -> Result<Version> {
+    if which(name).is_none() {
+        bail!("{} is not installed. {}", name, install_hint(name));
+    }
+    let Some(version) = version(name) else {
+        bail!("Could not tell which version of {} is installed", name);
+    };
+    if !version.satisfies(requirement)? {
+        bail!(
+            "{} {} is installed, but {} is required. {}",
+            name,
+            version,
+            requirement,
+            install_hint(name)
+        );
+    }
+    Ok(version)
+}
+
+/// How to install `name` on the OS drk is running on.
+pub fn install_hint(name: &str) -> String {
+    let os = std::env::consts::OS;
+    let hint = match (name, os) {
+        ("nix", "windows") => "Nix runs under WSL; see https://nixos.org/download",
+        ("nix", _) => "Install it with `curl -L https://nixos.org/nix/install | sh`",
+        ("git", "macos") => "Install it with `xcode-select --install` or `brew install git`",
+        ("git", "windows") => "Install it with `winget install Git.Git`",
+        ("git", _) => "Install it with your package manager, e.g. `sudo apt install git`",
+        ("docker", "macos") => "Install Docker Desktop with `brew install --cask docker`",
+        ("docker", "windows") => {
+            "Install Docker Desktop with `winget install Docker.DockerDesktop`"
+        }
+        ("docker", _) => "See https://docs.docker.com/engine/install/ for your distribution",
+        ("podman", "macos") => "Install it with `brew install podman`",
+        ("podman", "windows") => "Install it with `winget install RedHat.Podman`",
+        ("podman", _) => "Install it with your package manager, e.g. `sudo apt install podman`",
+        ("cargo" | "rustc", _) => "Install the Rust toolchain from https://rustup.rs",
+        ("node" | "npm", "macos") => "Install it with `brew install node`",
+        ("node" | "npm", "windows") => "Install it with `winget install OpenJS.NodeJS`",
+        ("node" | "npm", _) => "See https://nodejs.org/en/download for your distribution",
+        ("mise", "windows") => "Install it with `winget install jdx.mise`",
+        ("mise", _) => "Install it with `curl https://mise.run | sh`",
+        ("asdf", "macos") => "Install it with `brew install asdf`",
+        ("asdf", _) => "See https://asdf-vm.com/guide/getting-started.html",
+        _ => return format!("Install {} and make sure it's on your PATH", name),
+    };
+    hint.to_string()
+}
+
+/// The tool versions a project expects, from the `[tools]` section of its
+/// `drk.toml`. Each entry is a requirement as [`Version::satisfies`] reads it:
+///
+/// ```toml
+/// [tools]
+/// nix = ">=2.18"
+/// node = ">=20, <21"
+/// rustc = "1.78.0"
+/// ```
+///
+/// `drk check-tools` checks every entry; plugins get the catalog from
+/// [`Context::tool_catalog`](crate::Context::tool_catalog) to warn when what's
+/// installed drifts from what the project pins.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    /// The `drk.toml` the pins came from, if there is one
+    pub file: Option<PathBuf>,
+    pins: BTreeMap<String, String>,
+}
+
+/// How an installed tool compares to its pin in a [`Catalog`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolStatus {
+    Ok(Version),
+    /// Installed, but outside the pinned versions
+    Drift(Version),
+    /// Installed, but `--version` printed nothing drk could read
+    Unknown,
+    Missing,
+}
+
+impl Catalog {
+    /// Name of the project file holding the catalog.
+    pub const FILE: &'static str = "drk.toml";
+
+    /// The catalog of the project containing `dir`: the nearest ancestor
+    /// holding a `drk.toml` or `.git`, as for every other project setting.
+    /// Empty outside a project or without a `[tools]` section.
+    pub fn for_dir(dir: &Path) -> Result<Self> {
+        let root = dir
+            .ancestors()
+            .find(|d| d.join(Self::FILE).exists() || d.join(".git").exists());
+        let Some(file) = root.map(|r| r.join(Self::FILE)).filter(|f| f.exists()) else {
+            return Ok(Self::default());
+        };
+        let text = std::fs::read_to_string(&file)
+            .map_err(|e| anyhow!("Could not read {}: {}", file.display(), e))?;
+        let mut catalog =
+            Self::parse(&text).map_err(|e| anyhow!("In [tools] of {}: {:#}", file.display(), e))?;
+        catalog.file = Some(file);
+        Ok(catalog)
+    }
+
+    /// The catalog in the text of a `drk.toml`.
+    ///
+    /// ```
+    /// use drk_api::tools::Catalog;
+    ///
+    /// let catalog = Catalog::parse("[tools]\nnix = \">=2.18\"\nrustc = \"1.78\"\n").unwrap();
+    /// assert_eq!(catalog.requirement("nix"), Some(">=2.18"));
+    /// assert_eq!(catalog.requirement("node"), None);
+    /// assert_eq!(catalog.tools().count(), 2);
+    /// ```
+    pub fn parse(text: &str) -> Result<Self> {
+        let table: toml::Table = toml::from_str(text)?;
+        let Some(tools) = table.get("tools") else {
+            return Ok(Self::default());
+        };
+        let toml::Value::Table(tools) = tools else {
+            bail!("[tools] must be a table of tool = \"version requirement\"");
+        };
+        let mut pins = BTreeMap::new();
+        for (tool, requirement) in tools {
+            let requirement = match requirement {
+                toml::Value::String(s) => s.clone(),
+                // `node = 20` reads as "20"
+                toml::Value::Integer(n) => n.to_string(),
+                toml::Value::Float(n) => n.to_string(),
+                other => bail!("{} must be a version requirement, not {}", tool, other),
+            };
+            // Catch typos now rather than on every check
+            Version::parse("0")
+                .expect("0 is a version")
+                .satisfies(&requirement)?;
+            pins.insert(tool.clone(), requirement);
+        }
+        Ok(Self { file: None, pins })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+    }
+
+    /// What the project requires of `tool`, if it pins it.
+    pub fn requirement(&self, tool: &str) -> Option<&str> {
+        self.pins.get(tool).map(String::as_str)
+    }
+
+    /// Every pinned tool with its requirement, by name.
+    pub fn tools(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pins.iter().map(|(t, r)| (t.as_str(), r.as_str()))
+    }
+
+    /// How the installed `tool` compares to its pin; `None` if it isn't
+    /// pinned.
+    pub fn check(&self, tool: &str) -> Option<ToolStatus> {
+        let requirement = self.requirement(tool)?;
+        if which(tool).is_none() {
+            return Some(ToolStatus::Missing);
+        }
+        Some(match version(tool) {
+            Some(v) if v.satisfies(requirement).unwrap_or(false) => ToolStatus::Ok(v),
+            Some(v) => ToolStatus::Drift(v),
+            None => ToolStatus::Unknown,
+        })
+    }
+}
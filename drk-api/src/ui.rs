@@ -0,0 +1,220 @@
+//! Asking the user things from a plugin, through `ctx.ui()`.
+//!
+//! Prompts go to stderr, so they stay out of `--output json`, and each one is
+//! cleared once it's answered, so it can sit between a [`crate::Reporter`]'s
+//! lines. Without a terminal there's nobody to ask: `confirm` takes its
+//! default, `input` its default if it has one, and everything else fails.
+//!
+//! ```no_run
+//! # fn demo(ctx: &drk_api::Context) -> anyhow::Result<()> {
+//! let ui = ctx.ui();
+//! let templates = ["rust", "go", "python"];
+//! if let Some(i) = ui.select_from_list("Pick a template", &templates)? {
+//!     let name = ui.input("Project name", Some("demo"))?;
+//!     if ui.confirm(&format!("Create {} from {}?", name, templates[i]), true)? {
+//!         // ...
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::styling::{style_dim, style_primary, style_warning};
+use anyhow::{bail, Result};
+use console::{Key, Term};
+use std::fmt::Display;
+use std::io::IsTerminal;
+
+/// How many items of a list are shown at once; the rest scroll into view.
+const VISIBLE_ITEMS: usize = 10;
+
+/// Interactive prompts; see the module docs.
+pub struct Ui {
+    term: Term,
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ui {
+    pub fn new() -> Self {
+        Self {
+            term: Term::stderr(),
+        }
+    }
+
+    /// Whether there is someone to ask: stdin and stderr are a terminal.
+    pub fn is_interactive(&self) -> bool {
+        std::io::stdin().is_terminal() && self.term.is_term()
+    }
+
+    /// A yes/no question; an empty answer, or no terminal, gives `default`.
+    pub fn confirm(&self, prompt: &str, default: bool) -> Result<bool> {
+        if !self.is_interactive() {
+            return Ok(default);
+        }
+        let hint = if default { "[Y/n]" } else { "[y/N]" };
+        loop {
+            self.term.write_str(&format!(
+                "{} {} {} ",
+                style_primary("?"),
+                prompt,
+                style_dim(hint)
+            ))?;
+            let answer = self.term.read_line()?;
+            self.term.clear_last_lines(1)?;
+            match answer.trim().to_lowercase().as_str() {
+                "" => return Ok(default),
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => continue,
+            }
+        }
+    }
+
+    /// A line of text; an empty answer gives `default`, if there is one.
+    /// Without a terminal that default is the answer.
+    pub fn input(&self, prompt: &str, default: Option<&str>) -> Result<String> {
+        if !self.is_interactive() {
+            return match default {
+                Some(default) => Ok(default.to_string()),
+                None => bail!("Can't ask \"{}\": there's no terminal to ask on", prompt),
+            };
+        }
+        let hint = default
+            .map(|d| format!(" {}", style_dim(&format!("({})", d))))
+            .unwrap_or_default();
+        loop {
+            self.term
+                .write_str(&format!("{} {}{} ", style_primary("?"), prompt, hint))?;
+            let answer = self.term.read_line()?;
+            self.term.clear_last_lines(1)?;
+            match (answer.trim(), default) {
+                ("", Some(default)) => return Ok(default.to_string()),
+                ("", None) => continue,
+                (answer, _) => return Ok(answer.to_string()),
+            }
+        }
+    }
+
+    /// A line of text that isn't shown as it's typed, e.g. a passphrase.
+    /// May be empty; fails without a terminal.
+    pub fn password(&self, prompt: &str) -> Result<String> {
+        if !self.is_interactive() {
+            bail!("Can't ask \"{}\": there's no terminal to ask on", prompt);
+        }
+        self.term
+            .write_str(&format!("{} {} ", style_primary("?"), prompt))?;
+        let answer = self.term.read_secure_line()?;
+        self.term.clear_last_lines(1)?;
+        Ok(answer)
+    }
+
+    /// One of `items`, picked with the arrow keys and Enter. Typing narrows
+    /// the list to the items with the typed characters in order (`gc` finds
+    /// `git-commit`), closest matches first. The index of the pick, or
+    /// `None` if the user pressed Esc.
+    pub fn select_from_list<T: Display>(&self, prompt: &str, items: &[T]) -> Result<Option<usize>> {
+        if !self.is_interactive() {
+            bail!("Can't ask \"{}\": there's no terminal to ask on", prompt);
+        }
+        let labels: Vec<String> = items.iter().map(ToString::to_string).collect();
+        let mut filter = String::new();
+        let mut cursor = 0;
+        let mut drawn = 0;
+
+        self.term.hide_cursor()?;
+        let mut pick = || loop {
+            let mut ranked: Vec<((usize, usize), usize)> = (0..labels.len())
+                .filter_map(|i| Some((fuzzy_score(&filter, &labels[i])?, i)))
+                .collect();
+            ranked.sort();
+            let shown: Vec<usize> = ranked.into_iter().map(|(_, i)| i).collect();
+            cursor = cursor.min(shown.len().saturating_sub(1));
+
+            let mut lines = vec![format!(
+                "{} {} {}",
+                style_primary("?"),
+                prompt,
+                if filter.is_empty() {
+                    style_dim("(type to filter, Esc to cancel)").to_string()
+                } else {
+                    style_primary(&filter).to_string()
+                }
+            )];
+            let top = (cursor + 1).saturating_sub(VISIBLE_ITEMS);
+            for (row, &i) in shown.iter().enumerate().skip(top).take(VISIBLE_ITEMS) {
+                lines.push(if row == cursor {
+                    format!("{} {}", style_primary(">"), style_primary(&labels[i]))
+                } else {
+                    format!("  {}", labels[i])
+                });
+            }
+            if shown.is_empty() {
+                lines.push(style_warning("  nothing matches").to_string());
+            } else if shown.len() > VISIBLE_ITEMS {
+                let position = format!("  {} of {}", cursor + 1, shown.len());
+                lines.push(style_dim(&position).to_string());
+            }
+
+            self.term.clear_last_lines(drawn)?;
+            for line in &lines {
+                self.term.write_line(line)?;
+            }
+            drawn = lines.len();
+
+            match self.term.read_key()? {
+                Key::ArrowUp => cursor = cursor.saturating_sub(1),
+                Key::ArrowDown => cursor = (cursor + 1).min(shown.len().saturating_sub(1)),
+                Key::PageUp => cursor = cursor.saturating_sub(VISIBLE_ITEMS),
+                Key::PageDown => {
+                    cursor = (cursor + VISIBLE_ITEMS).min(shown.len().saturating_sub(1))
+                }
+                Key::Enter if !shown.is_empty() => return Ok(Some(shown[cursor])),
+                Key::Escape => return Ok(None),
+                Key::CtrlC => bail!("Interrupted"),
+                Key::Backspace => {
+                    filter.pop();
+                }
+                Key::Char(c) if !c.is_control() => {
+                    filter.push(c);
+                    cursor = 0;
+                }
+                _ => {}
+            }
+        };
+        let picked = pick();
+        // Also after a failed read, so the cursor isn't left hidden
+        let _ = self.term.clear_last_lines(drawn);
+        self.term.show_cursor()?;
+        picked
+    }
+}
+
+/// How closely `label` matches `needle` when it has all of its characters in
+/// order, ignoring case: the length of the shortest stretch holding them,
+/// then where it starts. Lower is closer; `None` if they aren't all there.
+fn fuzzy_score(needle: &str, label: &str) -> Option<(usize, usize)> {
+    let needle: Vec<char> = needle.to_lowercase().chars().collect();
+    let label: Vec<char> = label.to_lowercase().chars().collect();
+    let Some(&first) = needle.first() else {
+        return Some((0, 0));
+    };
+    (0..label.len())
+        .filter(|&start| label[start] == first)
+        .filter_map(|start| {
+            let mut rest = needle[1..].iter().peekable();
+            let mut end = start;
+            for (at, c) in label.iter().enumerate().skip(start + 1) {
+                if rest.peek() == Some(&c) {
+                    rest.next();
+                    end = at;
+                }
+            }
+            rest.peek().is_none().then_some((end - start, start))
+        })
+        .min()
+}
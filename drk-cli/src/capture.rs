@@ -0,0 +1,72 @@
+//! Catching what plugins print to stdout, for `exec-json` and cached results.
+
+#[cfg(unix)]
+pub use unix::Capture;
+
+#[cfg(not(unix))]
+pub use fallback::Capture;
+
+/// Whether [`Capture`] actually records output on this platform.
+pub const SUPPORTED: bool = cfg!(unix);
+
+#[cfg(unix)]
+mod unix {
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::os::fd::AsRawFd;
+
+    /// Points the process-wide stdout (fd 1) at a temp file until `finish`.
+    ///
+    /// Plugins are separate libraries with their own `std`, so redirecting the
+    /// file descriptor is the only way to catch what they print.
+    pub struct Capture {
+        file: std::fs::File,
+        saved_fd: i32,
+    }
+
+    impl Capture {
+        pub fn start() -> anyhow::Result<Self> {
+            std::io::stdout().flush()?;
+            let file = tempfile::tempfile()?;
+            // SAFETY: plain fd juggling on descriptors we own; fd 1 is restored in `finish`.
+            // Close-on-exec, so programs started meanwhile don't inherit the real stdout
+            let saved_fd = unsafe { libc::fcntl(1, libc::F_DUPFD_CLOEXEC, 0) };
+            if saved_fd < 0 || unsafe { libc::dup2(file.as_raw_fd(), 1) } < 0 {
+                anyhow::bail!(
+                    "Could not redirect stdout: {}",
+                    std::io::Error::last_os_error()
+                );
+            }
+            Ok(Self { file, saved_fd })
+        }
+
+        /// Restores stdout and returns everything written in between.
+        pub fn finish(mut self) -> anyhow::Result<String> {
+            std::io::stdout().flush()?;
+            // SAFETY: `saved_fd` is the copy of fd 1 made in `start`
+            unsafe {
+                libc::dup2(self.saved_fd, 1);
+                libc::close(self.saved_fd);
+            }
+            let mut output = Vec::new();
+            self.file.seek(SeekFrom::Start(0))?;
+            self.file.read_to_end(&mut output)?;
+            Ok(String::from_utf8_lossy(&output).into_owned())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod fallback {
+    /// Output capture needs fd redirection; elsewhere output passes through.
+    pub struct Capture;
+
+    impl Capture {
+        pub fn start() -> anyhow::Result<Self> {
+            Ok(Self)
+        }
+
+        pub fn finish(self) -> anyhow::Result<String> {
+            Ok(String::new())
+        }
+    }
+}
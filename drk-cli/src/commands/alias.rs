@@ -0,0 +1,102 @@
+use clap::{Arg, ArgMatches, Command};
+use drk_api::tools;
+use drk_api::{style_dim, style_primary};
+use drk_core::aliases;
+use drk_core::manager::PluginManager;
+use drk_core::state::StateStore;
+use drk_core::usage::UsageStats;
+
+use super::shell::{fish_quote, posix_quote};
+
+pub fn command() -> Command {
+    Command::new("alias")
+        .about("Work with command aliases defined under [aliases] in config")
+        .subcommand_required(true)
+        .subcommand(Command::new("list").about("List configured aliases"))
+        .subcommand(
+            Command::new("export")
+                .about("Print shell aliases for configured drk aliases, ready to be sourced")
+                .arg(
+                    Arg::new("shell")
+                        .long("shell")
+                        .value_parser(["bash", "zsh", "fish"])
+                        .required(true)
+                        .help("Shell syntax to emit"),
+                )
+                .arg(
+                    Arg::new("frequent")
+                        .long("frequent")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("5")
+                        .help("Also alias your N most used plugin commands to `drk <command>`; 0 for none"),
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches, manager: &PluginManager) -> anyhow::Result<()> {
+    let configured = aliases::from_config(manager.config());
+
+    match matches.subcommand() {
+        Some(("list", _)) => {
+            for (name, expansion) in &configured {
+                println!("{} {} {}", style_primary(name), style_dim("="), expansion);
+            }
+        }
+        Some(("export", sub)) => {
+            let shell = sub.get_one::<String>("shell").expect("shell is required");
+            println!(
+                "# drk aliases, generated by `drk alias export --shell {}`",
+                shell
+            );
+            for (name, expansion) in &configured {
+                if !aliases::is_valid_name(name) {
+                    println!("# skipped '{}': not a valid shell alias name", name);
+                    continue;
+                }
+                // Placeholders must be resolved by drk at run time, so keep those as `drk <alias>`
+                let target = if expansion.contains('{') {
+                    format!("drk {}", name)
+                } else {
+                    format!("drk {}", expansion)
+                };
+                // Plain aliases (not functions) keep the shell's completion for `drk` working
+                print_alias(shell, name, &target);
+            }
+
+            let frequent = *sub
+                .get_one::<usize>("frequent")
+                .expect("frequent has a default");
+            let used = StateStore::open(StateStore::default_path())
+                .map(|state| UsageStats::load(&state).ranked(None))
+                .unwrap_or_default();
+            let mut exported = 0;
+            for (command, _) in used {
+                if exported == frequent {
+                    break;
+                }
+                // Never shadow a program, or a name the user already aliased
+                if configured.contains_key(&command)
+                    || !aliases::is_valid_name(&command)
+                    || tools::which(&command).is_some()
+                {
+                    continue;
+                }
+                if exported == 0 {
+                    println!("# frequently used commands");
+                }
+                print_alias(shell, &command, &format!("drk {}", command));
+                exported += 1;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn print_alias(shell: &str, name: &str, target: &str) {
+    match shell {
+        "fish" => println!("alias {} {}", name, fish_quote(target)),
+        _ => println!("alias {}={}", name, posix_quote(target)),
+    }
+}
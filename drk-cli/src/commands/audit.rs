@@ -0,0 +1,67 @@
+use clap::{ArgMatches, Command};
+use drk_api::{
+    icon_error, icon_info, icon_success, icon_warning, style_error, style_primary, style_success,
+    style_warning,
+};
+use drk_core::manager::PluginManager;
+
+pub fn command() -> Command {
+    Command::new("audit")
+        .about("Inspect the tamper-evident audit log")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("verify")
+                .about("Check the hash chain for tampering, then apply the retention policy"),
+        )
+}
+
+pub fn run(matches: &ArgMatches, manager: &PluginManager) -> anyhow::Result<()> {
+    if let Some(("verify", _)) = matches.subcommand() {
+        verify(manager)?;
+    }
+    Ok(())
+}
+
+fn verify(manager: &PluginManager) -> anyhow::Result<()> {
+    let Some(audit) = manager.audit_log() else {
+        println!(
+            "{} {}",
+            style_warning(icon_warning()),
+            style_warning("Auditing is disabled in config ([audit] enabled = false)")
+        );
+        return Ok(());
+    };
+
+    // Pruning re-anchors the chain, so only prune a log that checks out
+    let report = audit.verify()?;
+    if report.is_intact() {
+        let pruned = audit.prune()?;
+        if pruned > 0 {
+            println!(
+                "{} Pruned {} entries past the retention policy",
+                style_primary(icon_info()),
+                pruned
+            );
+        }
+    }
+    match report.broken_at {
+        None => println!(
+            "{} {} ({} entries in {})",
+            style_success(icon_success()),
+            style_success("Audit log intact"),
+            report.entries,
+            style_primary(&audit.path().display().to_string())
+        ),
+        Some((seq, reason)) => {
+            eprintln!(
+                "{} {} entry #{}: {}",
+                style_error(icon_error()),
+                style_error("Audit log chain broken at"),
+                seq,
+                reason
+            );
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
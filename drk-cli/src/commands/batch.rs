@@ -0,0 +1,193 @@
+use anyhow::Context as _;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use drk_api::{
+    box_chars, icon_error, icon_success, icon_warning, pad, style_dim, style_error, style_primary,
+    style_success, style_warning, Align,
+};
+use drk_core::batch::{self, BatchLine};
+use drk_core::manager::PluginManager;
+use drk_core::snapshot::{self, Snapshot};
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+pub fn command() -> Command {
+    Command::new("batch")
+        .about("Run drk commands listed in a file, one per line")
+        .arg(
+            Arg::new("file")
+                .required(true)
+                .help("The command file, or - to read it from stdin"),
+        )
+        .arg(
+            Arg::new("stop-on-error")
+                .long("stop-on-error")
+                .help("Stop at the first command that fails (the default)")
+                .conflicts_with("continue")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("continue")
+                .long("continue")
+                .help("Run every command, even after one fails")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("atomic")
+                .long("atomic")
+                .help("If a command fails, undo the file changes of all of them in this directory")
+                .conflicts_with("continue")
+                .action(ArgAction::SetTrue),
+        )
+}
+
+struct Outcome<'a> {
+    line: &'a BatchLine,
+    /// `None` if the command was killed by a signal
+    code: Option<i32>,
+    elapsed: Duration,
+}
+
+/// Runs each line as its own `drk` process, then prints a summary. Returns
+/// whether every line ran and succeeded.
+pub fn run(matches: &ArgMatches, manager: &PluginManager) -> anyhow::Result<bool> {
+    let file = matches.get_one::<String>("file").expect("file is required");
+    let text = if file == "-" {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .context("Could not read commands from stdin")?;
+        text
+    } else {
+        std::fs::read_to_string(file).with_context(|| format!("Could not read {}", file))?
+    };
+    let lines = batch::parse(&text)?;
+    let keep_going = matches.get_flag("continue");
+    let exe = std::env::current_exe()?;
+    let snapshot = if matches.get_flag("atomic") {
+        let cwd = std::env::current_dir()?;
+        Some(Snapshot::take(&cwd, snapshot::max_bytes(manager.config()))?)
+    } else {
+        None
+    };
+
+    let mut outcomes = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        eprintln!(
+            "{} {}",
+            style_dim(&format!("[{}/{}]", i + 1, lines.len())),
+            style_primary(&format!("drk {}", line.command_line()))
+        );
+        let started = Instant::now();
+        let mut child = std::process::Command::new(&exe);
+        forward_globals(&mut child, matches);
+        let status = child
+            .args(&line.words)
+            .status()
+            .with_context(|| format!("Could not run line {}", line.number))?;
+        outcomes.push(Outcome {
+            line,
+            code: status.code(),
+            elapsed: started.elapsed(),
+        });
+        if !status.success() && !keep_going {
+            break;
+        }
+    }
+
+    summary(&lines, &outcomes);
+    let success = outcomes.len() == lines.len() && outcomes.iter().all(|o| o.code == Some(0));
+    if let Some(snapshot) = snapshot.filter(|_| !success) {
+        let root = snapshot.root().to_path_buf();
+        let changed = snapshot.restore()?;
+        println!(
+            "{} {} {}",
+            style_warning(icon_warning()),
+            style_warning(&format!(
+                "Rolled back {} file change{} in",
+                changed,
+                if changed == 1 { "" } else { "s" }
+            )),
+            style_primary(&root.display().to_string())
+        );
+    }
+    Ok(success)
+}
+
+/// Hands the global flags `drk batch` was given to a line's process, so
+/// `drk --role ci --yes batch FILE` runs every line as `ci` and confirmed.
+fn forward_globals(child: &mut std::process::Command, matches: &ArgMatches) {
+    if let Some(role) = matches.get_one::<String>("role") {
+        child.env("DRK_ROLE", role);
+    }
+    if let Some(theme) = matches.get_one::<String>("theme") {
+        child.env(drk_api::styling::THEME_ENV, theme);
+    }
+    if matches.get_flag("offline") {
+        child.env(drk_api::http::OFFLINE_ENV, "1");
+    }
+    if matches.get_flag("strict") {
+        child.env("DRK_STRICT", "1");
+    }
+    for flag in ["yes", "no-cache"] {
+        if matches.get_flag(flag) {
+            child.arg(format!("--{}", flag));
+        }
+    }
+}
+
+fn summary(lines: &[BatchLine], outcomes: &[Outcome]) {
+    println!();
+    println!(
+        "{}",
+        style_dim(&format!(
+            "{:<6} {:<10} {:>8}  {}",
+            "LINE", "STATUS", "TIME", "COMMAND"
+        ))
+    );
+    println!("{}", style_dim(&box_chars().horizontal.repeat(60)));
+    for outcome in outcomes {
+        let status = match outcome.code {
+            Some(0) => style_success(&pad("ok", 10, Align::Left)).to_string(),
+            Some(code) => style_error(&pad(&format!("exit {}", code), 10, Align::Left)).to_string(),
+            None => style_error(&pad("killed", 10, Align::Left)).to_string(),
+        };
+        println!(
+            "{} {} {:>8}  {}",
+            pad(&outcome.line.number.to_string(), 6, Align::Left),
+            status,
+            format!("{:.2}s", outcome.elapsed.as_secs_f64()),
+            outcome.line.command_line()
+        );
+    }
+    for line in &lines[outcomes.len()..] {
+        println!(
+            "{} {} {:>8}  {}",
+            pad(&line.number.to_string(), 6, Align::Left),
+            style_dim(&pad("skipped", 10, Align::Left)),
+            "",
+            style_dim(&line.command_line())
+        );
+    }
+
+    let failed = outcomes.iter().filter(|o| o.code != Some(0)).count();
+    let skipped = lines.len() - outcomes.len();
+    println!();
+    if failed == 0 && skipped == 0 {
+        println!(
+            "{} {}",
+            style_success(icon_success()),
+            style_success(&format!("All {} commands succeeded", lines.len()))
+        );
+    } else {
+        println!(
+            "{} {}",
+            style_error(icon_error()),
+            style_error(&format!(
+                "{} succeeded, {} failed, {} skipped",
+                outcomes.len() - failed,
+                failed,
+                skipped
+            ))
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use clap::Command;
+use drk_api::tools::{install_hint, Catalog};
+use drk_api::{
+    icon_error, icon_success, icon_warning, pad, style_dim, style_error, style_success,
+    style_warning, Align, ToolStatus,
+};
+
+pub fn command() -> Command {
+    Command::new("check-tools").about(
+        "Check installed tools against the versions pinned in the project's drk.toml [tools]",
+    )
+}
+
+/// Prints one line per pinned tool; false if any is missing or drifted.
+pub fn run() -> anyhow::Result<bool> {
+    let catalog = Catalog::for_dir(&std::env::current_dir()?)?;
+    if catalog.is_empty() {
+        println!(
+            "{}",
+            style_dim("This project pins no tools; add a [tools] section to its drk.toml")
+        );
+        return Ok(true);
+    }
+
+    let width = catalog
+        .tools()
+        .map(|(tool, _)| tool.len())
+        .max()
+        .unwrap_or(0);
+    let mut all_ok = true;
+    for (tool, requirement) in catalog.tools() {
+        let name = pad(tool, width, Align::Left);
+        let wanted = style_dim(&format!("wants {}", requirement)).to_string();
+        match catalog.check(tool).expect("the catalog pins it") {
+            ToolStatus::Ok(version) => println!(
+                "{} {} {} {}",
+                style_success(icon_success()),
+                name,
+                version,
+                wanted
+            ),
+            ToolStatus::Unknown => println!(
+                "{} {} {} {}",
+                style_warning(icon_warning()),
+                name,
+                style_warning("version unreadable"),
+                wanted
+            ),
+            ToolStatus::Drift(version) => {
+                all_ok = false;
+                println!(
+                    "{} {} {} {}",
+                    style_error(icon_error()),
+                    name,
+                    style_error(&version.to_string()),
+                    wanted
+                );
+                println!("  {}", style_dim(&install_hint(tool)));
+            }
+            ToolStatus::Missing => {
+                all_ok = false;
+                println!(
+                    "{} {} {} {}",
+                    style_error(icon_error()),
+                    name,
+                    style_error("not installed"),
+                    wanted
+                );
+                println!("  {}", style_dim(&install_hint(tool)));
+            }
+        }
+    }
+    Ok(all_ok)
+}
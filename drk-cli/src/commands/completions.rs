@@ -0,0 +1,59 @@
+use crate::capture::Capture;
+use clap::{Arg, ArgMatches, Command};
+use clap_complete::Shell;
+use std::io::Write;
+
+pub fn command() -> Command {
+    Command::new("completions")
+        .about("Print a shell completion script covering built-in and plugin commands")
+        .long_about(
+            "Print a shell completion script covering built-in and plugin commands.\n\n\
+             The script reflects the plugins loaded now; generate it again after \
+             installing or removing one.\n\n\
+             e.g. drk completions bash > ~/.local/share/bash-completion/completions/drk\n\
+             \x20    drk completions zsh > ~/.zfunc/_drk\n\
+             \x20    drk completions fish > ~/.config/fish/completions/drk.fish",
+        )
+        .arg(
+            Arg::new("shell")
+                .required(true)
+                .value_parser(["bash", "zsh", "fish", "powershell"])
+                .help("The shell to generate completions for"),
+        )
+}
+
+/// Runs `drk completions <shell>`. Stdout carries nothing but the script:
+/// plugin chatter while loading and building the command tree is discarded.
+pub fn run(argv: &[String]) -> anyhow::Result<()> {
+    let matches = Command::new("drk")
+        .subcommand(command())
+        .get_matches_from(argv);
+    let sub = matches
+        .subcommand_matches("completions")
+        .expect("only called for completions");
+
+    let loading = Capture::start()?;
+    let built = crate::load_manager(argv).map(|mut manager| {
+        let cli = crate::build_cli(&mut manager, false);
+        manager.unload_all();
+        cli
+    });
+    loading.finish()?;
+
+    print(sub, built?.app)
+}
+
+/// Writes the script for the tree `app` to stdout.
+pub fn print(matches: &ArgMatches, mut app: Command) -> anyhow::Result<()> {
+    let shell = match matches.get_one::<String>("shell").map(String::as_str) {
+        Some("zsh") => Shell::Zsh,
+        Some("fish") => Shell::Fish,
+        Some("powershell") => Shell::PowerShell,
+        _ => Shell::Bash,
+    };
+    // Generated in memory, since clap_complete panics on a failed write
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut app, "drk", &mut script);
+    std::io::stdout().write_all(&script)?;
+    Ok(())
+}
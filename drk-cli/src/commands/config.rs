@@ -0,0 +1,118 @@
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use drk_api::markdown::render as render_markdown;
+use drk_api::ui::Ui;
+use drk_api::{editor, icon_success, style_dim, style_error, style_success};
+use drk_core::cleanup;
+use drk_core::manager::PluginManager;
+use drk_core::paths;
+
+pub fn command() -> Command {
+    Command::new("config")
+        .about("Inspect and edit drk's configuration")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("docs")
+                .about("Describe the config keys each plugin reads, from their declared schemas")
+                .arg(Arg::new("plugin").help("Only document this plugin"))
+                .arg(
+                    Arg::new("markdown")
+                        .long("markdown")
+                        .help("Print Markdown instead of styled text, e.g. for a docs site")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("edit")
+                .about("Open the config file in $EDITOR; it's only saved if it's valid TOML"),
+        )
+}
+
+pub fn run(matches: &ArgMatches, manager: &PluginManager) -> anyhow::Result<()> {
+    if let Some(("edit", _)) = matches.subcommand() {
+        return edit();
+    }
+    if let Some(("docs", sub)) = matches.subcommand() {
+        let names = match sub.get_one::<String>("plugin") {
+            Some(name) if manager.metadata(name).is_none() => {
+                anyhow::bail!("No plugin named '{}' is loaded", name)
+            }
+            Some(name) => vec![name.clone()],
+            None => manager.plugin_names(),
+        };
+        let docs = docs(manager, &names);
+        if docs.is_empty() {
+            let message = match sub.get_one::<String>("plugin") {
+                Some(name) => format!("{} declares no config keys", name),
+                None => "No plugin declares its config keys".to_string(),
+            };
+            println!("{}", style_dim(&message));
+        } else if sub.get_flag("markdown") {
+            print!("{}", docs);
+        } else {
+            println!("{}", render_markdown(&docs));
+        }
+    }
+    Ok(())
+}
+
+/// Edits a copy of the config file and writes it back once it parses, so a
+/// typo can't leave drk unable to start.
+fn edit() -> anyhow::Result<()> {
+    let path = paths::config_file();
+    let original = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let scratch = editor::scratch_file("toml");
+    let _cleanup = cleanup::remove_on_exit(&scratch);
+
+    let mut text = original.clone();
+    loop {
+        text = editor::edit_text(&text, &scratch)?;
+        match text.parse::<toml::Table>() {
+            Ok(_) => break,
+            Err(e) => {
+                eprintln!("{} {}", style_error("Invalid TOML:"), e.message());
+                if !Ui::new().confirm("Edit it again?", true)? {
+                    anyhow::bail!("The config was left unchanged");
+                }
+            }
+        }
+    }
+    if text.trim_end() == original.trim_end() {
+        println!("{}", style_dim("The config is unchanged"));
+        return Ok(());
+    }
+    std::fs::create_dir_all(paths::config_dir())?;
+    std::fs::write(&path, text)?;
+    println!("{} Saved {}", style_success(icon_success()), path.display());
+    Ok(())
+}
+
+/// A Markdown section per plugin that declares config keys.
+fn docs(manager: &PluginManager, names: &[String]) -> String {
+    let mut out = String::new();
+    for name in names {
+        let keys = manager.config_schema(name).unwrap_or_default();
+        if keys.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("## [{}]\n\n", name));
+        if let Some(meta) = manager.metadata(name).filter(|m| !m.description.is_empty()) {
+            out.push_str(&format!("{}\n\n", meta.description));
+        }
+        for key in keys {
+            let default = match &key.default {
+                Some(value) => format!(", default `{}`", value),
+                None => String::new(),
+            };
+            out.push_str(&format!(
+                "- `{}` ({}{}): {}\n",
+                key.name, key.value_type, default, key.description
+            ));
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,313 @@
+use crate::capture::Capture;
+use anyhow::Context as _;
+use clap::{Arg, Command};
+use drk_api::{CommandMatches, PluginCommand, SystemEvent};
+use drk_core::availability;
+use drk_core::invocation::{CommandResult, Invocation};
+use drk_core::manager::{CommandOutcome, PluginManager};
+use drk_core::redact;
+use drk_core::roles::Role;
+use std::io::Read;
+
+pub fn command() -> Command {
+    Command::new("exec-json")
+        .about("Run a command described as JSON and print the result as JSON (for scripts and build tools)")
+        .arg(
+            Arg::new("invocation")
+                .value_name("JSON")
+                .help("e.g. '{\"command\": \"greet\", \"args\": {\"name\": \"Bob\"}}'; read from stdin if omitted"),
+        )
+}
+
+/// Runs `drk exec-json`. Stdout carries nothing but the result JSON: plugin
+/// chatter during loading is discarded and command output is captured into
+/// the result, with styling turned off.
+pub fn run(argv: &[String]) -> anyhow::Result<()> {
+    let matches = Command::new("drk")
+        .subcommand(command())
+        .get_matches_from(argv);
+    let sub = matches
+        .subcommand_matches("exec-json")
+        .expect("only called for exec-json");
+
+    let json = match sub.get_one::<String>("invocation") {
+        Some(json) => json.clone(),
+        None => {
+            let mut input = String::new();
+            std::io::stdin()
+                .read_to_string(&mut input)
+                .context("Could not read the invocation from stdin")?;
+            input
+        }
+    };
+
+    crate::output_mode::unstyled();
+
+    let loading = Capture::start()?;
+    let loaded = crate::load_manager(argv);
+    loading.finish()?;
+
+    let result = match (Invocation::parse(&json), loaded) {
+        (Ok(invocation), Ok(mut manager)) => {
+            let result = execute(&invocation, &mut manager);
+            // What plugins print while unloading isn't part of the result either
+            let unloading = Capture::start()?;
+            manager.unload_all();
+            unloading.finish()?;
+            result
+        }
+        (Err(e), _) | (_, Err(e)) => CommandResult {
+            error: Some(format!("{:#}", e)),
+            exit_code: 1,
+            ..Default::default()
+        },
+    };
+
+    println!("{}", serde_json::to_string(&result)?);
+    if !result.success {
+        std::process::exit(result.exit_code);
+    }
+    Ok(())
+}
+
+/// Runs `invocation` and reports how it went; also behind `--output json`.
+pub fn execute(invocation: &Invocation, manager: &mut PluginManager) -> CommandResult {
+    let mut result = CommandResult {
+        command: invocation.command.clone(),
+        exit_code: 1,
+        ..Default::default()
+    };
+
+    let command_name = invocation.command_name();
+    if super::BUILTIN_NAMES.contains(&command_name) {
+        result.error = Some(format!(
+            "Built-in command '{}' can't be run through exec-json",
+            invocation.command
+        ));
+        return result;
+    }
+
+    manager.collect_contributed_commands();
+    let plugin_commands = manager.get_all_plugin_commands();
+    let Some((plugin_name, top)) = plugin_commands.iter().find_map(|(plugin, cmds)| {
+        cmds.iter()
+            .find(|c| c.is_called(command_name))
+            .map(|c| (plugin.clone(), c.clone()))
+    }) else {
+        result.error = Some(format!("Unknown command: {}", invocation.command));
+        return result;
+    };
+    result.plugin = Some(plugin_name.clone());
+
+    // Nested commands are named by their path, e.g. "nix init"
+    let mut schema = &top;
+    let mut path = vec![top.name.clone()];
+    for name in &invocation.path()[1..] {
+        let Some(sub) = schema.subcommand(name) else {
+            result.error = Some(format!("Unknown command: {}", invocation.command));
+            return result;
+        };
+        schema = sub;
+        path.push(sub.name.clone());
+    }
+    // From here on aliases are spelled out as the names they stand for
+    let invocation = &Invocation {
+        command: path.join(" "),
+        ..invocation.clone()
+    };
+    if !schema.subcommands.is_empty() {
+        let names: Vec<&str> = schema.subcommands.iter().map(|c| c.name.as_str()).collect();
+        result.error = Some(format!(
+            "{} needs a subcommand: {}",
+            invocation.command,
+            names.join(", ")
+        ));
+        return result;
+    }
+
+    let cwd = std::env::current_dir().unwrap_or_default();
+    if let Some(missing) = availability::check(&top.requires, &cwd) {
+        result.error = Some(format!(
+            "{} is unavailable: {} ({})",
+            invocation.command, missing.reason, missing.hint
+        ));
+        return result;
+    }
+
+    // `DRK_ROLE` restricts exec-json like any other entry point; an invocation
+    // can't name a different role to get around it
+    let env_role = std::env::var("DRK_ROLE").ok();
+    let role_name = match (&invocation.role, &env_role) {
+        (Some(asked), Some(env)) if asked != env => {
+            result.error = Some(format!(
+                "Invocation asks for role '{}' but DRK_ROLE is '{}'",
+                asked, env
+            ));
+            return result;
+        }
+        (asked, env) => asked.as_ref().or(env.as_ref()),
+    };
+    let role = match role_name {
+        Some(name) => match Role::from_config(manager.config(), name) {
+            Ok(role) => Some(role),
+            Err(e) => {
+                result.error = Some(format!("{:#}", e));
+                return result;
+            }
+        },
+        None => None,
+    };
+    let matches = match invocation.to_matches(schema) {
+        Ok(matches) => matches,
+        Err(e) => {
+            result.error = Some(format!("{:#}", e));
+            return result;
+        }
+    };
+    let argv = invocation.to_argv(&matches, schema);
+    let resolved = Resolved {
+        plugin_name: &plugin_name,
+        top: &top,
+        matches,
+        argv: &argv,
+        role: role.as_ref(),
+        yes: invocation.yes,
+    };
+    run_resolved(resolved, result, manager)
+}
+
+/// A plugin command found and checked, ready to run.
+pub struct Resolved<'a> {
+    pub plugin_name: &'a str,
+    pub top: &'a PluginCommand,
+    pub matches: CommandMatches,
+    /// The command line after `drk`, starting with the command's name
+    pub argv: &'a [String],
+    pub role: Option<&'a Role>,
+    /// Whether a command marked dangerous may run
+    pub yes: bool,
+}
+
+/// Runs the command `resolved` describes with its output captured into
+/// `result`, then audits it and records it in history. Everything that runs a
+/// plugin command outside the terminal goes through here, so the role, the
+/// dangerous-command check and PreCommand vetoes apply to all of them.
+pub fn run_resolved(
+    resolved: Resolved,
+    mut result: CommandResult,
+    manager: &mut PluginManager,
+) -> CommandResult {
+    let Resolved {
+        plugin_name,
+        top,
+        matches,
+        argv,
+        role,
+        yes,
+    } = resolved;
+    let command_name = matches.command_name.clone();
+    result.plugin = Some(plugin_name.to_string());
+
+    if let Some(role) = role {
+        if !role.allows(plugin_name, &command_name) {
+            let detail = format!("denied for role '{}'", role.name);
+            crate::audit_command(manager, plugin_name, &command_name, &detail);
+            result.error = Some(format!(
+                "Role '{}' is not allowed to run {}",
+                role.name,
+                matches.path.join(" ")
+            ));
+            return result;
+        }
+    }
+
+    // Only for logging, so sensitive values are masked
+    let secrets = redact::sensitive_values(top, &matches.path, &matches.args);
+    manager.redact(&secrets);
+    let argv = redact::mask_args(argv, &secrets);
+    if let Some(command) = crate::confirm::dangerous(top, &matches.path) {
+        if !yes {
+            let warning = command
+                .warning
+                .as_deref()
+                .unwrap_or("it may change or delete things");
+            result.error = Some(format!(
+                "{} is marked dangerous ({}); set \"yes\": true or pass --yes to run it",
+                matches.path.join(" "),
+                warning
+            ));
+            return result;
+        }
+    }
+
+    let mut outcome = CommandOutcome::default();
+    let mut veto = None;
+    let output = Capture::start().and_then(|capture| {
+        veto = manager.fire_pre_command(&command_name, &argv[1..]);
+        if veto.is_some() {
+            manager.fire_event(SystemEvent::PostCommand {
+                name: command_name.clone(),
+                success: false,
+            });
+            return capture.finish();
+        }
+        outcome = manager.fire_event_with_result(SystemEvent::ExecuteCommand {
+            plugin_name: plugin_name.to_string(),
+            matches,
+        });
+        let cancelled = manager.cancellation_token().is_cancelled();
+        if cancelled {
+            manager.run_cancel_hooks();
+        }
+        manager.fire_event(SystemEvent::PostCommand {
+            name: command_name.clone(),
+            success: !cancelled && !outcome.failed(plugin_name),
+        });
+        capture.finish()
+    });
+    result.output = match output {
+        Ok(output) => output,
+        Err(e) => {
+            result.error = Some(format!("{:#}", e));
+            return result;
+        }
+    };
+
+    if let Some(veto) = veto {
+        let detail = format!("cancelled by {}: {}", veto.plugin, veto.reason);
+        crate::audit_command(manager, plugin_name, &command_name, &detail);
+        crate::record_history(&argv, role, false);
+        result.error = Some(format!(
+            "Plugin '{}' cancelled {}: {}",
+            veto.plugin, command_name, veto.reason
+        ));
+        return result;
+    }
+
+    if manager.cancellation_token().is_cancelled() {
+        crate::audit_command(manager, plugin_name, &command_name, "cancelled");
+        crate::record_history(&argv, role, false);
+        result.error = Some("Cancelled".to_string());
+        result.exit_code = crate::interrupt::EXIT_INTERRUPTED;
+        return result;
+    }
+
+    if let Some(output) = outcome.output(plugin_name) {
+        result.output.push_str(&output.render());
+        result.output.push('\n');
+        result.data = Some(output.data.clone());
+    }
+
+    crate::audit_command(manager, plugin_name, &command_name, &argv[1..].join(" "));
+    crate::record_usage(manager, &command_name);
+    let failed = outcome.error(plugin_name);
+    crate::record_history(&argv, role, failed.is_none());
+    if let Some(error) = failed {
+        result.error = Some(error.to_string());
+        return result;
+    }
+
+    result.success = true;
+    result.exit_code = 0;
+    result
+}
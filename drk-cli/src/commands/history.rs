@@ -0,0 +1,97 @@
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use drk_api::{icon_error, style_dim, style_error, style_primary};
+use drk_core::history::History;
+use drk_core::paths;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn command() -> Command {
+    Command::new("history")
+        .about("Show previously run commands")
+        .arg(
+            Arg::new("project")
+                .long("project")
+                .action(ArgAction::SetTrue)
+                .help("Only show commands run in the current project"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_name("ROLE")
+                .help("Only show commands run under this role"),
+        )
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("20")
+                .help("How many entries to show, newest last"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> anyhow::Result<()> {
+    let limit = *matches
+        .get_one::<usize>("limit")
+        .expect("limit has a default");
+    let profile = matches.get_one::<String>("profile");
+
+    let project = if matches.get_flag("project") {
+        let cwd = std::env::current_dir()?;
+        let Some(root) = paths::project_root(&cwd) else {
+            eprintln!(
+                "{} {}",
+                style_error(icon_error()),
+                style_error("Not inside a project (no drk.toml or .git found)")
+            );
+            std::process::exit(1);
+        };
+        Some(root.display().to_string())
+    } else {
+        None
+    };
+
+    let entries: Vec<_> = History::new(History::default_path())
+        .entries()?
+        .into_iter()
+        .filter(|e| project.is_none() || e.project == project)
+        .filter(|e| profile.is_none() || e.profile.as_ref() == profile)
+        .collect();
+
+    if entries.is_empty() {
+        println!("{}", style_dim("No matching history"));
+        return Ok(());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    for entry in entries.iter().skip(entries.len().saturating_sub(limit)) {
+        let mut line = format!(
+            "{:>8}  {}",
+            style_dim(&ago(now.saturating_sub(entry.timestamp))),
+            style_primary(&format!("drk {}", entry.command))
+        );
+        if !entry.success {
+            line.push_str(&format!(" {}", style_error("(failed)")));
+        }
+        if let Some(profile) = &entry.profile {
+            line.push_str(&format!(" {}", style_dim(&format!("[{}]", profile))));
+        }
+        // The project is implied when filtering by it
+        if let (None, Some(dir)) = (&project, &entry.project) {
+            line.push_str(&format!(" {}", style_dim(dir)));
+        }
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// `seconds` as a rough age, e.g. `2m ago`.
+pub fn ago(seconds: u64) -> String {
+    match seconds {
+        s if s < 60 => format!("{}s ago", s),
+        s if s < 3600 => format!("{}m ago", s / 60),
+        s if s < 86400 => format!("{}h ago", s / 3600),
+        s => format!("{}d ago", s / 86400),
+    }
+}
@@ -0,0 +1,41 @@
+//! Built-in commands that live in the host rather than in a plugin.
+
+pub mod alias;
+pub mod audit;
+pub mod batch;
+pub mod check_tools;
+pub mod completions;
+pub mod config;
+pub mod exec_json;
+pub mod history;
+pub mod plugin;
+pub mod print_invocation;
+pub mod rerun;
+pub mod saved;
+pub mod shell;
+pub mod state;
+pub mod suggest;
+pub mod tips;
+pub mod watch;
+pub mod wizard;
+
+/// Command names reserved by the host. Plugins can't register these.
+pub const BUILTIN_NAMES: &[&str] = &[
+    "alias",
+    "audit",
+    "batch",
+    "check-tools",
+    "completions",
+    "config",
+    "exec-json",
+    "history",
+    "hook",
+    "plugin",
+    "rerun",
+    "run-saved",
+    "save-as",
+    "shell-init",
+    "state",
+    "suggest",
+    "tips",
+];
@@ -0,0 +1,738 @@
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use drk_api::markdown::render as render_markdown;
+use drk_api::{
+    box_chars, icon_error, icon_success, icon_warning, pad, style_dim, style_error, style_primary,
+    style_success, style_warning, Align, PluginCommand,
+};
+use drk_core::changelog;
+use drk_core::installer;
+use drk_core::load_errors::{self, Category};
+use drk_core::manager::PluginManager;
+use drk_core::manifest::PluginManifest;
+use drk_core::paths;
+use drk_core::plugin_data;
+use drk_core::registry::{self, Index};
+use drk_core::resources;
+use std::path::PathBuf;
+
+pub fn command() -> Command {
+    Command::new("plugin")
+        .about("Manage installed plugins")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("install")
+                .about("Install a plugin")
+                .arg(
+                    Arg::new("name")
+                        .value_name("NAME")
+                        .help("A plugin from the registry index (see update-index)"),
+                )
+                .arg(
+                    Arg::new("from-source")
+                        .long("from-source")
+                        .value_name("FLAKE_REF")
+                        .help("Build the plugin from a nix flake output and install the result"),
+                )
+                .arg(
+                    Arg::new("bundle")
+                        .long("bundle")
+                        .value_name("FILE")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Install every plugin from an offline bundle"),
+                )
+                .arg(
+                    Arg::new("url")
+                        .long("url")
+                        .value_name("URL")
+                        .action(ArgAction::Append)
+                        .help("Download a plugin library and install it (repeatable)"),
+                )
+                .arg(
+                    Arg::new("path")
+                        .long("path")
+                        .value_name("PATH")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Install a plugin library, a directory of them, or a Cargo project (built with cargo build --release)"),
+                )
+                .arg(
+                    Arg::new("git")
+                        .long("git")
+                        .value_name("URL")
+                        .help("Clone a git repository and install from it like --path; URL#ref picks a branch or tag"),
+                )
+                .arg(
+                    Arg::new("no-build")
+                        .long("no-build")
+                        .action(ArgAction::SetTrue)
+                        .help("Install a Cargo project's existing release build instead of building it"),
+                )
+                .arg(
+                    Arg::new("jobs")
+                        .long("jobs")
+                        .short('j')
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("4")
+                        .requires("url")
+                        .help("How many downloads to run at once"),
+                )
+                .group(
+                    ArgGroup::new("source")
+                        .args(["name", "from-source", "bundle", "url", "path", "git"])
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("update-index")
+                .about("Download the registry index named in [registry] index"),
+        )
+        .subcommand(
+            Command::new("bundle")
+                .about("Pack installed plugins and their lockfile into a portable archive")
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .value_name("FILE")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .default_value("drk-plugins.tar")
+                        .help("Where to write the archive"),
+                ),
+        )
+        .subcommand(Command::new("list").about("List loaded plugins and whether they're enabled"))
+        .subcommand(
+            Command::new("info")
+                .about("Show a plugin's details and commands")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("The plugin to describe"),
+                ),
+        )
+        .subcommand(
+            Command::new("enable")
+                .about("Enable a plugin (saved in the config)")
+                .arg(Arg::new("name").required(true).help("The plugin to enable")),
+        )
+        .subcommand(
+            Command::new("disable")
+                .about("Disable a non-essential plugin (saved in the config)")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("The plugin to disable"),
+                ),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Show approximate time, memory, handle and event usage per loaded plugin")
+                .long_about(
+                    "Show approximate time, memory, handle and event usage per loaded plugin. \
+                     CALLS, TIME and the rest cover this run; TOTAL and SHARE add up the \
+                     time of every run, to find the plugin that slows drk down.",
+                ),
+        )
+        .subcommand(
+            Command::new("rebuild")
+                .about("Rebuild an installed plugin from the flake recorded in its manifest")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("The plugin to rebuild"),
+                ),
+        )
+        .subcommand(
+            Command::new("uninstall")
+                .about("Remove an installed plugin from the plugin directory")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("The plugin, or its library file name"),
+                ),
+        )
+        .subcommand(
+            Command::new("clean")
+                .about("Delete everything in a plugin's data directory")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("The plugin whose data to wipe"),
+                ),
+        )
+        .subcommand(
+            Command::new("reset")
+                .about("Rerun a plugin's first-install setup (default config, assets, quickstart)")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("The plugin to set up again"),
+                ),
+        )
+        .subcommand(
+            Command::new("errors")
+                .about("Show the plugin libraries that failed to load in the last run, and why")
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .action(ArgAction::SetTrue)
+                        .help("Also list libraries that aren't drk plugins at all"),
+                ),
+        )
+        .subcommand(
+            Command::new("upgrade")
+                .about("Rebuild a plugin from its recorded flake and show what changed")
+                .arg(
+                    Arg::new("name")
+                        .required(true)
+                        .help("The plugin to upgrade"),
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches, manager: &mut PluginManager) -> anyhow::Result<()> {
+    match matches.subcommand() {
+        Some(("install", sub)) => {
+            if let Some(name) = sub.get_one::<String>("name") {
+                let entry = from_registry(name)?;
+                report_installed(installer::install_from_urls(&[entry.url], 1)?);
+            } else if let Some(flake_ref) = sub.get_one::<String>("from-source") {
+                println!("Building {} with nix...", style_primary(flake_ref));
+                report_installed(installer::install_from_flake(flake_ref)?);
+            } else if let Some(bundle) = sub.get_one::<PathBuf>("bundle") {
+                report_installed(installer::install_from_bundle(bundle)?);
+            } else if let Some(path) = sub.get_one::<PathBuf>("path") {
+                let build = !sub.get_flag("no-build");
+                report_installed(installer::install_from_path(path, build)?);
+            } else if let Some(url) = sub.get_one::<String>("git") {
+                println!("Cloning {}...", style_primary(url));
+                let build = !sub.get_flag("no-build");
+                report_installed(installer::install_from_git(url, build)?);
+            } else if let Some(urls) = sub.get_many::<String>("url") {
+                let urls: Vec<String> = urls.cloned().collect();
+                let jobs = *sub.get_one::<usize>("jobs").expect("jobs has a default");
+                report_installed(installer::install_from_urls(&urls, jobs)?);
+            }
+        }
+        Some(("update-index", _)) => {
+            let Some(url) = registry::index_url(manager.config()) else {
+                anyhow::bail!("No registry configured; set [registry] index to the index URL");
+            };
+            let index = registry::update(url, &Index::default_path())?;
+            println!(
+                "{} {} {} plugin(s)",
+                style_success(icon_success()),
+                style_success("Registry index lists"),
+                index.plugins.len()
+            );
+        }
+        Some(("bundle", sub)) => {
+            let out = sub.get_one::<PathBuf>("out").expect("out has a default");
+            let count = installer::create_bundle(out)?;
+            println!(
+                "{} {} {} plugin(s) into {}",
+                style_success(icon_success()),
+                style_success("Bundled"),
+                count,
+                style_primary(&out.display().to_string())
+            );
+        }
+        Some(("list", _)) => list(manager),
+        Some(("info", sub)) => {
+            let name = sub.get_one::<String>("name").expect("name is required");
+            info(name, manager)?;
+        }
+        Some((toggle @ ("enable" | "disable"), sub)) => {
+            let name = sub.get_one::<String>("name").expect("name is required");
+            let enable = toggle == "enable";
+            if manager.is_enabled(name) == Some(enable) {
+                println!("{} is already {}d", style_primary(name), toggle);
+            } else {
+                manager.set_enabled(name, enable)?;
+                println!(
+                    "{} {} {}",
+                    style_success(icon_success()),
+                    style_success(if enable { "Enabled" } else { "Disabled" }),
+                    style_primary(name)
+                );
+            }
+        }
+        Some(("stats", _)) => stats(manager)?,
+        Some(("rebuild", sub)) => {
+            let name = sub.get_one::<String>("name").expect("name is required");
+            report_installed(rebuild(name, manager)?);
+        }
+        Some(("uninstall", sub)) => {
+            let name = sub.get_one::<String>("name").expect("name is required");
+            uninstall(name, manager)?;
+        }
+        Some(("clean", sub)) => {
+            let name = sub.get_one::<String>("name").expect("name is required");
+            clean(name)?;
+        }
+        Some(("reset", sub)) => {
+            let name = sub.get_one::<String>("name").expect("name is required");
+            manager.reinstall(name)?;
+            println!(
+                "{} {} {}",
+                style_success(icon_success()),
+                style_success("Reran setup for"),
+                style_primary(name)
+            );
+        }
+        Some(("errors", sub)) => errors(sub.get_flag("all"))?,
+        Some(("upgrade", sub)) => {
+            let name = sub.get_one::<String>("name").expect("name is required");
+            upgrade(name, manager)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Uninstalls the plugin loaded as `name`, or the library file `name` in
+/// the plugin directory.
+fn uninstall(name: &str, manager: &mut PluginManager) -> anyhow::Result<()> {
+    let dir = paths::plugins_dir();
+    let installed_here = |library: &std::path::Path| {
+        let parent = library.parent().and_then(|p| p.canonicalize().ok());
+        parent.is_some() && parent == dir.canonicalize().ok()
+    };
+    let file = match manager.library(name) {
+        Some(library) if installed_here(library) => library
+            .file_name()
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        Some(library) => anyhow::bail!(
+            "{} was loaded from {} rather than installed; remove it there",
+            name,
+            library.display()
+        ),
+        None if manager.metadata(name).is_some() => {
+            anyhow::bail!("{} is built into drk and can't be uninstalled", name)
+        }
+        None if dir.join(name).is_file() => name.to_string(),
+        None => anyhow::bail!("No installed plugin named '{}'", name),
+    };
+
+    if manager.library(name).is_some() {
+        if let Err(e) = manager.unload_plugin(name) {
+            eprintln!("{} {:#}", style_warning(icon_warning()), e);
+        }
+    }
+    let removed = installer::uninstall(&file)?;
+    println!(
+        "{} {} {}",
+        style_success(icon_success()),
+        style_success("Uninstalled"),
+        style_primary(&removed.display().to_string())
+    );
+    let data = plugin_data::dir_for(name);
+    if data.exists() {
+        println!(
+            "{}",
+            style_dim(&format!(
+                "Its data is still in {}; drk plugin clean {} removes it",
+                data.display(),
+                name
+            ))
+        );
+    }
+    Ok(())
+}
+
+/// Wipes the data directory of `name`.
+fn clean(name: &str) -> anyhow::Result<()> {
+    let dir = plugin_data::dir_for(name);
+    if !dir.exists() {
+        println!("{} has no data to clean", style_primary(name));
+        return Ok(());
+    }
+    let freed = plugin_data::dir_size(&dir);
+    std::fs::remove_dir_all(&dir)?;
+    println!(
+        "{} {} {} ({} freed)",
+        style_success(icon_success()),
+        style_success("Cleaned"),
+        style_primary(&dir.display().to_string()),
+        format_bytes(freed as i64)
+    );
+    Ok(())
+}
+
+/// Prints the last run's load failures grouped by category, each with a fix.
+fn errors(show_all: bool) -> anyhow::Result<()> {
+    let Some(report) = load_errors::load(&load_errors::default_path())? else {
+        println!("No plugin load has been recorded yet");
+        return Ok(());
+    };
+    if report.errors.is_empty() {
+        println!(
+            "{} {}",
+            style_success(icon_success()),
+            style_success("Every plugin loaded in the last run")
+        );
+        return Ok(());
+    }
+
+    for category in Category::ALL {
+        let failed: Vec<_> = report
+            .errors
+            .iter()
+            .filter(|e| e.category == category)
+            .collect();
+        if failed.is_empty() {
+            continue;
+        }
+        println!(
+            "{} {} ({})",
+            style_error(icon_error()),
+            style_error(category.label()),
+            failed.len()
+        );
+
+        // Build directories are full of ordinary shared libraries
+        if category == Category::NotAPlugin && !show_all {
+            println!("  {}", style_dim("use --all to list them"));
+        } else {
+            for error in failed {
+                println!("  {}", style_primary(&error.path.display().to_string()));
+                println!("    {}", style_dim(&error.message));
+                if category == Category::MissingDependency {
+                    let missing = load_errors::missing_libraries(&error.path);
+                    if !missing.is_empty() {
+                        println!("    missing: {}", missing.join(", "));
+                    }
+                }
+            }
+        }
+        println!("  {} {}\n", style_dim("fix:"), category.suggestion());
+    }
+    Ok(())
+}
+
+/// The registry entry for `name`, from the cached index.
+fn from_registry(name: &str) -> anyhow::Result<registry::Entry> {
+    let Some(index) = Index::load_cached(&Index::default_path()) else {
+        anyhow::bail!(
+            "No registry index cached; set [registry] index and run `drk plugin update-index`"
+        );
+    };
+    match index.find(name) {
+        Some(entry) => Ok(entry.clone()),
+        None => anyhow::bail!(
+            "'{}' is not in the registry index; `drk plugin update-index` refreshes it",
+            name
+        ),
+    }
+}
+
+/// Reinstalls `name` from the flake recorded in its manifest.
+fn rebuild(name: &str, manager: &PluginManager) -> anyhow::Result<Vec<PathBuf>> {
+    let Some(build) = manager.manifest(name).and_then(|m| m.build.as_ref()) else {
+        eprintln!(
+            "{} {} {}",
+            style_error(icon_error()),
+            style_error("No [build] flake recorded in the manifest of"),
+            style_primary(name)
+        );
+        std::process::exit(1);
+    };
+    println!("Building {} with nix...", style_primary(&build.flake));
+    installer::install_from_flake(&build.flake)
+}
+
+/// Rebuilds `name`, then prints the changelog sections between the loaded
+/// version and the one just installed.
+fn upgrade(name: &str, manager: &PluginManager) -> anyhow::Result<()> {
+    let old_version = manager.metadata(name).map(|m| m.version.clone());
+    let installed = rebuild(name, manager)?;
+
+    // The flake may produce several libraries; the upgraded one is whichever
+    // ships a manifest with a changelog
+    let manifest = installed
+        .iter()
+        .filter_map(|path| PluginManifest::load_for(path).ok().flatten())
+        .find(|m| m.changelog.is_some());
+    report_installed(installed);
+
+    let Some(manifest) = manifest else {
+        return Ok(());
+    };
+    let new_version = manifest.version.as_deref();
+    let old_version = old_version.unwrap_or_default();
+    if new_version.is_some_and(|new| changelog::compare(new, &old_version).is_le()) {
+        println!(
+            "{} is already at {}",
+            style_primary(name),
+            style_primary(&old_version)
+        );
+        return Ok(());
+    }
+
+    let notes = changelog::sections_between(
+        manifest.changelog.as_deref().unwrap_or_default(),
+        &old_version,
+        new_version,
+    );
+    if notes.is_empty() {
+        return Ok(());
+    }
+    println!(
+        "\n{} {} -> {}\n",
+        style_primary(name),
+        style_dim(&old_version),
+        style_success(new_version.unwrap_or("latest"))
+    );
+    println!("{}", render_markdown(&notes));
+    Ok(())
+}
+
+fn report_installed(paths: Vec<PathBuf>) {
+    for path in paths {
+        println!(
+            "{} {} {}",
+            style_success(icon_success()),
+            style_success("Installed"),
+            style_primary(&path.display().to_string())
+        );
+    }
+}
+
+fn list(manager: &PluginManager) {
+    let names = manager.plugin_names();
+    if names.is_empty() {
+        println!("No plugins loaded");
+        return;
+    }
+    println!(
+        "{}",
+        style_dim(&format!(
+            "{:<16} {:<9} {:<9} {:>8}  {}",
+            "PLUGIN", "VERSION", "STATUS", "COMMANDS", "DESCRIPTION"
+        ))
+    );
+    println!("{}", style_dim(&box_chars().horizontal.repeat(73)));
+    for name in names {
+        let Some(meta) = manager.metadata(&name) else {
+            continue;
+        };
+        let commands = manager.commands(&name).map_or(0, |c| c.len());
+        println!(
+            "{} {} {} {:>8}  {}",
+            pad(&name, 16, Align::Left),
+            pad(&meta.version, 9, Align::Left),
+            pad(&status(manager, &name), 9, Align::Left),
+            commands,
+            style_dim(&meta.description)
+        );
+    }
+}
+
+fn info(name: &str, manager: &PluginManager) -> anyhow::Result<()> {
+    let Some(meta) = manager.metadata(name) else {
+        anyhow::bail!("No plugin named '{}' is loaded", name);
+    };
+    println!("{} {}", style_primary(&meta.name), style_dim(&meta.version));
+    if !meta.description.is_empty() {
+        println!("  {}", meta.description);
+    }
+    println!();
+    println!("  {} {}", style_dim("author: "), meta.author);
+    println!("  {} {}", style_dim("status: "), status(manager, name));
+    if let Some(library) = manager.library(name) {
+        println!("  {} {}", style_dim("library:"), library.display());
+    }
+
+    let commands = manager.commands(name).unwrap_or_default();
+    println!();
+    if commands.is_empty() {
+        println!("{}", style_dim("No commands"));
+    }
+    for cmd in &commands {
+        print_command(cmd, "");
+    }
+    Ok(())
+}
+
+/// A command in `drk plugin info` with its arguments, followed by its nested
+/// commands under their full path.
+fn print_command(cmd: &PluginCommand, parent: &str) {
+    let name = match parent {
+        "" => cmd.name.clone(),
+        _ => format!("{} {}", parent, cmd.name),
+    };
+    let mut description = cmd.description.clone();
+    if !cmd.aliases.is_empty() {
+        description.push_str(&format!(" [aliases: {}]", cmd.aliases.join(", ")));
+    }
+    println!(
+        "  {} {}",
+        pad(&name, 14, Align::Left),
+        style_dim(&description)
+    );
+    let mut shorts = Vec::new();
+    for arg in &cmd.args {
+        let flag = match &arg.arg_type {
+            drk_api::ArgType::Positional => format!("<{}>", arg.name),
+            drk_api::ArgType::Variadic => format!("<{}>...", arg.name),
+            drk_api::ArgType::Boolean if arg.negatable => format!("--[no-]{}", arg.name),
+            drk_api::ArgType::Boolean => format!("--{}", arg.name),
+            drk_api::ArgType::OptionalValue => format!("--{}[=<value>]", arg.name),
+            drk_api::ArgType::Choice(choices) => format!("--{} <{}>", arg.name, choices.join("|")),
+            drk_api::ArgType::Count => match arg.count_short(&shorts) {
+                Some(short) => {
+                    shorts.push(short);
+                    format!("-{}, --{}...", short, arg.name)
+                }
+                None => format!("--{}...", arg.name),
+            },
+            _ => format!(
+                "--{} <{}>",
+                arg.name,
+                format!("{:?}", arg.arg_type).to_lowercase()
+            ),
+        };
+        let flag = if arg.multiple {
+            format!("{}...", flag)
+        } else {
+            flag
+        };
+        let mut notes = String::new();
+        if arg.required {
+            notes.push_str(" (required)");
+        }
+        if let Some(default) = &arg.default {
+            notes.push_str(&format!(" [default: {}]", default));
+        }
+        if let Some(env) = &arg.env {
+            notes.push_str(&format!(" [env: {}]", env));
+        }
+        println!(
+            "    {} {}{}",
+            pad(&flag, 24, Align::Left),
+            style_dim(&arg.description),
+            style_dim(&notes)
+        );
+    }
+    for nested in &cmd.subcommands {
+        print_command(nested, &name);
+    }
+}
+
+fn status(manager: &PluginManager, name: &str) -> String {
+    let essential = manager.metadata(name).is_some_and(|m| m.essential);
+    match manager.is_enabled(name) {
+        _ if essential => "essential".to_string(),
+        Some(true) => "enabled".to_string(),
+        _ => "disabled".to_string(),
+    }
+}
+
+fn stats(manager: &PluginManager) -> anyhow::Result<()> {
+    let slow_share = resources::slow_share(manager.config())?;
+    let resources = manager.resource_stats();
+    let events = manager.event_stats();
+    let earlier = manager
+        .state_store()
+        .map(resources::load_totals)
+        .unwrap_or_default();
+    let mut names: Vec<&String> = resources.keys().chain(events.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    // Time over every run, this one included
+    let total_of = |name: &String| {
+        let earlier = earlier.get(name).map(|t| t.busy()).unwrap_or_default();
+        let now = resources.get(name).map(|r| r.busy).unwrap_or_default();
+        (earlier + now).as_secs_f64()
+    };
+    let total: f64 = names.iter().map(|name| total_of(name)).sum();
+
+    println!(
+        "{}",
+        style_dim(&format!(
+            "{:<16} {:>6} {:>9} {:>9} {:>6} {:>11} {:>6} {:>8} {:>8} {:>12}",
+            "PLUGIN",
+            "CALLS",
+            "TIME",
+            "TOTAL",
+            "SHARE",
+            "MEMORY",
+            "FILES",
+            "EVENTS",
+            "DROPPED",
+            "ALLOCATED"
+        ))
+    );
+    println!("{}", style_dim(&box_chars().horizontal.repeat(100)));
+    let mut slow = Vec::new();
+    for name in names {
+        let res = resources.get(name).cloned().unwrap_or_default();
+        let ev = events.get(name).cloned().unwrap_or_default();
+        let all_runs = total_of(name);
+        let share = if total > 0.0 {
+            all_runs / total * 100.0
+        } else {
+            0.0
+        };
+        if share > slow_share {
+            slow.push(name);
+        }
+        println!(
+            "{} {:>6} {:>9} {:>9} {:>5.0}% {:>11} {:>+6} {:>8} {:>8} {:>12}",
+            pad(name, 16, Align::Left),
+            res.calls,
+            format!("{:.1} ms", res.busy.as_secs_f64() * 1000.0),
+            format_seconds(all_runs),
+            share,
+            format_bytes(res.rss_delta_bytes),
+            res.open_files_delta,
+            ev.emitted,
+            ev.dropped,
+            if res.allocations > 0 {
+                format_bytes(res.allocated_bytes as i64)
+            } else {
+                "-".to_string()
+            }
+        );
+    }
+    println!(
+        "{}",
+        style_dim(
+            "TOTAL and SHARE add up every run. Memory and file counts are process-wide deltas measured around plugin calls."
+        )
+    );
+    for name in slow {
+        println!(
+            "{} {}",
+            style_warning(icon_warning()),
+            style_warning(&format!(
+                "'{}' takes over {}% of the time drk spends in plugins ([stats] slow_share)",
+                name, slow_share
+            ))
+        );
+    }
+    Ok(())
+}
+
+/// Handler time, in the largest unit that keeps it readable.
+fn format_seconds(seconds: f64) -> String {
+    if seconds >= 60.0 {
+        format!("{:.1} min", seconds / 60.0)
+    } else if seconds >= 1.0 {
+        format!("{:.1} s", seconds)
+    } else {
+        format!("{:.1} ms", seconds * 1000.0)
+    }
+}
+
+fn format_bytes(bytes: i64) -> String {
+    let sign = if bytes < 0 { "-" } else { "" };
+    let abs = bytes.unsigned_abs() as f64;
+    if abs >= 1024.0 * 1024.0 {
+        format!("{}{:.1} MiB", sign, abs / (1024.0 * 1024.0))
+    } else if abs >= 1024.0 {
+        format!("{}{:.1} KiB", sign, abs / 1024.0)
+    } else {
+        format!("{}{} B", sign, abs)
+    }
+}
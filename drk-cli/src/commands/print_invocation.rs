@@ -0,0 +1,114 @@
+//! `--print-invocation`: what a plugin command would do, without doing it.
+//!
+//! Shows the plugin that would handle the command, the arguments it would
+//! receive once aliases, saved commands, `DRK_*` variables and placeholders
+//! are resolved, and the events that would fire.
+
+use drk_api::{pad, style_dim, style_primary, Align, CommandMatches, PluginCommand};
+use drk_core::manager::PluginManager;
+use drk_core::redact;
+use drk_core::roles::Role;
+
+pub const FLAG: &str = "--print-invocation";
+
+/// Everything routing resolved for the command, up to running it.
+pub struct Preview<'a> {
+    pub plugin_name: &'a str,
+    /// The final command line, after `drk`, with secrets masked
+    pub args: &'a [String],
+    /// Values of sensitive arguments, masked in the output
+    pub secrets: &'a [String],
+    pub matches: &'a CommandMatches,
+    /// The schema of the invoked (possibly nested) command
+    pub schema: Option<&'a PluginCommand>,
+    pub role: Option<&'a Role>,
+    /// Age in seconds of the cached result that would be shown instead
+    pub cached: Option<u64>,
+    /// The command on the path marked dangerous, which asks before running
+    pub dangerous: Option<&'a PluginCommand>,
+}
+
+pub fn print(preview: &Preview, manager: &PluginManager) {
+    let row = |label: &str, value: &str| {
+        println!("{} {}", style_dim(&pad(label, 10, Align::Left)), value);
+    };
+
+    let args: Vec<&str> = preview
+        .args
+        .iter()
+        .map(String::as_str)
+        .filter(|a| *a != FLAG)
+        .collect();
+    row(
+        "Command",
+        &style_primary(&format!("drk {}", args.join(" "))).to_string(),
+    );
+    let version = manager
+        .metadata(preview.plugin_name)
+        .map(|m| format!(" {}", m.version))
+        .unwrap_or_default();
+    let library = manager
+        .library(preview.plugin_name)
+        .map(|path| format!(" ({})", path.display()))
+        .unwrap_or_else(|| " (built in)".to_string());
+    row(
+        "Plugin",
+        &format!("{}{}{}", preview.plugin_name, version, library),
+    );
+    if let Some(role) = preview.role {
+        row("Role", &role.name);
+    }
+    if let Some(command) = preview.dangerous {
+        let warning = command.warning.as_deref().unwrap_or("marked dangerous");
+        row("Confirm", &format!("asks first (--yes skips): {}", warning));
+    }
+    let container = manager
+        .manifest(preview.plugin_name)
+        .and_then(|m| m.container.as_ref())
+        .filter(|_| !drk_core::container::is_inside());
+    if let Some(spec) = container {
+        row("Container", &spec.image);
+    }
+
+    println!("{}", style_dim("Arguments"));
+    let declared = preview
+        .schema
+        .map(|s| s.args.as_slice())
+        .unwrap_or_default();
+    if declared.is_empty() {
+        println!("  {}", style_dim("(none)"));
+    }
+    for arg in declared {
+        let value = match preview.matches.args.get(&arg.name) {
+            Some(value) => format!("{:?}", redact::mask_text(value, preview.secrets)),
+            None => style_dim("(not given)").to_string(),
+        };
+        println!("  {} {}", pad(&arg.name, 14, Align::Left), value);
+    }
+
+    println!("{}", style_dim("Events"));
+    if container.is_some() {
+        println!(
+            "  {}",
+            style_dim("none in this process; the command runs in the container")
+        );
+    } else if let Some(age) = preview.cached {
+        println!(
+            "  {}",
+            style_dim(&format!(
+                "none; the result cached {} would be shown (--no-cache to run it)",
+                super::history::ago(age)
+            ))
+        );
+    } else {
+        let name = &preview.matches.command_name;
+        println!("  PreCommand     {} {:?}", name, &args[1..]);
+        println!(
+            "  ExecuteCommand {} -> {}",
+            preview.matches.path.join(" "),
+            preview.plugin_name
+        );
+        println!("  PostCommand    {}", name);
+    }
+    println!("{}", style_dim("Nothing was run."));
+}
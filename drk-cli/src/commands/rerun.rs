@@ -0,0 +1,68 @@
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use drk_api::{icon_success, icon_warning, style_dim, style_primary, style_success, style_warning};
+use drk_core::manager::PluginManager;
+use drk_core::run_record::RunRecord;
+use std::path::PathBuf;
+
+pub fn command() -> Command {
+    Command::new("rerun")
+        .about("Replay a run recorded with --record, after reporting what changed since")
+        .arg(
+            Arg::new("file")
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("The run record to replay"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Only report the differences")
+                .action(ArgAction::SetTrue),
+        )
+}
+
+pub fn run(matches: &ArgMatches, manager: &PluginManager) -> anyhow::Result<()> {
+    let path = matches
+        .get_one::<PathBuf>("file")
+        .expect("file is required");
+    let record = RunRecord::load(path)?;
+    let now = RunRecord::capture(env!("CARGO_PKG_VERSION"), &record.args, manager);
+
+    let divergences = record.divergences(&now);
+    if divergences.is_empty() {
+        println!(
+            "{} {}",
+            style_success(icon_success()),
+            style_success("Same drk, plugins, config and environment as the recorded run")
+        );
+    } else {
+        eprintln!(
+            "{} {}",
+            style_warning(icon_warning()),
+            style_warning("This setup differs from the recorded run:")
+        );
+        for divergence in &divergences {
+            eprintln!("  {}", divergence);
+        }
+    }
+    if matches.get_flag("dry-run") {
+        return Ok(());
+    }
+
+    eprintln!(
+        "{} {}",
+        style_dim("replaying:"),
+        style_primary(&format!("drk {}", record.args.join(" ")))
+    );
+    // drk's own settings are restored; anything else is as it is now
+    let status = std::process::Command::new(std::env::current_exe()?)
+        .args(&record.args)
+        .current_dir(if record.cwd.is_dir() {
+            record.cwd.clone()
+        } else {
+            std::env::current_dir()?
+        })
+        .envs(record.env.iter().filter(|(key, _)| key.starts_with("DRK_")))
+        .status()?;
+    std::process::exit(status.code().unwrap_or(1));
+}
@@ -0,0 +1,51 @@
+use clap::{Arg, ArgMatches, Command};
+use drk_api::{icon_success, style_primary, style_success};
+use drk_core::manager::PluginManager;
+use drk_core::{paths, saved};
+
+pub fn save_as_command() -> Command {
+    Command::new("save-as")
+        .about("Save a parameterized invocation: drk save-as <name> -- <command> [args with {placeholders}]")
+        .arg(Arg::new("name").required(true).help("Name to save the invocation under"))
+        .arg(
+            Arg::new("command")
+                .required(true)
+                .num_args(1..)
+                .last(true)
+                .help("The drk command and arguments to save"),
+        )
+}
+
+/// `run-saved` is expanded before parsing (see `saved::expand_invocation`);
+/// this definition only exists for help output.
+pub fn run_saved_command() -> Command {
+    Command::new("run-saved")
+        .about("Run a saved invocation, filling placeholders with key=value pairs")
+        .arg(Arg::new("name").required(true).help("The saved invocation"))
+        .arg(
+            Arg::new("values")
+                .num_args(0..)
+                .help("Placeholder values as key=value"),
+        )
+}
+
+pub fn run_save_as(matches: &ArgMatches, manager: &mut PluginManager) -> anyhow::Result<()> {
+    let name = matches.get_one::<String>("name").expect("name is required");
+    let words: Vec<String> = matches
+        .get_many::<String>("command")
+        .expect("command is required")
+        .cloned()
+        .collect();
+
+    saved::save(manager.config_mut(), name, &words);
+    manager.save_config(paths::config_file())?;
+
+    println!(
+        "{} {} {} {}",
+        style_success(icon_success()),
+        style_success("Saved"),
+        style_primary(name),
+        words.join(" ")
+    );
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use clap::{Arg, ArgMatches, Command};
+use drk_core::manager::PluginManager;
+use drk_core::{paths, project};
+
+/// Set by the hook to the project whose `[env]` is currently exported.
+const ROOT_VAR: &str = "DRK_ENV_ROOT";
+/// Set by the hook to the variables it exported, so it can unset them later.
+const KEYS_VAR: &str = "DRK_ENV_KEYS";
+
+pub fn init_command() -> Command {
+    Command::new("shell-init")
+        .about(
+            "Print shell integration code; add `eval \"$(drk shell-init bash)\"` to your shell rc",
+        )
+        .arg(
+            Arg::new("shell")
+                .required(true)
+                .value_parser(["bash", "zsh", "fish"])
+                .help("Shell syntax to emit"),
+        )
+}
+
+pub fn hook_command() -> Command {
+    Command::new("hook")
+        .about("Called by the shell integration; prints code for the shell to evaluate")
+        .hide(true)
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("env")
+                .about("Export the current project's [env] and unset the previous one's")
+                .arg(
+                    Arg::new("shell")
+                        .long("shell")
+                        .required(true)
+                        .value_parser(["bash", "zsh", "fish"]),
+                ),
+        )
+}
+
+/// Handles `shell-init` and `hook`. These run on every prompt and their output
+/// is evaluated by the shell, so they skip plugin loading entirely.
+pub fn run(argv: &[String]) -> anyhow::Result<()> {
+    let matches = Command::new("drk")
+        .subcommand(init_command())
+        .subcommand(hook_command())
+        .get_matches_from(argv);
+
+    match matches.subcommand() {
+        Some(("shell-init", sub)) => init(sub),
+        Some(("hook", sub)) => match sub.subcommand() {
+            Some(("env", sub)) => hook_env(sub),
+            _ => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
+
+fn init(matches: &ArgMatches) -> anyhow::Result<()> {
+    let shell = matches
+        .get_one::<String>("shell")
+        .expect("shell is required");
+    // Call back into this exact binary, even if it isn't first on PATH
+    let exe = std::env::current_exe()?.display().to_string();
+
+    println!(
+        "# drk shell integration, generated by `drk shell-init {}`",
+        shell
+    );
+    match shell.as_str() {
+        "fish" => print!(
+            r#"function _drk_hook --on-variable PWD
+    {exe} hook env --shell fish | source
+end
+_drk_hook
+{exe} completions fish | source
+"#,
+            exe = fish_quote(&exe)
+        ),
+        "zsh" => print!(
+            r#"_drk_hook() {{
+  eval "$({exe} hook env --shell zsh)"
+}}
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd _drk_hook
+_drk_hook
+source <({exe} completions zsh)
+"#,
+            exe = posix_quote(&exe)
+        ),
+        _ => print!(
+            r#"_drk_hook() {{
+  local status=$?
+  if [ "$PWD" != "${{_DRK_LAST_PWD-}}" ]; then
+    _DRK_LAST_PWD="$PWD"
+    eval "$({exe} hook env --shell bash)"
+  fi
+  return $status
+}}
+case ";${{PROMPT_COMMAND-}};" in
+  *";_drk_hook;"*) ;;
+  *) PROMPT_COMMAND="_drk_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}" ;;
+esac
+source <({exe} completions bash)
+"#,
+            exe = posix_quote(&exe)
+        ),
+    }
+    Ok(())
+}
+
+fn hook_env(matches: &ArgMatches) -> anyhow::Result<()> {
+    let shell = matches
+        .get_one::<String>("shell")
+        .expect("shell is required");
+    let fish = shell == "fish";
+
+    let cwd = std::env::current_dir()?;
+    let root = paths::project_root(&cwd).filter(|r| project::project_file(r).exists());
+    let root_str = root.as_ref().map(|r| r.display().to_string());
+    let previous_root = std::env::var(ROOT_VAR).ok();
+
+    // Still in the same project: nothing to do
+    if root_str == previous_root {
+        return Ok(());
+    }
+
+    let mut out = Vec::new();
+    let unset = |name: &str| {
+        if fish {
+            format!("set -e {}", name)
+        } else {
+            format!("unset {}", name)
+        }
+    };
+    let export = |name: &str, value: &str| {
+        if fish {
+            format!("set -gx {} {}", name, fish_quote(value))
+        } else {
+            format!("export {}={}", name, posix_quote(value))
+        }
+    };
+
+    if let Ok(keys) = std::env::var(KEYS_VAR) {
+        out.extend(keys.split_whitespace().map(unset));
+        out.push(unset(KEYS_VAR));
+        out.push(unset(ROOT_VAR));
+    }
+
+    if let (Some(root), Some(root_str)) = (&root, &root_str) {
+        let mut manager = PluginManager::new();
+        manager.load_config(paths::config_file())?;
+        let vars = project::env_vars(root, manager.config())?;
+        if !vars.is_empty() {
+            for (key, value) in &vars {
+                out.push(export(key, value));
+            }
+            let keys: Vec<&str> = vars.keys().map(String::as_str).collect();
+            out.push(export(KEYS_VAR, &keys.join(" ")));
+            out.push(export(ROOT_VAR, root_str));
+        }
+    }
+
+    for line in out {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+pub fn posix_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+pub fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
@@ -0,0 +1,82 @@
+use clap::{Arg, ArgMatches, Command};
+use drk_api::{icon_success, style_dim, style_primary, style_success};
+use drk_core::manager::PluginManager;
+use drk_core::resources;
+use drk_core::state::{StateLimits, StateStore, PLUGIN_PREFIX};
+use drk_core::usage::UsageStats;
+use std::path::PathBuf;
+
+pub fn command() -> Command {
+    Command::new("state")
+        .about("Inspect and maintain drk's persistent state store")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("gc")
+                .about("Drop empty entries, state of removed plugins and stale command usage"),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Print the state store as JSON")
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .value_name("FILE")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Write to a file instead of stdout"),
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches, manager: &PluginManager) -> anyhow::Result<()> {
+    let mut store = StateStore::open(StateStore::default_path())?;
+
+    match matches.subcommand() {
+        Some(("gc", _)) => {
+            let limits = StateLimits::from_config(manager.config());
+            let before: usize = store.sizes().iter().map(|(_, size)| size).sum();
+
+            let mut usage = UsageStats::load(&store);
+            let pruned = usage.prune(limits.usage_max_age_days);
+            if pruned > 0 {
+                usage.store(&mut store)?;
+            }
+
+            // Disabled plugins are still loaded, so only uninstalled ones lose their state
+            let mut totals = resources::load_totals(&store);
+            let counted = totals.len();
+            totals.retain(|plugin, _| manager.metadata(plugin).is_some());
+            if totals.len() < counted {
+                store.set(resources::TOTALS_KEY, &totals)?;
+            }
+            let removed = store.compact(|key| match key.strip_prefix(PLUGIN_PREFIX) {
+                Some(plugin) => manager.metadata(plugin).is_some(),
+                None => true,
+            });
+            store.save()?;
+
+            let after: usize = store.sizes().iter().map(|(_, size)| size).sum();
+            for key in &removed {
+                println!("{} {}", style_dim("removed"), style_primary(key));
+            }
+            println!(
+                "{} {} {} stale command(s) and {} entr{}; {} -> {} bytes",
+                style_success(icon_success()),
+                style_success("Pruned"),
+                pruned,
+                removed.len(),
+                if removed.len() == 1 { "y" } else { "ies" },
+                before,
+                after
+            );
+        }
+        Some(("export", sub)) => {
+            let json = serde_json::to_string_pretty(&store.export())?;
+            match sub.get_one::<PathBuf>("out") {
+                Some(out) => std::fs::write(out, json)?,
+                None => println!("{}", json),
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use clap::{Arg, ArgMatches, Command};
+use drk_api::{style_dim, style_primary};
+use drk_core::paths;
+use drk_core::state::StateStore;
+use drk_core::usage::UsageStats;
+
+pub fn command() -> Command {
+    Command::new("suggest")
+        .about("List the commands you're most likely to run next in this project")
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("5")
+                .help("How many suggestions to show"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> anyhow::Result<()> {
+    let limit = *matches
+        .get_one::<usize>("limit")
+        .expect("limit has a default");
+    let state = StateStore::open(StateStore::default_path())?;
+    let stats = UsageStats::load(&state);
+
+    let cwd = std::env::current_dir()?;
+    let project = paths::project_root(&cwd);
+
+    // Prefer what's used in this project, fall back to overall habits
+    let mut ranked = stats.ranked(project.as_deref());
+    if ranked.is_empty() {
+        ranked = stats.ranked(None);
+    }
+
+    if ranked.is_empty() {
+        println!("{}", style_dim("No command history yet"));
+        return Ok(());
+    }
+
+    for (name, _) in ranked.into_iter().take(limit) {
+        let used = stats.commands.get(&name).map_or(0, |u| u.count);
+        println!(
+            "{} {}",
+            style_primary(&format!("drk {}", name)),
+            style_dim(&format!("(used {} times)", used))
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use clap::{Arg, ArgMatches, Command};
+use drk_api::{icon_success, style_dim, style_primary, style_success};
+use drk_core::manager::PluginManager;
+use drk_core::state::StateStore;
+use drk_core::tips::{self, Settings, TipState};
+use drk_core::usage::UsageStats;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn command() -> Command {
+    Command::new("tips")
+        .about("List the tips plugins show after commands, or dismiss one")
+        .subcommand(
+            Command::new("dismiss").about("Never show a tip again").arg(
+                Arg::new("tip")
+                    .required(true)
+                    .help("The tip, as <plugin>.<id> from `drk tips`"),
+            ),
+        )
+        .subcommand(Command::new("reset").about("Show dismissed and already seen tips again"))
+}
+
+pub fn run(matches: &ArgMatches, manager: &PluginManager) -> anyhow::Result<()> {
+    let mut store = StateStore::open(StateStore::default_path())?;
+    let mut state = TipState::load(&store);
+    let available = manager.tips();
+
+    match matches.subcommand() {
+        Some(("dismiss", sub)) => {
+            let key = sub.get_one::<String>("tip").expect("tip is required");
+            if !available.iter().any(|(p, tip)| tips::key(p, tip) == *key) {
+                anyhow::bail!("No tip called '{}'; `drk tips` lists them", key);
+            }
+            state.dismissed.insert(key.clone());
+            state.store(&mut store)?;
+            store.save()?;
+            println!(
+                "{} {} {}",
+                style_success(icon_success()),
+                style_success("Dismissed"),
+                style_primary(key)
+            );
+        }
+        Some(("reset", _)) => {
+            TipState::default().store(&mut store)?;
+            store.save()?;
+            println!(
+                "{} {}",
+                style_success(icon_success()),
+                style_success("All tips can show again")
+            );
+        }
+        _ => {
+            if available.is_empty() {
+                println!("No plugin offers tips");
+                return Ok(());
+            }
+            if !Settings::from_config(manager.config()).enabled {
+                println!("{}", style_dim("Tips are off ([tips] enabled = false)"));
+            }
+            let usage = UsageStats::load(&store);
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            for (plugin, tip) in &available {
+                let key = tips::key(plugin, tip);
+                let count = usage.commands.get(&tip.command).map_or(0, |u| u.count);
+                let status = if state.dismissed.contains(&key) {
+                    "dismissed".to_string()
+                } else if let Some(at) = state.shown.get(&key) {
+                    format!("shown {}", super::history::ago(now.saturating_sub(*at)))
+                } else {
+                    format!(
+                        "after {} runs of `{}` ({} so far)",
+                        tip.min_runs, tip.command, count
+                    )
+                };
+                println!("{} {}", style_primary(&key), style_dim(&status));
+                println!("  {}", tips::render(tip, count));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,169 @@
+//! `drk --watch-plugins [COMMAND...]`: a plugin development loop. Plugin
+//! libraries are polled for changes; a rebuilt one is reloaded in place and
+//! the command, if given, runs again against the new build.
+
+use crate::commands::exec_json::{run_resolved, Resolved};
+use drk_api::{
+    icon_error, icon_info, icon_success, style_dim, style_error, style_primary, style_success,
+    CommandMatches,
+};
+use drk_core::cli;
+use drk_core::invocation::CommandResult;
+use drk_core::manager::PluginManager;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub const FLAG: &str = "--watch-plugins";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches until Ctrl-C. `argv` is the command line without the flag.
+pub fn run(argv: &[String], manager: &mut PluginManager) -> anyhow::Result<()> {
+    // By library rather than by plugin, so one that fails to reload comes
+    // back once it's fixed
+    let mut watched: HashMap<PathBuf, (String, Option<SystemTime>)> = manager
+        .plugin_names()
+        .into_iter()
+        .filter_map(|name| {
+            let path = manager.library(&name)?.to_path_buf();
+            let modified = modified(&path);
+            Some((path, (name, modified)))
+        })
+        .collect();
+    eprintln!(
+        "{} {}",
+        style_primary(icon_info()),
+        style_primary(&format!(
+            "Watching {} plugin libraries; Ctrl-C to stop",
+            watched.len()
+        ))
+    );
+    run_command(argv, manager);
+
+    let cancel = manager.cancellation_token();
+    while !cancel.is_cancelled() {
+        std::thread::sleep(POLL_INTERVAL);
+        let mut reloaded = false;
+        for (path, (name, seen)) in watched.iter_mut() {
+            let current = modified(path);
+            if current.is_none() || current == *seen {
+                continue;
+            }
+            *seen = current;
+            reloaded = true;
+
+            let result = if manager.library(name).is_some() {
+                manager.reload_plugin(name)
+            } else {
+                manager.load_plugin_file(path)
+            };
+            // The new build may go by another name
+            if let Some(loaded) = manager
+                .plugin_names()
+                .into_iter()
+                .find(|n| manager.library(n) == Some(path.as_path()))
+            {
+                *name = loaded;
+            }
+            match result {
+                Ok(()) => {
+                    let commands: Vec<String> = manager
+                        .commands(name)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|c| c.name)
+                        .collect();
+                    eprintln!(
+                        "{} {} {}",
+                        style_success(icon_success()),
+                        style_success(&format!("Reloaded {}", name)),
+                        style_dim(&format!("({})", commands.join(", ")))
+                    );
+                }
+                Err(e) => eprintln!(
+                    "{} {}",
+                    style_error(icon_error()),
+                    style_error(&format!("Could not reload {}: {:#}", name, e))
+                ),
+            }
+        }
+        if reloaded {
+            run_command(argv, manager);
+        }
+    }
+    Ok(())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Runs the plugin command in `argv` against the loaded plugins, reporting
+/// rather than returning failures so watching goes on. It goes through the
+/// same checks as `exec-json`: the role, PreCommand vetoes, the audit log and
+/// history. Nobody is there to confirm each rerun, so a command marked
+/// dangerous is refused.
+fn run_command(argv: &[String], manager: &mut PluginManager) {
+    if argv.len() < 2 {
+        return;
+    }
+    let built = crate::build_cli(manager, false);
+    let matches = match built.app.try_get_matches_from(argv) {
+        Ok(matches) => matches,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    let Some((command_name, sub_matches)) = matches.subcommand() else {
+        return;
+    };
+    let top = built
+        .command_to_plugin
+        .get(command_name)
+        .and_then(|plugin| {
+            let cmds = built.plugin_commands.get(plugin)?;
+            Some((plugin, cmds.iter().find(|c| c.name == command_name)?))
+        });
+    let Some((plugin_name, top)) = top else {
+        report_error(&format!(
+            "{} only runs plugin commands, not '{}'",
+            FLAG, command_name
+        ));
+        return;
+    };
+    let role = match crate::active_role(&matches, manager.config()) {
+        Ok(role) => role,
+        Err(e) => {
+            report_error(&format!("{:#}", e));
+            return;
+        }
+    };
+
+    let mut path = vec![command_name.to_string()];
+    let mut args = HashMap::new();
+    cli::collect_args(top, sub_matches, &mut path, &mut args);
+    eprintln!("{}", style_dim(&format!("$ drk {}", argv[1..].join(" "))));
+    let resolved = Resolved {
+        plugin_name,
+        top,
+        matches: CommandMatches {
+            command_name: command_name.to_string(),
+            path,
+            args,
+        },
+        argv: &argv[1..],
+        role: role.as_ref(),
+        yes: false,
+    };
+    let result = run_resolved(resolved, CommandResult::default(), manager);
+    print!("{}", result.output);
+    if let Some(error) = &result.error {
+        report_error(error);
+    }
+}
+
+fn report_error(message: &str) {
+    eprintln!("{} {}", style_error(icon_error()), style_error(message));
+}
@@ -0,0 +1,298 @@
+//! `drk <command> --wizard`: walks through every argument of a plugin
+//! command, prompting with its description, then shows the command line it
+//! adds up to and runs it once confirmed. Values already on the command line
+//! are offered as the defaults.
+
+use anyhow::{bail, Context as _};
+use drk_api::{
+    icon_info, style_dim, style_primary, ArgType, CommandArg, PluginCommand, VALUES_SEPARATOR,
+};
+use drk_core::cli;
+use drk_core::invocation::Invocation;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{IsTerminal, Write};
+
+pub const FLAG: &str = "--wizard";
+
+/// Global flags carried over to the command line the wizard builds, and
+/// whether they take a value.
+const GLOBAL_FLAGS: &[(&str, bool)] = &[
+    ("--role", true),
+    ("--theme", true),
+    ("--record", true),
+    ("--no-cache", false),
+    ("--strict", false),
+];
+
+/// Whether `--wizard` was passed before any `--`.
+pub fn requested(argv: &[String]) -> bool {
+    argv.iter()
+        .skip(1)
+        .take_while(|a| *a != "--")
+        .any(|a| a == FLAG)
+}
+
+/// Prompts for the plugin command `argv[1]` names and returns the command
+/// line to run instead, or `None` if the user didn't confirm it.
+pub fn run(
+    argv: &[String],
+    plugin_commands: &HashMap<String, Vec<PluginCommand>>,
+) -> anyhow::Result<Option<Vec<String>>> {
+    let argv: Vec<String> = argv.iter().filter(|a| *a != FLAG).cloned().collect();
+    let Some(name) = argv.get(1).filter(|a| !a.starts_with('-')) else {
+        bail!(
+            "{} needs a command to walk through, e.g. drk greet {}",
+            FLAG,
+            FLAG
+        );
+    };
+    let Some(top) = plugin_commands
+        .values()
+        .flatten()
+        .find(|c| c.is_called(name))
+    else {
+        bail!(
+            "{} only walks through plugin commands, not '{}'",
+            FLAG,
+            name
+        );
+    };
+    if !std::io::stdin().is_terminal() {
+        bail!("{} needs a terminal to ask on", FLAG);
+    }
+
+    // Whatever parses of the given command line becomes the defaults
+    let mut path = vec![top.name.clone()];
+    let mut given = HashMap::new();
+    let parsed = cli::build(std::slice::from_ref(top))
+        .ignore_errors(true)
+        .try_get_matches_from(&argv);
+    let mut schema = top;
+    if let Some((_, sub)) = parsed.as_ref().ok().and_then(|m| m.subcommand()) {
+        schema = cli::collect_args(top, sub, &mut path, &mut given);
+    }
+    while !schema.subcommands.is_empty() {
+        let choices: Vec<String> = schema
+            .subcommands
+            .iter()
+            .map(|c| format!("{} {}", c.name, style_dim(&c.description)))
+            .collect();
+        let picked = choose(&format!("drk {}", path.join(" ")), &choices, None)?;
+        schema = &schema.subcommands[picked];
+        path.push(schema.name.clone());
+    }
+
+    eprintln!(
+        "{} {} {}",
+        style_primary(icon_info()),
+        style_primary(&format!("drk {}", path.join(" "))),
+        style_dim(&schema.description)
+    );
+    let mut args = BTreeMap::new();
+    for arg in &schema.args {
+        if let Some(value) = ask(arg, given.get(&arg.name))? {
+            args.insert(arg.name.clone(), serde_json::Value::String(value));
+        }
+    }
+
+    let invocation = Invocation {
+        command: path.join(" "),
+        args,
+        role: None,
+        yes: false,
+    };
+    let matches = invocation.to_matches(schema)?;
+    let mut wizard_argv = vec![argv[0].clone()];
+    wizard_argv.extend(globals(&argv));
+    wizard_argv.extend(invocation.to_argv(&matches, schema));
+
+    let shown: Vec<String> = wizard_argv[1..].iter().map(|a| quote(a)).collect();
+    eprintln!("\n  drk {}\n", style_primary(&shown.join(" ")));
+    let run = read_line("Run it? [Y/n] ")?;
+    Ok(matches!(run.to_lowercase().as_str(), "" | "y" | "yes").then_some(wizard_argv))
+}
+
+/// Asks for one argument until the answer fits its type. `None` leaves it out.
+fn ask(arg: &CommandArg, given: Option<&String>) -> anyhow::Result<Option<String>> {
+    let kind = match arg.arg_type {
+        _ if arg.takes_many() => "values separated by spaces",
+        ArgType::Integer => "integer",
+        ArgType::Float => "number",
+        ArgType::Count => "how many times",
+        ArgType::Duration => "duration, like 1h30m",
+        ArgType::DateTime => "date and time, like 2026-03-01 09:30 or tomorrow 9:00",
+        _ => "text",
+    };
+    eprintln!(
+        "\n{}{} {}",
+        style_primary(&arg.name),
+        if arg.required { " (required)" } else { "" },
+        style_dim(&arg.description)
+    );
+
+    match arg.arg_type {
+        ArgType::Boolean if arg.negatable => {
+            let choices = [
+                "leave unset".to_string(),
+                format!("--{}", arg.name),
+                format!("--no-{}", arg.name),
+            ];
+            let default = match given.map(String::as_str) {
+                Some("true") => 1,
+                Some("false") => 2,
+                _ => 0,
+            };
+            let picked = choose(&arg.name, &choices, Some(default))?;
+            return Ok([None, Some("true"), Some("false")][picked].map(String::from));
+        }
+        ArgType::Boolean => {
+            let default = given.is_some();
+            let hint = if default { "[Y/n]" } else { "[y/N]" };
+            let answer = read_line(&format!("--{}? {} ", arg.name, hint))?.to_lowercase();
+            let on = match answer.as_str() {
+                "" => default,
+                answer => matches!(answer, "y" | "yes"),
+            };
+            return Ok(on.then(|| "true".to_string()));
+        }
+        ArgType::OptionalValue => {
+            let choices = [
+                "leave out".to_string(),
+                format!("--{}", arg.name),
+                format!("--{}=<value>", arg.name),
+            ];
+            let default = match given.map(String::as_str) {
+                None => 0,
+                Some("") => 1,
+                Some(_) => 2,
+            };
+            match choose(&arg.name, &choices, Some(default))? {
+                0 => return Ok(None),
+                1 => return Ok(Some(String::new())),
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+
+    // Past the menu an optional value must be typed out
+    let given = given.filter(|v| !v.is_empty());
+    let must_answer = arg.required || arg.arg_type == ArgType::OptionalValue;
+    loop {
+        let shown_default = given.map(|v| v.replace(VALUES_SEPARATOR, " "));
+        let prompt = match &shown_default {
+            Some(value) => format!("{} [{}]: ", kind, value),
+            None => format!("{}: ", kind),
+        };
+        let answer = read_line(&prompt)?;
+        let answer = match (answer.is_empty(), &shown_default) {
+            (true, Some(value)) => value.clone(),
+            (true, None) if must_answer => {
+                eprintln!("{}", style_dim("  required; type a value"));
+                continue;
+            }
+            (true, None) => return Ok(None),
+            (false, _) => answer,
+        };
+        let words: Vec<&str> = if arg.takes_many() {
+            answer.split_whitespace().collect()
+        } else {
+            vec![&answer]
+        };
+        let valid = match arg.arg_type {
+            ArgType::Integer => words.iter().all(|word| word.parse::<i64>().is_ok()),
+            ArgType::Float => words.iter().all(|word| word.parse::<f64>().is_ok()),
+            ArgType::Count => answer.parse::<u8>().is_ok(),
+            ArgType::Duration | ArgType::DateTime => words
+                .iter()
+                .all(|word| cli::canonical(&arg.arg_type, word).is_ok()),
+            _ => true,
+        };
+        if !valid {
+            eprintln!(
+                "{}",
+                style_dim(&format!("  '{}' isn't a valid {}", answer, kind))
+            );
+            continue;
+        }
+        return Ok(match arg.arg_type {
+            _ if arg.takes_many() => Some(
+                answer
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(&VALUES_SEPARATOR.to_string()),
+            ),
+            ArgType::Count if answer == "0" => None,
+            _ => Some(answer),
+        });
+    }
+}
+
+/// A numbered menu; returns the index picked. Enter takes `default`.
+fn choose(title: &str, choices: &[String], default: Option<usize>) -> anyhow::Result<usize> {
+    for (i, choice) in choices.iter().enumerate() {
+        eprintln!("  {}) {}", i + 1, choice);
+    }
+    let prompt = match default {
+        Some(d) => format!("{} [{}]: ", title, d + 1),
+        None => format!("{}: ", title),
+    };
+    loop {
+        let answer = read_line(&prompt)?;
+        if let (true, Some(d)) = (answer.is_empty(), default) {
+            return Ok(d);
+        }
+        let by_number = answer
+            .parse::<usize>()
+            .ok()
+            .filter(|n| (1..=choices.len()).contains(n));
+        let by_name = choices
+            .iter()
+            .position(|c| c.split(' ').next() == Some(answer.as_str()));
+        match by_number.map(|n| n - 1).or(by_name) {
+            Some(i) => return Ok(i),
+            None => eprintln!(
+                "{}",
+                style_dim(&format!("  pick 1-{} or a name", choices.len()))
+            ),
+        }
+    }
+}
+
+fn read_line(prompt: &str) -> anyhow::Result<String> {
+    eprint!("{}", prompt);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    let read = std::io::stdin()
+        .read_line(&mut answer)
+        .context("Could not read the answer")?;
+    if read == 0 {
+        bail!("Input ended before the wizard was done");
+    }
+    Ok(answer.trim().to_string())
+}
+
+/// The global flags of `argv` worth keeping, with their values.
+fn globals(argv: &[String]) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut args = argv.iter().skip(1).take_while(|a| *a != "--");
+    while let Some(arg) = args.next() {
+        let flag = arg.split('=').next().unwrap_or_default();
+        let Some((_, takes_value)) = GLOBAL_FLAGS.iter().find(|(name, _)| *name == flag) else {
+            continue;
+        };
+        kept.push(arg.clone());
+        if *takes_value && !arg.contains('=') {
+            kept.extend(args.next().cloned());
+        }
+    }
+    kept
+}
+
+/// `arg` as it would be typed in a shell.
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || "'\"$`\\".contains(c)) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
@@ -0,0 +1,54 @@
+//! The host's "are you sure?" for commands plugins mark `dangerous`, so every
+//! destructive plugin command asks the same way and `--yes` skips it.
+
+use drk_api::{icon_warning, style_primary, style_warning, PluginCommand};
+use std::io::{IsTerminal, Write};
+
+pub const FLAG: &str = "--yes";
+
+/// The dangerous command on `path` (outermost first) under `top`, if any.
+/// A dangerous command makes the commands nested under it dangerous too.
+pub fn dangerous<'a>(top: &'a PluginCommand, path: &[String]) -> Option<&'a PluginCommand> {
+    let mut command = top;
+    let mut found = top.dangerous.then_some(top);
+    for name in path.iter().skip(1) {
+        let Some(nested) = command.subcommand(name) else {
+            break;
+        };
+        command = nested;
+        if command.dangerous {
+            found = Some(command);
+        }
+    }
+    found
+}
+
+/// Asks whether to run the dangerous `command`, invoked as `invocation`.
+/// Without a terminal to ask on, only `yes` lets it run.
+pub fn confirm(command: &PluginCommand, invocation: &str, yes: bool) -> anyhow::Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "drk {} is marked dangerous; pass {} to run it without a terminal",
+            invocation,
+            FLAG
+        );
+    }
+    let warning = command
+        .warning
+        .as_deref()
+        .unwrap_or("It may change or delete things that can't be restored");
+    eprintln!(
+        "{} {} {}",
+        style_warning(icon_warning()),
+        style_primary(&format!("drk {}", invocation)),
+        style_warning(&format!("is dangerous: {}", warning))
+    );
+    eprint!("Run it anyway? [y/N] ");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
@@ -0,0 +1,206 @@
+//! `--debug-cli`: a trace of how the dynamic command tree is assembled.
+//!
+//! Meant for plugin authors whose command doesn't show up. Every step goes to
+//! stderr: which plugin contributed which command, conflicts clap would trip
+//! over, and which strings had to be leaked to give clap `'static` names.
+
+use clap::Command;
+use drk_api::{style_dim, style_primary, style_warning, ArgType, PluginCommand};
+use std::collections::HashSet;
+use std::fmt::Display;
+
+pub const FLAG: &str = "--debug-cli";
+
+/// Args every command inherits from the top level; plugin args with these
+/// names collide with them.
+const GLOBAL_ARGS: &[&str] = &[
+    "role",
+    "theme",
+    "no-cache",
+    "offline",
+    "record",
+    "strict",
+    "debug-cli",
+    "wizard",
+    "print-invocation",
+    "yes",
+    "output",
+    "help",
+];
+
+/// Whether `--debug-cli` was passed. Checked before clap runs, because the
+/// trace covers building the parser itself.
+pub fn requested(args: &[String]) -> bool {
+    args.iter()
+        .skip(1)
+        .take_while(|a| *a != "--")
+        .any(|a| a == FLAG)
+}
+
+pub struct Trace {
+    enabled: bool,
+    leaked_strings: usize,
+    leaked_bytes: usize,
+    conflicts: usize,
+}
+
+impl Trace {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            leaked_strings: 0,
+            leaked_bytes: 0,
+            conflicts: 0,
+        }
+    }
+
+    fn log(&self, message: impl Display) {
+        if self.enabled {
+            eprintln!("{} {}", style_dim("[debug-cli]"), message);
+        }
+    }
+
+    /// Lists the built-in subcommands; their names are `'static` literals.
+    pub fn builtins(&self, app: &Command) {
+        for command in app.get_subcommands() {
+            let hidden = if command.is_hide_set() {
+                ", hidden"
+            } else {
+                ""
+            };
+            self.log(format!(
+                "builtin  {} (owned{})",
+                style_primary(command.get_name()),
+                hidden
+            ));
+        }
+    }
+
+    /// Records a plugin command being added, along with any conflicts among
+    /// its own arguments. `leaked` is the strings leaked to build it.
+    pub fn plugin_command(&mut self, plugin: &str, command: &PluginCommand, leaked: &[&str]) {
+        let bytes: usize = leaked.iter().map(|s| s.len()).sum();
+        self.leaked_strings += leaked.len();
+        self.leaked_bytes += bytes;
+        self.log(format!(
+            "plugin   {} <- '{}' ({} args, leaked {} strings / {} bytes)",
+            style_primary(&command.name),
+            plugin,
+            command.args.len(),
+            leaked.len(),
+            bytes
+        ));
+
+        let mut seen = HashSet::new();
+        let mut after_variadic = false;
+        let mut after_optional = false;
+        for arg in &command.args {
+            if !seen.insert(arg.name.as_str()) {
+                self.conflict(format!(
+                    "'{}' declares argument '{}' twice",
+                    command.name, arg.name
+                ));
+            }
+            if GLOBAL_ARGS.contains(&arg.name.as_str()) {
+                self.conflict(format!(
+                    "'{}' argument '{}' collides with the global --{}",
+                    command.name, arg.name, arg.name
+                ));
+            }
+            if arg.negatable && arg.arg_type != ArgType::Boolean {
+                self.conflict(format!(
+                    "'{}' argument '{}' is negatable but not Boolean",
+                    command.name, arg.name
+                ));
+            }
+            let repeatable = matches!(
+                arg.arg_type,
+                ArgType::String
+                    | ArgType::Integer
+                    | ArgType::Float
+                    | ArgType::Choice(_)
+                    | ArgType::Duration
+                    | ArgType::DateTime
+            );
+            if arg.multiple && !repeatable {
+                self.conflict(format!(
+                    "'{}' argument '{}' is multiple, but only String, Integer, Float, Choice, Duration and DateTime flags can repeat",
+                    command.name, arg.name
+                ));
+            }
+            if arg.arg_type == ArgType::Choice(Vec::new()) {
+                self.conflict(format!(
+                    "'{}' argument '{}' is a Choice without any values to choose",
+                    command.name, arg.name
+                ));
+            }
+            if let (ArgType::Choice(choices), Some(default)) = (&arg.arg_type, &arg.default) {
+                if !choices.contains(default) {
+                    self.conflict(format!(
+                        "'{}' argument '{}' defaults to '{}', which isn't one of its choices",
+                        command.name, arg.name, default
+                    ));
+                }
+            }
+            if arg.required && arg.default.is_some() {
+                self.conflict(format!(
+                    "'{}' argument '{}' is required but has a default, so it's never missing",
+                    command.name, arg.name
+                ));
+            }
+            if let Some(negated) = arg.negated_name() {
+                if command.args.iter().any(|other| other.name == negated) {
+                    self.conflict(format!(
+                        "'{}' argument '{}' collides with the --{} of '{}'",
+                        command.name, negated, negated, arg.name
+                    ));
+                }
+            }
+            if !matches!(arg.arg_type, ArgType::Positional | ArgType::Variadic) {
+                continue;
+            }
+            // Positionals are filled in order, so these could never be reached
+            if after_variadic {
+                self.conflict(format!(
+                    "'{}' positional '{}' comes after a variadic one",
+                    command.name, arg.name
+                ));
+            }
+            if arg.required && after_optional {
+                self.conflict(format!(
+                    "'{}' positional '{}' is required but follows an optional one",
+                    command.name, arg.name
+                ));
+            }
+            after_variadic |= arg.arg_type == ArgType::Variadic;
+            after_optional |= !arg.required;
+        }
+    }
+
+    /// Records a plugin command registered hidden because the environment
+    /// doesn't meet its requirements.
+    pub fn unavailable(&self, plugin: &str, command: &PluginCommand, reason: &str) {
+        self.log(format!(
+            "plugin   {} <- '{}' (unavailable, hidden: {})",
+            style_primary(&command.name),
+            plugin,
+            reason
+        ));
+    }
+
+    pub fn conflict(&mut self, message: impl Display) {
+        self.conflicts += 1;
+        self.log(format!("{} {}", style_warning("conflict"), message));
+    }
+
+    /// Prints the totals once the tree is complete.
+    pub fn finish(&self, app: &Command) {
+        self.log(format!(
+            "done: {} subcommands, {} conflicts, {} strings ({} bytes) leaked for clap",
+            app.get_subcommands().count(),
+            self.conflicts,
+            self.leaked_strings,
+            self.leaked_bytes
+        ));
+    }
+}
@@ -0,0 +1,38 @@
+//! Ctrl-C handling.
+//!
+//! The first Ctrl-C trips the manager's cancellation token so plugins can
+//! stop cooperatively; a second one exits immediately, after running the
+//! registered cleanup handlers.
+
+use drk_api::{icon_warning, style_warning, CancellationToken};
+
+/// Exit code for a command interrupted by SIGINT, as shells report it.
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+#[cfg(unix)]
+pub fn install(token: CancellationToken) -> anyhow::Result<()> {
+    use signal_hook::consts::{SIGINT, SIGTERM};
+
+    let mut signals = signal_hook::iterator::Signals::new([SIGINT, SIGTERM])?;
+    // A plain thread, so cancelling (which takes a lock) never runs inside the signal handler
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            if token.is_cancelled() {
+                drk_core::cleanup::exit(EXIT_INTERRUPTED);
+            }
+            eprintln!(
+                "\n{} {}",
+                style_warning(icon_warning()),
+                style_warning("Cancelling... press Ctrl-C again to quit immediately")
+            );
+            token.cancel();
+        }
+    });
+    Ok(())
+}
+
+/// Without signal support the default Ctrl-C behaviour (terminate) applies.
+#[cfg(not(unix))]
+pub fn install(_token: CancellationToken) -> anyhow::Result<()> {
+    Ok(())
+}
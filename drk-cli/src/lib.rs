@@ -0,0 +1,1319 @@
+//! The `drk` command line: loading plugins, building the command tree from
+//! them and routing an invocation to a built-in command or a plugin. The
+//! `drk` binary is a thin wrapper around [`run`].
+
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::{Arg, ArgAction, Command};
+use drk_api::{
+    icon_error, icon_info, icon_warning, style_dim, style_error, style_primary, style_warning,
+    CommandMatches, PluginCommand, SystemEvent,
+};
+use drk_core::aliases;
+use drk_core::audit::AuditLog;
+use drk_core::availability::{self, Unavailable};
+use drk_core::cleanup;
+use drk_core::cli;
+use drk_core::container;
+use drk_core::history::{History, HistoryEntry};
+use drk_core::invocation::CommandResult;
+use drk_core::load_errors::{self, Category};
+use drk_core::manager::{CommandOutcome, PluginManager};
+use drk_core::paths;
+use drk_core::redact;
+use drk_core::registry::Index;
+use drk_core::result_cache;
+use drk_core::roles::Role;
+use drk_core::run_record::RunRecord;
+use drk_core::saved;
+use drk_core::state::StateStore;
+use drk_core::tips::{self, TipState};
+use drk_core::typo;
+use drk_core::usage::UsageStats;
+use output_limit::Limits;
+use std::collections::{HashMap, HashSet};
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+mod capture;
+mod commands;
+mod confirm;
+mod debug_cli;
+mod interrupt;
+mod output_limit;
+mod output_mode;
+
+/// Runs drk with `raw_args`, the full command line including the program name.
+pub fn run(raw_args: &[String]) -> anyhow::Result<()> {
+    cleanup::install_panic_hook();
+
+    // Shell hooks run on every prompt and their output is eval'd, so they
+    // skip config and plugin loading (and the plugins' startup chatter)
+    if matches!(
+        raw_args.get(1).map(String::as_str),
+        Some("shell-init" | "hook")
+    ) {
+        return commands::shell::run(raw_args);
+    }
+
+    if raw_args.get(1).is_some_and(|a| a == "exec-json") {
+        return commands::exec_json::run(raw_args);
+    }
+
+    if raw_args.get(1).is_some_and(|a| a == "completions") {
+        return commands::completions::run(raw_args);
+    }
+
+    // Set before loading so plugin load messages are themed too
+    if let Some(theme) = theme_flag(raw_args) {
+        std::env::set_var(drk_api::styling::THEME_ENV, theme);
+    }
+    // Plugins make their own requests, so they learn through the environment
+    if offline_flag(raw_args) {
+        std::env::set_var(drk_api::http::OFFLINE_ENV, "1");
+    }
+
+    // Machine-readable output leaves no room for styling or load messages
+    let json_output = output_mode::json_requested(raw_args);
+    if json_output {
+        output_mode::unstyled();
+    }
+    let loading = json_output.then(capture::Capture::start).transpose()?;
+    let loaded = load_manager(raw_args);
+    if let Some(loading) = loading {
+        loading.finish()?;
+    }
+    let mut manager = loaded?;
+    let result = if raw_args.get(1).is_some_and(|a| a == commands::watch::FLAG) {
+        let argv: Vec<String> = raw_args[..1]
+            .iter()
+            .chain(&raw_args[2..])
+            .cloned()
+            .collect();
+        commands::watch::run(&argv, &mut manager)
+    } else {
+        route_invocation(raw_args, &mut manager)
+    };
+    manager.unload_all();
+    result
+}
+
+/// Builds the CLI from the loaded plugins and runs the invoked command.
+/// Exits the process with a non-zero code if the command fails.
+pub fn route_invocation(raw_args: &[String], manager: &mut PluginManager) -> anyhow::Result<()> {
+    // With `--output json` stdout is for the result alone
+    let building = output_mode::json_requested(raw_args)
+        .then(capture::Capture::start)
+        .transpose()?;
+    let Cli {
+        app,
+        command_to_plugin,
+        unavailable,
+        plugin_commands,
+    } = build_cli(manager, debug_cli::requested(raw_args));
+    if let Some(building) = building {
+        building.finish()?;
+    }
+    let cwd = std::env::current_dir().unwrap_or_default();
+
+    // 6. Parse command-line arguments
+    let mut argv = resolve_argv(raw_args.to_vec(), &command_to_plugin);
+    if argv.get(1).is_some_and(|a| a == "run-saved")
+        && argv.get(2).is_some_and(|name| !name.starts_with('-'))
+    {
+        argv = saved::expand_invocation(argv, manager.config())?;
+    }
+    if argv.get(1).is_some_and(|first| {
+        !command_to_plugin.contains_key(first) && !commands::BUILTIN_NAMES.contains(&first.as_str())
+    }) {
+        let configured = aliases::from_config(manager.config());
+        argv = aliases::expand(argv, &configured, manager.config())?;
+    }
+    if commands::wizard::requested(&argv) {
+        match commands::wizard::run(&argv, &plugin_commands)? {
+            Some(confirmed) => argv = confirmed,
+            None => exit(manager, 0),
+        }
+    }
+    let available: Vec<String> = app
+        .get_subcommands()
+        .map(|c| c.get_name().to_string())
+        .collect();
+
+    // Nothing claims the first word: the `[cli] fallback` plugin may take it.
+    // A `--role` flag would come first, so only `DRK_ROLE` can restrict it
+    if argv.get(1).is_some_and(|first| {
+        !first.starts_with('-') && first != "help" && !available.contains(first)
+    }) {
+        let role = match std::env::var("DRK_ROLE") {
+            Ok(name) => Some(Role::from_config(manager.config(), &name)?),
+            Err(_) => None,
+        };
+        let allowed = match (&role, manager.fallback_plugin()) {
+            (Some(role), Some(fallback)) => role.allows(fallback, &argv[1]),
+            _ => true,
+        };
+        let errors = manager
+            .fallback_plugin()
+            .map_or(0, |fallback| manager.errors_from(fallback));
+        let limiter = output_limit::Limiter::start(Limits::from_config(manager.config()))?;
+        let handled = allowed.then(|| manager.run_fallback(&argv[1..])).flatten();
+        limiter.finish()?;
+        if let Some(plugin_name) = handled {
+            if manager.cancellation_token().is_cancelled() {
+                manager.run_cancel_hooks();
+                audit_command(manager, &plugin_name, &argv[1], "cancelled");
+                record_history(&argv[1..], role.as_ref(), false);
+                manager.unload_all();
+                cleanup::exit(interrupt::EXIT_INTERRUPTED);
+            }
+            let success = manager.errors_from(&plugin_name) == errors;
+            let detail = format!("fallback: {}", argv[1..].join(" "));
+            audit_command(manager, &plugin_name, &argv[1], &detail);
+            record_history(&argv[1..], role.as_ref(), success);
+            exit(manager, if success { 0 } else { 1 });
+        }
+    }
+
+    let matches = match app.clone().try_get_matches_from(&argv) {
+        Ok(m) => m,
+        Err(e) => match correct_typo(&e, &argv, &available, manager.config()) {
+            // The user accepted the correction, so parse the fixed-up invocation
+            Some(corrected) => {
+                argv = corrected;
+                app.clone().try_get_matches_from(&argv).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    exit(manager, 1);
+                })
+            }
+            None => exit(manager, 1),
+        },
+    };
+
+    // Resolve the active role before anything gets dispatched
+    let role = active_role(&matches, manager.config())?;
+
+    // Values of sensitive plugin args are masked in everything drk writes down
+    let secrets = sensitive_values(&matches, &command_to_plugin, &plugin_commands);
+    manager.redact(&secrets);
+    let logged = redact::mask_args(&argv, &secrets);
+
+    if let Some(path) = matches.get_one::<PathBuf>("record") {
+        let args = without_record_flag(&logged[1..]);
+        RunRecord::capture(env!("CARGO_PKG_VERSION"), &args, manager).save(path)?;
+        eprintln!(
+            "{}",
+            style_dim(&format!("Recorded this run to {}", path.display()))
+        );
+    }
+
+    // 7. Route to the appropriate plugin
+    if let Some((command_name, sub_matches)) = matches.subcommand() {
+        // Built-ins that run other commands or change the setup need the role too
+        if let Some(role) = &role {
+            let builtin = match sub_matches.subcommand_name() {
+                Some(sub) => format!("{} {}", command_name, sub),
+                None => command_name.to_string(),
+            };
+            if !command_to_plugin.contains_key(command_name) && !role.allows_builtin(&builtin) {
+                eprintln!(
+                    "{} {} {}",
+                    style_error(icon_error()),
+                    style_error(&format!("Role '{}' is not allowed to run", role.name)),
+                    style_primary(&builtin)
+                );
+                let detail = format!("denied for role '{}'", role.name);
+                audit_command(manager, "drk", &builtin, &detail);
+                exit(manager, 1);
+            }
+        }
+
+        // Built-in commands are handled by the host itself
+        if command_name == "alias" {
+            return commands::alias::run(sub_matches, manager);
+        }
+        if command_name == "audit" {
+            return commands::audit::run(sub_matches, manager);
+        }
+        if command_name == "batch" {
+            if !commands::batch::run(sub_matches, manager)? {
+                exit(manager, 1);
+            }
+            return Ok(());
+        }
+        if command_name == "check-tools" {
+            if !commands::check_tools::run()? {
+                exit(manager, 1);
+            }
+            return Ok(());
+        }
+        if command_name == "completions" {
+            return commands::completions::print(sub_matches, app);
+        }
+        if command_name == "config" {
+            return commands::config::run(sub_matches, manager);
+        }
+        if command_name == "history" {
+            return commands::history::run(sub_matches);
+        }
+        if command_name == "plugin" {
+            return commands::plugin::run(sub_matches, manager);
+        }
+        if command_name == "rerun" {
+            return commands::rerun::run(sub_matches, manager);
+        }
+        if command_name == "state" {
+            return commands::state::run(sub_matches, manager);
+        }
+        if command_name == "suggest" {
+            return commands::suggest::run(sub_matches);
+        }
+        if command_name == "tips" {
+            return commands::tips::run(sub_matches, manager);
+        }
+        if command_name == "save-as" {
+            return commands::saved::run_save_as(sub_matches, manager);
+        }
+
+        if let Some(missing) = unavailable.get(command_name) {
+            eprintln!(
+                "{} {} {}",
+                style_error(icon_error()),
+                style_primary(command_name),
+                style_error(&format!("is unavailable: {}", missing.reason))
+            );
+            eprintln!("{} {}", style_warning(icon_warning()), missing.hint);
+            exit(manager, 1);
+        }
+
+        // Find which plugin owns this command
+        if let Some(plugin_name) = command_to_plugin.get(command_name) {
+            if let Some(role) = &role {
+                if !role.allows(plugin_name, command_name) {
+                    eprintln!(
+                        "{} {} {}",
+                        style_error(icon_error()),
+                        style_error(&format!("Role '{}' is not allowed to run", role.name)),
+                        style_primary(command_name)
+                    );
+                    let detail = format!("denied for role '{}'", role.name);
+                    audit_command(manager, plugin_name, command_name, &detail);
+                    exit(manager, 1);
+                }
+            }
+
+            // Get the command schema to know which args to extract, following
+            // it down to the nested command that was invoked
+            let mut path = vec![command_name.to_string()];
+            let mut arg_map = HashMap::new();
+            let top = plugin_commands
+                .get(plugin_name)
+                .and_then(|cmds| cmds.iter().find(|c| c.name == command_name));
+            let schema =
+                top.map(|top| cli::collect_args(top, sub_matches, &mut path, &mut arg_map));
+            let dangerous = top.and_then(|top| confirm::dangerous(top, &path));
+
+            // Fire ExecuteCommand event
+            let cmd_matches = CommandMatches {
+                command_name: command_name.to_string(),
+                path,
+                args: arg_map,
+            };
+
+            // Cacheable commands reuse a recent enough result instead of running
+            let cache_key = schema
+                .filter(|cmd| cmd.cacheable && capture::SUPPORTED && !matches.get_flag("no-cache"))
+                .map(|cmd| {
+                    let version = manager
+                        .metadata(plugin_name)
+                        .map(|m| m.version.clone())
+                        .unwrap_or_default();
+                    result_cache::key(plugin_name, &version, cmd, &cmd_matches, &cwd)
+                });
+            let cache_dir = result_cache::default_dir();
+            let max_age = result_cache::max_age(manager.config());
+            let cached = cache_key
+                .as_deref()
+                .and_then(|key| result_cache::lookup(&cache_dir, key, max_age));
+
+            if matches.get_flag("print-invocation") {
+                let preview = commands::print_invocation::Preview {
+                    plugin_name,
+                    args: &logged[1..],
+                    secrets: &secrets,
+                    matches: &cmd_matches,
+                    schema,
+                    role: role.as_ref(),
+                    cached: cached.as_ref().map(|entry| entry.age()),
+                    dangerous,
+                };
+                commands::print_invocation::print(&preview, manager);
+                exit(manager, 0);
+            }
+
+            // The exec-json path captures the output and reports it as JSON
+            if matches
+                .get_one::<String>("output")
+                .is_some_and(|o| o == "json")
+            {
+                let result = CommandResult {
+                    command: cmd_matches.path.join(" "),
+                    exit_code: 1,
+                    ..Default::default()
+                };
+                let resolved = commands::exec_json::Resolved {
+                    plugin_name,
+                    top: top.expect("plugin commands have a schema"),
+                    matches: cmd_matches,
+                    argv: &argv[1..],
+                    role: role.as_ref(),
+                    yes: matches.get_flag("yes"),
+                };
+                let result = commands::exec_json::run_resolved(resolved, result, manager);
+                output_mode::respond(&result, manager);
+            }
+
+            // Destructive commands run only once the user confirms
+            if let Some(command) = dangerous.filter(|_| cached.is_none()) {
+                if !confirm::confirm(command, &logged[1..].join(" "), matches.get_flag("yes"))? {
+                    audit_command(manager, plugin_name, command_name, "not confirmed");
+                    exit(manager, 1);
+                }
+            }
+
+            // Plugins that declare a container image run in there instead of in-process
+            if let Some(spec) = manager
+                .manifest(plugin_name)
+                .and_then(|m| m.container.as_ref())
+                .filter(|_| !container::is_inside())
+            {
+                let code = container::run(spec, manager.config(), &argv[1..])?;
+                let detail = format!("ran in container {} (exit {})", spec.image, code);
+                audit_command(manager, plugin_name, command_name, &detail);
+                record_history(&logged[1..], role.as_ref(), code == 0);
+                exit(manager, code);
+            }
+
+            // Fire PreCommand; any plugin may cancel the command here
+            let args: Vec<String> = logged.iter().skip(2).cloned().collect();
+            if let Some(veto) = manager.fire_pre_command(command_name, &args) {
+                eprintln!(
+                    "{} {}",
+                    style_error(icon_error()),
+                    style_error(&format!(
+                        "Plugin '{}' cancelled {}: {}",
+                        veto.plugin, command_name, veto.reason
+                    ))
+                );
+                manager.fire_event(SystemEvent::PostCommand {
+                    name: command_name.to_string(),
+                    success: false,
+                });
+                let detail = format!("cancelled by {}: {}", veto.plugin, veto.reason);
+                audit_command(manager, plugin_name, command_name, &detail);
+                record_history(&logged[1..], role.as_ref(), false);
+                exit(manager, 1);
+            }
+
+            let limits = Limits::from_config(manager.config());
+
+            // A cached result is a successful earlier run
+            let mut outcome = CommandOutcome::default();
+            if let Some(entry) = &cached {
+                output_limit::print(&entry.output, limits)?;
+                eprintln!(
+                    "{}",
+                    style_dim(&format!(
+                        "(cached {}; --no-cache to run it again)",
+                        commands::history::ago(entry.age())
+                    ))
+                );
+            } else if let Some(key) = &cache_key {
+                let capture = capture::Capture::start()?;
+                outcome = manager.fire_event_with_result(SystemEvent::ExecuteCommand {
+                    plugin_name: plugin_name.clone(),
+                    matches: cmd_matches,
+                });
+                output_mode::print_text(&outcome, plugin_name);
+                let output = capture.finish()?;
+                output_limit::print(&output, limits)?;
+                // Failed or interrupted runs aren't worth repeating
+                if !outcome.failed(plugin_name) && !manager.cancellation_token().is_cancelled() {
+                    if let Err(e) = result_cache::store(&cache_dir, key, &output, max_age) {
+                        eprintln!("Failed to cache the result: {:#}", e);
+                    }
+                }
+            } else {
+                let limiter = output_limit::Limiter::start(limits)?;
+                outcome = manager.fire_event_with_result(SystemEvent::ExecuteCommand {
+                    plugin_name: plugin_name.clone(),
+                    matches: cmd_matches,
+                });
+                output_mode::print_text(&outcome, plugin_name);
+                limiter.finish()?;
+            }
+
+            // Ctrl-C during the command: let plugins roll back, then exit like an interrupted process
+            if manager.cancellation_token().is_cancelled() {
+                manager.run_cancel_hooks();
+                manager.fire_event(SystemEvent::PostCommand {
+                    name: command_name.to_string(),
+                    success: false,
+                });
+                audit_command(manager, plugin_name, command_name, "cancelled");
+                record_history(&logged[1..], role.as_ref(), false);
+                manager.unload_all();
+                cleanup::exit(interrupt::EXIT_INTERRUPTED);
+            }
+
+            // Fire PostCommand event; the command failed if its plugin returned an error
+            let success = !outcome.failed(plugin_name);
+            manager.fire_event(SystemEvent::PostCommand {
+                name: command_name.to_string(),
+                success,
+            });
+
+            let detail = match cached {
+                Some(_) => format!("{} (from cache)", args.join(" ")),
+                None => args.join(" "),
+            };
+            audit_command(manager, plugin_name, command_name, &detail);
+            record_usage(manager, command_name);
+            record_history(&logged[1..], role.as_ref(), success);
+            if !success {
+                exit(manager, 1);
+            }
+            show_tip(manager, command_name);
+        } else {
+            eprintln!("Unknown command: {}", command_name);
+            exit(manager, 1);
+        }
+    }
+
+    Ok(())
+}
+
+/// The command tree: the built-in commands plus every loaded plugin's.
+pub struct Cli {
+    pub app: Command,
+    /// Which plugin owns each plugin command
+    pub command_to_plugin: HashMap<String, String>,
+    /// Commands whose requirements aren't met here; they only print a hint
+    pub unavailable: HashMap<String, Unavailable>,
+    pub plugin_commands: HashMap<String, Vec<PluginCommand>>,
+}
+
+/// Assembles the command tree once, for parsing the command line or for
+/// `drk completions` (steps 4-5). `debug` traces it to stderr.
+///
+/// # Example
+/// ```
+/// # use drk_api::{ArgType, CommandArg, Context, Plugin, PluginCommand, PluginMetadata, SystemEvent};
+/// # use drk_core::manager::PluginManager;
+/// # use std::collections::HashMap;
+/// struct Scaffold;
+///
+/// impl Plugin for Scaffold {
+///     fn metadata(&self) -> PluginMetadata {
+///         PluginMetadata {
+///             name: "scaffold".to_string(),
+///             version: "0.1.0".to_string(),
+///             author: "drk contributors".to_string(),
+///             description: "Project templates".to_string(),
+///             essential: false,
+///         }
+///     }
+///
+///     fn get_commands(&self) -> Vec<PluginCommand> {
+///         let arg = |name: &str, arg_type| CommandArg {
+///             name: name.to_string(),
+///             description: String::new(),
+///             required: false,
+///             arg_type,
+///             ..Default::default()
+///         };
+///         vec![PluginCommand {
+///             name: "scaffold".to_string(),
+///             args: vec![
+///                 arg("template", ArgType::Positional),
+///                 arg("name", ArgType::String),
+///                 arg("count", ArgType::Integer),
+///                 arg("ratio", ArgType::Float),
+///                 arg("force", ArgType::Boolean),
+///             ],
+///             ..Default::default()
+///         }]
+///     }
+///
+///     fn handle_event(&mut self, _: &SystemEvent, _: &mut Context) -> anyhow::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let mut manager = PluginManager::new();
+/// manager.register_plugin(Box::new(Scaffold))?;
+/// let cli = drk_cli::build_cli(&mut manager, false);
+/// assert_eq!(cli.command_to_plugin["scaffold"], "scaffold");
+///
+/// let argv = "drk scaffold rust --name app --count 3 --ratio 0.5 --force";
+/// let matches = cli.app.try_get_matches_from(argv.split(' '))?;
+/// let (_, sub) = matches.subcommand().unwrap();
+/// let (mut path, mut args) = (vec!["scaffold".to_string()], HashMap::new());
+/// drk_core::cli::collect_args(&cli.plugin_commands["scaffold"][0], sub, &mut path, &mut args);
+///
+/// assert_eq!(args["template"], "rust");
+/// assert_eq!(args["name"], "app");
+/// assert_eq!(args["count"], "3");
+/// assert_eq!(args["ratio"], "0.5");
+/// assert_eq!(args["force"], "true");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn build_cli(manager: &mut PluginManager, debug: bool) -> Cli {
+    // 4. Build the CLI dynamically from plugin commands
+    let mut app = Command::new("drk")
+        .version("0.1.0")
+        .author("drk contributors")
+        .about("A modular, plugin-based CLI tool")
+        .subcommand_required(false)
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("role")
+                .long("role")
+                .env("DRK_ROLE")
+                .global(true)
+                .help("Restrict commands to those allowed by a config role")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("debug-cli")
+                .long("debug-cli")
+                .global(true)
+                .help("Trace how the command tree is built from plugins (to stderr)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("wizard")
+                .long("wizard")
+                .global(true)
+                .help("Prompt for each argument of the command, then confirm before running it")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .global(true)
+                .help("Run commands marked dangerous without asking for confirmation")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("print-invocation")
+                .long("print-invocation")
+                .global(true)
+                .help("Show the plugin, resolved arguments and events of the command without running it")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .global(true)
+                .value_parser(output_mode::FORMATS)
+                .default_value("text")
+                .help("json prints the result of a plugin command as JSON, like exec-json")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("no-cache")
+                .long("no-cache")
+                .global(true)
+                .help("Run cacheable commands even if a recent result is saved")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("offline")
+                .long("offline")
+                .global(true)
+                .help("Keep drk and its plugins off the network; also DRK_OFFLINE=1 or [http] offline")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("record")
+                .long("record")
+                .global(true)
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Write what this run depends on to FILE, for `drk rerun`")
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .global(true)
+                .help("Fail if any plugin fails to load; also DRK_STRICT=1 or [cli] strict")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("watch-plugins")
+                .long("watch-plugins")
+                .value_name("COMMAND")
+                .help("Reload plugin libraries when they're rebuilt, running COMMAND after each reload; must come first")
+                .num_args(0..)
+                .trailing_var_arg(true)
+                .allow_hyphen_values(true)
+                .action(ArgAction::Set),
+        )
+        .arg(
+            Arg::new("theme")
+                .long("theme")
+                .global(true)
+                .value_parser(drk_api::styling::Theme::NAMES.to_vec())
+                .help("Color theme; overrides [output] theme")
+                .action(ArgAction::Set),
+        )
+        .subcommand(commands::alias::command())
+        .subcommand(commands::audit::command())
+        .subcommand(commands::batch::command())
+        .subcommand(commands::check_tools::command())
+        .subcommand(commands::completions::command())
+        .subcommand(commands::config::command())
+        .subcommand(commands::exec_json::command())
+        .subcommand(commands::history::command())
+        .subcommand(commands::plugin::command())
+        .subcommand(commands::rerun::command())
+        .subcommand(commands::saved::save_as_command())
+        .subcommand(commands::saved::run_saved_command())
+        .subcommand(commands::shell::init_command())
+        .subcommand(commands::shell::hook_command())
+        .subcommand(commands::state::command())
+        .subcommand(commands::suggest::command())
+        .subcommand(commands::tips::command());
+
+    let mut trace = debug_cli::Trace::new(debug);
+    trace.builtins(&app);
+
+    // 5. Collect commands from all loaded plugins, including the ones they
+    // contribute for this run
+    manager.fire_event(SystemEvent::BuildingCli);
+    manager.collect_contributed_commands();
+    let plugin_commands = manager.get_all_plugin_commands();
+
+    let mut command_to_plugin: HashMap<String, String> = HashMap::new();
+    let mut unavailable: HashMap<String, Unavailable> = HashMap::new();
+    let cwd = std::env::current_dir().unwrap_or_default();
+    // An alias never hides a command's own name, whichever plugin comes first
+    let names: HashSet<&str> = plugin_commands
+        .values()
+        .flatten()
+        .map(|cmd| cmd.name.as_str())
+        .collect();
+
+    for (plugin_name, cmds) in plugin_commands.iter() {
+        for cmd in cmds {
+            if commands::BUILTIN_NAMES.contains(&cmd.name.as_str()) {
+                eprintln!(
+                    "Plugin '{}' tried to register reserved command '{}', skipping it",
+                    plugin_name, cmd.name
+                );
+                trace.conflict(format!(
+                    "'{}' from '{}' is a built-in name; skipped",
+                    cmd.name, plugin_name
+                ));
+                continue;
+            }
+            if let Some(owner) = command_to_plugin.get(&cmd.name) {
+                trace.conflict(format!(
+                    "'{}' from '{}' is already registered by '{}'",
+                    cmd.name, plugin_name, owner
+                ));
+            }
+            let mut cmd = cmd.clone();
+            cmd.aliases.retain(|alias| {
+                let taken = if commands::BUILTIN_NAMES.contains(&alias.as_str()) {
+                    Some("a built-in".to_string())
+                } else if names.contains(alias.as_str()) {
+                    Some("a command".to_string())
+                } else {
+                    command_to_plugin
+                        .get(alias)
+                        .map(|owner| format!("an alias from '{}'", owner))
+                };
+                if let Some(taken) = &taken {
+                    trace.conflict(format!(
+                        "alias '{}' of '{}' is already {}; dropped",
+                        alias, cmd.name, taken
+                    ));
+                }
+                taken.is_none()
+            });
+            for alias in &cmd.aliases {
+                command_to_plugin.insert(alias.clone(), plugin_name.clone());
+            }
+
+            // Keep the name so invoking it explains what's missing, but take
+            // any arguments so clap doesn't complain about them first
+            if let Some(missing) = availability::check(&cmd.requires, &cwd) {
+                trace.unavailable(plugin_name, &cmd, &missing.reason);
+                let cmd_name: &'static str = Box::leak(cmd.name.clone().into_boxed_str());
+                let cmd_desc: &'static str = Box::leak(cmd.description.clone().into_boxed_str());
+                let aliases: Vec<&'static str> = cmd
+                    .aliases
+                    .iter()
+                    .map(|alias| &*Box::leak(alias.clone().into_boxed_str()))
+                    .collect();
+                app = app.subcommand(
+                    Command::new(cmd_name)
+                        .about(cmd_desc)
+                        .hide(true)
+                        .aliases(aliases)
+                        .arg(
+                            Arg::new("args")
+                                .num_args(0..)
+                                .trailing_var_arg(true)
+                                .allow_hyphen_values(true),
+                        ),
+                );
+                command_to_plugin.insert(cmd.name.clone(), plugin_name.clone());
+                unavailable.insert(cmd.name.clone(), missing);
+                continue;
+            }
+
+            let mut leaked = Vec::new();
+            let subcommand = cli::subcommand(&cmd, &mut leaked);
+            trace.plugin_command(plugin_name, &cmd, &leaked);
+            app = app.subcommand(subcommand);
+            command_to_plugin.insert(cmd.name.clone(), plugin_name.clone());
+        }
+    }
+
+    trace.finish(&app);
+    manager.fire_event(SystemEvent::CliBuilt {
+        commands: app
+            .get_subcommands()
+            .map(|c| c.get_name().to_string())
+            .collect(),
+    });
+
+    Cli {
+        app,
+        command_to_plugin,
+        unavailable,
+        plugin_commands,
+    }
+}
+
+/// Exits with `code` after plugins have been unloaded, which
+/// `std::process::exit` alone would skip.
+fn exit(manager: &mut PluginManager, code: i32) -> ! {
+    manager.unload_all();
+    std::process::exit(code)
+}
+
+/// Loads config, host services and every plugin, then fires Startup (steps 0-3).
+/// `raw_args` is the command line, for the flags that apply while loading.
+pub fn load_manager(raw_args: &[String]) -> anyhow::Result<PluginManager> {
+    let mut manager = PluginManager::new();
+    interrupt::install(manager.cancellation_token())?;
+
+    // 0. Load the user config so plugin toggles and host settings apply
+    manager.load_config(paths::config_file())?;
+    if let Some(audit) = AuditLog::from_config(manager.config()) {
+        manager.set_audit_log(audit);
+    }
+    match StateStore::open(StateStore::default_path()) {
+        Ok(store) => manager.set_state_store(store),
+        Err(e) => eprintln!(
+            "{} {:#}; plugin state won't be saved this run",
+            style_warning(icon_warning()),
+            e
+        ),
+    }
+
+    // Plugins carry their own copy of the styling code, so `[output]` choices
+    // are handed to all of them through the environment. Flags and variables
+    // already set win over config.
+    for (key, var) in [
+        ("glyphs", drk_api::styling::GLYPHS_ENV),
+        ("emoji", drk_api::styling::EMOJI_ENV),
+        ("theme", drk_api::styling::THEME_ENV),
+    ] {
+        if let Some(value) = manager
+            .config()
+            .get("output")
+            .and_then(|o| o.get(key))
+            .and_then(|v| v.as_str())
+        {
+            if std::env::var_os(var).is_none() {
+                std::env::set_var(var, value);
+            }
+        }
+    }
+    let offline_configured = manager
+        .config()
+        .get("http")
+        .and_then(|http| http.get("offline"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if offline_configured && std::env::var_os(drk_api::http::OFFLINE_ENV).is_none() {
+        std::env::set_var(drk_api::http::OFFLINE_ENV, "1");
+    }
+
+    // 1. Define where plugins live: defaults, then [plugin_dirs] and DRK_PLUGIN_PATH
+    let search_path = paths::plugin_search_path(manager.config());
+
+    // 2. Load plugins dynamically; later directories shadow earlier ones
+    manager.load_plugins_from_search_path(&search_path)?;
+    if let Err(e) = load_errors::save(&load_errors::default_path(), manager.load_errors()) {
+        eprintln!("Failed to record plugin load errors: {:#}", e);
+    }
+
+    // CI would rather stop than run with part of its tooling missing. Other
+    // shared libraries in a build directory aren't plugins that failed
+    if strict_requested(raw_args, manager.config()) {
+        let failed: Vec<String> = manager
+            .load_errors()
+            .iter()
+            .filter(|e| e.category != Category::NotAPlugin)
+            .map(|e| format!("  {}", e.message))
+            .collect();
+        if !failed.is_empty() {
+            anyhow::bail!(
+                "Strict mode: {} plugin(s) failed to load:\n{}\nSee `drk plugin errors` for fixes",
+                failed.len(),
+                failed.join("\n")
+            );
+        }
+    }
+
+    // 3. Announce the startup phases. Config and discovery finish before any
+    // plugin exists, so their events are delivered now, in order
+    manager.fire_event(SystemEvent::ConfigLoaded);
+    let libraries = manager
+        .discovered_libraries()
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect();
+    manager.fire_event(SystemEvent::PluginsDiscovered { libraries });
+    let plugins = manager.plugin_names();
+    manager.fire_event(SystemEvent::PluginsLoaded { plugins });
+    // Plugins seen enabled for the first time get to set themselves up
+    manager.run_install_hooks();
+    manager.fire_event(SystemEvent::Startup);
+
+    Ok(manager)
+}
+
+/// Busybox-style dispatch: when drk is invoked through a symlink named after a
+/// plugin command (e.g. `greet -> drk`), treat it as `drk greet ...`.
+fn resolve_argv(argv: Vec<String>, command_to_plugin: &HashMap<String, String>) -> Vec<String> {
+    let invoked_as = argv
+        .first()
+        .and_then(|arg0| std::path::Path::new(arg0).file_stem())
+        .map(|stem| stem.to_string_lossy().into_owned());
+
+    match invoked_as {
+        Some(name) if name != "drk" && command_to_plugin.contains_key(&name) => {
+            let mut resolved = vec!["drk".to_string(), name];
+            resolved.extend(argv.into_iter().skip(1));
+            resolved
+        }
+        _ => argv,
+    }
+}
+
+/// Reports a parse error. For unknown subcommands it suggests the closest
+/// command, preferring ones the user actually runs, and with `[help] autocorrect`
+/// offers to run it. Returns the corrected argv if the user accepted.
+fn correct_typo(
+    error: &clap::Error,
+    argv: &[String],
+    available: &[String],
+    config: &HashMap<String, toml::Value>,
+) -> Option<Vec<String>> {
+    let typo = match error.get(ContextKind::InvalidSubcommand) {
+        Some(ContextValue::String(typo)) if error.kind() == ErrorKind::InvalidSubcommand => typo,
+        _ => {
+            eprintln!("{}", error);
+            return None;
+        }
+    };
+
+    // A command nothing loaded provides may come with a plugin not installed yet
+    let index = Index::load_cached(&Index::default_path());
+    if let Some(entry) = index.as_ref().and_then(|index| index.provider_of(typo)) {
+        eprintln!(
+            "{} {} {}\n  It's provided by plugin {}; install it with {}",
+            style_error(icon_error()),
+            style_error("Unknown command"),
+            style_primary(typo),
+            style_primary(&entry.name),
+            style_primary(&format!("drk plugin install {}", entry.name))
+        );
+        return None;
+    }
+
+    let stats = StateStore::open(StateStore::default_path())
+        .map(|state| UsageStats::load(&state))
+        .unwrap_or_default();
+    let Some(suggestion) = stats
+        .suggest_for_typo(typo, available)
+        .or_else(|| typo::closest(typo, available))
+    else {
+        eprintln!("{}", error);
+        return None;
+    };
+
+    let used = match stats.commands.get(&suggestion) {
+        Some(u) => format!(" (you've used it {} times)", u.count),
+        None => String::new(),
+    };
+    eprintln!(
+        "{} {} {}\n  Did you mean {}?{}",
+        style_error(icon_error()),
+        style_error("Unknown command"),
+        style_primary(typo),
+        style_primary(&format!("drk {}", suggestion)),
+        used
+    );
+
+    // Never auto-run without a human to confirm it
+    if !typo::autocorrect_enabled(config) || !std::io::stdin().is_terminal() {
+        return None;
+    }
+    eprint!("Run {} instead? [Y/n] ", style_primary(&suggestion));
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).ok()?;
+    if !matches!(answer.trim().to_lowercase().as_str(), "" | "y" | "yes") {
+        return None;
+    }
+
+    let position = argv.iter().skip(1).position(|a| a == typo)? + 1;
+    let mut corrected = argv.to_vec();
+    corrected[position] = suggestion;
+    Some(corrected)
+}
+
+/// Bumps the usage counters that drive `drk suggest` and typo suggestions.
+fn record_usage(manager: &mut PluginManager, command_name: &str) {
+    let Some(state) = manager.state_store_mut() else {
+        return;
+    };
+    let mut stats = UsageStats::load(state);
+    let project = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| paths::project_root(&cwd));
+    stats.record(command_name, project.as_deref());
+    if let Err(e) = stats.store(state).and_then(|_| state.save()) {
+        eprintln!("Failed to record command usage: {}", e);
+    }
+}
+
+/// Shows a tip about `command_name` on stderr if one is due and someone is
+/// there to read it.
+fn show_tip(manager: &mut PluginManager, command_name: &str) {
+    let settings = tips::Settings::from_config(manager.config());
+    if !settings.enabled || !std::io::stderr().is_terminal() {
+        return;
+    }
+    let available = manager.tips();
+    let Some(state) = manager.state_store_mut() else {
+        return;
+    };
+    let count = UsageStats::load(state)
+        .commands
+        .get(command_name)
+        .map_or(0, |u| u.count);
+    let mut seen = TipState::load(state);
+    let Some((plugin, tip)) = seen.due(&available, command_name, count, &settings) else {
+        return;
+    };
+    let key = tips::key(plugin, tip);
+    eprintln!(
+        "{} {}",
+        style_primary(icon_info()),
+        tips::render(tip, count)
+    );
+    eprintln!(
+        "  {}",
+        style_dim(&format!("`drk tips dismiss {}` to stop seeing this", key))
+    );
+    seen.mark_shown(&key);
+    if let Err(e) = seen.store(state).and_then(|_| state.save()) {
+        eprintln!("Failed to record the tip: {}", e);
+    }
+}
+
+/// Appends the invocation to the command history behind `drk history`.
+fn record_history(args: &[String], role: Option<&Role>, success: bool) {
+    let project = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| paths::project_root(&cwd));
+    let entry = HistoryEntry::new(
+        args,
+        project.as_deref(),
+        role.map(|r| r.name.as_str()),
+        success,
+    );
+    if let Err(e) = History::new(History::default_path()).append(&entry) {
+        eprintln!("Failed to record history: {}", e);
+    }
+}
+
+/// The values given for sensitive arguments of the plugin command in `matches`.
+fn sensitive_values(
+    matches: &clap::ArgMatches,
+    command_to_plugin: &HashMap<String, String>,
+    plugin_commands: &HashMap<String, Vec<PluginCommand>>,
+) -> Vec<String> {
+    let Some((name, sub)) = matches.subcommand() else {
+        return Vec::new();
+    };
+    let top = command_to_plugin
+        .get(name)
+        .and_then(|plugin| plugin_commands.get(plugin))
+        .and_then(|cmds| cmds.iter().find(|c| c.name == name));
+    let Some(top) = top else {
+        return Vec::new();
+    };
+    let (mut path, mut args) = (vec![name.to_string()], HashMap::new());
+    cli::collect_args(top, sub, &mut path, &mut args);
+    redact::sensitive_values(top, &path, &args)
+}
+
+/// Appends a command entry to the audit log, if auditing is enabled.
+fn audit_command(manager: &PluginManager, plugin_name: &str, command_name: &str, detail: &str) {
+    if let Some(audit) = manager.audit_log() {
+        let subject = format!("{}:{}", plugin_name, command_name);
+        if let Err(e) = audit.record("command", &subject, detail) {
+            eprintln!("Failed to write audit entry: {}", e);
+        }
+    }
+}
+
+/// The role set by `--role` or `DRK_ROLE`, looked up in the config.
+pub(crate) fn active_role(
+    matches: &clap::ArgMatches,
+    config: &HashMap<String, toml::Value>,
+) -> anyhow::Result<Option<Role>> {
+    let env_role = std::env::var("DRK_ROLE").ok();
+    let flag = matches.get_one::<String>("role").map(String::as_str);
+    role_name(flag, env_role.as_deref())?
+        .map(|name| Role::from_config(config, name))
+        .transpose()
+}
+
+/// `DRK_ROLE` is set by whoever restricts drk, so a `--role` flag can't
+/// swap it for another role. clap already falls back to it without a flag.
+fn role_name<'a>(flag: Option<&'a str>, env: Option<&'a str>) -> anyhow::Result<Option<&'a str>> {
+    match (flag, env) {
+        (Some(flag), Some(env)) if flag != env => {
+            anyhow::bail!("--role asks for role '{}' but DRK_ROLE is '{}'", flag, env)
+        }
+        (flag, env) => Ok(flag.or(env)),
+    }
+}
+
+/// Whether plugin load failures are fatal: `--strict`, `DRK_STRICT=1` or
+/// `[cli] strict = true`. Looked up before clap runs, since plugins load first.
+fn strict_requested(args: &[String], config: &HashMap<String, toml::Value>) -> bool {
+    args.iter()
+        .skip(1)
+        .take_while(|a| *a != "--")
+        .any(|a| a == "--strict")
+        || std::env::var("DRK_STRICT").is_ok_and(|v| matches!(v.as_str(), "1" | "true" | "yes"))
+        || config
+            .get("cli")
+            .and_then(|cli| cli.get("strict"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+}
+
+/// `args` without `--record FILE`, so a replay doesn't record over the file.
+fn without_record_flag(args: &[String]) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            out.push(arg.clone());
+            out.extend(args.by_ref().cloned());
+        } else if arg == "--record" {
+            args.next();
+        } else if !arg.starts_with("--record=") {
+            out.push(arg.clone());
+        }
+    }
+    out
+}
+
+/// Whether `--offline` comes before any `--`.
+fn offline_flag(args: &[String]) -> bool {
+    args.iter()
+        .skip(1)
+        .take_while(|a| *a != "--")
+        .any(|a| a == "--offline")
+}
+
+/// The `--theme` value, found before clap runs so loading output is themed.
+/// clap validates it later.
+fn theme_flag(args: &[String]) -> Option<&str> {
+    let mut args = args.iter().skip(1).take_while(|a| *a != "--");
+    while let Some(arg) = args.next() {
+        if arg == "--theme" {
+            return args.next().map(String::as_str);
+        }
+        if let Some(value) = arg.strip_prefix("--theme=") {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use drk_api::{ArgType, CommandArg, Context, Plugin, PluginMetadata};
+
+    /// A plugin with one command taking an argument of every type.
+    struct EveryType;
+
+    impl Plugin for EveryType {
+        fn metadata(&self) -> PluginMetadata {
+            PluginMetadata {
+                name: "every".to_string(),
+                version: "0.1.0".to_string(),
+                author: "drk contributors".to_string(),
+                description: "Takes one argument of each type".to_string(),
+                essential: false,
+            }
+        }
+
+        fn get_commands(&self) -> Vec<PluginCommand> {
+            let arg = |name: &str, arg_type: ArgType| CommandArg {
+                name: name.to_string(),
+                arg_type,
+                ..Default::default()
+            };
+            vec![PluginCommand {
+                name: "every".to_string(),
+                args: vec![
+                    arg("name", ArgType::String),
+                    arg("count", ArgType::Integer),
+                    arg("ratio", ArgType::Float),
+                    arg("force", ArgType::Boolean),
+                    arg("first", ArgType::Positional),
+                    arg("rest", ArgType::Variadic),
+                    arg("verbose", ArgType::Count),
+                    arg(
+                        "template",
+                        ArgType::Choice(vec!["rust".to_string(), "go".to_string()]),
+                    ),
+                    arg("timeout", ArgType::Duration),
+                    arg("at", ArgType::DateTime),
+                ],
+                ..Default::default()
+            }]
+        }
+
+        fn handle_event(&mut self, _event: &SystemEvent, _ctx: &mut Context) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Parses `drk every <args>` with the command tree drk builds and
+    /// collects what the plugin would be handed.
+    fn collect(args: &[&str]) -> Result<CommandMatches, clap::Error> {
+        let mut manager = PluginManager::new();
+        manager.register_plugin(Box::new(EveryType)).unwrap();
+        let built = build_cli(&mut manager, false);
+        manager.unload_all();
+
+        let argv = ["drk", "every"].iter().chain(args);
+        let matches = built.app.try_get_matches_from(argv)?;
+        let (command_name, sub) = matches.subcommand().unwrap();
+        let schema = &built.plugin_commands["every"][0];
+        let mut path = vec![command_name.to_string()];
+        let mut collected = HashMap::new();
+        cli::collect_args(schema, sub, &mut path, &mut collected);
+        Ok(CommandMatches {
+            command_name: command_name.to_string(),
+            path,
+            args: collected,
+        })
+    }
+
+    #[test]
+    fn role_flag_cannot_replace_drk_role() {
+        let mut manager = PluginManager::new();
+        manager.register_plugin(Box::new(EveryType)).unwrap();
+        let built = build_cli(&mut manager, false);
+        manager.unload_all();
+
+        let matches = built
+            .app
+            .try_get_matches_from(["drk", "--role", "admin", "every"])
+            .unwrap();
+        let flag = matches.get_one::<String>("role").map(String::as_str);
+        let denied = role_name(flag, Some("ci")).unwrap_err();
+        assert!(denied.to_string().contains("DRK_ROLE is 'ci'"));
+        assert_eq!(role_name(flag, Some("admin")).unwrap(), Some("admin"));
+        assert_eq!(role_name(flag, None).unwrap(), Some("admin"));
+        assert_eq!(role_name(None, Some("ci")).unwrap(), Some("ci"));
+    }
+
+    #[test]
+    fn collects_every_argument_type() {
+        let matches = collect(&[
+            "one",
+            "two",
+            "three",
+            "--name",
+            "Bob",
+            "--count",
+            "3",
+            "--ratio",
+            "0.5",
+            "--force",
+            "-vv",
+            "--template",
+            "go",
+            "--timeout",
+            "90m",
+            "--at",
+            "2026-03-01T09:30:00Z",
+        ])
+        .unwrap();
+        let arg = |name: &str| matches.args.get(name).map(String::as_str);
+        assert_eq!(arg("name"), Some("Bob"));
+        assert_eq!(arg("count"), Some("3"));
+        assert_eq!(arg("ratio"), Some("0.5"));
+        assert_eq!(arg("force"), Some("true"));
+        assert_eq!(arg("first"), Some("one"));
+        assert_eq!(matches.values("rest"), ["two", "three"]);
+        assert_eq!(arg("verbose"), Some("2"));
+        assert_eq!(arg("template"), Some("go"));
+        assert_eq!(arg("timeout"), Some("1h30m"));
+        assert_eq!(
+            matches.duration("timeout").unwrap(),
+            Some(std::time::Duration::from_secs(90 * 60))
+        );
+        assert_eq!(arg("at"), Some("2026-03-01T09:30:00+00:00"));
+        assert!(matches.datetime("at").unwrap().is_some());
+    }
+
+    #[test]
+    fn leaves_out_arguments_not_given() {
+        let matches = collect(&[]).unwrap();
+        assert!(matches.args.is_empty(), "{:?}", matches.args);
+        assert!(matches.values("rest").is_empty());
+    }
+
+    #[test]
+    fn rejects_values_of_the_wrong_type() {
+        for args in [
+            ["--count", "three"],
+            ["--ratio", "half"],
+            ["--template", "java"],
+            ["--timeout", "25"],
+            ["--at", "next week"],
+        ] {
+            assert!(collect(&args).is_err(), "{:?} was accepted", args);
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! Caps how much a command may print, so runaway plugin output can't flood
+//! the terminal or a log.
+//!
+//! Past `[output] max_lines` lines or `max_bytes` bytes of stdout the rest is
+//! held back; all of it goes to a temp file whose path is printed when the
+//! command ends. `0` turns a limit off.
+//!
+//! ```toml
+//! [output]
+//! max_lines = 5000
+//! max_bytes = 1048576
+//! ```
+
+use drk_api::{icon_warning, style_primary, style_warning};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+const DEFAULT_MAX_LINES: usize = 5_000;
+const DEFAULT_MAX_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_lines: Option<usize>,
+    pub max_bytes: Option<usize>,
+}
+
+impl Limits {
+    pub fn from_config(config: &HashMap<String, toml::Value>) -> Self {
+        let section = config.get("output");
+        let limit = |key: &str, default: usize| {
+            let value = section
+                .and_then(|s| s.get(key))
+                .and_then(|v| v.as_integer())
+                .map_or(default, |v| v.max(0) as usize);
+            (value > 0).then_some(value)
+        };
+        Self {
+            max_lines: limit("max_lines", DEFAULT_MAX_LINES),
+            max_bytes: limit("max_bytes", DEFAULT_MAX_BYTES),
+        }
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.max_lines.is_none() && self.max_bytes.is_none()
+    }
+}
+
+/// Output held back from the terminal.
+struct Truncated {
+    path: PathBuf,
+    lines: usize,
+    bytes: usize,
+    /// The `[output]` key whose limit was reached, and its value
+    limit: (&'static str, usize),
+}
+
+impl Truncated {
+    fn report(&self) {
+        eprintln!(
+            "{} {}",
+            style_warning(icon_warning()),
+            style_warning(&format!(
+                "Output truncated at [output] {} = {}; all {} lines ({} bytes) are in",
+                self.limit.0, self.limit.1, self.lines, self.bytes
+            ))
+        );
+        eprintln!("  {}", style_primary(&self.path.display().to_string()));
+    }
+}
+
+/// Passes output through until a limit is reached, keeping all of it in a
+/// temp file in case it is.
+struct Sink<W: Write> {
+    out: W,
+    limits: Limits,
+    full: tempfile::NamedTempFile,
+    lines: usize,
+    bytes: usize,
+    shown_lines: usize,
+    shown_bytes: usize,
+    /// Set once a limit is reached
+    truncated: Option<(&'static str, usize)>,
+    /// Whether the shown part ends a line
+    at_line_start: bool,
+}
+
+impl<W: Write> Sink<W> {
+    fn new(out: W, limits: Limits) -> io::Result<Self> {
+        Ok(Self {
+            out,
+            limits,
+            full: tempfile::Builder::new()
+                .prefix("drk-output-")
+                .suffix(".log")
+                .tempfile()?,
+            lines: 0,
+            bytes: 0,
+            shown_lines: 0,
+            shown_bytes: 0,
+            truncated: None,
+            at_line_start: true,
+        })
+    }
+
+    fn write(&mut self, chunk: &[u8]) -> io::Result<()> {
+        self.full.write_all(chunk)?;
+        self.bytes += chunk.len();
+        self.lines += chunk.iter().filter(|b| **b == b'\n').count();
+        if self.truncated.is_some() {
+            return Ok(());
+        }
+
+        let shown = self.allowed(chunk);
+        if let Some(last) = chunk[..shown].last() {
+            self.out.write_all(&chunk[..shown])?;
+            self.out.flush()?;
+            self.at_line_start = *last == b'\n';
+        }
+        Ok(())
+    }
+
+    /// How much of `chunk` fits under the limits.
+    fn allowed(&mut self, chunk: &[u8]) -> usize {
+        for (i, byte) in chunk.iter().enumerate() {
+            if let Some(max) = self.limits.max_lines.filter(|max| self.shown_lines >= *max) {
+                self.truncated = Some(("max_lines", max));
+                return i;
+            }
+            if let Some(max) = self.limits.max_bytes.filter(|max| self.shown_bytes >= *max) {
+                self.truncated = Some(("max_bytes", max));
+                return i;
+            }
+            self.shown_bytes += 1;
+            if *byte == b'\n' {
+                self.shown_lines += 1;
+            }
+        }
+        chunk.len()
+    }
+
+    /// Keeps the temp file if anything was held back, and says where it is.
+    fn finish(mut self) -> io::Result<()> {
+        let Some(limit) = self.truncated else {
+            return Ok(());
+        };
+        if !self.at_line_start {
+            self.out.write_all(b"\n")?;
+        }
+        self.out.flush()?;
+        self.full.flush()?;
+        let (_, path) = self.full.keep().map_err(|e| e.error)?;
+        Truncated {
+            path,
+            lines: self.lines,
+            bytes: self.bytes,
+            limit,
+        }
+        .report();
+        Ok(())
+    }
+}
+
+/// Prints output captured earlier (e.g. a cached result) within the limits.
+pub fn print(output: &str, limits: Limits) -> anyhow::Result<()> {
+    if limits.is_unlimited() {
+        print!("{}", output);
+        return Ok(());
+    }
+    let mut sink = Sink::new(io::stdout(), limits)?;
+    sink.write(output.as_bytes())?;
+    sink.finish()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+pub use unix::Limiter;
+
+#[cfg(not(unix))]
+pub use fallback::Limiter;
+
+#[cfg(unix)]
+mod unix {
+    use super::{Limits, Sink};
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::fd::FromRawFd;
+    use std::thread::JoinHandle;
+
+    /// Routes the process-wide stdout (fd 1) through a [`Sink`] until
+    /// `finish`. Like [`crate::capture::Capture`], redirecting the file
+    /// descriptor is what catches output from plugins' own `std`.
+    pub struct Limiter {
+        saved_fd: i32,
+        reader: Option<JoinHandle<std::io::Result<()>>>,
+    }
+
+    impl Limiter {
+        pub fn start(limits: Limits) -> anyhow::Result<Self> {
+            if limits.is_unlimited() {
+                return Ok(Self {
+                    saved_fd: -1,
+                    reader: None,
+                });
+            }
+            std::io::stdout().flush()?;
+            // SAFETY: plain fd juggling on descriptors we own; fd 1 is restored in `finish`
+            let (saved_fd, mut source, out) = unsafe {
+                let (read_fd, write_fd) = channel()?;
+                // Close-on-exec, so programs the plugin leaves running in the
+                // background don't hold the real stdout open after drk exits
+                let saved_fd = libc::fcntl(1, libc::F_DUPFD_CLOEXEC, 0);
+                let out_fd = libc::fcntl(1, libc::F_DUPFD_CLOEXEC, 0);
+                libc::fcntl(read_fd, libc::F_SETFD, libc::FD_CLOEXEC);
+                if saved_fd < 0 || out_fd < 0 || libc::dup2(write_fd, 1) < 0 {
+                    anyhow::bail!(
+                        "Could not redirect stdout: {}",
+                        std::io::Error::last_os_error()
+                    );
+                }
+                // fd 1 is the only write end left, so restoring it ends the stream
+                libc::close(write_fd);
+                (
+                    saved_fd,
+                    File::from_raw_fd(read_fd),
+                    File::from_raw_fd(out_fd),
+                )
+            };
+
+            let reader = std::thread::spawn(move || {
+                let mut sink = Sink::new(out, limits)?;
+                let mut buf = vec![0; 64 * 1024];
+                loop {
+                    let n = match source.read(&mut buf) {
+                        Ok(n) => n,
+                        // A pty reports its closed end as EIO rather than EOF
+                        Err(e) if e.raw_os_error() == Some(libc::EIO) => 0,
+                        Err(e) => return Err(e),
+                    };
+                    if n == 0 {
+                        break;
+                    }
+                    sink.write(&buf[..n])?;
+                }
+                sink.finish()
+            });
+            Ok(Self {
+                saved_fd,
+                reader: Some(reader),
+            })
+        }
+
+        /// Restores stdout and, if output was held back, says where it went.
+        /// Waits for anything else still writing to it, such as a program the
+        /// plugin started in the background.
+        pub fn finish(mut self) -> anyhow::Result<()> {
+            let Some(reader) = self.reader.take() else {
+                return Ok(());
+            };
+            std::io::stdout().flush()?;
+            // SAFETY: `saved_fd` is the copy of fd 1 made in `start`
+            unsafe {
+                libc::dup2(self.saved_fd, 1);
+                libc::close(self.saved_fd);
+            }
+            match reader.join() {
+                Ok(result) => Ok(result?),
+                Err(_) => anyhow::bail!("The output limiter stopped unexpectedly"),
+            }
+        }
+    }
+
+    /// A (read, write) pair to stand in for stdout. On a terminal that's a
+    /// pseudo-terminal of the same size, so plugins keep their colors and
+    /// progress displays; otherwise a pipe.
+    unsafe fn channel() -> anyhow::Result<(i32, i32)> {
+        let mut fds = [0; 2];
+        if libc::isatty(1) == 1 {
+            let mut size: libc::winsize = std::mem::zeroed();
+            libc::ioctl(1, libc::TIOCGWINSZ, &mut size);
+            if libc::openpty(
+                &mut fds[0],
+                &mut fds[1],
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::addr_of_mut!(size),
+            ) == 0
+            {
+                // Raw, so output arrives as written instead of with `\n` turned into `\r\n`
+                let mut termios: libc::termios = std::mem::zeroed();
+                if libc::tcgetattr(fds[1], &mut termios) == 0 {
+                    libc::cfmakeraw(&mut termios);
+                    libc::tcsetattr(fds[1], libc::TCSANOW, &termios);
+                }
+                return Ok((fds[0], fds[1]));
+            }
+        }
+        if libc::pipe(fds.as_mut_ptr()) < 0 {
+            anyhow::bail!(
+                "Could not create a pipe: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok((fds[0], fds[1]))
+    }
+}
+
+#[cfg(not(unix))]
+mod fallback {
+    use super::Limits;
+
+    /// Limiting live output needs fd redirection; elsewhere it passes through.
+    pub struct Limiter;
+
+    impl Limiter {
+        pub fn start(_limits: Limits) -> anyhow::Result<Self> {
+            Ok(Self)
+        }
+
+        pub fn finish(self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(max_lines: Option<usize>, max_bytes: Option<usize>) -> Limits {
+        Limits {
+            max_lines,
+            max_bytes,
+        }
+    }
+
+    fn sink(limits: Limits, chunks: &[&str]) -> Sink<Vec<u8>> {
+        let mut sink = Sink::new(Vec::new(), limits).unwrap();
+        for chunk in chunks {
+            sink.write(chunk.as_bytes()).unwrap();
+        }
+        sink
+    }
+
+    fn shown(sink: &Sink<Vec<u8>>) -> &str {
+        std::str::from_utf8(&sink.out).unwrap()
+    }
+
+    #[test]
+    fn limits_come_from_config() {
+        let config: HashMap<String, toml::Value> =
+            toml::from_str("[output]\nmax_lines = 0\nmax_bytes = 100").unwrap();
+        let from_config = Limits::from_config(&config);
+        assert_eq!(from_config.max_lines, None);
+        assert_eq!(from_config.max_bytes, Some(100));
+
+        let defaults = Limits::from_config(&HashMap::new());
+        assert_eq!(defaults.max_lines, Some(DEFAULT_MAX_LINES));
+        assert_eq!(defaults.max_bytes, Some(DEFAULT_MAX_BYTES));
+    }
+
+    #[test]
+    fn output_under_the_limits_passes_through() {
+        let sink = sink(limits(Some(3), Some(100)), &["one\n", "two\nthree\n"]);
+        assert_eq!(shown(&sink), "one\ntwo\nthree\n");
+        assert!(sink.truncated.is_none());
+        sink.finish().unwrap();
+    }
+
+    #[test]
+    fn lines_past_max_lines_are_held_back() {
+        let sink = sink(limits(Some(2), None), &["one\ntw", "o\nthree\n", "four\n"]);
+        assert_eq!(shown(&sink), "one\ntwo\n");
+        assert_eq!(sink.truncated, Some(("max_lines", 2)));
+        assert_eq!((sink.lines, sink.bytes), (4, 19));
+        let full = std::fs::read_to_string(sink.full.path()).unwrap();
+        assert_eq!(full, "one\ntwo\nthree\nfour\n");
+    }
+
+    #[test]
+    fn max_bytes_may_cut_a_line() {
+        let sink = sink(limits(Some(10), Some(4)), &["hello world\n"]);
+        assert_eq!(shown(&sink), "hell");
+        assert_eq!(sink.truncated, Some(("max_bytes", 4)));
+        assert!(!sink.at_line_start);
+    }
+}
@@ -0,0 +1,57 @@
+//! `--output json`: a plugin command prints the same JSON result `drk
+//! exec-json` does instead of its usual output, so drk can be scripted from
+//! CI and other tools without a second syntax.
+
+use crate::capture::Capture;
+use drk_core::invocation::CommandResult;
+use drk_core::manager::{CommandOutcome, PluginManager};
+
+pub const FLAG: &str = "--output";
+
+/// The formats `--output` takes; `text` is the default.
+pub const FORMATS: [&str; 2] = ["text", "json"];
+
+/// Whether `--output json` was given before any `--`. Checked before
+/// plugins load, so their startup chatter stays off stdout.
+pub fn json_requested(argv: &[String]) -> bool {
+    let mut args = argv.iter().skip(1).take_while(|a| *a != "--");
+    while let Some(arg) = args.next() {
+        if arg == FLAG {
+            return args.next().is_some_and(|v| v == "json");
+        }
+        if let Some(value) = arg.strip_prefix("--output=") {
+            return value == "json";
+        }
+    }
+    false
+}
+
+/// Turns off styling, in drk and in every plugin.
+pub fn unstyled() {
+    // Plugins have their own copies of the styling code; the environment reaches all of them
+    std::env::set_var("CLICOLOR", "0");
+    std::env::remove_var("CLICOLOR_FORCE");
+    std::env::set_var(drk_api::styling::EMOJI_ENV, "none");
+}
+
+/// Unloads the plugins, keeping what they print meanwhile off stdout, then
+/// prints `result` and exits with its code.
+pub fn respond(result: &CommandResult, manager: &mut PluginManager) -> ! {
+    let unloading = Capture::start();
+    manager.unload_all();
+    if let Ok(unloading) = unloading {
+        let _ = unloading.finish();
+    }
+    match serde_json::to_string(result) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Could not print the result: {}", e),
+    }
+    drk_core::cleanup::exit(result.exit_code)
+}
+
+/// Prints the text of the output `plugin` handed over, in text mode.
+pub fn print_text(outcome: &CommandOutcome, plugin: &str) {
+    if let Some(output) = outcome.output(plugin) {
+        println!("{}", output.render());
+    }
+}
@@ -0,0 +1,123 @@
+//! User-defined command aliases.
+//!
+//! ```toml
+//! [aliases]
+//! g = "greet --name World"
+//! tpl = "nix --template {config.nix.default_template}"
+//! ```
+//!
+//! Placeholders are resolved with [`crate::interpolate`] after the alias is
+//! split into words, so a config value containing spaces stays one argument.
+//!
+//! An alias never shadows a real command; `drk g` only expands when no plugin
+//! or built-in is called `g`.
+
+use crate::interpolate::interpolate;
+use anyhow::{Context as _, Result};
+use std::collections::{BTreeMap, HashMap};
+
+/// Reads the `[aliases]` section. Non-string entries are ignored.
+pub fn from_config(config: &HashMap<String, toml::Value>) -> BTreeMap<String, String> {
+    config
+        .get("aliases")
+        .and_then(|a| a.as_table())
+        .map(|table| {
+            table
+                .iter()
+                .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Replaces `argv[1]` with the words of its alias, if it is one.
+pub fn expand(
+    argv: Vec<String>,
+    aliases: &BTreeMap<String, String>,
+    config: &HashMap<String, toml::Value>,
+) -> Result<Vec<String>> {
+    let Some(expansion) = argv.get(1).and_then(|name| aliases.get(name)) else {
+        return Ok(argv);
+    };
+
+    let words = shell_words::split(expansion)
+        .with_context(|| format!("Alias '{}' is not valid shell syntax", argv[1]))?;
+
+    let mut expanded = vec![argv[0].clone()];
+    for word in words {
+        expanded
+            .push(interpolate(&word, config).with_context(|| format!("In alias '{}'", argv[1]))?);
+    }
+    expanded.extend(argv.into_iter().skip(2));
+    Ok(expanded)
+}
+
+/// Whether `name` can be used as a shell alias/function name.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> HashMap<String, toml::Value> {
+        toml::from_str(
+            r#"
+            [aliases]
+            g = "greet --name World"
+            tpl = "nix --template {config.nix.template}"
+            jobs = "build --jobs {config.build.jobs}"
+            broken = "nix --template {config.nix.missing}"
+            unquoted = "greet 'World"
+
+            [nix]
+            template = "rust workspace"
+
+            [build]
+            jobs = 4
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn run(args: &[&str]) -> Result<Vec<String>> {
+        let config = config();
+        let argv = args.iter().map(|arg| arg.to_string()).collect();
+        expand(argv, &from_config(&config), &config)
+    }
+
+    #[test]
+    fn aliases_expand_in_place_of_their_name() {
+        assert_eq!(
+            run(&["drk", "g", "--loud"]).unwrap(),
+            ["drk", "greet", "--name", "World", "--loud"]
+        );
+        assert_eq!(run(&["drk", "greet"]).unwrap(), ["drk", "greet"]);
+    }
+
+    #[test]
+    fn config_values_stay_one_argument() {
+        assert_eq!(
+            run(&["drk", "tpl"]).unwrap(),
+            ["drk", "nix", "--template", "rust workspace"]
+        );
+        assert_eq!(
+            run(&["drk", "jobs"]).unwrap(),
+            ["drk", "build", "--jobs", "4"]
+        );
+    }
+
+    #[test]
+    fn bad_aliases_name_themselves() {
+        let missing = run(&["drk", "broken"]).unwrap_err();
+        assert_eq!(missing.to_string(), "In alias 'broken'");
+        assert!(format!("{:#}", missing).contains("'nix.missing' is not set"));
+
+        let unquoted = run(&["drk", "unquoted"]).unwrap_err();
+        assert!(unquoted.to_string().contains("Alias 'unquoted'"));
+    }
+}
@@ -0,0 +1,77 @@
+//! Files shipped alongside a plugin library (templates, completions,
+//! schemas).
+//!
+//! A package keeps them in a directory named like the library with an
+//! `.assets` extension (`libdrk_nix.so` -> `libdrk_nix.assets/`), which the
+//! installer copies along with the library. When the plugin loads, they are
+//! extracted to `<data dir>/assets/`, where `ctx.asset("templates/flake.nix")`
+//! finds them. Extraction is redone whenever the library changes.
+
+use anyhow::{Context as _, Result};
+use drk_api::ASSETS_DIR;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Records which library build the extracted assets came from.
+const STAMP_FILE: &str = ".source";
+
+/// The asset directory packaged with `library`.
+pub fn source_for(library: &Path) -> PathBuf {
+    library.with_extension("assets")
+}
+
+/// Extracts the assets packaged with `library` into `data_dir`, unless the
+/// ones already there came from the same build. Returns whether anything
+/// was copied.
+pub fn sync(library: &Path, data_dir: &Path) -> Result<bool> {
+    let source = source_for(library);
+    if !source.is_dir() {
+        return Ok(false);
+    }
+    let dest = data_dir.join(ASSETS_DIR);
+    let stamp = stamp(library)?;
+    if fs::read_to_string(dest.join(STAMP_FILE)).is_ok_and(|s| s == stamp) {
+        return Ok(false);
+    }
+
+    if dest.exists() {
+        fs::remove_dir_all(&dest)
+            .with_context(|| format!("Could not replace old assets at {:?}", dest))?;
+    }
+    copy_dir(&source, &dest)?;
+    fs::write(dest.join(STAMP_FILE), stamp)?;
+    Ok(true)
+}
+
+/// Size and modification time: cheap, and different for every rebuild.
+fn stamp(library: &Path) -> Result<String> {
+    let meta = fs::metadata(library)?;
+    let modified = meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    Ok(format!("{}-{}", meta.len(), modified))
+}
+
+/// Copies the tree at `source` to `dest`, which must not exist yet.
+pub fn copy_dir(source: &Path, dest: &Path) -> Result<()> {
+    for entry in walkdir::WalkDir::new(source) {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(source)?;
+        let target = dest.join(relative);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)
+                .with_context(|| format!("Could not copy asset {:?}", entry.path()))?;
+            // Nix store files are read-only, and would be stuck on Windows
+            let mut perms = fs::metadata(&target)?.permissions();
+            #[allow(clippy::permissions_set_readonly_false)]
+            perms.set_readonly(false);
+            fs::set_permissions(&target, perms)?;
+        }
+    }
+    Ok(())
+}
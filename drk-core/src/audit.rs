@@ -0,0 +1,514 @@
+//! Tamper-evident audit log.
+//!
+//! Every entry stores the hash of the previous entry, so editing or deleting a
+//! line in the middle of the log breaks the chain and is caught by `verify()`.
+//! The chain starts at [`GENESIS_HASH`]; once retention drops the head, a
+//! `retention` entry records where the log now starts, so cutting off more of
+//! the head is caught too.
+//! The log is a JSON-lines file that lives in the drk data directory.
+//! Parallel invocations take an exclusive lock on the file while appending,
+//! so two of them never chain to the same previous entry.
+
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The `prev_hash` of the very first entry in a fresh log.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// A single line of the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub user: String,
+    /// What happened: "command", "config" or "retention".
+    pub kind: String,
+    /// Command name or plugin that changed the config.
+    pub subject: String,
+    pub detail: String,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn compute_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(
+            format!(
+                "{}|{}|{}|{}|{}|{}|{}",
+                self.seq,
+                self.timestamp,
+                self.user,
+                self.kind,
+                self.subject,
+                self.detail,
+                self.prev_hash
+            )
+            .as_bytes(),
+        );
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+/// How much history the log keeps. Configured under `[audit]` in config.toml.
+///
+/// Pruning rewrites the whole log, so `record` leaves it until the log holds a
+/// tenth more than `max_entries` or its oldest entry is a day past
+/// `max_age_days`. `drk audit verify` prunes right away.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Keep at most this many entries.
+    pub max_entries: Option<usize>,
+    /// Drop entries older than this many days.
+    pub max_age_days: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// Reads `max_entries` and `max_age_days` from the `[audit]` config section.
+    pub fn from_config(section: Option<&toml::Value>) -> Self {
+        let get = |key: &str| {
+            section
+                .and_then(|s| s.get(key))
+                .and_then(|v| v.as_integer())
+                .filter(|v| *v > 0)
+        };
+        Self {
+            max_entries: get("max_entries").map(|v| v as usize),
+            max_age_days: get("max_age_days").map(|v| v as u64),
+        }
+    }
+}
+
+/// Result of walking the hash chain.
+#[derive(Debug)]
+pub struct VerifyReport {
+    pub entries: usize,
+    /// The first entry that failed verification and why.
+    pub broken_at: Option<(u64, String)>,
+}
+
+impl VerifyReport {
+    pub fn is_intact(&self) -> bool {
+        self.broken_at.is_none()
+    }
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    retention: RetentionPolicy,
+}
+
+impl AuditLog {
+    pub fn new<P: Into<PathBuf>>(path: P, retention: RetentionPolicy) -> Self {
+        Self {
+            path: path.into(),
+            retention,
+        }
+    }
+
+    /// Builds the log from the `[audit]` config section.
+    /// Returns `None` when auditing was turned off with `enabled = false`.
+    pub fn from_config(config: &HashMap<String, toml::Value>) -> Option<Self> {
+        let section = config.get("audit");
+        let enabled = section
+            .and_then(|s| s.get("enabled"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        if !enabled {
+            return None;
+        }
+
+        let path = section
+            .and_then(|s| s.get("path"))
+            .and_then(|v| v.as_str())
+            .map(PathBuf::from)
+            .unwrap_or_else(Self::default_path);
+
+        Some(Self::new(path, RetentionPolicy::from_config(section)))
+    }
+
+    /// The default log location inside the drk data directory.
+    pub fn default_path() -> PathBuf {
+        crate::paths::data_dir().join("audit.log")
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads every entry currently in the log.
+    pub fn entries(&self) -> Result<Vec<AuditEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("Could not read audit log at {:?}", self.path))?;
+        parse_entries(&content)
+    }
+
+    /// Appends a new entry chained to the previous one, holding an exclusive
+    /// lock from reading the last entry until the append is done.
+    pub fn record(&self, kind: &str, subject: &str, detail: &str) -> Result<()> {
+        let mut file = self.open_locked()?;
+        let last = match last_line(&mut file)? {
+            Some(line) => Some(serde_json::from_str(&line).context("Malformed last audit entry")?),
+            None => None,
+        };
+        let entry = self.append(&mut file, last.as_ref(), kind, subject, detail)?;
+
+        if self.pruning_due(&mut file, &entry)? {
+            self.prune_locked(&mut file)?;
+        }
+        Ok(())
+    }
+
+    /// Drops what the retention policy doesn't keep. Returns how many went.
+    pub fn prune(&self) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let mut file = self.open_locked()?;
+        self.prune_locked(&mut file)
+    }
+
+    /// Walks the chain and reports the first entry that doesn't check out.
+    ///
+    /// The first entry must follow [`GENESIS_HASH`], or be the start recorded
+    /// by the latest `retention` entry when the head was pruned.
+    pub fn verify(&self) -> Result<VerifyReport> {
+        let entries = self.entries()?;
+        let report = |broken_at: Option<(u64, &str)>| VerifyReport {
+            entries: entries.len(),
+            broken_at: broken_at.map(|(seq, reason)| (seq, reason.to_string())),
+        };
+
+        if let Some(first) = entries.first() {
+            let anchored = if first.seq == 0 {
+                first.prev_hash == GENESIS_HASH
+            } else {
+                entries
+                    .iter()
+                    .rev()
+                    .find_map(pruned_start)
+                    .is_some_and(|(seq, prev)| seq == first.seq && prev == first.prev_hash)
+            };
+            if !anchored {
+                return Ok(report(Some((first.seq, "the start of the log is missing"))));
+            }
+        }
+
+        let mut expected_prev: Option<&str> = None;
+        for entry in &entries {
+            if let Some(prev) = expected_prev {
+                if entry.prev_hash != prev {
+                    return Ok(report(Some((entry.seq, "previous hash does not match"))));
+                }
+            }
+            if entry.compute_hash() != entry.hash {
+                return Ok(report(Some((entry.seq, "entry contents were modified"))));
+            }
+            expected_prev = Some(&entry.hash);
+        }
+
+        Ok(report(None))
+    }
+
+    fn open_locked(&self) -> Result<File> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Could not open audit log at {:?}", self.path))?;
+        file.lock()
+            .with_context(|| format!("Could not lock audit log at {:?}", self.path))?;
+        Ok(file)
+    }
+
+    /// Writes an entry chained to `last` at the end of the locked `file`.
+    fn append(
+        &self,
+        file: &mut File,
+        last: Option<&AuditEntry>,
+        kind: &str,
+        subject: &str,
+        detail: &str,
+    ) -> Result<AuditEntry> {
+        let (seq, prev_hash) = match last {
+            Some(last) => (last.seq + 1, last.hash.clone()),
+            None => (0, GENESIS_HASH.to_string()),
+        };
+        let mut entry = AuditEntry {
+            seq,
+            timestamp: now(),
+            user: current_user(),
+            kind: kind.to_string(),
+            subject: subject.to_string(),
+            detail: detail.to_string(),
+            prev_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(entry)
+    }
+
+    /// Whether the log has grown far enough past the retention policy to be
+    /// worth rewriting. Only reads the first line, so `record` stays cheap.
+    fn pruning_due(&self, file: &mut File, last: &AuditEntry) -> Result<bool> {
+        if self.retention.max_age_days.is_none() && self.retention.max_entries.is_none() {
+            return Ok(false);
+        }
+        let Some(line) = first_line(file)? else {
+            return Ok(false);
+        };
+        let first: AuditEntry =
+            serde_json::from_str(&line).context("Malformed first audit entry")?;
+
+        if let Some(max) = self.retention.max_entries {
+            let held = last.seq.saturating_sub(first.seq).saturating_add(1);
+            if held > (max + max.div_ceil(10)) as u64 {
+                return Ok(true);
+            }
+        }
+        if let Some(days) = self.retention.max_age_days {
+            if first.timestamp < cutoff(days).saturating_sub(DAY) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Drops what the retention policy doesn't keep from the locked `file`
+    /// and records where the log now starts.
+    fn prune_locked(&self, file: &mut File) -> Result<usize> {
+        if self.retention.max_age_days.is_none() && self.retention.max_entries.is_none() {
+            return Ok(0);
+        }
+        let mut content = String::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_string(&mut content)
+            .with_context(|| format!("Could not read audit log at {:?}", self.path))?;
+        let mut kept = parse_entries(&content)?;
+        let Some(last) = kept.last().cloned() else {
+            return Ok(0);
+        };
+        let total = kept.len();
+
+        if let Some(days) = self.retention.max_age_days {
+            let cutoff = cutoff(days);
+            kept.retain(|e| e.timestamp >= cutoff);
+        }
+        if let Some(max) = self.retention.max_entries {
+            if kept.len() > max {
+                // Leave room for the retention entry
+                kept.drain(..kept.len() - max.saturating_sub(1));
+            }
+        }
+
+        let dropped = total - kept.len();
+        if dropped == 0 {
+            return Ok(0);
+        }
+
+        let mut content = String::new();
+        for entry in &kept {
+            content.push_str(&serde_json::to_string(entry)?);
+            content.push('\n');
+        }
+        // Appends go to the end, which is the start once it's emptied
+        file.set_len(0)
+            .and_then(|_| file.write_all(content.as_bytes()))
+            .with_context(|| format!("Could not rewrite audit log at {:?}", self.path))?;
+
+        // With everything expired, the retention entry itself starts the log
+        let (seq, prev) = match kept.first() {
+            Some(first) => (first.seq, first.prev_hash.clone()),
+            None => (last.seq + 1, last.hash.clone()),
+        };
+        let detail = format!(
+            "starts at #{} after {}; dropped {} entries",
+            seq, prev, dropped
+        );
+        self.append(file, Some(&last), "retention", "audit", &detail)?;
+        Ok(dropped)
+    }
+}
+
+/// The start a `retention` entry recorded: the first kept seq and its `prev_hash`.
+fn pruned_start(entry: &AuditEntry) -> Option<(u64, &str)> {
+    if entry.kind != "retention" {
+        return None;
+    }
+    let (seq, rest) = entry
+        .detail
+        .strip_prefix("starts at #")?
+        .split_once(" after ")?;
+    let prev = rest.split(';').next()?;
+    Some((seq.parse().ok()?, prev))
+}
+
+/// Oldest timestamp a log keeping `days` of history holds on to.
+fn cutoff(days: u64) -> u64 {
+    now().saturating_sub(days.saturating_mul(DAY))
+}
+
+fn parse_entries(content: &str) -> Result<Vec<AuditEntry>> {
+    content
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Malformed audit entry on line {}", i + 1))
+        })
+        .collect()
+}
+
+/// The first non-empty line of `file`.
+fn first_line(file: &mut File) -> Result<Option<String>> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if !line.trim().is_empty() {
+            return Ok(Some(line.trim_end().to_string()));
+        }
+    }
+}
+
+/// The last non-empty line of `file`, read backwards from the end so
+/// appending stays cheap however long the log gets.
+fn last_line(file: &mut File) -> Result<Option<String>> {
+    const CHUNK: u64 = 4096;
+    let mut start = file.seek(SeekFrom::End(0))?;
+    let mut tail: Vec<u8> = Vec::new();
+    loop {
+        let end = tail
+            .iter()
+            .rposition(|b| !b.is_ascii_whitespace())
+            .map_or(0, |i| i + 1);
+        let line_start = tail[..end].iter().rposition(|b| *b == b'\n').map(|i| i + 1);
+        if line_start.is_some() || start == 0 {
+            let line = &tail[line_start.unwrap_or(0)..end];
+            if line.is_empty() {
+                return Ok(None);
+            }
+            return Ok(Some(String::from_utf8_lossy(line).into_owned()));
+        }
+        let from = start.saturating_sub(CHUNK);
+        let mut chunk = vec![0; (start - from) as usize];
+        file.seek(SeekFrom::Start(from))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&tail);
+        tail = chunk;
+        start = from;
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(dir: &Path, max_entries: usize) -> AuditLog {
+        let retention = RetentionPolicy {
+            max_entries: Some(max_entries),
+            max_age_days: None,
+        };
+        AuditLog::new(dir.join("audit.log"), retention)
+    }
+
+    fn record(log: &AuditLog, count: usize) {
+        for i in 0..count {
+            log.record("command", "build", &i.to_string()).unwrap();
+        }
+    }
+
+    #[test]
+    fn record_prunes_once_past_the_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = log(dir.path(), 10);
+
+        record(&log, 11);
+        assert_eq!(log.entries().unwrap().len(), 11);
+
+        record(&log, 1);
+        let entries = log.entries().unwrap();
+        assert_eq!(entries.len(), 10);
+        assert_eq!(entries[0].seq, 3);
+        assert_eq!(entries[9].kind, "retention");
+        assert!(log.verify().unwrap().is_intact());
+    }
+
+    #[test]
+    fn prune_keeps_the_policy_and_the_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = log(dir.path(), 10);
+        record(&log, 11);
+
+        assert_eq!(log.prune().unwrap(), 2);
+        assert_eq!(log.prune().unwrap(), 0);
+        assert_eq!(log.entries().unwrap().len(), 10);
+        assert!(log.verify().unwrap().is_intact());
+    }
+
+    #[test]
+    fn verify_catches_a_cut_off_head() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = log(dir.path(), 10);
+        let cut = |log: &AuditLog| {
+            let content = fs::read_to_string(log.path()).unwrap();
+            let (_, rest) = content.split_once('\n').unwrap();
+            fs::write(log.path(), rest).unwrap();
+        };
+
+        record(&log, 3);
+        cut(&log);
+        assert_eq!(log.verify().unwrap().broken_at.unwrap().0, 1);
+
+        fs::remove_file(log.path()).unwrap();
+        record(&log, 12);
+        cut(&log);
+        let (seq, reason) = log.verify().unwrap().broken_at.unwrap();
+        assert_eq!(seq, 4);
+        assert_eq!(reason, "the start of the log is missing");
+    }
+
+    #[test]
+    fn huge_max_age_keeps_everything() {
+        assert_eq!(cutoff(u64::MAX), 0);
+    }
+}
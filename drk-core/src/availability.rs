@@ -0,0 +1,52 @@
+//! Whether a plugin command can run here, judged from the
+//! [`Requirement`]s it declares.
+//!
+//! The host checks these while building the CLI, so a command whose tool
+//! isn't installed is hidden and explains itself instead of failing halfway
+//! through the plugin.
+
+use crate::paths;
+use drk_api::{tools, Requirement};
+use std::path::Path;
+
+/// The first requirement a command doesn't meet, and what to do about it.
+#[derive(Debug, Clone)]
+pub struct Unavailable {
+    pub reason: String,
+    pub hint: String,
+}
+
+/// Checks `requires` against the environment, with project files looked up
+/// from `cwd`. `None` means the command is available.
+pub fn check(requires: &[Requirement], cwd: &Path) -> Option<Unavailable> {
+    requires.iter().find_map(|requirement| match requirement {
+        Requirement::Binary(name) if tools::which(name).is_none() => Some(Unavailable {
+            reason: format!("needs `{}`, which isn't on PATH", name),
+            hint: tools::install_hint(name),
+        }),
+        Requirement::Os(os) if os != std::env::consts::OS => Some(Unavailable {
+            reason: format!("only runs on {}", os),
+            hint: format!("This machine runs {}", std::env::consts::OS),
+        }),
+        Requirement::ProjectFile(file) if !in_project(file, cwd) => Some(Unavailable {
+            reason: format!("needs a project with a {}", file),
+            hint: format!("Run it from a directory containing {}", file),
+        }),
+        _ => None,
+    })
+}
+
+/// Whether `file` exists in `cwd` or one of its ancestors, stopping at the
+/// project root (or at `cwd` itself outside a project).
+fn in_project(file: &str, cwd: &Path) -> bool {
+    let root = paths::project_root(cwd).unwrap_or_else(|| cwd.to_path_buf());
+    for dir in cwd.ancestors() {
+        if dir.join(file).exists() {
+            return true;
+        }
+        if dir == root {
+            break;
+        }
+    }
+    false
+}
@@ -0,0 +1,87 @@
+//! Command files for `drk batch`: one drk invocation per line.
+//!
+//! ```text
+//! # set up a new project
+//! nix --template rust
+//! greet --name "the team"
+//! ```
+//!
+//! Blank lines and `#` comments are skipped, and a leading `drk` is optional.
+//! Words are split like a shell would, so quotes keep spaces in an argument.
+
+use anyhow::{Context as _, Result};
+
+#[derive(Debug, Clone)]
+pub struct BatchLine {
+    /// 1-based, counting blank and comment lines
+    pub number: usize,
+    /// The arguments after `drk`
+    pub words: Vec<String>,
+}
+
+impl BatchLine {
+    /// The invocation as it would be typed, quoted where needed.
+    pub fn command_line(&self) -> String {
+        shell_words::join(&self.words)
+    }
+}
+
+/// The commands in a batch file, in order.
+pub fn parse(text: &str) -> Result<Vec<BatchLine>> {
+    let mut lines = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words =
+            shell_words::split(line).with_context(|| format!("Line {}: {}", index + 1, line))?;
+        if words.first().is_some_and(|w| w == "drk") {
+            words.remove(0);
+        }
+        if words.is_empty() {
+            continue;
+        }
+        lines.push(BatchLine {
+            number: index + 1,
+            words,
+        });
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_keep_their_numbers_and_quoting() {
+        let lines = parse(
+            "# set up a new project\n\
+             nix --template rust\n\
+             \n\
+             drk greet --name \"the team\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].number, 2);
+        assert_eq!(lines[0].words, ["nix", "--template", "rust"]);
+        assert_eq!(lines[1].number, 4);
+        assert_eq!(lines[1].words, ["greet", "--name", "the team"]);
+        assert_eq!(lines[1].command_line(), "greet --name 'the team'");
+    }
+
+    #[test]
+    fn a_lone_drk_is_skipped() {
+        let lines = parse("  drk  \n  # indented comment\nstatus").unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].number, 3);
+    }
+
+    #[test]
+    fn unbalanced_quotes_name_the_line() {
+        let err = parse("status\ngreet --name 'the team").unwrap_err();
+        assert_eq!(err.to_string(), "Line 2: greet --name 'the team");
+    }
+}
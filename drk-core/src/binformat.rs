@@ -0,0 +1,203 @@
+//! Checks a library's binary format and architecture before it is opened.
+//!
+//! The dynamic loader's own errors for foreign binaries ("invalid ELF header",
+//! "wrong ELF class") don't say what the file actually is. Reading the header
+//! first lets drk say "this is a library for x86_64 (ELF), but drk is running
+//! on aarch64 macos, which needs Mach-O".
+
+use anyhow::{bail, Result};
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Elf,
+    Pe,
+    MachO,
+}
+
+impl Format {
+    /// The format shared libraries have on the OS drk was built for.
+    pub fn native() -> Self {
+        if cfg!(target_os = "windows") {
+            Format::Pe
+        } else if cfg!(target_vendor = "apple") {
+            Format::MachO
+        } else {
+            Format::Elf
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Elf => "ELF",
+            Format::Pe => "PE",
+            Format::MachO => "Mach-O",
+        })
+    }
+}
+
+/// What a library's header says about it. Architectures use Rust's names
+/// (`std::env::consts::ARCH`), or `unknown` for ones drk doesn't know.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryInfo {
+    pub format: Format,
+    /// Several for macOS universal binaries
+    pub archs: Vec<&'static str>,
+}
+
+/// Reads the header of `path`. `Ok(None)` means it isn't a recognized
+/// library format at all.
+pub fn inspect(path: &Path) -> Result<Option<BinaryInfo>> {
+    let mut header = Vec::with_capacity(4096);
+    std::fs::File::open(path)?
+        .take(4096)
+        .read_to_end(&mut header)?;
+    Ok(parse(&header))
+}
+
+/// Fails with a precise message if `path` can't be a library for this host.
+/// Files that can't be read are left for the loader to report.
+pub fn check(path: &Path) -> Result<()> {
+    let Ok(info) = inspect(path) else {
+        return Ok(());
+    };
+    let host_arch = std::env::consts::ARCH;
+    let host = format!("{} {}", host_arch, std::env::consts::OS);
+
+    let Some(info) = info else {
+        bail!(
+            "{:?} is not a shared library (unrecognized header); expected a {} library for {}",
+            path,
+            Format::native(),
+            host
+        );
+    };
+    let archs = info.archs.join("/");
+    if info.format != Format::native() {
+        bail!(
+            "{:?} is a library for {} ({}), but drk is running on {}, which needs {}",
+            path,
+            archs,
+            info.format,
+            host,
+            Format::native()
+        );
+    }
+    // Architectures missing from the tables above are left to the loader
+    if !info.archs.contains(&host_arch) && !info.archs.contains(&"unknown") {
+        bail!(
+            "{:?} is a library for {} ({}), but drk is running on {}",
+            path,
+            archs,
+            info.format,
+            host
+        );
+    }
+    Ok(())
+}
+
+fn parse(header: &[u8]) -> Option<BinaryInfo> {
+    match header {
+        [0x7f, b'E', b'L', b'F', ..] => parse_elf(header),
+        [b'M', b'Z', ..] => parse_pe(header),
+        [0xfe, 0xed, 0xfa, 0xce | 0xcf, ..] => Some(macho(u32_at(header, 4, true)?)),
+        [0xce | 0xcf, 0xfa, 0xed, 0xfe, ..] => Some(macho(u32_at(header, 4, false)?)),
+        [0xca, 0xfe, 0xba, 0xbe, ..] => parse_fat(header),
+        _ => None,
+    }
+}
+
+fn parse_elf(header: &[u8]) -> Option<BinaryInfo> {
+    let is_64 = *header.get(4)? == 2;
+    let big_endian = *header.get(5)? == 2;
+    let machine = u16_at(header, 18, big_endian)?;
+    let arch = match machine {
+        3 => "x86",
+        62 => "x86_64",
+        40 => "arm",
+        183 => "aarch64",
+        243 if is_64 => "riscv64",
+        243 => "riscv32",
+        21 => "powerpc64",
+        22 => "s390x",
+        258 => "loongarch64",
+        _ => "unknown",
+    };
+    Some(BinaryInfo {
+        format: Format::Elf,
+        archs: vec![arch],
+    })
+}
+
+fn parse_pe(header: &[u8]) -> Option<BinaryInfo> {
+    let pe_offset = u32_at(header, 0x3c, false)? as usize;
+    if header.get(pe_offset..pe_offset + 4)? != b"PE\0\0" {
+        return None;
+    }
+    let arch = match u16_at(header, pe_offset + 4, false)? {
+        0x14c => "x86",
+        0x8664 => "x86_64",
+        0xaa64 => "aarch64",
+        0x1c0 | 0x1c4 => "arm",
+        _ => "unknown",
+    };
+    Some(BinaryInfo {
+        format: Format::Pe,
+        archs: vec![arch],
+    })
+}
+
+/// Universal binaries: a big-endian table of the architectures inside.
+fn parse_fat(header: &[u8]) -> Option<BinaryInfo> {
+    let count = u32_at(header, 4, true)? as usize;
+    // Java class files share the magic; they carry a version here, not a count
+    if count == 0 || count > 20 {
+        return None;
+    }
+    let archs = (0..count)
+        .map(|i| u32_at(header, 8 + i * 20, true).map(macho_arch))
+        .collect::<Option<Vec<_>>>()?;
+    Some(BinaryInfo {
+        format: Format::MachO,
+        archs,
+    })
+}
+
+fn macho(cputype: u32) -> BinaryInfo {
+    BinaryInfo {
+        format: Format::MachO,
+        archs: vec![macho_arch(cputype)],
+    }
+}
+
+fn macho_arch(cputype: u32) -> &'static str {
+    match cputype {
+        7 => "x86",
+        0x0100_0007 => "x86_64",
+        12 => "arm",
+        0x0100_000c => "aarch64",
+        _ => "unknown",
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize, big_endian: bool) -> Option<u16> {
+    let raw: [u8; 2] = bytes.get(offset..offset + 2)?.try_into().ok()?;
+    Some(if big_endian {
+        u16::from_be_bytes(raw)
+    } else {
+        u16::from_le_bytes(raw)
+    })
+}
+
+fn u32_at(bytes: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let raw: [u8; 4] = bytes.get(offset..offset + 4)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(raw)
+    } else {
+        u32::from_le_bytes(raw)
+    })
+}
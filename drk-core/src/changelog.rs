@@ -0,0 +1,92 @@
+//! Picking the relevant part of a plugin changelog.
+//!
+//! Changelogs are Markdown with one heading per release, newest first, in the
+//! usual keep-a-changelog shape:
+//!
+//! ```markdown
+//! ## [0.3.0] - 2024-05-01
+//! - Added `drk nix update`
+//!
+//! ## 0.2.0
+//! - Fixed template lookup
+//! ```
+
+use std::cmp::Ordering;
+
+/// The release sections newer than `from` and no newer than `to`, in the
+/// order they appear. With no `to`, everything newer than `from` is kept.
+///
+/// # Example
+/// ```
+/// # use drk_core::changelog::sections_between;
+/// let log = "# Changelog\n\n## 0.3.0\n- c\n\n## 0.2.0\n- b\n\n## 0.1.0\n- a\n";
+/// let picked = sections_between(log, "0.1.0", Some("0.2.0"));
+/// assert!(picked.contains("- b"));
+/// assert!(!picked.contains("- c") && !picked.contains("- a"));
+/// ```
+pub fn sections_between(changelog: &str, from: &str, to: Option<&str>) -> String {
+    let mut out = Vec::new();
+    // Level of the release heading we're inside, and whether we keep it
+    let mut current: Option<(usize, bool)> = None;
+
+    for line in changelog.lines() {
+        if let Some((level, title)) = heading(line) {
+            if let Some(version) = release_version(title) {
+                let keep = compare(version, from) == Ordering::Greater
+                    && to.is_none_or(|to| compare(version, to) != Ordering::Greater);
+                current = Some((level, keep));
+            } else if current.is_some_and(|(open, _)| level <= open) {
+                current = None;
+            }
+        }
+
+        if let Some((_, true)) = current {
+            out.push(line);
+        }
+    }
+
+    out.join("\n").trim().to_string()
+}
+
+/// Compares dotted version strings numerically, ignoring a leading `v` and
+/// any pre-release or build suffix.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let parse = |v: &str| -> Vec<u64> {
+        v.trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    let (a, b) = (parse(a), parse(b));
+    let len = a.len().max(b.len());
+    (0..len)
+        .map(|i| {
+            a.get(i)
+                .copied()
+                .unwrap_or(0)
+                .cmp(&b.get(i).copied().unwrap_or(0))
+        })
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || !line[level..].starts_with(' ') {
+        return None;
+    }
+    Some((level, line[level..].trim()))
+}
+
+/// `[1.2.0] - 2024-01-01`, `v1.2.0` and `1.2.0` all name release 1.2.0.
+fn release_version(title: &str) -> Option<&str> {
+    let first = title.split_whitespace().next()?;
+    let version = first.trim_matches(|c| c == '[' || c == ']');
+    let digits = version.trim_start_matches('v');
+    digits
+        .starts_with(|c: char| c.is_ascii_digit())
+        .then_some(version)
+}
@@ -0,0 +1,79 @@
+//! Cleanup that must happen even when drk doesn't finish normally.
+//!
+//! Code that leaves something behind while it works (a staging directory, a
+//! half-copied file) registers a handler and keeps the returned guard. If the
+//! work completes, dropping the guard forgets the handler. If the process
+//! instead dies through [`exit`] (Ctrl-C) or a panic, every handler still
+//! registered runs first.
+//!
+//! `std::process::exit` skips destructors, so exits that may interrupt work in
+//! progress should go through [`exit`].
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+type Handler = Box<dyn FnOnce() + Send>;
+
+static HANDLERS: Mutex<Vec<(u64, Handler)>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Keeps a handler registered. Dropping it unregisters without running.
+#[must_use = "the handler is unregistered as soon as the guard is dropped"]
+pub struct CleanupGuard {
+    id: u64,
+}
+
+impl Drop for CleanupGuard {
+    fn drop(&mut self) {
+        handlers().retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// Registers `handler` to run if drk exits abnormally while the guard lives.
+pub fn register(handler: impl FnOnce() + Send + 'static) -> CleanupGuard {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    handlers().push((id, Box::new(handler)));
+    CleanupGuard { id }
+}
+
+/// Removes `path` (file or directory) on abnormal exit.
+pub fn remove_on_exit(path: impl Into<PathBuf>) -> CleanupGuard {
+    let path = path.into();
+    register(move || {
+        let _ = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+    })
+}
+
+/// Runs every registered handler, newest first. Each runs at most once.
+pub fn run_all() {
+    // Take them out first so a handler that registers or panics can't deadlock
+    let pending = std::mem::take(&mut *handlers());
+    for (_, handler) in pending.into_iter().rev() {
+        handler();
+    }
+}
+
+/// Runs the handlers, then exits with `code`.
+pub fn exit(code: i32) -> ! {
+    run_all();
+    std::process::exit(code)
+}
+
+/// Makes panics run the handlers before the usual panic report.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        run_all();
+        previous(info);
+    }));
+}
+
+fn handlers() -> std::sync::MutexGuard<'static, Vec<(u64, Handler)>> {
+    // A panicking handler must not disable cleanup for everyone else
+    HANDLERS.lock().unwrap_or_else(|e| e.into_inner())
+}
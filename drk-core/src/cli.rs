@@ -0,0 +1,333 @@
+//! The clap commands drk builds from plugin command schemas.
+//!
+//! drk parses the command line with exactly these, so a plugin's tests can
+//! check that its [`PluginCommand`]s produce the CLI it expects without
+//! running the `drk` binary.
+
+use clap::builder::PossibleValuesParser;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use drk_api::time;
+use drk_api::{ArgType, PluginCommand, VALUES_SEPARATOR};
+use std::collections::HashMap;
+
+/// A `drk` command with `commands` as its subcommands, for parsing test
+/// command lines. Built-in commands and global flags aren't included.
+///
+/// # Example
+/// ```
+/// # use drk_api::{ArgType, CommandArg, CommandMatches, PluginCommand};
+/// # use std::collections::HashMap;
+/// let arg = |name: &str, required, arg_type| CommandArg {
+///     name: name.to_string(),
+///     description: String::new(),
+///     required,
+///     arg_type,
+///     ..Default::default()
+/// };
+/// let scaffold = PluginCommand {
+///     name: "scaffold".to_string(),
+///     description: "Copy a template".to_string(),
+///     args: vec![
+///         arg("template", true, ArgType::Positional),
+///         arg("dest", true, ArgType::Positional),
+///         arg("extras", false, ArgType::Variadic),
+///         arg("verbose", false, ArgType::Count),
+///         arg("color", false, ArgType::OptionalValue),
+///         CommandArg { negatable: true, ..arg("cache", false, ArgType::Boolean) },
+///         arg("license", false, ArgType::Choice(vec!["mit".into(), "apache-2.0".into()])),
+///         CommandArg { default: Some("main".into()), ..arg("branch", false, ArgType::String) },
+///         CommandArg { multiple: true, ..arg("port", false, ArgType::Integer) },
+///     ],
+///     ..Default::default()
+/// };
+/// let cli = drk_core::cli::build(&[scaffold.clone()]);
+///
+/// let argv = ["drk", "scaffold", "-vv", "rust", "app", "ci", "docker", "--verbose", "--color"];
+/// let matches = cli.clone().try_get_matches_from(argv).unwrap();
+/// let (_, sub) = matches.subcommand().unwrap();
+/// let (mut path, mut args) = (vec!["scaffold".to_string()], HashMap::new());
+/// drk_core::cli::collect_args(&scaffold, sub, &mut path, &mut args);
+/// assert_eq!(args["template"], "rust");
+/// assert_eq!(args["dest"], "app");
+/// assert_eq!(args["verbose"], "3");
+/// assert_eq!(args["color"], "");
+/// assert_eq!(args["branch"], "main");
+///
+/// let matches = CommandMatches { command_name: "scaffold".to_string(), path, args };
+/// assert_eq!(matches.values("extras"), ["ci", "docker"]);
+///
+/// let argv = ["drk", "scaffold", "rust", "app", "--color=never", "--cache", "--no-cache", "--license", "mit",
+///     "--port", "80", "--port", "443"];
+/// let matches = cli.clone().try_get_matches_from(argv).unwrap();
+/// let (_, sub) = matches.subcommand().unwrap();
+/// let mut args = HashMap::new();
+/// drk_core::cli::collect_args(&scaffold, sub, &mut vec![], &mut args);
+/// assert_eq!(args["color"], "never");
+/// assert_eq!(args["cache"], "false");
+/// assert_eq!(args["license"], "mit");
+/// assert_eq!(args["port"], "80\n443");
+/// assert!(cli.clone().try_get_matches_from(["drk", "scaffold", "rust", "app", "--license", "gpl"]).is_err());
+///
+/// assert!(cli.try_get_matches_from(["drk", "scaffold", "rust"]).is_err());
+/// ```
+pub fn build(commands: &[PluginCommand]) -> Command {
+    let mut leaked = Vec::new();
+    commands
+        .iter()
+        .fold(Command::new("drk").subcommand_required(true), |app, cmd| {
+            app.subcommand(subcommand(cmd, &mut leaked))
+        })
+}
+
+/// Builds the clap command for one plugin command schema, nested commands
+/// included. Strings are leaked for the `'static` lifetime clap wants and
+/// listed in `leaked` for `--debug-cli`.
+pub fn subcommand(cmd: &PluginCommand, leaked: &mut Vec<&'static str>) -> Command {
+    // Leak strings to get 'static lifetime for clap
+    let cmd_name: &'static str = Box::leak(cmd.name.clone().into_boxed_str());
+    let cmd_desc: &'static str = Box::leak(cmd.description.clone().into_boxed_str());
+    leaked.extend([cmd_name, cmd_desc]);
+
+    // Build a clap subcommand from the plugin's command schema
+    let mut subcommand = Command::new(cmd_name).about(cmd_desc);
+    for alias in &cmd.aliases {
+        let alias: &'static str = Box::leak(alias.clone().into_boxed_str());
+        leaked.push(alias);
+        subcommand = subcommand.visible_alias(alias);
+    }
+
+    // Add arguments based on the schema; positionals take the next index
+    let mut index = 0;
+    let mut shorts = Vec::new();
+    for arg in &cmd.args {
+        let arg_name: &'static str = Box::leak(arg.name.clone().into_boxed_str());
+        let arg_desc: &'static str = Box::leak(arg.description.clone().into_boxed_str());
+        leaked.extend([arg_name, arg_desc]);
+
+        let mut clap_arg = match &arg.arg_type {
+            ArgType::Positional => {
+                index += 1;
+                Arg::new(arg_name)
+                    .help(arg_desc)
+                    .required(arg.required)
+                    .index(index)
+            }
+            ArgType::Variadic => {
+                index += 1;
+                Arg::new(arg_name)
+                    .help(arg_desc)
+                    .required(arg.required)
+                    .index(index)
+                    .num_args(1..)
+                    .action(ArgAction::Append)
+            }
+            ArgType::String => Arg::new(arg_name)
+                .long(arg_name)
+                .help(arg_desc)
+                .required(arg.required)
+                .action(ArgAction::Set),
+            ArgType::Choice(choices) => {
+                let choices: Vec<&'static str> = choices
+                    .iter()
+                    .map(|c| &*Box::leak(c.clone().into_boxed_str()))
+                    .collect();
+                leaked.extend(&choices);
+                Arg::new(arg_name)
+                    .long(arg_name)
+                    .help(arg_desc)
+                    .required(arg.required)
+                    .value_parser(PossibleValuesParser::new(choices))
+                    .action(ArgAction::Set)
+            }
+            ArgType::Integer => Arg::new(arg_name)
+                .long(arg_name)
+                .help(arg_desc)
+                .required(arg.required)
+                .value_parser(clap::value_parser!(i64))
+                .action(ArgAction::Set),
+            // Checked here, and passed on in their canonical form
+            ArgType::Duration => Arg::new(arg_name)
+                .long(arg_name)
+                .help(arg_desc)
+                .required(arg.required)
+                .value_parser(|text: &str| canonical(&ArgType::Duration, text))
+                .action(ArgAction::Set),
+            ArgType::DateTime => Arg::new(arg_name)
+                .long(arg_name)
+                .help(arg_desc)
+                .required(arg.required)
+                .value_parser(|text: &str| canonical(&ArgType::DateTime, text))
+                .action(ArgAction::Set),
+            ArgType::Float => Arg::new(arg_name)
+                .long(arg_name)
+                .help(arg_desc)
+                .required(arg.required)
+                .value_parser(clap::value_parser!(f64))
+                .action(ArgAction::Set),
+            ArgType::Boolean => {
+                let mut clap_arg = Arg::new(arg_name)
+                    .long(arg_name)
+                    .help(arg_desc)
+                    .required(false)
+                    .action(ArgAction::SetTrue);
+                if let Some(negated) = arg.negated_name() {
+                    let negated: &'static str = Box::leak(negated.into_boxed_str());
+                    let negated_desc: &'static str =
+                        Box::leak(format!("Turn off --{}", arg.name).into_boxed_str());
+                    leaked.extend([negated, negated_desc]);
+                    clap_arg = clap_arg.overrides_with(negated);
+                    subcommand = subcommand.arg(
+                        Arg::new(negated)
+                            .long(negated)
+                            .help(negated_desc)
+                            .action(ArgAction::SetTrue)
+                            .overrides_with(arg_name),
+                    );
+                }
+                clap_arg
+            }
+            ArgType::OptionalValue => Arg::new(arg_name)
+                .long(arg_name)
+                .help(arg_desc)
+                .required(arg.required)
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("")
+                .action(ArgAction::Set),
+            ArgType::Count => {
+                let mut clap_arg = Arg::new(arg_name)
+                    .long(arg_name)
+                    .help(arg_desc)
+                    .required(false)
+                    .action(ArgAction::Count);
+                if let Some(short) = arg.count_short(&shorts) {
+                    shorts.push(short);
+                    clap_arg = clap_arg.short(short);
+                }
+                clap_arg
+            }
+        };
+        if arg.multiple {
+            clap_arg = clap_arg.action(ArgAction::Append);
+        }
+        if let Some(default) = &arg.default {
+            let default: &'static str = Box::leak(default.clone().into_boxed_str());
+            leaked.push(default);
+            clap_arg = clap_arg.default_value(default);
+        }
+        if let Some(env) = &arg.env {
+            let env: &'static str = Box::leak(env.clone().into_boxed_str());
+            leaked.push(env);
+            clap_arg = clap_arg.env(env);
+        }
+
+        subcommand = subcommand.arg(clap_arg);
+    }
+
+    for nested in &cmd.subcommands {
+        subcommand = subcommand.subcommand(self::subcommand(nested, leaked));
+    }
+    if !cmd.subcommands.is_empty() {
+        subcommand = subcommand
+            .subcommand_required(true)
+            .arg_required_else_help(true);
+    }
+    subcommand
+}
+
+/// The form drk passes a value of type `arg_type` on in: the same text,
+/// except for durations and times, which are checked and normalized.
+pub fn canonical(arg_type: &ArgType, text: &str) -> Result<String, String> {
+    match arg_type {
+        ArgType::Duration => time::parse_duration(text)
+            .map(time::format_duration)
+            .map_err(|e| e.to_string()),
+        ArgType::DateTime => time::parse_datetime(text)
+            .map(|datetime| time::format_datetime(&datetime))
+            .map_err(|e| e.to_string()),
+        _ => Ok(text.to_string()),
+    }
+}
+
+/// Reads the arguments `schema` declares from `matches`, then those of the
+/// nested command invoked under it, extending `path` down to that command.
+/// Returns the schema of the command at the end of the path.
+pub fn collect_args<'a>(
+    schema: &'a PluginCommand,
+    matches: &ArgMatches,
+    path: &mut Vec<String>,
+    args: &mut HashMap<String, String>,
+) -> &'a PluginCommand {
+    for arg_def in &schema.args {
+        if arg_def.takes_many() {
+            let values: Option<Vec<String>> = match arg_def.arg_type {
+                ArgType::Integer => matches
+                    .get_many::<i64>(&arg_def.name)
+                    .map(|values| values.map(i64::to_string).collect()),
+                ArgType::Float => matches
+                    .get_many::<f64>(&arg_def.name)
+                    .map(|values| values.map(f64::to_string).collect()),
+                _ => matches
+                    .get_many::<String>(&arg_def.name)
+                    .map(|values| values.cloned().collect()),
+            };
+            if let Some(values) = values {
+                args.insert(
+                    arg_def.name.clone(),
+                    values.join(&VALUES_SEPARATOR.to_string()),
+                );
+            }
+            continue;
+        }
+        match arg_def.arg_type {
+            ArgType::String
+            | ArgType::Positional
+            | ArgType::Variadic
+            | ArgType::OptionalValue
+            | ArgType::Choice(_)
+            | ArgType::Duration
+            | ArgType::DateTime => {
+                if let Some(value) = matches.get_one::<String>(&arg_def.name) {
+                    args.insert(arg_def.name.clone(), value.clone());
+                }
+            }
+            ArgType::Integer => {
+                if let Some(value) = matches.get_one::<i64>(&arg_def.name) {
+                    args.insert(arg_def.name.clone(), value.to_string());
+                }
+            }
+            ArgType::Float => {
+                if let Some(value) = matches.get_one::<f64>(&arg_def.name) {
+                    args.insert(arg_def.name.clone(), value.to_string());
+                }
+            }
+            ArgType::Boolean => {
+                // Of the flag and its --no- pair only the last one given is set
+                if matches.get_flag(&arg_def.name) {
+                    args.insert(arg_def.name.clone(), "true".to_string());
+                } else if let Some(negated) = arg_def.negated_name() {
+                    if matches.get_flag(&negated) {
+                        args.insert(arg_def.name.clone(), "false".to_string());
+                    }
+                }
+            }
+            ArgType::Count => {
+                let count = matches.get_count(&arg_def.name);
+                if count > 0 {
+                    args.insert(arg_def.name.clone(), count.to_string());
+                }
+            }
+        }
+    }
+
+    let nested = matches
+        .subcommand()
+        .and_then(|(name, sub)| Some((schema.subcommand(name)?, sub)));
+    match nested {
+        Some((nested, sub)) => {
+            path.push(nested.name.clone());
+            collect_args(nested, sub, path, args)
+        }
+        None => schema,
+    }
+}
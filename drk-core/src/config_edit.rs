@@ -0,0 +1,65 @@
+//! Editing config.toml in place.
+//!
+//! drk keeps the config as plain `toml` values, which forget comments and
+//! key order. Saving goes through the file's `toml_edit` document instead
+//! and rewrites only the values that changed, so a hand-written config
+//! keeps its comments, ordering and formatting.
+
+use anyhow::{Context as _, Result};
+use toml_edit::Item;
+
+/// Brings `document`, parsed from a file holding `before`, in line with
+/// `after`: changed values are replaced, new ones appended and removed ones
+/// dropped. Tables are updated key by key, so the untouched parts of a
+/// section keep their comments too.
+pub fn update(
+    document: &mut toml_edit::Table,
+    before: &toml::Table,
+    after: &toml::Table,
+) -> Result<()> {
+    let removed: Vec<String> = document
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !after.contains_key(key))
+        .collect();
+    for key in removed {
+        document.remove(&key);
+    }
+
+    for (key, value) in after {
+        let old = before.get(key);
+        if old == Some(value) {
+            continue;
+        }
+        match (old, value, document.get_mut(key)) {
+            (Some(toml::Value::Table(old)), toml::Value::Table(new), Some(Item::Table(table))) => {
+                update(table, old, new)?;
+            }
+            _ => {
+                document.insert(key, to_item(value)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `value` as a `toml_edit` item: a `[table]` for a table, so it reads like
+/// the rest of the file, and an inline value otherwise.
+fn to_item(value: &toml::Value) -> Result<Item> {
+    match value {
+        toml::Value::Table(values) => {
+            let mut table = toml_edit::Table::new();
+            for (key, value) in values {
+                table.insert(key, to_item(value)?);
+            }
+            Ok(Item::Table(table))
+        }
+        value => {
+            let value: toml_edit::Value = value
+                .to_string()
+                .parse()
+                .context("Could not convert a config value")?;
+            Ok(Item::Value(value))
+        }
+    }
+}
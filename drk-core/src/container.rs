@@ -0,0 +1,105 @@
+//! Running plugin commands inside a container.
+//!
+//! The host re-invokes drk inside the image declared in the plugin manifest,
+//! with the current directory mounted as the workspace. The inner drk sees
+//! `DRK_IN_CONTAINER` and runs the command in-process as usual.
+
+use crate::manifest::ContainerSpec;
+use anyhow::{bail, Context as _, Result};
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Set inside the container so the inner drk doesn't try to containerize again.
+pub const INSIDE_ENV: &str = "DRK_IN_CONTAINER";
+
+/// Runtimes probed when nothing is configured, in order of preference.
+const KNOWN_RUNTIMES: &[&str] = &["podman", "docker"];
+
+pub fn is_inside() -> bool {
+    std::env::var_os(INSIDE_ENV).is_some()
+}
+
+/// Picks the container runtime: the manifest's choice, then `[container] runtime`
+/// in config, then whichever known runtime is on PATH.
+pub fn resolve_runtime(
+    spec: &ContainerSpec,
+    config: &HashMap<String, toml::Value>,
+) -> Result<String> {
+    let configured = spec.runtime.clone().or_else(|| {
+        config
+            .get("container")
+            .and_then(|c| c.get("runtime"))
+            .and_then(|v| v.as_str())
+            .map(String::from)
+    });
+
+    if let Some(runtime) = configured {
+        if find_in_path(&runtime).is_none() {
+            bail!("Container runtime '{}' was not found on PATH", runtime);
+        }
+        return Ok(runtime);
+    }
+
+    KNOWN_RUNTIMES
+        .iter()
+        .find(|rt| find_in_path(rt).is_some())
+        .map(|rt| rt.to_string())
+        .context("No container runtime found. Install podman or docker")
+}
+
+/// Builds the `<runtime> run ...` invocation that replays `argv` inside the image.
+pub fn build_command(spec: &ContainerSpec, runtime: &str, argv: &[String]) -> Result<Command> {
+    let cwd = std::env::current_dir().context("Could not read the current directory")?;
+
+    let mut cmd = Command::new(runtime);
+    cmd.arg("run").arg("--rm").arg("-i");
+    if std::io::stdout().is_terminal() {
+        cmd.arg("-t");
+    }
+    cmd.arg("-v")
+        .arg(format!("{}:{}", cwd.display(), spec.workdir))
+        .arg("-w")
+        .arg(&spec.workdir)
+        .arg("-e")
+        .arg(format!("{}=1", INSIDE_ENV));
+
+    for mount in &spec.mounts {
+        cmd.arg("-v").arg(mount);
+    }
+    // `-e NAME` without a value makes the runtime copy it from our environment
+    for var in &spec.env {
+        if std::env::var_os(var).is_some() {
+            cmd.arg("-e").arg(var);
+        }
+    }
+
+    cmd.arg(&spec.image).arg(&spec.entrypoint).args(argv);
+    Ok(cmd)
+}
+
+/// Runs `argv` inside the container and returns its exit code.
+pub fn run(
+    spec: &ContainerSpec,
+    config: &HashMap<String, toml::Value>,
+    argv: &[String],
+) -> Result<i32> {
+    let runtime = resolve_runtime(spec, config)?;
+    let status = build_command(spec, &runtime, argv)?
+        .status()
+        .with_context(|| format!("Failed to start '{}'", runtime))?;
+    Ok(status.code().unwrap_or(1))
+}
+
+fn find_in_path(binary: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path).find_map(|dir| {
+        let candidate = dir.join(binary);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        let exe = candidate.with_extension("exe");
+        exe.is_file().then_some(exe)
+    })
+}
@@ -0,0 +1,186 @@
+//! Guards for events emitted by plugins.
+//!
+//! Plugins emit events through `ctx.event_sender`; the manager queues them and
+//! delivers them after the current dispatch. These limits keep a misbehaving
+//! plugin from flooding the queue or pinning huge payloads in memory.
+//!
+//! ```toml
+//! [events]
+//! max_payload_bytes = 1048576  # larger Custom payloads are dropped
+//! warn_payload_bytes = 65536   # larger ones are delivered but reported
+//! max_queued = 1000            # events processed per top-level fire_event
+//! max_depth = 8                # how deep events may trigger further events
+//! concurrent = true            # subscribers handle a broadcast event at once
+//! ```
+
+use drk_api::SystemEvent;
+use std::any::Any;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct EventLimits {
+    pub max_payload_bytes: usize,
+    pub warn_payload_bytes: usize,
+    pub max_queued: usize,
+    pub max_depth: usize,
+    /// Whether the subscribers of a broadcast event handle it concurrently
+    pub concurrent: bool,
+}
+
+impl Default for EventLimits {
+    fn default() -> Self {
+        Self {
+            max_payload_bytes: 1024 * 1024,
+            warn_payload_bytes: 64 * 1024,
+            max_queued: 1000,
+            max_depth: 8,
+            concurrent: true,
+        }
+    }
+}
+
+impl EventLimits {
+    /// Reads the `[events]` section, keeping defaults for anything unset.
+    pub fn from_config(config: &HashMap<String, toml::Value>) -> Self {
+        let mut limits = Self::default();
+        let Some(section) = config.get("events") else {
+            return limits;
+        };
+        let get = |key: &str| {
+            section
+                .get(key)
+                .and_then(|v| v.as_integer())
+                .filter(|v| *v > 0)
+                .map(|v| v as usize)
+        };
+        if let Some(v) = get("max_payload_bytes") {
+            limits.max_payload_bytes = v;
+        }
+        if let Some(v) = get("warn_payload_bytes") {
+            limits.warn_payload_bytes = v;
+        }
+        if let Some(v) = get("max_queued") {
+            limits.max_queued = v;
+        }
+        if let Some(v) = get("max_depth") {
+            limits.max_depth = v;
+        }
+        if let Some(v) = section.get("concurrent").and_then(|v| v.as_bool()) {
+            limits.concurrent = v;
+        }
+        limits
+    }
+}
+
+/// Per-plugin counters for emitted events.
+#[derive(Debug, Clone, Default)]
+pub struct EventStats {
+    pub emitted: u64,
+    pub dropped: u64,
+    /// Largest measurable payload seen, in bytes.
+    pub largest_payload: usize,
+}
+
+impl EventStats {
+    /// Adds `other`'s counts to these.
+    pub fn add(&mut self, other: &EventStats) {
+        self.emitted += other.emitted;
+        self.dropped += other.dropped;
+        self.largest_payload = self.largest_payload.max(other.largest_payload);
+    }
+}
+
+/// What the guard decided about an emitted event.
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Accept,
+    /// Delivered, but the payload is suspiciously large.
+    Warn(usize),
+    /// Dropped because the payload exceeds the hard limit.
+    Reject(usize),
+}
+
+/// Checks an emitted event against `limits`.
+pub fn check(event: &SystemEvent, limits: &EventLimits) -> Verdict {
+    match event_size(event) {
+        Some(size) if size > limits.max_payload_bytes => Verdict::Reject(size),
+        Some(size) if size > limits.warn_payload_bytes => Verdict::Warn(size),
+        _ => Verdict::Accept,
+    }
+}
+
+/// Best-effort size of what a `Custom` event carries: its payload plus its
+/// data as JSON. `None` for other events and unmeasurable payloads.
+pub fn event_size(event: &SystemEvent) -> Option<usize> {
+    let SystemEvent::Custom { payload, data, .. } = event else {
+        return None;
+    };
+    let payload = payload.as_ref().and_then(|p| payload_size(p.as_ref()));
+    let data = data.as_ref().map(|d| d.to_string().len());
+    match (payload, data) {
+        (None, None) => None,
+        (payload, data) => Some(payload.unwrap_or(0) + data.unwrap_or(0)),
+    }
+}
+
+/// Best-effort payload size for the common payload types.
+///
+/// `Any` can't be measured in general, so unknown types report `None` and are
+/// only subject to the queue-length limits.
+pub fn payload_size(payload: &(dyn Any + Send + Sync)) -> Option<usize> {
+    if let Some(s) = payload.downcast_ref::<String>() {
+        Some(s.len())
+    } else if let Some(s) = payload.downcast_ref::<&'static str>() {
+        Some(s.len())
+    } else if let Some(bytes) = payload.downcast_ref::<Vec<u8>>() {
+        Some(bytes.len())
+    } else {
+        payload
+            .downcast_ref::<Vec<String>>()
+            .map(|items| items.iter().map(String::len).sum())
+    }
+}
+
+/// A short, log-safe description of a payload, used instead of dumping it.
+pub fn describe_payload(payload: &(dyn Any + Send + Sync)) -> String {
+    match payload.downcast_ref::<String>() {
+        Some(s) if s.len() <= 64 => format!("{:?}", s),
+        Some(s) => format!("string of {} bytes", s.len()),
+        None => match payload_size(payload) {
+            Some(size) => format!("{} bytes", size),
+            None => "opaque payload".to_string(),
+        },
+    }
+}
+
+/// Event data as compact JSON, or its size when that's too long to log.
+pub fn describe_data(data: &serde_json::Value) -> String {
+    let json = data.to_string();
+    if json.len() <= 64 {
+        json
+    } else {
+        format!("JSON of {} bytes", json.len())
+    }
+}
+
+/// Log-safe rendering of an event; Custom payloads are summarized, not dumped.
+pub fn describe_event(event: &SystemEvent) -> String {
+    match event {
+        SystemEvent::Custom {
+            source,
+            event,
+            payload,
+            data,
+        } => format!(
+            "Custom {{ source: {:?}, event: {:?}, payload: {}, data: {} }}",
+            source,
+            event,
+            payload
+                .as_ref()
+                .map_or_else(|| "none".to_string(), |p| describe_payload(p.as_ref())),
+            data.as_ref()
+                .map_or_else(|| "none".to_string(), describe_data)
+        ),
+        other => format!("{:?}", other),
+    }
+}
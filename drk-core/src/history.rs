@@ -0,0 +1,105 @@
+//! Command history.
+//!
+//! Every plugin command that runs is appended to a JSON-lines file in the data
+//! directory, together with the project it ran in and the role (profile) it
+//! ran under. Parallel invocations take an exclusive lock on the file while
+//! appending, so lines never interleave.
+
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The command line after `drk`, shell-quoted.
+    pub command: String,
+    /// Project root the command ran in, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// The `--role` the command ran under, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    pub success: bool,
+}
+
+impl HistoryEntry {
+    /// An entry for `args` (without the program name) run now.
+    pub fn new(
+        args: &[String],
+        project: Option<&Path>,
+        profile: Option<&str>,
+        success: bool,
+    ) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            command: shell_words::join(args),
+            project: project.map(|p| p.display().to_string()),
+            profile: profile.map(String::from),
+            success,
+        }
+    }
+}
+
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The default history file inside the drk data directory.
+    pub fn default_path() -> PathBuf {
+        crate::paths::data_dir().join("history.jsonl")
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends one entry, holding an exclusive lock while writing.
+    pub fn append(&self, entry: &HistoryEntry) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Could not open history at {:?}", self.path))?;
+        file.lock()
+            .with_context(|| format!("Could not lock history at {:?}", self.path))?;
+
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        file.write_all(line.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// All entries, oldest first. Lines that don't parse are skipped.
+    pub fn entries(&self) -> Result<Vec<HistoryEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let file = File::open(&self.path)
+            .with_context(|| format!("Could not open history at {:?}", self.path))?;
+        file.lock_shared()
+            .with_context(|| format!("Could not lock history at {:?}", self.path))?;
+
+        Ok(BufReader::new(&file)
+            .lines()
+            .map_while(|line| line.ok())
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect())
+    }
+}
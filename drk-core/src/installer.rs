@@ -0,0 +1,399 @@
+//! Installing plugin libraries into the user plugin directory.
+
+use crate::assets;
+use crate::cleanup;
+use crate::lockfile::{sha256_file, LockedPlugin, Lockfile, LOCKFILE_NAME};
+use crate::manifest::{BuildSpec, PluginManifest};
+use crate::paths;
+use anyhow::{bail, Context as _, Result};
+use drk_api::download::{Download, DownloadPool};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// True for files that look like a loadable plugin on any OS, including
+/// WebAssembly modules when drk is built with the `wasm` feature.
+pub fn is_plugin_library(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        ext == "dll" || ext == "so" || ext == "dylib" || (cfg!(feature = "wasm") && ext == "wasm")
+    })
+}
+
+/// Builds `flake_ref` with nix and installs every plugin library it produces.
+///
+/// The flake reference is recorded in each plugin's manifest so the plugin
+/// can be rebuilt later with `drk plugin rebuild`.
+pub fn install_from_flake(flake_ref: &str) -> Result<Vec<PathBuf>> {
+    let output = Command::new("nix")
+        .args(["build", flake_ref, "--no-link", "--print-out-paths"])
+        .output()
+        .context("Failed to run nix. Is it installed and on PATH?")?;
+
+    if !output.status.success() {
+        bail!(
+            "nix build {} failed:\n{}",
+            flake_ref,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let mut installed = Vec::new();
+    for out_path in String::from_utf8_lossy(&output.stdout).lines() {
+        for entry in walkdir::WalkDir::new(out_path.trim())
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            if is_plugin_library(entry.path()) {
+                let dest = install_library(entry.path(), Some(flake_ref))?;
+                let mut manifest = PluginManifest::load_for(&dest)?.unwrap_or_default();
+                manifest.build = Some(BuildSpec {
+                    flake: flake_ref.to_string(),
+                });
+                manifest.save_for(&dest)?;
+                installed.push(dest);
+            }
+        }
+    }
+
+    if installed.is_empty() {
+        bail!(
+            "{} built successfully but produced no plugin library",
+            flake_ref
+        );
+    }
+    Ok(installed)
+}
+
+/// Packs every installed plugin, its manifest and assets, and a freshly checksummed
+/// lockfile into a tar archive for machines without network access.
+pub fn create_bundle(out: &Path) -> Result<usize> {
+    let dir = paths::plugins_dir();
+    let previous = Lockfile::load(&dir)?;
+    let mut lock = Lockfile::default();
+
+    let file = fs::File::create(out).with_context(|| format!("Could not create {:?}", out))?;
+    let mut archive = tar::Builder::new(file);
+
+    let libraries: Vec<PathBuf> = if dir.exists() {
+        fs::read_dir(&dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| is_plugin_library(p))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    for library in &libraries {
+        let name = file_name(library)?;
+        archive.append_path_with_name(library, &name)?;
+
+        let manifest = PluginManifest::path_for(library);
+        if manifest.exists() {
+            archive.append_path_with_name(&manifest, file_name(&manifest)?)?;
+        }
+        let assets = assets::source_for(library);
+        if assets.is_dir() {
+            archive.append_dir_all(file_name(&assets)?, &assets)?;
+        }
+
+        lock.upsert(LockedPlugin {
+            sha256: sha256_file(library)?,
+            source: previous.get(&name).and_then(|p| p.source.clone()),
+            file: name,
+        });
+    }
+
+    let lock_content = toml::to_string_pretty(&lock)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(lock_content.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive.append_data(&mut header, LOCKFILE_NAME, lock_content.as_bytes())?;
+    archive.finish()?;
+
+    Ok(libraries.len())
+}
+
+/// Installs every plugin from a bundle made by `create_bundle`.
+///
+/// All checksums are verified before anything is copied, so a corrupted
+/// bundle never leaves a half-installed plugin directory behind.
+pub fn install_from_bundle(bundle: &Path) -> Result<Vec<PathBuf>> {
+    let staging = tempfile::tempdir()?;
+    let _cleanup = cleanup::remove_on_exit(staging.path());
+    let file = fs::File::open(bundle).with_context(|| format!("Could not open {:?}", bundle))?;
+    tar::Archive::new(file)
+        .unpack(staging.path())
+        .with_context(|| format!("Could not unpack bundle {:?}", bundle))?;
+
+    let lock = Lockfile::load(staging.path())?;
+    if lock.plugins.is_empty() {
+        bail!(
+            "Bundle {:?} has no {} or lists no plugins",
+            bundle,
+            LOCKFILE_NAME
+        );
+    }
+
+    for entry in &lock.plugins {
+        let library = staging.path().join(&entry.file);
+        let actual =
+            sha256_file(&library).with_context(|| format!("Bundle is missing {}", entry.file))?;
+        if actual != entry.sha256 {
+            bail!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                entry.file,
+                entry.sha256,
+                actual
+            );
+        }
+    }
+
+    lock.plugins
+        .iter()
+        .map(|entry| install_library(&staging.path().join(&entry.file), entry.source.as_deref()))
+        .collect()
+}
+
+/// Downloads the plugin libraries at `urls`, `jobs` at a time, and installs
+/// them with each URL recorded as its source.
+///
+/// Nothing is installed unless every download succeeds, so a flaky mirror
+/// never leaves only half of a set of plugins behind.
+pub fn install_from_urls(urls: &[String], jobs: usize) -> Result<Vec<PathBuf>> {
+    let staging = tempfile::tempdir()?;
+    let _cleanup = cleanup::remove_on_exit(staging.path());
+    let downloads = urls
+        .iter()
+        .map(|url| {
+            let name = url
+                .split(['?', '#'])
+                .next()
+                .and_then(|path| path.rsplit('/').next())
+                .filter(|name| is_plugin_library(Path::new(name)))
+                .with_context(|| {
+                    format!(
+                        "{} doesn't point at a plugin library (.so, .dylib or .dll)",
+                        url
+                    )
+                })?;
+            Ok(Download::new(url, staging.path().join(name)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let libraries = DownloadPool::new(jobs)
+        .run(downloads)
+        .context("Nothing was installed")?;
+    libraries
+        .iter()
+        .zip(urls)
+        .map(|(library, url)| install_library(library, Some(url)))
+        .collect()
+}
+
+/// Installs from a local `path`: a plugin library, a directory of prebuilt
+/// libraries, or a Cargo project. A Cargo project is built with
+/// `cargo build --release` first; with `build` false, the libraries of an
+/// earlier release build are installed as they are.
+pub fn install_from_path(path: &Path, build: bool) -> Result<Vec<PathBuf>> {
+    let path = fs::canonicalize(path).with_context(|| format!("Could not find {:?}", path))?;
+    let origin = path.display().to_string();
+    if path.is_file() {
+        if !is_plugin_library(&path) {
+            bail!("{:?} isn't a plugin library", path);
+        }
+        return Ok(vec![install_library(&path, Some(&origin))?]);
+    }
+    install_from_dir(&path, build, &origin)
+}
+
+/// Clones the git repository at `url` and installs from the checkout like
+/// [`install_from_path`]. A `#ref` suffix picks a branch or tag.
+pub fn install_from_git(url: &str, build: bool) -> Result<Vec<PathBuf>> {
+    let (repo, reference) = match url.rsplit_once('#') {
+        Some((repo, reference)) => (repo, Some(reference)),
+        None => (url, None),
+    };
+    let checkout = tempfile::tempdir()?;
+    let _cleanup = cleanup::remove_on_exit(checkout.path());
+
+    let mut git = Command::new("git");
+    git.args(["clone", "--depth", "1", "--quiet"]);
+    if let Some(reference) = reference {
+        git.args(["--branch", reference]);
+    }
+    let output = git
+        .arg(repo)
+        .arg(checkout.path())
+        .output()
+        .context("Failed to run git. Is it installed and on PATH?")?;
+    if !output.status.success() {
+        bail!(
+            "git clone {} failed:\n{}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    install_from_dir(checkout.path(), build, &format!("git+{}", url))
+}
+
+/// Installs the plugin libraries in `dir`, or those its Cargo project builds.
+fn install_from_dir(dir: &Path, build: bool, origin: &str) -> Result<Vec<PathBuf>> {
+    let libraries = if !dir.join("Cargo.toml").exists() {
+        fs::read_dir(dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| is_plugin_library(p))
+            .collect()
+    } else if build {
+        cargo_build(dir)?
+    } else {
+        let release = std::env::var_os("CARGO_TARGET_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| dir.join("target"))
+            .join("release");
+        fs::read_dir(&release)
+            .with_context(|| {
+                format!(
+                    "No release build in {:?}; build it or drop --no-build",
+                    release
+                )
+            })?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| is_plugin_library(p))
+            .collect::<Vec<_>>()
+    };
+
+    if libraries.is_empty() {
+        bail!("Found no plugin library in {}", origin);
+    }
+    libraries
+        .iter()
+        .map(|library| install_library(library, Some(origin)))
+        .collect()
+}
+
+/// Runs `cargo build --release` in `dir` and returns the libraries it built.
+fn cargo_build(dir: &Path) -> Result<Vec<PathBuf>> {
+    // Build output goes to the terminal; the artifact list comes on stdout
+    let output = Command::new("cargo")
+        .args([
+            "build",
+            "--release",
+            "--message-format=json-render-diagnostics",
+        ])
+        .current_dir(dir)
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .context("Failed to run cargo. Is it installed and on PATH?")?;
+    if !output.status.success() {
+        bail!("cargo build --release failed in {:?}", dir);
+    }
+
+    let mut libraries = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Ok(message) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        let is_cdylib = message["target"]["crate_types"]
+            .as_array()
+            .is_some_and(|types| types.iter().any(|t| t == "cdylib"));
+        if message["reason"] != "compiler-artifact" || !is_cdylib {
+            continue;
+        }
+        let files = message["filenames"].as_array().into_iter().flatten();
+        libraries.extend(
+            files
+                .filter_map(|f| f.as_str())
+                .map(PathBuf::from)
+                .filter(|p| is_plugin_library(p)),
+        );
+    }
+    Ok(libraries)
+}
+
+/// Removes the installed library `file` with its manifest and assets, and
+/// drops it from the lockfile. The plugin's data directory is left alone.
+pub fn uninstall(file: &str) -> Result<PathBuf> {
+    let dir = paths::plugins_dir();
+    let library = dir.join(file);
+    if !library.is_file() {
+        bail!("{} isn't installed in {:?}", file, dir);
+    }
+    fs::remove_file(&library).with_context(|| format!("Could not remove {:?}", library))?;
+    let manifest = PluginManifest::path_for(&library);
+    if manifest.exists() {
+        fs::remove_file(&manifest)?;
+    }
+    let assets = assets::source_for(&library);
+    if assets.is_dir() {
+        fs::remove_dir_all(&assets)?;
+    }
+
+    let mut lock = Lockfile::load(&dir)?;
+    if lock.remove(file) {
+        lock.save(&dir)?;
+    }
+    Ok(library)
+}
+
+/// Copies one library (and its manifest and assets, if any) into the plugin directory
+/// and records it in the lockfile.
+fn install_library(source: &Path, origin: Option<&str>) -> Result<PathBuf> {
+    let dest_dir = paths::plugins_dir();
+    fs::create_dir_all(&dest_dir)
+        .with_context(|| format!("Could not create plugin directory {:?}", dest_dir))?;
+
+    let name = file_name(source)?;
+    let dest = dest_dir.join(&name);
+
+    // Stage next to the destination and rename, so an interrupted copy never
+    // leaves a truncated library where the loader will find it
+    let staged = dest_dir.join(format!(".{}.partial", name));
+    let guard = cleanup::remove_on_exit(&staged);
+    if staged.exists() {
+        fs::remove_file(&staged)?;
+    }
+    fs::copy(source, &staged).with_context(|| format!("Could not copy {:?}", source))?;
+    // Nix store files are read-only
+    make_writable(&staged)?;
+    fs::rename(&staged, &dest).with_context(|| format!("Could not install {:?}", dest))?;
+    drop(guard);
+
+    if let Some(manifest) = PluginManifest::load_for(source)? {
+        manifest.save_for(&dest)?;
+    }
+    let assets = assets::source_for(source);
+    if assets.is_dir() {
+        let dest_assets = assets::source_for(&dest);
+        if dest_assets.exists() {
+            fs::remove_dir_all(&dest_assets)?;
+        }
+        assets::copy_dir(&assets, &dest_assets)?;
+    }
+
+    let mut lock = Lockfile::load(&dest_dir)?;
+    lock.upsert(LockedPlugin {
+        file: name,
+        sha256: sha256_file(&dest)?,
+        source: origin.map(String::from),
+    });
+    lock.save(&dest_dir)?;
+
+    Ok(dest)
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .with_context(|| format!("{:?} has no file name", path))
+}
+
+fn make_writable(path: &Path) -> Result<()> {
+    let mut perms = fs::metadata(path)?.permissions();
+    #[allow(clippy::permissions_set_readonly_false)]
+    perms.set_readonly(false);
+    fs::set_permissions(path, perms)?;
+    Ok(())
+}
@@ -0,0 +1,4 @@
+//! `{config.*}` / `{env.*}` interpolation, shared with plugins through
+//! [`drk_api::interpolate`].
+
+pub use drk_api::interpolate::{interpolate, interpolate_with};
@@ -0,0 +1,208 @@
+//! Structured invocations for programmatic callers (`drk exec-json`).
+//!
+//! Build tools describe a command as JSON instead of assembling a command line:
+//!
+//! ```json
+//! {"command": "greet", "args": {"name": "Bob"}, "role": "ci"}
+//! ```
+//!
+//! and get a [`CommandResult`] back. Arguments are checked against the
+//! command's schema the same way the CLI parser would check flags. A nested
+//! command is named by its path, e.g. `"command": "nix init"`.
+
+use crate::cli;
+use anyhow::{bail, Context as _, Result};
+use drk_api::{ArgType, CommandMatches, PluginCommand, VALUES_SEPARATOR};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Invocation {
+    pub command: String,
+    /// Argument name -> value. Strings, numbers and booleans are accepted.
+    #[serde(default)]
+    pub args: BTreeMap<String, serde_json::Value>,
+    /// Role to enforce, like `--role` on the command line. Defaults to
+    /// `DRK_ROLE`, and must match it when both are set.
+    #[serde(default)]
+    pub role: Option<String>,
+    /// Runs a command marked dangerous, like `--yes`; nothing asks first.
+    #[serde(default)]
+    pub yes: bool,
+}
+
+/// What `drk exec-json` prints.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CommandResult {
+    pub command: String,
+    /// The plugin that handled the command, if it got that far.
+    pub plugin: Option<String>,
+    pub success: bool,
+    pub exit_code: i32,
+    /// Everything the command printed to stdout, unstyled, followed by the
+    /// text of its output.
+    pub output: String,
+    /// The data the plugin handed over with `ctx.output`, if it did.
+    pub data: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+impl Invocation {
+    pub fn parse(json: &str) -> Result<Self> {
+        serde_json::from_str(json).context("Invalid invocation JSON")
+    }
+
+    /// The command's path, outermost first; see [`CommandMatches::path`].
+    pub fn path(&self) -> Vec<String> {
+        self.command.split_whitespace().map(String::from).collect()
+    }
+
+    /// The top-level command.
+    pub fn command_name(&self) -> &str {
+        self.command.split_whitespace().next().unwrap_or_default()
+    }
+
+    /// Validates the arguments against `schema` and converts them into the
+    /// matches plugins receive.
+    pub fn to_matches(&self, schema: &PluginCommand) -> Result<CommandMatches> {
+        for name in self.args.keys() {
+            if !schema.args.iter().any(|a| &a.name == name) {
+                bail!("Unknown argument '{}' for command '{}'", name, self.command);
+            }
+        }
+
+        let mut args = HashMap::new();
+        for def in &schema.args {
+            // Like on the command line: the environment, then the default
+            let fallback = || {
+                def.env
+                    .as_deref()
+                    .and_then(|var| std::env::var(var).ok())
+                    .or_else(|| def.default.clone())
+                    .map(serde_json::Value::String)
+            };
+            let Some(value) = self.args.get(&def.name).cloned().or_else(fallback) else {
+                if def.required {
+                    bail!("Missing required argument '{}'", def.name);
+                }
+                continue;
+            };
+
+            let mut text = match &value {
+                // Variadic and multiple values may come as a list
+                serde_json::Value::Array(items) if def.takes_many() => items
+                    .iter()
+                    .map(|item| match item {
+                        serde_json::Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(&VALUES_SEPARATOR.to_string()),
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Number(n) => n.to_string(),
+                serde_json::Value::Bool(b) => b.to_string(),
+                other => bail!("Argument '{}' must be a scalar, got {}", def.name, other),
+            };
+            let items: Vec<&str> = if def.takes_many() {
+                text.split(VALUES_SEPARATOR).collect()
+            } else {
+                vec![&text]
+            };
+            let mut canonical_items = Vec::new();
+            for item in items {
+                if let ArgType::Choice(choices) = &def.arg_type {
+                    if !choices.iter().any(|choice| choice == item) {
+                        bail!(
+                            "Argument '{}' must be one of {}, got {}",
+                            def.name,
+                            choices.join(", "),
+                            value
+                        );
+                    }
+                }
+                let valid = match def.arg_type {
+                    ArgType::Integer => item.parse::<i64>().is_ok(),
+                    ArgType::Count => item.parse::<u8>().is_ok(),
+                    ArgType::Float => item.parse::<f64>().is_ok(),
+                    ArgType::Boolean => item.parse::<bool>().is_ok(),
+                    ArgType::String
+                    | ArgType::Positional
+                    | ArgType::Variadic
+                    | ArgType::OptionalValue
+                    | ArgType::Choice(_) => true,
+                    ArgType::Duration | ArgType::DateTime => {
+                        match cli::canonical(&def.arg_type, item) {
+                            Ok(canonical) => {
+                                canonical_items.push(canonical);
+                                true
+                            }
+                            Err(_) => false,
+                        }
+                    }
+                };
+                if !valid {
+                    bail!(
+                        "Argument '{}' expects {:?}, got {}",
+                        def.name,
+                        def.arg_type,
+                        value
+                    );
+                }
+            }
+            // As the CLI passes them on
+            if !canonical_items.is_empty() {
+                text = canonical_items.join(&VALUES_SEPARATOR.to_string());
+            }
+
+            // Flags are only present when set, matching what the CLI sends
+            if (def.arg_type == ArgType::Boolean && text == "false" && !def.negatable)
+                || (def.arg_type == ArgType::Count && text == "0")
+            {
+                continue;
+            }
+            args.insert(def.name.clone(), text);
+        }
+
+        Ok(CommandMatches {
+            command_name: self.command_name().to_string(),
+            path: self.path(),
+            args,
+        })
+    }
+
+    /// The equivalent command line (after `drk`), for history and hooks.
+    pub fn to_argv(&self, matches: &CommandMatches, schema: &PluginCommand) -> Vec<String> {
+        let mut argv = self.path();
+        for def in &schema.args {
+            let Some(value) = matches.args.get(&def.name) else {
+                continue;
+            };
+            match def.arg_type {
+                _ if def.multiple => {
+                    for item in value.split(VALUES_SEPARATOR) {
+                        argv.push(format!("--{}", def.name));
+                        argv.push(item.to_string());
+                    }
+                }
+                ArgType::Positional => argv.push(value.clone()),
+                ArgType::Variadic => argv.extend(value.split(VALUES_SEPARATOR).map(String::from)),
+                ArgType::Boolean => match def.negated_name() {
+                    Some(negated) if value == "false" => argv.push(format!("--{}", negated)),
+                    _ => argv.push(format!("--{}", def.name)),
+                },
+                ArgType::OptionalValue if value.is_empty() => argv.push(format!("--{}", def.name)),
+                ArgType::OptionalValue => argv.push(format!("--{}={}", def.name, value)),
+                ArgType::Count => {
+                    let times = value.parse().unwrap_or(0);
+                    argv.extend(std::iter::repeat_n(format!("--{}", def.name), times));
+                }
+                _ => {
+                    argv.push(format!("--{}", def.name));
+                    argv.push(value.clone());
+                }
+            }
+        }
+        argv
+    }
+}
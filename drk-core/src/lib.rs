@@ -1,6 +1,48 @@
 // We export the manager so the CLI can use it
 pub mod manager;
 
-// Re-export commonly used items from the API for convenience,
-// though strict usage should usually depend on drk-api directly.
+pub mod aliases;
+pub mod assets;
+pub mod audit;
+pub mod availability;
+pub mod batch;
+pub mod binformat;
+pub mod changelog;
+pub mod cleanup;
+pub mod cli;
+pub mod config_edit;
+pub mod container;
+pub mod events;
+pub mod history;
+pub mod installer;
+pub mod interpolate;
+pub mod invocation;
+pub mod load_errors;
+pub mod lockfile;
+pub mod manifest;
+pub mod migrations;
+pub mod paths;
+pub mod permissions;
+pub mod plugin_data;
+pub mod project;
+pub mod redact;
+pub mod registry;
+pub mod resources;
+pub mod result_cache;
+pub mod roles;
+pub mod run_record;
+pub mod saved;
+pub mod snapshot;
+pub mod stable;
+pub mod state;
+pub mod tips;
+pub mod typo;
+pub mod usage;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+// drk-api is the one plugin API: `Plugin`, `Context` and
+// `PluginMetadata` are re-exported from it rather than defined here, and
+// events go through `SystemEvent` and `PluginManager::fire_event`. Plugins
+// should still depend on drk-api directly.
 pub use drk_api::*;
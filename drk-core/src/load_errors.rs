@@ -0,0 +1,176 @@
+//! Plugin libraries that failed to load, kept for `drk plugin errors`.
+//!
+//! Each run overwrites the report in the data directory, so it always
+//! describes the most recent attempt to load the plugin set.
+
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Why a library failed to load, guessed from the loader's error message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    /// Not a library at all, or one without a `_plugin_create` entry point
+    NotAPlugin,
+    /// Built against a drk-api that no longer exports what it links to
+    ApiMismatch,
+    /// Built for another CPU or OS
+    WrongArchitecture,
+    /// A shared library it depends on can't be found
+    MissingDependency,
+    /// Loaded, but its `on_load` hook failed
+    InitFailed,
+    Other,
+}
+
+impl Category {
+    /// Every category, most actionable first.
+    pub const ALL: [Category; 6] = [
+        Category::ApiMismatch,
+        Category::WrongArchitecture,
+        Category::MissingDependency,
+        Category::InitFailed,
+        Category::Other,
+        Category::NotAPlugin,
+    ];
+
+    pub fn classify(message: &str) -> Self {
+        let message = message.to_lowercase();
+        let any = |needles: &[&str]| needles.iter().any(|n| message.contains(n));
+
+        if any(&["_plugin_create", "not a shared library"]) {
+            Category::NotAPlugin
+        } else if any(&["on_load"]) {
+            Category::InitFailed
+        } else if any(&[
+            "is a library for",
+            "wrong elf class",
+            "invalid elf header",
+            "wrong architecture",
+            "incompatible architecture",
+            "not a valid win32 application",
+        ]) {
+            Category::WrongArchitecture
+        } else if any(&[
+            "undefined symbol",
+            "symbol not found",
+            "built against drk-api",
+            "built with rustc",
+        ]) {
+            Category::ApiMismatch
+        } else if any(&[
+            "cannot open shared object file",
+            "library not loaded",
+            "specified module could not be found",
+        ]) {
+            Category::MissingDependency
+        } else {
+            Category::Other
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Category::NotAPlugin => "not a drk plugin",
+            Category::ApiMismatch => "API mismatch",
+            Category::WrongArchitecture => "wrong architecture",
+            Category::MissingDependency => "missing dependency",
+            Category::InitFailed => "initialization failed",
+            Category::Other => "other",
+        }
+    }
+
+    pub fn suggestion(self) -> &'static str {
+        match self {
+            Category::NotAPlugin => {
+                "Remove it from the plugin directory, or export it with `declare_plugin!`"
+            }
+            Category::ApiMismatch => {
+                "Rebuild the plugin against the current drk-api (`drk plugin rebuild <name>`)"
+            }
+            Category::WrongArchitecture => {
+                "Rebuild the plugin for this machine's target, or install a matching build"
+            }
+            Category::MissingDependency => {
+                "Install the missing libraries or rebuild with them linked statically"
+            }
+            Category::InitFailed => {
+                "Check the plugin's config section; the message says what it rejected"
+            }
+            Category::Other => "See the loader message for details",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadError {
+    pub path: PathBuf,
+    pub message: String,
+    pub category: Category,
+}
+
+impl LoadError {
+    pub fn new(path: &Path, error: &anyhow::Error) -> Self {
+        let message = format!("{:#}", error);
+        Self {
+            path: path.to_path_buf(),
+            category: Category::classify(&message),
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    /// Seconds since the Unix epoch when the run happened.
+    pub timestamp: u64,
+    pub errors: Vec<LoadError>,
+}
+
+/// Where the last run's report is kept.
+pub fn default_path() -> PathBuf {
+    crate::paths::data_dir().join("load-errors.json")
+}
+
+/// Replaces the stored report with `errors`.
+pub fn save(path: &Path, errors: &[LoadError]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let report = Report {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        errors: errors.to_vec(),
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&report)?)
+        .with_context(|| format!("Could not write load errors to {:?}", path))
+}
+
+/// The stored report, or `None` if no run has written one yet.
+pub fn load(path: &Path) -> Result<Option<Report>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = std::fs::read_to_string(path)?;
+    let report = serde_json::from_str(&text)
+        .with_context(|| format!("Could not parse load errors at {:?}", path))?;
+    Ok(Some(report))
+}
+
+/// Shared libraries `library` links against that the dynamic loader can't
+/// resolve, probed with `ldd`. Empty where `ldd` isn't available.
+pub fn missing_libraries(library: &Path) -> Vec<String> {
+    let Ok(output) = std::process::Command::new("ldd").arg(library).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| line.contains("not found"))
+        .filter_map(|line| line.split_whitespace().next())
+        .map(String::from)
+        .collect()
+}
@@ -0,0 +1,82 @@
+//! `plugins.lock`: the record of what's installed in the user plugin directory.
+//!
+//! ```toml
+//! [[plugin]]
+//! file = "libdrk_nix.so"
+//! sha256 = "9f86d08..."
+//! source = "github:dark1zinn/drk#drk-nix"
+//! ```
+
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const LOCKFILE_NAME: &str = "plugins.lock";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedPlugin {
+    /// Library file name inside the plugin directory.
+    pub file: String,
+    pub sha256: String,
+    /// Where it was installed from, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(default, rename = "plugin")]
+    pub plugins: Vec<LockedPlugin>,
+}
+
+impl Lockfile {
+    pub fn path_in(dir: &Path) -> PathBuf {
+        dir.join(LOCKFILE_NAME)
+    }
+
+    /// Loads the lockfile from `dir`, or an empty one if there isn't any yet.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = Self::path_in(dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read lockfile at {:?}", path))?;
+        toml::from_str(&content).with_context(|| format!("Invalid lockfile at {:?}", path))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = Self::path_in(dir);
+        fs::write(&path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Could not write lockfile at {:?}", path))
+    }
+
+    /// Adds or replaces the entry for `entry.file`.
+    pub fn upsert(&mut self, entry: LockedPlugin) {
+        self.plugins.retain(|p| p.file != entry.file);
+        self.plugins.push(entry);
+        self.plugins.sort_by(|a, b| a.file.cmp(&b.file));
+    }
+
+    /// Drops the entry for `file`; returns whether there was one.
+    pub fn remove(&mut self, file: &str) -> bool {
+        let before = self.plugins.len();
+        self.plugins.retain(|p| p.file != file);
+        self.plugins.len() != before
+    }
+
+    pub fn get(&self, file: &str) -> Option<&LockedPlugin> {
+        self.plugins.iter().find(|p| p.file == file)
+    }
+}
+
+/// Hex-encoded SHA-256 of a file's contents.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("Could not read {:?}", path))?;
+    Ok(Sha256::digest(&bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
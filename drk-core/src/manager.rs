@@ -1,8 +1,34 @@
-use anyhow::{Context as _, Result};
-use drk_api::{Context, Plugin, PluginCommand, PluginMetadata, SystemEvent};
+use crate::assets;
+use crate::audit::AuditLog;
+use crate::binformat;
+use crate::cleanup;
+use crate::config_edit;
+use crate::events::{self, EventLimits, EventStats, Verdict};
+use crate::load_errors::LoadError;
+use crate::manifest::PluginManifest;
+use crate::migrations;
+use crate::paths;
+use crate::permissions::{self, Gate};
+use crate::plugin_data;
+use crate::redact;
+use crate::resources::{self, Probe, ResourceStats};
+use crate::stable::StablePlugin;
+use crate::state::{self, StateLimits, StateStore};
+use anyhow::{bail, Context as _, Result};
+use drk_api::abi::PluginVTable;
+use drk_api::{
+    icon_warning, style_warning, CancellationToken, Capability, CommandOutput, ConfigKey, Context,
+    ControlFlow, EventKind, Plugin, PluginCommand, PluginDeclaration, PluginMetadata, SystemEvent,
+    Tip, API_VERSION, OUTPUT_EVENT, RUSTC_VERSION, SAVE_CONFIG_EVENT,
+};
 use libloading::{Library, Symbol};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ffi::{c_char, CStr};
+use std::path::{Path, PathBuf};
+
+/// State key recording which plugins have run `on_install`, with the
+/// version they were at.
+const INSTALLED_KEY: &str = "installed";
 
 /// A wrapper around a dynamically loaded plugin.
 ///
@@ -12,10 +38,84 @@ use std::path::Path;
 /// where the code is unloaded from memory before the object is destroyed.
 struct LoadedPlugin {
     instance: Box<dyn Plugin>,
-    _lib: Library,
-    #[allow(dead_code)]
+    /// `None` for plugins registered with `register_plugin`
+    _lib: Option<Library>,
     metadata: PluginMetadata,
+    manifest: Option<PluginManifest>,
+    /// The file it was loaded from; `None` for registered plugins
+    library: Option<PathBuf>,
     enabled: bool,
+    /// Whether `on_load` ran, which makes `on_unload` due
+    started: bool,
+    /// The events it's given, from `Plugin::subscriptions`
+    subscriptions: Vec<EventKind>,
+}
+
+impl LoadedPlugin {
+    /// Whether plugin `name` is given `event`
+    fn wants(&self, name: &str, event: &SystemEvent) -> bool {
+        if let SystemEvent::ExecuteCommand { plugin_name, .. } = event {
+            if plugin_name == name {
+                return true;
+            }
+        }
+        self.subscriptions.iter().any(|kind| kind.matches(event))
+    }
+}
+
+/// An error a plugin returned while handling an event.
+#[derive(Debug, Clone)]
+pub struct PluginFailure {
+    pub plugin: String,
+    pub error: String,
+}
+
+/// A plugin's refusal to let a command run, from `Plugin::on_pre_command`.
+#[derive(Debug, Clone)]
+pub struct Veto {
+    pub plugin: String,
+    pub reason: String,
+}
+
+/// A result a plugin handed over with `ctx.output`.
+#[derive(Debug, Clone)]
+pub struct PluginOutput {
+    pub plugin: String,
+    pub output: CommandOutput,
+}
+
+/// What came of [`PluginManager::fire_event_with_result`].
+#[derive(Debug, Clone, Default)]
+pub struct CommandOutcome {
+    /// In the order they happened
+    pub failures: Vec<PluginFailure>,
+    /// In the order they were handed over
+    pub outputs: Vec<PluginOutput>,
+}
+
+impl CommandOutcome {
+    /// Whether `plugin` returned an error. Another plugin failing, e.g. a
+    /// logger watching `ExecuteCommand`, doesn't fail the command.
+    pub fn failed(&self, plugin: &str) -> bool {
+        self.failures.iter().any(|f| f.plugin == plugin)
+    }
+
+    /// The first error `plugin` returned.
+    pub fn error(&self, plugin: &str) -> Option<&str> {
+        self.failures
+            .iter()
+            .find(|f| f.plugin == plugin)
+            .map(|f| f.error.as_str())
+    }
+
+    /// The last output `plugin` handed over, if any.
+    pub fn output(&self, plugin: &str) -> Option<&CommandOutput> {
+        self.outputs
+            .iter()
+            .rev()
+            .find(|o| o.plugin == plugin)
+            .map(|o| &o.output)
+    }
 }
 
 pub struct PluginManager {
@@ -23,6 +123,38 @@ pub struct PluginManager {
     plugins: HashMap<String, LoadedPlugin>,
     /// Configuration storage (In-memory representation of config.toml)
     config_store: HashMap<String, toml::Value>,
+    /// The file `load_config` read, where config changes are written back
+    config_path: Option<PathBuf>,
+    /// Where config changes made by plugins are recorded, if auditing is on
+    audit: Option<AuditLog>,
+    /// Backing store for `ctx.state`; without one plugin state isn't persisted
+    state: Option<StateStore>,
+    /// Tripped on Ctrl-C; plugins see it as `ctx.cancelled()`
+    cancel: CancellationToken,
+    /// Plugin Name -> commands it contributed for this run
+    contributed: HashMap<String, Vec<PluginCommand>>,
+    /// Every plugin library found this run, loaded or not
+    discovered: Vec<PathBuf>,
+    /// Libraries that failed to load this run, for `drk plugin errors`
+    load_errors: Vec<LoadError>,
+    /// Plugin Name -> counters for the events it emitted
+    event_stats: HashMap<String, EventStats>,
+    /// Plugin Name -> approximate memory/handle cost of its calls
+    resource_stats: HashMap<String, ResourceStats>,
+    /// Errors plugins returned from calls so far
+    failures: Vec<PluginFailure>,
+    /// Results plugins handed over with `ctx.output` so far
+    outputs: Vec<PluginOutput>,
+    /// Values masked in what the manager prints, see [`Self::redact`]
+    secrets: Vec<String>,
+    /// Capabilities the user allowed once, for the rest of this run
+    granted_once: HashSet<(String, Capability)>,
+}
+
+impl Default for PluginManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PluginManager {
@@ -30,9 +162,110 @@ impl PluginManager {
         Self {
             plugins: HashMap::new(),
             config_store: HashMap::new(),
+            config_path: None,
+            contributed: HashMap::new(),
+            discovered: Vec::new(),
+            load_errors: Vec::new(),
+            audit: None,
+            state: None,
+            cancel: CancellationToken::new(),
+            event_stats: HashMap::new(),
+            resource_stats: HashMap::new(),
+            failures: Vec::new(),
+            outputs: Vec::new(),
+            secrets: Vec::new(),
+            granted_once: HashSet::new(),
+        }
+    }
+
+    /// Loads a TOML config file into the in-memory config store.
+    /// A missing file is not an error; the store simply stays empty.
+    pub fn load_config<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        self.config_path = Some(path.to_path_buf());
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read config at {:?}", path))?;
+        let table: toml::Table = toml::from_str(&content)
+            .with_context(|| format!("Invalid TOML in config at {:?}", path))?;
+
+        self.config_store.extend(table);
+        Ok(())
+    }
+
+    /// Read-only view of the config store
+    pub fn config(&self) -> &HashMap<String, toml::Value> {
+        &self.config_store
+    }
+
+    /// Mutable access for host features that edit config (saved commands, toggles)
+    pub fn config_mut(&mut self) -> &mut HashMap<String, toml::Value> {
+        &mut self.config_store
+    }
+
+    /// Writes the config store back out as TOML, creating parent directories as needed.
+    /// An existing file is edited in place: only the values that changed are
+    /// rewritten, so the user's comments and ordering survive.
+    pub fn save_config<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let table: toml::Table = self
+            .config_store
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        let existing = std::fs::read_to_string(path).ok();
+        let edited = existing.as_deref().and_then(|text| {
+            let mut document: toml_edit::DocumentMut = text.parse().ok()?;
+            let before: toml::Table = text.parse().ok()?;
+            config_edit::update(document.as_table_mut(), &before, &table).ok()?;
+            Some(document.to_string())
+        });
+        let content = match edited {
+            Some(content) => content,
+            None => toml::to_string_pretty(&table)?,
+        };
+        std::fs::write(path, content)
+            .with_context(|| format!("Could not write config at {:?}", path))
+    }
+
+    /// Saves the config store to the file `load_config` read. Without a
+    /// loaded config there's nowhere to save to, and nothing happens.
+    pub fn write_config(&self) -> Result<()> {
+        match &self.config_path {
+            Some(path) => self.save_config(path),
+            None => Ok(()),
         }
     }
 
+    /// Records config changes made by plugins into the given audit log
+    pub fn set_audit_log(&mut self, audit: AuditLog) {
+        self.audit = Some(audit);
+    }
+
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit.as_ref()
+    }
+
+    /// Persists each plugin's `ctx.state` into the given store
+    pub fn set_state_store(&mut self, store: StateStore) {
+        self.state = Some(store);
+    }
+
+    pub fn state_store(&self) -> Option<&StateStore> {
+        self.state.as_ref()
+    }
+
+    pub fn state_store_mut(&mut self) -> Option<&mut StateStore> {
+        self.state.as_mut()
+    }
+
     /// Recursively scans a directory for shared libraries
     pub fn load_plugins_from_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
         let path = path.as_ref();
@@ -44,62 +277,170 @@ impl PluginManager {
             .into_iter()
             .filter_map(|e| e.ok())
         {
-            let p = entry.path();
-            // Check for library extensions based on OS
-            let is_lib = p
-                .extension()
-                .map_or(false, |ext| ext == "dll" || ext == "so" || ext == "dylib");
-
-            if is_lib {
-                // We use unsafe here because loading arbitrary DLLs is inherently unsafe
-                unsafe {
-                    if let Err(e) = self.load_plugin(p) {
-                        eprintln!("Failed to load plugin at {:?}: {}", p, e);
-                    }
+            self.try_load(entry.path());
+        }
+        Ok(())
+    }
+
+    /// Loads the libraries installed by `drk plugin install`. Only the top
+    /// level is scanned: subdirectories are plugin data directories.
+    pub fn load_installed_plugins(&mut self) -> Result<()> {
+        self.load_top_level(&paths::plugins_dir())
+    }
+
+    /// Loads the plugins in `dirs`, given lowest priority first as from
+    /// [`paths::plugin_search_path`]. Of plugins sharing a name, only the
+    /// one in the last directory is loaded. Only the top level of each
+    /// directory is scanned.
+    pub fn load_plugins_from_search_path(&mut self, dirs: &[PathBuf]) -> Result<()> {
+        // Highest priority first, so shadowed plugins are skipped before they start
+        for dir in dirs.iter().rev() {
+            self.load_top_level(dir)?;
+        }
+        Ok(())
+    }
+
+    fn load_top_level(&mut self, dir: &Path) -> Result<()> {
+        if !dir.is_dir() {
+            return Ok(());
+        }
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+            .with_context(|| format!("Could not read plugin directory {:?}", dir))?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .collect();
+        entries.sort();
+        for path in entries {
+            self.try_load(&path);
+        }
+        Ok(())
+    }
+
+    /// Loads `p` if it looks like a plugin library, reporting failures
+    fn try_load(&mut self, p: &Path) {
+        // Check for library extensions based on OS
+        if crate::installer::is_plugin_library(p) {
+            self.discovered.push(p.to_path_buf());
+            // We use unsafe here because loading arbitrary DLLs is inherently unsafe
+            unsafe {
+                if let Err(e) = self.load_plugin(p) {
+                    eprintln!("Failed to load plugin at {:?}: {}", p, e);
+                    self.load_errors.push(LoadError::new(p, &e));
                 }
             }
         }
-        Ok(())
     }
 
     /// Loads a single plugin from a path
     unsafe fn load_plugin(&mut self, path: &Path) -> Result<()> {
-        // 1. Load the library into memory
+        self.load_plugin_as(path, path)
+    }
+
+    /// Loads the library in `file` as the plugin at `path`, where its
+    /// manifest and assets are looked for and where it's reported as loaded from
+    unsafe fn load_plugin_as(&mut self, file: &Path, path: &Path) -> Result<()> {
+        // WebAssembly modules run sandboxed; there's no library to keep open
+        #[cfg(feature = "wasm")]
+        if crate::wasm::is_wasm_module(path) {
+            let instance = crate::wasm::WasmPlugin::load(file)?;
+            return self.finish_load(Box::new(instance), None, path);
+        }
+
+        // 1. Load the library into memory, once we know it's built for this host
+        binformat::check(file)?;
         let lib =
-            Library::new(path).with_context(|| format!("Could not open library at {:?}", path))?;
+            Library::new(file).with_context(|| format!("Could not open library at {:?}", file))?;
 
-        // 2. Find the entry point symbol
-        // This signature MUST match the `_plugin_create` function in `drk-api` macro
-        let func: Symbol<unsafe extern "C" fn() -> *mut dyn Plugin> = lib
-            .get(b"_plugin_create")
-            .context("Could not find '_plugin_create' symbol. Is this a valid drk plugin?")?;
+        // 2. Create the instance through the newest entry point the plugin exports
+        let instance = create_instance(&lib)?;
+        self.finish_load(instance, Some(lib), path)
+    }
 
-        // 3. Invoke the creator to get the pointer
-        let raw_ptr = func();
+    /// Steps shared by every kind of plugin file once it has an instance
+    fn finish_load(
+        &mut self,
+        instance: Box<dyn Plugin>,
+        lib: Option<Library>,
+        path: &Path,
+    ) -> Result<()> {
+        // 3. Read the optional manifest sitting next to the library, and
+        // unpack the assets packaged with it
+        let manifest = match PluginManifest::load_for(path) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                // The instance's code lives in `lib`, so it has to go first
+                drop(instance);
+                drop(lib);
+                return Err(e);
+            }
+        };
+        let name = instance.metadata().name;
+        if let Err(e) = assets::sync(path, &plugin_data::dir_for(&name)) {
+            eprintln!(
+                "{} Could not extract assets of plugin '{}': {:#}",
+                style_warning(icon_warning()),
+                name,
+                e
+            );
+        }
 
-        // 4. Convert raw pointer back to Box.
-        // We now own this memory.
-        let mut instance = Box::from_raw(raw_ptr);
+        // 4. Store it. IMPORTANT: `lib` moves in with the instance so it stays alive.
+        self.add_plugin(instance, lib, Some(path.to_path_buf()), manifest)
+    }
+
+    /// Adds a plugin compiled into the host instead of loaded from a library,
+    /// e.g. in tests. It can be reached again through [`Self::plugin`].
+    pub fn register_plugin(&mut self, plugin: Box<dyn Plugin>) -> Result<()> {
+        self.add_plugin(plugin, None, None, None)
+    }
 
-        // 5. Read Metadata
+    fn add_plugin(
+        &mut self,
+        mut instance: Box<dyn Plugin>,
+        lib: Option<Library>,
+        library: Option<PathBuf>,
+        manifest: Option<PluginManifest>,
+    ) -> Result<()> {
         let metadata = instance.metadata();
         let name = metadata.name.clone();
+        if let Some(existing) = self.plugins.get(&name) {
+            let from = |library: Option<&PathBuf>| {
+                library.map_or("drk itself".to_string(), |p| format!("{:?}", p))
+            };
+            println!(
+                "Skipping plugin {} from {}: already loaded from {}",
+                name,
+                from(library.as_ref()),
+                from(existing.library.as_ref())
+            );
+            // The instance's code lives in `lib`, so it has to go first
+            drop(instance);
+            drop(lib);
+            return Ok(());
+        }
+        self.migrate_config(&name, instance.as_ref());
 
-        // 6. Check if enabled via config
+        // Initialize only if enabled via config
         let enabled = self.is_plugin_enabled(&name, &metadata);
-
-        // 7. Initialize if enabled
         if enabled {
-            instance.on_load()?;
+            let probe = Probe::start();
+            if let Err(e) = instance.on_load() {
+                drop(instance);
+                drop(lib);
+                return Err(e.context("on_load failed"));
+            }
+            probe.finish(self.resource_stats.entry(name.clone()).or_default());
         }
 
-        // 8. Store everything.
-        // IMPORTANT: Move `lib` into the struct so it stays alive.
         let loaded = LoadedPlugin {
+            subscriptions: instance.subscriptions(),
             instance,
             _lib: lib,
             metadata: metadata.clone(),
+            manifest,
+            library,
             enabled,
+            started: enabled,
         };
 
         println!(
@@ -111,6 +452,55 @@ impl PluginManager {
         Ok(())
     }
 
+    /// Brings the plugin's config section up to its declared layout version,
+    /// backing up the original and persisting the result.
+    fn migrate_config(&mut self, name: &str, plugin: &dyn Plugin) {
+        let Some(toml::Value::Table(section)) = self.config_store.get(name) else {
+            return;
+        };
+        let from = migrations::section_version(section);
+        let to = plugin.config_version();
+        if from >= to {
+            return;
+        }
+
+        let result = migrations::migrate(plugin, section).and_then(|migrated| {
+            let backup = migrations::backup_section(name, from, section)?;
+            Ok((migrated, backup))
+        });
+        let (migrated, backup) = match result {
+            Ok(done) => done,
+            Err(e) => {
+                eprintln!(
+                    "{} Could not migrate config for plugin '{}', leaving it unchanged: {:#}",
+                    style_warning(icon_warning()),
+                    name,
+                    e
+                );
+                return;
+            }
+        };
+
+        self.config_store
+            .insert(name.to_string(), toml::Value::Table(migrated));
+        if let Err(e) = self.write_config() {
+            eprintln!("Failed to save migrated config: {}", e);
+        }
+        if let Some(audit) = &self.audit {
+            let detail = format!("migrated config from v{} to v{}", from, to);
+            if let Err(e) = audit.record("config", name, &detail) {
+                eprintln!("Failed to write audit entry: {}", e);
+            }
+        }
+        println!(
+            "Migrated config for plugin '{}' from v{} to v{} (backup: {})",
+            name,
+            from,
+            to,
+            backup.display()
+        );
+    }
+
     fn is_plugin_enabled(&self, name: &str, meta: &PluginMetadata) -> bool {
         if meta.essential {
             return true;
@@ -124,14 +514,141 @@ impl PluginManager {
         true
     }
 
+    /// Plugin libraries found so far this run, in discovery order
+    pub fn discovered_libraries(&self) -> &[PathBuf] {
+        &self.discovered
+    }
+
+    /// Names of all loaded plugins, sorted
+    pub fn plugin_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.plugins.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Libraries that failed to load so far this run
+    pub fn load_errors(&self) -> &[LoadError] {
+        &self.load_errors
+    }
+
+    /// The loaded plugin `name` as its concrete type. Only statically
+    /// registered plugins can be reached this way: a library's types are
+    /// distinct from the host's even when they share a name.
+    pub fn plugin<T: Plugin>(&self, name: &str) -> Option<&T> {
+        self.plugins.get(name)?.instance.downcast_ref()
+    }
+
+    /// Mutable form of [`Self::plugin`].
+    pub fn plugin_mut<T: Plugin>(&mut self, name: &str) -> Option<&mut T> {
+        self.plugins.get_mut(name)?.instance.downcast_mut()
+    }
+
+    /// Metadata reported by a loaded plugin
+    pub fn metadata(&self, name: &str) -> Option<&PluginMetadata> {
+        self.plugins.get(name).map(|p| &p.metadata)
+    }
+
+    /// Whether plugin `name` is enabled, if it's loaded
+    pub fn is_enabled(&self, name: &str) -> Option<bool> {
+        self.plugins.get(name).map(|p| p.enabled)
+    }
+
+    /// The library plugin `name` was loaded from
+    pub fn library(&self, name: &str) -> Option<&Path> {
+        self.plugins.get(name).and_then(|p| p.library.as_deref())
+    }
+
+    /// The commands plugin `name` offers, whether or not it's enabled
+    pub fn commands(&self, name: &str) -> Option<Vec<PluginCommand>> {
+        let plugin = self.plugins.get(name)?;
+        let mut commands = plugin.instance.get_commands();
+        for extra in self.contributed.get(name).into_iter().flatten() {
+            commands.retain(|c| c.name != extra.name);
+            commands.push(extra.clone());
+        }
+        Some(commands)
+    }
+
+    /// Turns plugin `name` on or off by writing `enabled` to its config
+    /// section, so the choice sticks for later runs. Essential plugins can't
+    /// be disabled.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<()> {
+        let Some(plugin) = self.plugins.get_mut(name) else {
+            bail!("No plugin named '{}' is loaded", name);
+        };
+        if !enabled && plugin.metadata.essential {
+            bail!("Plugin '{}' is essential and can't be disabled", name);
+        }
+        plugin.enabled = enabled;
+
+        // A new section starts out in the plugin's current layout
+        let version = plugin.instance.config_version();
+        let section = self
+            .config_store
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let mut section = toml::Table::new();
+                section.insert(
+                    migrations::VERSION_KEY.to_string(),
+                    toml::Value::Integer(version.into()),
+                );
+                toml::Value::Table(section)
+            });
+        let toml::Value::Table(section) = section else {
+            bail!("[{}] in the config is not a table", name);
+        };
+        section.insert("enabled".to_string(), toml::Value::Boolean(enabled));
+        self.write_config()?;
+        if let Some(audit) = &self.audit {
+            let detail = if enabled { "enabled" } else { "disabled" };
+            if let Err(e) = audit.record("config", name, detail) {
+                eprintln!("Failed to write audit entry: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// The config keys plugin `name` declares
+    pub fn config_schema(&self, name: &str) -> Option<Vec<ConfigKey>> {
+        self.plugins.get(name).map(|p| p.instance.config_schema())
+    }
+
+    /// Tips from every enabled plugin, paired with the plugin's name
+    pub fn tips(&self) -> Vec<(String, Tip)> {
+        let mut tips: Vec<(String, Tip)> = self
+            .plugins
+            .iter()
+            .filter(|(_, plugin)| plugin.enabled)
+            .flat_map(|(name, plugin)| {
+                plugin
+                    .instance
+                    .tips()
+                    .into_iter()
+                    .map(move |tip| (name.clone(), tip))
+            })
+            .collect();
+        tips.sort_by(|a, b| (&a.0, &a.1.id).cmp(&(&b.0, &b.1.id)));
+        tips
+    }
+
+    /// The manifest shipped alongside a plugin, if it has one
+    pub fn manifest(&self, name: &str) -> Option<&PluginManifest> {
+        self.plugins.get(name).and_then(|p| p.manifest.as_ref())
+    }
+
     /// Returns all commands from all loaded and enabled plugins
     /// Returns a HashMap of plugin_name -> Vec<PluginCommand>
+    /// Commands contributed for this run replace declared ones of the same name.
     pub fn get_all_plugin_commands(&self) -> HashMap<String, Vec<PluginCommand>> {
         let mut result = HashMap::new();
 
         for (name, plugin) in &self.plugins {
             if plugin.enabled {
-                let commands = plugin.instance.get_commands();
+                let mut commands = plugin.instance.get_commands();
+                for extra in self.contributed.get(name).into_iter().flatten() {
+                    commands.retain(|c| c.name != extra.name);
+                    commands.push(extra.clone());
+                }
                 if !commands.is_empty() {
                     result.insert(name.clone(), commands);
                 }
@@ -141,38 +658,1059 @@ impl PluginManager {
         result
     }
 
-    /// The Central Event Bus Dispatcher
-    /// This replaces the old `EventBus` struct.
+    /// Asks every enabled plugin for the commands it contributes this run
+    /// (`Plugin::contribute_commands`). Call it before building the CLI.
+    pub fn collect_contributed_commands(&mut self) {
+        let limits = EventLimits::from_config(&self.config_store);
+        let mut contributed: HashMap<String, Vec<PluginCommand>> = HashMap::new();
+        let emitted = self.call_plugins(
+            &limits,
+            "contribute_commands",
+            |_| true,
+            |name, plugin, ctx| {
+                let commands = plugin.contribute_commands(ctx)?;
+                if !commands.is_empty() {
+                    contributed.insert(name.to_string(), commands);
+                }
+                Ok(())
+            },
+        );
+        self.contributed = contributed;
+        for event in emitted {
+            self.fire_event(event);
+        }
+    }
+
+    /// The plugin named in `[cli] fallback`, which gets invocations no
+    /// command matches.
+    pub fn fallback_plugin(&self) -> Option<&str> {
+        self.config_store
+            .get("cli")
+            .and_then(|cli| cli.get("fallback"))
+            .and_then(|v| v.as_str())
+    }
+
+    /// Offers `argv` (everything after `drk`) to the fallback plugin. Returns
+    /// its name if it took the invocation; a plugin that fails counts as
+    /// having taken it, since it has already reported why.
+    pub fn run_fallback(&mut self, argv: &[String]) -> Option<String> {
+        let name = self.fallback_plugin()?.to_string();
+        match self.plugins.get(&name) {
+            Some(plugin) if plugin.enabled => {}
+            Some(_) => return None,
+            None => {
+                eprintln!(
+                    "{} [cli] fallback names '{}', which isn't loaded",
+                    style_warning(icon_warning()),
+                    name
+                );
+                return None;
+            }
+        }
+
+        let limits = EventLimits::from_config(&self.config_store);
+        let mut handled = false;
+        let emitted = self.call_plugins(
+            &limits,
+            "handle_unknown_command",
+            |n| n == name,
+            |_, plugin, ctx| {
+                let result = plugin.handle_unknown_command(argv, ctx);
+                handled = result.as_ref().map_or(true, |taken| *taken);
+                result.map(|_| ())
+            },
+        );
+        for event in emitted {
+            self.fire_event(event);
+        }
+        handled.then_some(name)
+    }
+
+    /// Runs `Plugin::on_install` for every enabled plugin that hasn't had it
+    /// yet. Which plugins have is remembered in the state store, so without
+    /// one nothing runs.
+    pub fn run_install_hooks(&mut self) {
+        let Some(store) = &self.state else {
+            return;
+        };
+        let installed: BTreeMap<String, String> = store.get(INSTALLED_KEY).unwrap_or_default();
+        let pending: Vec<String> = self
+            .plugins
+            .iter()
+            .filter(|(name, plugin)| plugin.enabled && !installed.contains_key(*name))
+            .map(|(name, _)| name.clone())
+            .collect();
+        if !pending.is_empty() {
+            self.install(&pending);
+        }
+    }
+
+    /// Runs `name`'s `on_install` again, as if it had just been installed.
+    pub fn reinstall(&mut self, name: &str) -> Result<()> {
+        match self.plugins.get(name) {
+            None => bail!("No plugin named '{}' is loaded", name),
+            Some(plugin) if !plugin.enabled => bail!("Plugin '{}' is disabled", name),
+            Some(_) => {}
+        }
+        if !self
+            .install(&[name.to_string()])
+            .contains(&name.to_string())
+        {
+            bail!("on_install of plugin '{}' failed", name);
+        }
+        Ok(())
+    }
+
+    /// Calls `on_install` on `names` and records the ones that succeeded,
+    /// saving any config they created. Returns those names.
+    fn install(&mut self, names: &[String]) -> Vec<String> {
+        let limits = EventLimits::from_config(&self.config_store);
+        let config_before = self.config_store.clone();
+        let mut done = Vec::new();
+        let emitted = self.call_plugins(
+            &limits,
+            "on_install",
+            |name| names.iter().any(|n| n == name),
+            |name, plugin, ctx| {
+                plugin.on_install(ctx)?;
+                done.push(name.to_string());
+                Ok(())
+            },
+        );
+
+        // A section on_install created is in the plugin's current layout
+        for name in &done {
+            let Some(plugin) = self.plugins.get(name) else {
+                continue;
+            };
+            if let Some(toml::Value::Table(section)) = self.config_store.get_mut(name) {
+                if !config_before.contains_key(name)
+                    && !section.contains_key(migrations::VERSION_KEY)
+                {
+                    let version = plugin.instance.config_version();
+                    section.insert(
+                        migrations::VERSION_KEY.to_string(),
+                        toml::Value::Integer(version.into()),
+                    );
+                }
+            }
+        }
+
+        if self.config_store != config_before {
+            if let Err(e) = self.write_config() {
+                eprintln!("Failed to save config written by on_install: {}", e);
+            }
+        }
+        if let Some(store) = &mut self.state {
+            let mut installed: BTreeMap<String, String> =
+                store.get(INSTALLED_KEY).unwrap_or_default();
+            for name in &done {
+                if let Some(plugin) = self.plugins.get(name) {
+                    installed.insert(name.clone(), plugin.metadata.version.clone());
+                }
+            }
+            if let Err(e) = store
+                .set(INSTALLED_KEY, &installed)
+                .and_then(|_| store.save())
+            {
+                eprintln!("Failed to record installed plugins: {:#}", e);
+            }
+        }
+
+        for event in emitted {
+            self.fire_event(event);
+        }
+        done
+    }
+
+    /// The central event dispatcher every plugin subscribes through, by
+    /// implementing `Plugin::handle_event`.
+    ///
+    /// Events emitted by plugins while handling `event` are queued and
+    /// delivered afterwards, breadth-first, within the `[events]` limits.
     pub fn fire_event(&mut self, event: SystemEvent) {
-        // We collect keys first to avoid borrowing `self.plugins` while iterating mutably
-        // (Though since we have ownership of the manager here, we can just iterate if careful,
-        // but collecting keys is often safer if plugins try to modify the manager later).
+        let limits = EventLimits::from_config(&self.config_store);
+        let mut queue = VecDeque::from([(event, 0usize)]);
+        let mut processed = 0;
+
+        while let Some((event, depth)) = queue.pop_front() {
+            processed += 1;
+            if processed > limits.max_queued {
+                eprintln!(
+                    "{} Event storm: more than {} events queued, dropping the remaining {}",
+                    style_warning(icon_warning()),
+                    limits.max_queued,
+                    queue.len() + 1
+                );
+                break;
+            }
+
+            let emitted = self.dispatch(&event, &limits);
+            if !emitted.is_empty() && depth >= limits.max_depth {
+                eprintln!(
+                    "{} Dropping {} event(s) nested deeper than {} levels",
+                    style_warning(icon_warning()),
+                    emitted.len(),
+                    limits.max_depth
+                );
+                continue;
+            }
+            queue.extend(emitted.into_iter().map(|e| (e, depth + 1)));
+        }
+    }
+
+    /// Fires `PreCommand` for the command `name` through each enabled
+    /// plugin's `Plugin::on_pre_command`, until one cancels it. Returns
+    /// that plugin's veto; the caller must then not run the command.
+    pub fn fire_pre_command(&mut self, name: &str, args: &[String]) -> Option<Veto> {
+        let limits = EventLimits::from_config(&self.config_store);
+        let event = SystemEvent::PreCommand {
+            name: name.to_string(),
+            args: args.to_vec(),
+        };
+        let during = format!("event {}", events::describe_event(&event));
+        let during = redact::mask_text(&during, &self.secrets);
+        let mut veto = None;
+        let subscribed = self.subscribers(&event);
+        let emitted = self.call_plugins(
+            &limits,
+            &during,
+            |name| subscribed.contains(name),
+            |plugin_name, plugin, ctx| {
+                // Once cancelled, the command isn't about to run anymore
+                if veto.is_some() {
+                    return Ok(());
+                }
+                if let ControlFlow::Cancel(reason) = plugin.on_pre_command(name, args, ctx)? {
+                    veto = Some(Veto {
+                        plugin: plugin_name.to_string(),
+                        reason,
+                    });
+                }
+                Ok(())
+            },
+        );
+        for event in emitted {
+            self.fire_event(event);
+        }
+        veto
+    }
+
+    /// Like [`Self::fire_event`], also returning which plugins failed to
+    /// handle `event` or the events it led to. For `ExecuteCommand`, ask the
+    /// outcome about the plugin running the command.
+    ///
+    /// # Example
+    /// ```
+    /// # use drk_api::{CommandMatches, Context, Plugin, PluginMetadata, SystemEvent};
+    /// # use drk_core::manager::PluginManager;
+    /// struct Failing;
+    ///
+    /// impl Plugin for Failing {
+    ///     fn metadata(&self) -> PluginMetadata {
+    ///         PluginMetadata {
+    ///             name: "failing".to_string(),
+    ///             version: "0.1.0".to_string(),
+    ///             author: "drk contributors".to_string(),
+    ///             description: "Fails every command".to_string(),
+    ///             essential: false,
+    ///         }
+    ///     }
+    ///
+    ///     fn handle_event(&mut self, event: &SystemEvent, _: &mut Context) -> anyhow::Result<()> {
+    ///         match event {
+    ///             SystemEvent::ExecuteCommand { .. } => anyhow::bail!("no such template"),
+    ///             _ => Ok(()),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut manager = PluginManager::new();
+    /// manager.register_plugin(Box::new(Failing))?;
+    /// let outcome = manager.fire_event_with_result(SystemEvent::ExecuteCommand {
+    ///     plugin_name: "failing".to_string(),
+    ///     matches: CommandMatches {
+    ///         command_name: "fail".to_string(),
+    ///         path: vec!["fail".to_string()],
+    ///         args: Default::default(),
+    ///     },
+    /// });
+    /// assert!(outcome.failed("failing"));
+    /// assert_eq!(outcome.error("failing"), Some("no such template"));
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn fire_event_with_result(&mut self, event: SystemEvent) -> CommandOutcome {
+        let before = (self.failures.len(), self.outputs.len());
+        self.fire_event(event);
+        CommandOutcome {
+            failures: self.failures[before.0..].to_vec(),
+            outputs: self.outputs[before.1..].to_vec(),
+        }
+    }
 
-        // For this implementation, simple iteration is fine because `handle_event`
-        // takes `&mut Context`, not `&mut PluginManager`.
+    /// The token plugins see as `ctx.cancelled()`; trip it to ask them to stop
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
 
+    /// Lets every enabled plugin roll back after an interrupted command
+    pub fn run_cancel_hooks(&mut self) {
         for (name, plugin) in &mut self.plugins {
             if !plugin.enabled {
                 continue;
             }
+            if let Err(e) = plugin.instance.on_cancel() {
+                eprintln!("Error in plugin '{}' while cancelling: {}", name, e);
+            }
+        }
+    }
 
-            // Construct the context to pass into the plugin
-            // This exposes the config and a way to emit events (if we had a queue)
-            let mut ctx = Context {
-                config: &mut self.config_store,
-                event_sender: &mut |_evt| {
-                    // TODO: In a real system, you push this event to a queue
-                    // and process it after the current loop finishes to avoid recursion depth issues.
-                    println!(
-                        "Plugin {} tried to emit an event (nested events not yet implemented)",
-                        name
-                    );
-                },
+    /// Calls `on_unload` on plugin `name` and drops it, then its library.
+    pub fn unload_plugin(&mut self, name: &str) -> Result<()> {
+        let Some(plugin) = self.plugins.remove(name) else {
+            bail!("No plugin named '{}' is loaded", name);
+        };
+        self.contributed.remove(name);
+        unload(plugin).with_context(|| format!("on_unload of plugin '{}' failed", name))
+    }
+
+    /// Unloads plugin `name` and loads its library again, e.g. after it was
+    /// rebuilt. The new build's commands are in the next
+    /// [`Self::get_all_plugin_commands`].
+    pub fn reload_plugin(&mut self, name: &str) -> Result<()> {
+        let Some(plugin) = self.plugins.get(name) else {
+            bail!("No plugin named '{}' is loaded", name);
+        };
+        let Some(path) = plugin.library.clone() else {
+            bail!("Plugin '{}' is built into drk and can't be reloaded", name);
+        };
+        if let Err(e) = self.unload_plugin(name) {
+            eprintln!("{} {:#}", style_warning(icon_warning()), e);
+        }
+
+        // The dynamic loader hands back the old image for a path it has
+        // opened before, since Rust libraries are rarely really unloaded, so
+        // the new build is opened from a copy under a fresh name
+        let copy = reload_copy(&path)?;
+        // We use unsafe here because loading arbitrary DLLs is inherently unsafe
+        let result = unsafe { self.load_plugin_as(&copy, &path) };
+        // Once mapped the file isn't needed, except on Windows where it's locked
+        let _ = std::fs::remove_file(&copy);
+        result
+    }
+
+    /// Loads the plugin library at `path`, returning why it couldn't be loaded.
+    pub fn load_plugin_file(&mut self, path: &Path) -> Result<()> {
+        // We use unsafe here because loading arbitrary DLLs is inherently unsafe
+        unsafe { self.load_plugin(path) }
+    }
+
+    /// Fires `Shutdown`, then unloads every plugin. Failures are reported
+    /// rather than returned, so one plugin can't keep the rest from cleaning
+    /// up. Does nothing once everything is unloaded.
+    pub fn unload_all(&mut self) {
+        if self.plugins.is_empty() {
+            return;
+        }
+        self.fire_event(SystemEvent::Shutdown);
+        for (name, plugin) in std::mem::take(&mut self.plugins) {
+            if let Err(e) = unload(plugin) {
+                eprintln!("Error in plugin '{}' while unloading: {}", name, e);
+            }
+        }
+        self.contributed.clear();
+
+        // Handler time adds up across runs for `drk plugin stats`. The file
+        // is read again, since a command like `drk state gc` may have
+        // rewritten it since this store was opened
+        if let Some(store) = &mut self.state {
+            if !self.resource_stats.is_empty() {
+                let result = StateStore::open(store.path()).and_then(|mut current| {
+                    resources::add_to_totals(&mut current, &self.resource_stats)?;
+                    current.save()?;
+                    Ok(current)
+                });
+                match result {
+                    Ok(current) => *store = current,
+                    Err(e) => eprintln!("Failed to record plugin time: {:#}", e),
+                }
+            }
+        }
+    }
+
+    /// Masks `values`, e.g. of sensitive arguments, wherever the manager
+    /// prints events from now on.
+    pub fn redact(&mut self, values: &[String]) {
+        self.secrets.extend(values.iter().cloned());
+    }
+
+    /// Per-plugin counters for events they emitted
+    pub fn event_stats(&self) -> &HashMap<String, EventStats> {
+        &self.event_stats
+    }
+
+    /// How many errors `plugin` has returned this run; compare before and
+    /// after a call to tell whether it succeeded
+    pub fn errors_from(&self, plugin: &str) -> usize {
+        self.failures.iter().filter(|f| f.plugin == plugin).count()
+    }
+
+    /// Per-plugin resource usage measured around calls into the plugin
+    pub fn resource_stats(&self) -> &HashMap<String, ResourceStats> {
+        &self.resource_stats
+    }
+
+    /// Delivers one event to every enabled plugin and returns what they emitted.
+    ///
+    /// Subscribers of a broadcast event handle it at the same time, unless
+    /// `[events] concurrent = false`. A command's `ExecuteCommand` reaches
+    /// them one at a time: the command owns the terminal while it runs.
+    fn dispatch(&mut self, event: &SystemEvent, limits: &EventLimits) -> Vec<SystemEvent> {
+        let during = format!("event {}", events::describe_event(event));
+        let during = redact::mask_text(&during, &self.secrets);
+        let subscribed = self.subscribers(event);
+        let include = |name: &str| subscribed.contains(name);
+        let handle =
+            |_: &str, plugin: &mut dyn Plugin, ctx: &mut Context| plugin.handle_event(event, ctx);
+        let command = matches!(event, SystemEvent::ExecuteCommand { .. });
+        if limits.concurrent && !command && subscribed.len() > 1 {
+            self.call_plugins_concurrently(limits, &during, include, handle)
+        } else {
+            self.call_plugins(limits, &during, include, handle)
+        }
+    }
+
+    /// The plugins subscribed to `event`
+    fn subscribers(&self, event: &SystemEvent) -> HashSet<String> {
+        self.plugins
+            .iter()
+            .filter(|(name, plugin)| plugin.wants(name, event))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// The enabled plugins `include` accepts, by name
+    fn callable(&self, include: impl Fn(&str) -> bool) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .plugins
+            .iter()
+            .filter(|(name, plugin)| plugin.enabled && include(name))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Calls `call` on every enabled plugin `include` accepts with a full context, then persists
+    /// state and audits config changes. Returns the events they emitted.
+    fn call_plugins(
+        &mut self,
+        limits: &EventLimits,
+        during: &str,
+        include: impl Fn(&str) -> bool,
+        mut call: impl FnMut(&str, &mut dyn Plugin, &mut Context) -> Result<()>,
+    ) -> Vec<SystemEvent> {
+        let mut emitted = Vec::new();
+        let mut save_requested = false;
+
+        // One at a time, each seeing the config as the ones before left it
+        for name in self.callable(include) {
+            let setup = self.call_setup(&name);
+            // Only snapshot the config when someone is going to look at the diff
+            let before = self.audit.as_ref().map(|_| self.config_store.clone());
+            let Some(plugin) = self.plugins.get_mut(&name) else {
+                continue;
             };
+            let outcome = call_plugin(
+                &name,
+                plugin.instance.as_mut(),
+                &mut self.config_store,
+                &mut self.granted_once,
+                setup,
+                limits,
+                |plugin, ctx| call(&name, plugin, ctx),
+            );
+            let changed = before.map(|before| changed_sections(&before, &self.config_store));
+            let (events, save) = self.finish_call(&name, outcome, changed, during);
+            emitted.extend(events);
+            save_requested |= save;
+        }
+
+        if save_requested {
+            self.save_plugin_config();
+        }
+        emitted
+    }
 
-            if let Err(e) = plugin.instance.handle_event(&event, &mut ctx) {
-                eprintln!("Error in plugin '{}' during event {:?}: {}", name, event, e);
+    /// Like [`Self::call_plugins`], but every plugin runs on a thread of its
+    /// own, with its own copy of the config. Their changes are merged section
+    /// by section afterwards, in name order; when two plugins change the same
+    /// section differently, the later one's change is kept and reported.
+    fn call_plugins_concurrently(
+        &mut self,
+        limits: &EventLimits,
+        during: &str,
+        include: impl Fn(&str) -> bool,
+        call: impl Fn(&str, &mut dyn Plugin, &mut Context) -> Result<()> + Sync,
+    ) -> Vec<SystemEvent> {
+        let mut setups: HashMap<String, CallSetup> = self
+            .callable(include)
+            .into_iter()
+            .map(|name| {
+                let setup = self.call_setup(&name);
+                (name, setup)
+            })
+            .collect();
+        let base = &self.config_store;
+        let once = &self.granted_once;
+        let call = &call;
+
+        let mut results = std::thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .plugins
+                .iter_mut()
+                .filter_map(|(name, plugin)| {
+                    let setup = setups.remove(name)?;
+                    let instance = plugin.instance.as_mut();
+                    Some(scope.spawn(move || {
+                        let mut config = base.clone();
+                        let mut once = once.clone();
+                        let outcome = call_plugin(
+                            name,
+                            instance,
+                            &mut config,
+                            &mut once,
+                            setup,
+                            limits,
+                            |plugin, ctx| call(name, plugin, ctx),
+                        );
+                        (name.clone(), outcome, config, once)
+                    }))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect::<Vec<_>>()
+        });
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let base = self.config_store.clone();
+        let mut changed_by: HashMap<String, String> = HashMap::new();
+        let mut emitted = Vec::new();
+        let mut save_requested = false;
+        for (name, outcome, config, once) in results {
+            self.granted_once.extend(once);
+            let changed = changed_sections(&base, &config);
+            for section in &changed {
+                if let Some(earlier) = changed_by.get(section) {
+                    if self.config_store.get(section) != config.get(section) {
+                        eprintln!(
+                            "{} Plugins '{}' and '{}' both changed [{}] during {}; keeping the change from '{}'",
+                            style_warning(icon_warning()),
+                            earlier,
+                            name,
+                            section,
+                            during,
+                            name
+                        );
+                    }
+                }
+                match config.get(section) {
+                    Some(value) => self.config_store.insert(section.clone(), value.clone()),
+                    None => self.config_store.remove(section),
+                };
+                changed_by.insert(section.clone(), name.clone());
             }
+            let changed = self.audit.as_ref().map(|_| changed);
+            let (events, save) = self.finish_call(&name, outcome, changed, during);
+            emitted.extend(events);
+            save_requested |= save;
+        }
+
+        if save_requested {
+            self.save_plugin_config();
+        }
+        emitted
+    }
+
+    /// What a call into plugin `name` is handed besides the config
+    fn call_setup(&self, name: &str) -> CallSetup {
+        let saved_state = self
+            .state
+            .as_ref()
+            .and_then(|store| store.get(&state::plugin_key(name)))
+            .unwrap_or_default();
+        // Only measure the directory when there's a quota to hold it to
+        let data_dir = plugin_data::dir_for(name);
+        let over_quota = plugin_data::quota_for(&self.config_store, name)
+            .is_some_and(|quota| plugin_data::dir_size(&data_dir) > quota);
+        CallSetup {
+            saved_state,
+            data_dir,
+            over_quota,
+            cancel: self.cancel.clone(),
         }
     }
+
+    /// Records what a call into plugin `name` produced: its error, output,
+    /// state, counters, and the config sections it `changed` when they're
+    /// audited. Returns the events it emitted and whether the config is to
+    /// be saved.
+    fn finish_call(
+        &mut self,
+        name: &str,
+        outcome: CallOutcome,
+        changed: Option<Vec<String>>,
+        during: &str,
+    ) -> (Vec<SystemEvent>, bool) {
+        let CallOutcome {
+            result,
+            emitted,
+            handed_over,
+            mut save_requested,
+            saved_state,
+            state: plugin_state,
+            remembered,
+            events,
+            resources,
+        } = outcome;
+        self.event_stats
+            .entry(name.to_string())
+            .or_default()
+            .add(&events);
+        self.resource_stats
+            .entry(name.to_string())
+            .or_default()
+            .add(&resources);
+
+        if let Err(e) = result {
+            eprintln!("Error in plugin '{}' during {}: {}", name, during, e);
+            self.failures.push(PluginFailure {
+                plugin: name.to_string(),
+                error: format!("{:#}", e),
+            });
+        }
+        self.outputs
+            .extend(handed_over.into_iter().map(|output| PluginOutput {
+                plugin: name.to_string(),
+                output,
+            }));
+
+        if plugin_state != saved_state {
+            if let Some(store) = &mut self.state {
+                let quota = StateLimits::from_config(&self.config_store).plugin_quota_bytes;
+                let result = store
+                    .set_plugin(name, &plugin_state, quota)
+                    .and_then(|_| store.save());
+                if let Err(e) = result {
+                    eprintln!(
+                        "{} Not saving state of plugin '{}': {:#}",
+                        style_warning(icon_warning()),
+                        name,
+                        e
+                    );
+                }
+            }
+        }
+
+        if let (Some(audit), Some(changed)) = (&self.audit, changed) {
+            if !changed.is_empty() {
+                let detail = format!("changed sections: {}", changed.join(", "));
+                if let Err(e) = audit.record("config", name, &detail) {
+                    eprintln!("Failed to write audit entry: {}", e);
+                }
+            }
+        }
+
+        // Lasting permission answers are saved with the plugins' config changes
+        for (capability, allowed) in remembered {
+            permissions::remember(&mut self.config_store, name, capability, allowed);
+            save_requested = true;
+            if let Some(audit) = &self.audit {
+                let answer = if allowed { "allowed" } else { "denied" };
+                let detail = format!("{} {}", answer, capability.key());
+                if let Err(e) = audit.record("permission", name, &detail) {
+                    eprintln!("Failed to write audit entry: {}", e);
+                }
+            }
+        }
+        (emitted, save_requested)
+    }
+
+    /// Plugins' config changes are saved once all of them have run
+    fn save_plugin_config(&self) {
+        if let Err(e) = self.write_config() {
+            eprintln!("Failed to save config changed by plugins: {:#}", e);
+        }
+    }
+}
+
+/// What a call into a plugin is handed besides the config
+struct CallSetup {
+    saved_state: toml::Table,
+    data_dir: PathBuf,
+    over_quota: bool,
+    cancel: CancellationToken,
+}
+
+/// What one call into a plugin produced, recorded by the manager afterwards
+struct CallOutcome {
+    result: Result<()>,
+    emitted: Vec<SystemEvent>,
+    handed_over: Vec<CommandOutput>,
+    save_requested: bool,
+    saved_state: toml::Table,
+    state: toml::Table,
+    remembered: Vec<(Capability, bool)>,
+    events: EventStats,
+    resources: ResourceStats,
+}
+
+/// Calls into plugin `name` through `call`, with a context over `config`.
+/// Touches nothing of the manager's, so plugins can be called side by side.
+fn call_plugin(
+    name: &str,
+    plugin: &mut dyn Plugin,
+    config: &mut HashMap<String, toml::Value>,
+    once: &mut HashSet<(String, Capability)>,
+    setup: CallSetup,
+    limits: &EventLimits,
+    call: impl FnOnce(&mut dyn Plugin, &mut Context) -> Result<()>,
+) -> CallOutcome {
+    let mut emitted = Vec::new();
+    let mut handed_over = Vec::new();
+    let mut save_requested = false;
+    let mut stats = EventStats::default();
+    let mut plugin_state = setup.saved_state.clone();
+
+    // Cleanup the plugin registers only matters until its handler returns
+    let mut exit_guards = Vec::new();
+    let mut gate = Gate::new(config, name, once);
+
+    // Construct the context to pass into the plugin
+    let mut ctx = Context {
+        config,
+        event_sender: &mut |evt| {
+            // A request to the host rather than an event for other plugins
+            if is_save_config_request(&evt) {
+                save_requested = true;
+                return;
+            }
+            if let Some(output) = output_request(&evt) {
+                match output {
+                    Ok(output) => handed_over.push(output),
+                    Err(e) => eprintln!(
+                        "{} Ignoring output from plugin '{}': {}",
+                        style_warning(icon_warning()),
+                        name,
+                        e
+                    ),
+                }
+                return;
+            }
+            stats.emitted += 1;
+            if let Some(size) = events::event_size(&evt) {
+                stats.largest_payload = stats.largest_payload.max(size);
+            }
+
+            match events::check(&evt, limits) {
+                Verdict::Accept => emitted.push(evt),
+                Verdict::Warn(size) => {
+                    eprintln!(
+                        "{} Plugin '{}' emitted a large event payload ({} bytes)",
+                        style_warning(icon_warning()),
+                        name,
+                        size
+                    );
+                    emitted.push(evt);
+                }
+                Verdict::Reject(size) => {
+                    stats.dropped += 1;
+                    eprintln!(
+                        "{} Dropped event from plugin '{}': payload of {} bytes exceeds the {} byte limit",
+                        style_warning(icon_warning()),
+                        name,
+                        size,
+                        limits.max_payload_bytes
+                    );
+                }
+            }
+        },
+        state: &mut plugin_state,
+        data_dir: (!setup.over_quota).then_some(setup.data_dir.as_path()),
+        cancel: &setup.cancel,
+        on_exit: &mut |handler| exit_guards.push(cleanup::register(handler)),
+        permit: &mut |capability| gate.request(capability),
+    };
+
+    let mut resources = ResourceStats::default();
+    let probe = Probe::start();
+    let result = call(plugin, &mut ctx);
+    probe.finish(&mut resources);
+    drop(exit_guards);
+
+    CallOutcome {
+        result,
+        emitted,
+        handed_over,
+        save_requested,
+        saved_state: setup.saved_state,
+        state: plugin_state,
+        remembered: gate.remembered,
+        events: stats,
+        resources,
+    }
+}
+
+/// The top-level sections that differ between `before` and `after`, sorted
+fn changed_sections(
+    before: &HashMap<String, toml::Value>,
+    after: &HashMap<String, toml::Value>,
+) -> Vec<String> {
+    let mut changed: Vec<String> = after
+        .keys()
+        .chain(before.keys())
+        .filter(|k| before.get(*k) != after.get(*k))
+        .cloned()
+        .collect();
+    changed.sort();
+    changed.dedup();
+    changed
+}
+
+/// Copies the library at `path` to a name the dynamic loader hasn't seen.
+fn reload_copy(path: &Path) -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join("drk-reload");
+    std::fs::create_dir_all(&dir)?;
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut copy = dir.join(format!("{}-{}", stem, stamp));
+    if let Some(ext) = path.extension() {
+        copy.set_extension(ext);
+    }
+    std::fs::copy(path, &copy).with_context(|| format!("Could not copy {:?}", path))?;
+    Ok(copy)
+}
+
+/// Runs `on_unload` if it's due and drops the instance before the library
+/// its code lives in.
+fn unload(plugin: LoadedPlugin) -> Result<()> {
+    let LoadedPlugin {
+        mut instance,
+        _lib: lib,
+        started,
+        ..
+    } = plugin;
+    let result = if started {
+        instance.on_unload()
+    } else {
+        Ok(())
+    };
+    drop(instance);
+    drop(lib);
+    result
+}
+
+/// Instantiates the plugin in `lib` through the compiler-independent
+/// `_plugin_vtable` when it exports one. Older plugins are loaded through
+/// `_plugin_create_v2` or the original `_plugin_create`, which only work when
+/// they were built with the same rustc as drk.
+unsafe fn create_instance(lib: &Library) -> Result<Box<dyn Plugin>> {
+    if let Ok(vtable) = lib.get::<unsafe extern "C" fn() -> *const PluginVTable>(b"_plugin_vtable")
+    {
+        return Ok(Box::new(StablePlugin::new(vtable())?));
+    }
+
+    // The rest hand over Rust trait objects, which only line up when both
+    // sides were built from the same drk-api by the same compiler
+    check_same_build(lib)?;
+
+    type CreateV2 = unsafe extern "C" fn() -> *const PluginDeclaration;
+
+    if let Ok(declare) = lib.get::<CreateV2>(b"_plugin_create_v2") {
+        let declaration = &*declare();
+        // Only the version field is known to be laid out as expected
+        if declaration.api_version != API_VERSION {
+            bail!(
+                "Plugin was built against drk-api v{}, but this drk needs v{}; rebuild the plugin",
+                declaration.api_version,
+                API_VERSION
+            );
+        }
+        return Ok((declaration.create)());
+    }
+
+    // These signatures MUST match the functions `declare_plugin!` exports
+    let func: Symbol<unsafe extern "C" fn() -> *mut dyn Plugin> = lib
+        .get(b"_plugin_create")
+        .context("Could not find '_plugin_create' symbol. Is this a valid drk plugin?")?;
+    // We own the memory the creator leaks
+    Ok(Box::from_raw(func()))
+}
+
+/// Refuses a plugin unless it reports the same drk-api version and rustc as
+/// drk's own. Plugins from before `declare_plugin!` exported these can't be
+/// checked, so they're refused too.
+unsafe fn check_same_build(lib: &Library) -> Result<()> {
+    const REBUILD: &str = "rebuild the plugin against this drk-api";
+    let api_version = lib
+        .get::<unsafe extern "C" fn() -> u32>(b"_plugin_api_version")
+        .with_context(|| format!("Plugin doesn't report its drk-api version; {}", REBUILD))?;
+    let api_version = api_version();
+    if api_version != API_VERSION {
+        bail!(
+            "Plugin was built against drk-api v{}, but this drk needs v{}; rebuild the plugin",
+            api_version,
+            API_VERSION
+        );
+    }
+    let rustc = lib
+        .get::<unsafe extern "C" fn() -> *const c_char>(b"_plugin_rustc_version")
+        .with_context(|| format!("Plugin doesn't report its compiler; {}", REBUILD))?;
+    let rustc = CStr::from_ptr(rustc()).to_string_lossy();
+    if rustc != RUSTC_VERSION {
+        bail!(
+            "Plugin was built with {}, but drk with {}; rebuild the plugin with the same compiler",
+            rustc,
+            RUSTC_VERSION
+        );
+    }
+    Ok(())
+}
+
+fn is_save_config_request(event: &SystemEvent) -> bool {
+    matches!(event, SystemEvent::Custom { event, .. } if event == SAVE_CONFIG_EVENT)
+}
+
+/// The output a `ctx.output` call carries, if `event` is one.
+fn output_request(event: &SystemEvent) -> Option<Result<CommandOutput, String>> {
+    let SystemEvent::Custom { event, payload, .. } = event else {
+        return None;
+    };
+    if event != OUTPUT_EVENT {
+        return None;
+    }
+    let json = payload
+        .as_ref()
+        .and_then(|p| p.downcast_ref::<String>())
+        .ok_or_else(|| "it carries no JSON".to_string());
+    Some(json.and_then(|json| serde_json::from_str(json).map_err(|e| e.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    /// Counts itself in on a `Custom` event, then waits for the others to
+    /// arrive, and writes `[<name>] seen` and `[shared] by` to the config.
+    struct Waiter {
+        name: &'static str,
+        arrived: Arc<AtomicUsize>,
+        expected: usize,
+        patience: Duration,
+        saw_everyone: Arc<AtomicUsize>,
+    }
+
+    impl Plugin for Waiter {
+        fn metadata(&self) -> PluginMetadata {
+            PluginMetadata {
+                name: self.name.to_string(),
+                version: "0.1.0".to_string(),
+                author: "drk contributors".to_string(),
+                description: "Waits for the other plugins".to_string(),
+                essential: false,
+            }
+        }
+
+        fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> Result<()> {
+            if !matches!(event, SystemEvent::Custom { .. }) {
+                return Ok(());
+            }
+            self.arrived.fetch_add(1, Ordering::SeqCst);
+            let deadline = Instant::now() + self.patience;
+            while self.arrived.load(Ordering::SeqCst) < self.expected {
+                if Instant::now() > deadline {
+                    return Ok(());
+                }
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            self.saw_everyone.fetch_add(1, Ordering::SeqCst);
+
+            let mut section = toml::Table::new();
+            section.insert("seen".to_string(), true.into());
+            ctx.config
+                .insert(self.name.to_string(), toml::Value::Table(section));
+            let mut shared = toml::Table::new();
+            shared.insert("by".to_string(), self.name.into());
+            ctx.config
+                .insert("shared".to_string(), toml::Value::Table(shared));
+            Ok(())
+        }
+    }
+
+    /// A manager with `names` registered as [`Waiter`]s, each waiting up to
+    /// `patience`, and the counter of those that saw all of them arrive.
+    fn waiters(names: &[&'static str], patience: Duration) -> (PluginManager, Arc<AtomicUsize>) {
+        let mut manager = PluginManager::new();
+        let arrived = Arc::new(AtomicUsize::new(0));
+        let saw_everyone = Arc::new(AtomicUsize::new(0));
+        for name in names {
+            let waiter = Waiter {
+                name,
+                arrived: arrived.clone(),
+                expected: names.len(),
+                patience,
+                saw_everyone: saw_everyone.clone(),
+            };
+            manager.register_plugin(Box::new(waiter)).unwrap();
+        }
+        (manager, saw_everyone)
+    }
+
+    #[test]
+    fn subscribers_handle_a_broadcast_event_together() {
+        let (mut manager, saw_everyone) = waiters(&["a", "b", "c"], Duration::from_secs(5));
+        manager.fire_event(SystemEvent::custom("test", "ping"));
+        assert_eq!(saw_everyone.load(Ordering::SeqCst), 3);
+        manager.unload_all();
+    }
+
+    #[test]
+    fn config_changes_of_concurrent_plugins_are_merged() {
+        let (mut manager, _) = waiters(&["a", "b"], Duration::from_secs(5));
+        manager.fire_event(SystemEvent::custom("test", "ping"));
+        let config = manager.config();
+        assert_eq!(config["a"].get("seen"), Some(&toml::Value::Boolean(true)));
+        assert_eq!(config["b"].get("seen"), Some(&toml::Value::Boolean(true)));
+        // Both changed it; the later name's change is kept
+        assert_eq!(
+            config["shared"].get("by").and_then(|v| v.as_str()),
+            Some("b")
+        );
+        manager.unload_all();
+    }
+
+    #[test]
+    fn plugins_take_turns_when_concurrency_is_off() {
+        let (mut manager, saw_everyone) = waiters(&["a", "b"], Duration::from_millis(200));
+        let events: toml::Table = toml::from_str("concurrent = false").unwrap();
+        manager
+            .config_mut()
+            .insert("events".to_string(), toml::Value::Table(events));
+        // The first to run gives up waiting for the second
+        manager.fire_event(SystemEvent::custom("test", "ping"));
+        assert_eq!(saw_everyone.load(Ordering::SeqCst), 1);
+        manager.unload_all();
+    }
 }
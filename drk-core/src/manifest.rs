@@ -0,0 +1,103 @@
+//! Optional per-plugin manifest files.
+//!
+//! A manifest is a TOML file sitting next to the plugin library with the same
+//! file stem (`libdrk_nix.so` -> `libdrk_nix.toml`). It carries host-side
+//! settings that don't belong in the compiled `PluginMetadata`.
+//!
+//! ```toml
+//! version = "0.2.0"
+//! changelog = """
+//! ## 0.2.0
+//! - Added `drk nix update`
+//! """
+//!
+//! [build]
+//! flake = "github:dark1zinn/drk#drk-nix"
+//!
+//! [container]
+//! image = "ghcr.io/example/drk-nix:latest"
+//! env = ["GITHUB_TOKEN"]
+//! ```
+
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginManifest {
+    /// Version of the library this manifest ships with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Markdown release notes, newest release first. Shown on `drk plugin upgrade`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changelog: Option<String>,
+    /// Run this plugin's commands inside a container instead of in-process.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<ContainerSpec>,
+    /// How to build this plugin from source.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<BuildSpec>,
+}
+
+/// A reproducible source for the plugin.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildSpec {
+    /// Flake output that builds the plugin, e.g. `github:owner/repo#plugin`.
+    pub flake: String,
+}
+
+/// Where and how a containerized plugin runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerSpec {
+    pub image: String,
+    /// "podman" or "docker". Falls back to `[container] runtime` in config, then auto-detection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<String>,
+    /// Names of host environment variables passed into the container.
+    #[serde(default)]
+    pub env: Vec<String>,
+    /// Extra `host:container` volume mounts.
+    #[serde(default)]
+    pub mounts: Vec<String>,
+    /// Where the current directory is mounted inside the container.
+    #[serde(default = "default_workdir")]
+    pub workdir: String,
+    /// The drk binary inside the image.
+    #[serde(default = "default_entrypoint")]
+    pub entrypoint: String,
+}
+
+fn default_workdir() -> String {
+    "/workspace".to_string()
+}
+
+fn default_entrypoint() -> String {
+    "drk".to_string()
+}
+
+impl PluginManifest {
+    /// The manifest path for a given plugin library.
+    pub fn path_for(library: &Path) -> PathBuf {
+        library.with_extension("toml")
+    }
+
+    /// Writes the manifest next to `library`.
+    pub fn save_for(&self, library: &Path) -> Result<()> {
+        let path = Self::path_for(library);
+        std::fs::write(&path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Could not write plugin manifest at {:?}", path))
+    }
+
+    /// Loads the manifest next to `library`, if there is one.
+    pub fn load_for(library: &Path) -> Result<Option<Self>> {
+        let path = Self::path_for(library);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not read plugin manifest at {:?}", path))?;
+        let manifest = toml::from_str(&content)
+            .with_context(|| format!("Invalid plugin manifest at {:?}", path))?;
+        Ok(Some(manifest))
+    }
+}
@@ -0,0 +1,67 @@
+//! Versioned migrations for plugin config sections.
+//!
+//! A plugin declares the layout version it expects through
+//! `Plugin::config_version` and upgrades older sections one step at a time in
+//! `Plugin::migrate_config`. The version a section is at is stored inside it:
+//!
+//! ```toml
+//! [basic]
+//! config_version = 1
+//! greeting_prefix = "Hi"
+//! ```
+//!
+//! Sections without the key count as version 0.
+
+use crate::paths;
+use anyhow::{Context as _, Result};
+use drk_api::Plugin;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Key inside a plugin's section that records its layout version.
+pub const VERSION_KEY: &str = "config_version";
+
+/// The layout version recorded in `section`.
+pub fn section_version(section: &toml::Table) -> u32 {
+    section
+        .get(VERSION_KEY)
+        .and_then(|v| v.as_integer())
+        .and_then(|v| u32::try_from(v).ok())
+        .unwrap_or(0)
+}
+
+/// Runs every step from the section's version up to the plugin's and returns
+/// the migrated copy. `section` itself is left untouched, so a failing step
+/// never leaves a half-migrated config behind.
+pub fn migrate(plugin: &dyn Plugin, section: &toml::Table) -> Result<toml::Table> {
+    let target = plugin.config_version();
+    let mut migrated = section.clone();
+
+    for from in section_version(section)..target {
+        plugin
+            .migrate_config(from, &mut migrated)
+            .with_context(|| format!("Config migration from v{} to v{} failed", from, from + 1))?;
+    }
+
+    migrated.insert(VERSION_KEY.to_string(), toml::Value::Integer(target.into()));
+    Ok(migrated)
+}
+
+/// Saves the pre-migration `section` under the config directory's `backups/`.
+pub fn backup_section(plugin: &str, version: u32, section: &toml::Table) -> Result<PathBuf> {
+    let dir = paths::config_dir().join("backups");
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Could not create backup directory {:?}", dir))?;
+
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path = dir.join(format!("{}.v{}.{}.toml", plugin, version, stamp));
+
+    let mut wrapper = toml::Table::new();
+    wrapper.insert(plugin.to_string(), toml::Value::Table(section.clone()));
+    std::fs::write(&path, toml::to_string_pretty(&wrapper)?)
+        .with_context(|| format!("Could not write config backup at {:?}", path))?;
+    Ok(path)
+}
@@ -0,0 +1,108 @@
+//! Well-known filesystem locations used by drk.
+//!
+//! Everything resolves through `directories`, so Linux gets XDG paths,
+//! macOS gets `~/Library/...` and Windows gets `%APPDATA%`.
+
+use directories::{BaseDirs, ProjectDirs};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Extra plugin directories, separated like `PATH`.
+pub const PLUGIN_PATH_ENV: &str = "DRK_PLUGIN_PATH";
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "drk")
+}
+
+/// Directory holding `config.toml`.
+pub fn config_dir() -> PathBuf {
+    project_dirs()
+        .map(|d| d.config_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from(".drk"))
+}
+
+/// Directory for persistent data (audit log, state, installed plugins).
+pub fn data_dir() -> PathBuf {
+    project_dirs()
+        .map(|d| d.data_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from(".drk"))
+}
+
+/// Where `drk plugin install` puts plugin libraries.
+pub fn plugins_dir() -> PathBuf {
+    data_dir().join("plugins")
+}
+
+/// Directories searched for plugin libraries, lowest priority first: the
+/// system-wide directories, `./target/debug` for plugins under development,
+/// the user plugin directory, then `plugin_dirs` from config and finally
+/// `DRK_PLUGIN_PATH`. A plugin found in several of them is loaded from the
+/// last one.
+///
+/// # Example
+/// ```
+/// # use drk_core::paths;
+/// # use std::collections::HashMap;
+/// # use std::path::PathBuf;
+/// let mut config = HashMap::new();
+/// config.insert("plugin_dirs".to_string(), toml::toml! { dirs = ["/opt/drk"] }["dirs"].clone());
+/// let extra = std::env::join_paths(["/tmp/a", "/tmp/b"]).unwrap();
+/// std::env::set_var(paths::PLUGIN_PATH_ENV, extra);
+///
+/// let dirs = paths::plugin_search_path(&config);
+/// assert!(dirs.contains(&paths::plugins_dir()));
+/// assert_eq!(dirs[dirs.len() - 3..], ["/opt/drk", "/tmp/a", "/tmp/b"].map(PathBuf::from));
+/// ```
+pub fn plugin_search_path(config: &HashMap<String, toml::Value>) -> Vec<PathBuf> {
+    let mut dirs = system_plugin_dirs();
+    dirs.push(PathBuf::from("./target/debug"));
+    dirs.push(plugins_dir());
+
+    let configured = config
+        .get("plugin_dirs")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str());
+    dirs.extend(configured.map(expand_home));
+    if let Some(env) = std::env::var_os(PLUGIN_PATH_ENV) {
+        dirs.extend(std::env::split_paths(&env).filter(|p| !p.as_os_str().is_empty()));
+    }
+    dirs
+}
+
+/// Where a package manager or administrator puts plugins for every user.
+fn system_plugin_dirs() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        return std::env::var_os("ProgramData")
+            .map(|data| vec![PathBuf::from(data).join("drk").join("plugins")])
+            .unwrap_or_default();
+    }
+    let mut dirs = vec![PathBuf::from("/usr/lib/drk/plugins")];
+    if cfg!(target_os = "macos") {
+        dirs.push(PathBuf::from("/opt/homebrew/lib/drk/plugins"));
+    }
+    dirs.push(PathBuf::from("/usr/local/lib/drk/plugins"));
+    dirs
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), BaseDirs::new()) {
+        (Some(rest), Some(base)) => base.home_dir().join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// The main configuration file.
+pub fn config_file() -> PathBuf {
+    config_dir().join("config.toml")
+}
+
+/// The root of the project containing `start`: the nearest ancestor holding a
+/// `drk.toml` or `.git`.
+pub fn project_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join("drk.toml").exists() || dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
@@ -0,0 +1,180 @@
+//! Capabilities plugins ask for before using them (`ctx.request_permission`),
+//! and what the user answered.
+//!
+//! The first request for a capability prompts on the terminal:
+//!
+//! ```text
+//! [WARN] Plugin Nix wants to access the network
+//!   Allow? [o]nce, [a]lways, [d]eny:
+//! ```
+//!
+//! "Once" lasts for the current run. "Always" and "deny" are saved in config
+//! and asked no more; edit or remove the entry to change them:
+//!
+//! ```toml
+//! [permissions.Nix]
+//! network = "allow"
+//! process-spawn = "deny"
+//! ```
+//!
+//! Without a terminal to ask on, whatever config doesn't allow is denied.
+
+use drk_api::{icon_warning, style_dim, style_primary, style_warning, Capability};
+use std::collections::{HashMap, HashSet};
+use std::io::IsTerminal;
+use std::sync::Mutex;
+
+/// Held while a prompt waits for an answer, so plugins handling an event
+/// side by side ask one at a time.
+static PROMPT: Mutex<()> = Mutex::new(());
+
+/// The config section holding saved answers, one table per plugin.
+pub const SECTION: &str = "permissions";
+
+/// What config says about `plugin` using `capability`: `Some(true)` for
+/// "allow", `Some(false)` for "deny", `None` if it has never been answered.
+pub fn saved(
+    config: &HashMap<String, toml::Value>,
+    plugin: &str,
+    capability: Capability,
+) -> Option<bool> {
+    match config
+        .get(SECTION)?
+        .get(plugin)?
+        .get(capability.key())?
+        .as_str()?
+    {
+        "allow" => Some(true),
+        "deny" => Some(false),
+        _ => None,
+    }
+}
+
+/// Saves a lasting answer under `[permissions.<plugin>]`.
+pub fn remember(
+    config: &mut HashMap<String, toml::Value>,
+    plugin: &str,
+    capability: Capability,
+    allowed: bool,
+) {
+    let section = config
+        .entry(SECTION.to_string())
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    let Some(section) = section.as_table_mut() else {
+        return;
+    };
+    let plugin = section
+        .entry(plugin.to_string())
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    if let Some(plugin) = plugin.as_table_mut() {
+        let answer = if allowed { "allow" } else { "deny" };
+        plugin.insert(capability.key().to_string(), answer.into());
+    }
+}
+
+/// Answers one plugin's requests during a call into it. Lasting answers are
+/// collected in `remembered` for the caller to save once the call returns,
+/// since the plugin holds the config meanwhile.
+pub struct Gate<'a> {
+    plugin: &'a str,
+    /// Saved answers as of the start of the call
+    saved: HashMap<Capability, bool>,
+    /// (plugin, capability) allowed once during this run
+    once: &'a mut HashSet<(String, Capability)>,
+    pub remembered: Vec<(Capability, bool)>,
+}
+
+impl<'a> Gate<'a> {
+    pub fn new(
+        config: &HashMap<String, toml::Value>,
+        plugin: &'a str,
+        once: &'a mut HashSet<(String, Capability)>,
+    ) -> Self {
+        Self {
+            plugin,
+            saved: Capability::ALL
+                .into_iter()
+                .filter_map(|c| Some((c, saved(config, plugin, c)?)))
+                .collect(),
+            once,
+            remembered: Vec::new(),
+        }
+    }
+
+    /// Whether the plugin may use `capability`, asking the user if nobody
+    /// has answered yet.
+    pub fn request(&mut self, capability: Capability) -> bool {
+        if let Some(allowed) = self.saved.get(&capability) {
+            return *allowed;
+        }
+        if self.once.contains(&(self.plugin.to_string(), capability)) {
+            return true;
+        }
+
+        if !std::io::stdin().is_terminal() {
+            eprintln!(
+                "{} Plugin {} wants to {}, but there's no terminal to ask on. {}",
+                style_warning(icon_warning()),
+                style_primary(self.plugin),
+                capability.describe(),
+                style_dim(&format!(
+                    "To allow it, set {} = \"allow\" under [{}.{}] in the config",
+                    capability.key(),
+                    SECTION,
+                    self.plugin
+                ))
+            );
+            return false;
+        }
+
+        match ask(self.plugin, capability) {
+            Answer::Once => {
+                self.once.insert((self.plugin.to_string(), capability));
+                true
+            }
+            Answer::Always => {
+                self.remember(capability, true);
+                true
+            }
+            Answer::Deny => {
+                self.remember(capability, false);
+                false
+            }
+            Answer::Unclear => false,
+        }
+    }
+
+    fn remember(&mut self, capability: Capability, allowed: bool) {
+        self.saved.insert(capability, allowed);
+        self.remembered.push((capability, allowed));
+    }
+}
+
+enum Answer {
+    Once,
+    Always,
+    Deny,
+    /// Denied for now, but asked again next time
+    Unclear,
+}
+
+fn ask(plugin: &str, capability: Capability) -> Answer {
+    let _prompt = PROMPT.lock().unwrap_or_else(|e| e.into_inner());
+    eprintln!(
+        "{} Plugin {} wants to {}",
+        style_warning(icon_warning()),
+        style_primary(plugin),
+        capability.describe()
+    );
+    eprint!("  Allow? [o]nce, [a]lways, [d]eny: ");
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return Answer::Unclear;
+    }
+    match answer.trim().to_lowercase().as_str() {
+        "o" | "once" => Answer::Once,
+        "a" | "always" => Answer::Always,
+        "d" | "deny" => Answer::Deny,
+        _ => Answer::Unclear,
+    }
+}
@@ -0,0 +1,46 @@
+//! Per-plugin data directories.
+//!
+//! Each plugin gets `<data dir>/plugins/<name>/` through
+//! `ctx.plugin_data_dir()`. Directories can be capped in size; a plugin over
+//! its quota is refused the directory until `drk plugin clean <name>`.
+//!
+//! ```toml
+//! [plugin_data]
+//! quota_bytes = 104857600   # default for every plugin
+//!
+//! [nix]
+//! data_quota_bytes = 524288000  # override for one plugin
+//! ```
+
+use crate::paths;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The data directory of `plugin`. Not created until the plugin asks for it.
+pub fn dir_for(plugin: &str) -> PathBuf {
+    paths::plugins_dir().join(plugin)
+}
+
+/// The size limit for `plugin`'s directory, if one is configured.
+pub fn quota_for(config: &HashMap<String, toml::Value>, plugin: &str) -> Option<u64> {
+    let read = |section: &str, key: &str| {
+        config
+            .get(section)
+            .and_then(|s| s.get(key))
+            .and_then(|v| v.as_integer())
+            .filter(|v| *v > 0)
+            .map(|v| v as u64)
+    };
+    read(plugin, "data_quota_bytes").or_else(|| read("plugin_data", "quota_bytes"))
+}
+
+/// Total size of the files under `dir`; zero if it doesn't exist.
+pub fn dir_size(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
@@ -0,0 +1,57 @@
+//! Per-project settings from the `drk.toml` at a project root.
+//!
+//! ```toml
+//! # drk.toml
+//! [env]
+//! DATABASE_URL = "postgres://localhost/{env.USER}_dev"
+//! RUST_LOG = "debug"
+//! ```
+//!
+//! With shell integration (`drk shell-init`), the `[env]` variables are
+//! exported when entering the project and unset again when leaving it.
+//! The `[tools]` section is read by [`drk_api::tools::Catalog`], where
+//! plugins can reach it too.
+
+use crate::interpolate::interpolate;
+use anyhow::{Context as _, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Name of the per-project file.
+pub const PROJECT_FILE: &str = "drk.toml";
+
+/// The `drk.toml` of the project root `root`.
+pub fn project_file(root: &Path) -> PathBuf {
+    root.join(PROJECT_FILE)
+}
+
+/// The project's `[env]` variables with placeholders resolved. A project
+/// without `drk.toml` or without `[env]` has no variables.
+pub fn env_vars(
+    root: &Path,
+    config: &HashMap<String, toml::Value>,
+) -> Result<BTreeMap<String, String>> {
+    let path = project_file(root);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Could not read project file at {:?}", path))?;
+    let table: toml::Table = toml::from_str(&content)
+        .with_context(|| format!("Invalid TOML in project file at {:?}", path))?;
+
+    let Some(toml::Value::Table(env)) = table.get("env") else {
+        return Ok(BTreeMap::new());
+    };
+    env.iter()
+        .map(|(key, value)| {
+            let raw = match value {
+                toml::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            let resolved = interpolate(&raw, config)
+                .with_context(|| format!("In [env] {} of {:?}", key, path))?;
+            Ok((key.clone(), resolved))
+        })
+        .collect()
+}
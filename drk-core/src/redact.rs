@@ -0,0 +1,76 @@
+//! Masking the values of arguments declared `sensitive`.
+//!
+//! Plugins still receive the real values in `ExecuteCommand`; everything
+//! drk writes down or prints about the command (`PreCommand` args, history,
+//! the audit log, run records, error traces) gets [`MASK`] instead.
+
+use drk_api::{PluginCommand, VALUES_SEPARATOR};
+use std::collections::HashMap;
+
+pub const MASK: &str = "********";
+
+/// The values given for sensitive arguments of the commands on `path`
+/// (outermost first) under `top`, from the collected `args`.
+pub fn sensitive_values(
+    top: &PluginCommand,
+    path: &[String],
+    args: &HashMap<String, String>,
+) -> Vec<String> {
+    let mut commands = vec![top];
+    for name in path.iter().skip(1) {
+        match commands.last().and_then(|c| c.subcommand(name)) {
+            Some(nested) => commands.push(nested),
+            None => break,
+        }
+    }
+    commands
+        .iter()
+        .flat_map(|c| &c.args)
+        .filter(|arg| arg.sensitive)
+        .filter_map(|arg| args.get(&arg.name))
+        .flat_map(|value| value.split(VALUES_SEPARATOR))
+        .filter(|value| !value.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// `args` with every secret value masked, whether given as its own word or
+/// as `--flag=value`.
+///
+/// # Example
+/// ```
+/// # use drk_core::redact::{mask_args, mask_text};
+/// let args = ["deploy", "--token", "s3cret", "--key=abc", "prod"].map(String::from);
+/// let secrets = ["s3cret".to_string(), "abc".to_string()];
+/// assert_eq!(
+///     mask_args(&args, &secrets),
+///     ["deploy", "--token", "********", "--key=********", "prod"]
+/// );
+/// assert_eq!(mask_text("token s3cret", &secrets), "token ********");
+/// ```
+pub fn mask_args(args: &[String], secrets: &[String]) -> Vec<String> {
+    args.iter()
+        .map(|arg| {
+            if secrets.contains(arg) {
+                return MASK.to_string();
+            }
+            match arg.split_once('=') {
+                Some((flag, value))
+                    if flag.starts_with("--") && secrets.iter().any(|s| s == value) =>
+                {
+                    format!("{}={}", flag, MASK)
+                }
+                _ => arg.clone(),
+            }
+        })
+        .collect()
+}
+
+/// `text` with every occurrence of a secret masked, longest secrets first.
+pub fn mask_text(text: &str, secrets: &[String]) -> String {
+    let mut secrets: Vec<&String> = secrets.iter().filter(|s| !s.is_empty()).collect();
+    secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    secrets.into_iter().fold(text.to_string(), |text, secret| {
+        text.replace(secret.as_str(), MASK)
+    })
+}
@@ -0,0 +1,93 @@
+//! The plugin registry index: which plugins exist, where to download them
+//! and which commands they provide.
+//!
+//! The index is a JSON file published at the URL in `[registry] index`.
+//! `drk plugin update-index` saves a copy to `<data dir>/registry.json`, and
+//! everything else reads only that copy, so nothing goes to the network
+//! unasked.
+//!
+//! ```json
+//! { "plugins": [
+//!   { "name": "drk-docker", "description": "Docker helpers", "version": "0.2.0",
+//!     "url": "https://example.com/libdrk_docker.so", "commands": ["docker"] }
+//! ] }
+//! ```
+
+use crate::paths;
+use anyhow::{bail, Context as _, Result};
+use drk_api::download::{Download, DownloadPool};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Index {
+    #[serde(default)]
+    pub plugins: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Where the plugin library is downloaded from
+    pub url: String,
+    /// Top-level commands the plugin registers
+    #[serde(default)]
+    pub commands: Vec<String>,
+}
+
+impl Index {
+    /// Where the cached copy lives.
+    pub fn default_path() -> PathBuf {
+        paths::data_dir().join("registry.json")
+    }
+
+    /// The cached copy at `path`, if there is a readable one.
+    pub fn load_cached(path: &Path) -> Option<Self> {
+        let text = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Entry> {
+        self.plugins.iter().find(|p| p.name == name)
+    }
+
+    /// The plugin that registers `command`, if any does.
+    pub fn provider_of(&self, command: &str) -> Option<&Entry> {
+        self.plugins
+            .iter()
+            .find(|p| p.commands.iter().any(|c| c == command))
+    }
+}
+
+/// `[registry] index`.
+pub fn index_url(config: &HashMap<String, toml::Value>) -> Option<&str> {
+    config
+        .get("registry")
+        .and_then(|r| r.get("index"))
+        .and_then(|v| v.as_str())
+}
+
+/// Downloads the index at `url` and, if it parses, saves it to `path`.
+pub fn update(url: &str, path: &Path) -> Result<Index> {
+    let staging = tempfile::tempdir()?;
+    let download = staging.path().join("registry.json");
+    DownloadPool::new(1).run(vec![Download::new(url, &download)])?;
+
+    let text = fs::read_to_string(&download)?;
+    let index: Index =
+        serde_json::from_str(&text).with_context(|| format!("{} is not a registry index", url))?;
+    if index.plugins.is_empty() {
+        bail!("The registry index at {} lists no plugins", url);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, text).with_context(|| format!("Could not save the index to {:?}", path))?;
+    Ok(index)
+}
@@ -0,0 +1,222 @@
+//! Approximate resource accounting per plugin.
+//!
+//! The manager takes a [`Probe`] around every call into a plugin (`on_load`,
+//! `handle_event`) and attributes the difference to that plugin:
+//!
+//! * time spent in the call,
+//! * resident memory growth, from `/proc/self/statm` (Linux only),
+//! * open file descriptors, from `/proc/self/fd` (Linux only),
+//! * heap allocations, when the host installs [`CountingAllocator`]
+//!   (drk-cli's `alloc-stats` feature).
+//!
+//! Call counts and time also add up across runs, in the state store under
+//! [`TOTALS_KEY`], so `drk plugin stats` can point at the plugin that slows
+//! drk down over time rather than in the one run it's asked in.
+//!
+//! Plugins are cdylibs with their own copy of the Rust allocator, so the
+//! counting allocator only sees host-side work done on a plugin's behalf.
+//! Memory and handle numbers are process-wide deltas, so treat them as hints;
+//! plugins handling a broadcast event side by side share them too.
+
+use crate::state::StateStore;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// A `System` wrapper that counts allocations. Install it with
+/// `#[global_allocator]` to get allocation numbers in `drk plugin stats`.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Totals counted so far: `(bytes, allocations)`. Zero unless the counting
+/// allocator is installed.
+pub fn allocation_counters() -> (u64, u64) {
+    (
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+        ALLOCATIONS.load(Ordering::Relaxed),
+    )
+}
+
+/// Accumulated cost of one plugin.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceStats {
+    /// Number of calls into the plugin that were measured.
+    pub calls: u64,
+    /// Wall-clock time spent inside those calls.
+    pub busy: Duration,
+    /// Net resident memory growth across those calls, in bytes.
+    pub rss_delta_bytes: i64,
+    /// Net change in open file descriptors.
+    pub open_files_delta: i64,
+    /// Bytes allocated through the host allocator during the calls.
+    pub allocated_bytes: u64,
+    pub allocations: u64,
+}
+
+impl ResourceStats {
+    /// Adds `other`'s counts to these.
+    pub fn add(&mut self, other: &ResourceStats) {
+        self.calls += other.calls;
+        self.busy += other.busy;
+        self.rss_delta_bytes += other.rss_delta_bytes;
+        self.open_files_delta += other.open_files_delta;
+        self.allocated_bytes += other.allocated_bytes;
+        self.allocations += other.allocations;
+    }
+}
+
+/// The share of all plugin time, in percent, above which `drk plugin stats`
+/// flags a plugin as slow: `[stats] slow_share` (default 50).
+pub fn slow_share(config: &HashMap<String, toml::Value>) -> Result<f64> {
+    let Some(value) = config.get("stats").and_then(|s| s.get("slow_share")) else {
+        return Ok(50.0);
+    };
+    match value
+        .as_integer()
+        .map(|i| i as f64)
+        .or_else(|| value.as_float())
+    {
+        Some(share) if (0.0..=100.0).contains(&share) => Ok(share),
+        _ => bail!(
+            "[stats] slow_share must be a percentage from 0 to 100, not {}",
+            value
+        ),
+    }
+}
+
+/// The state store key holding each plugin's handler time over every run.
+pub const TOTALS_KEY: &str = "plugin-time";
+
+/// One plugin's calls and handler time, summed over every run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Totals {
+    pub calls: u64,
+    /// Microseconds
+    pub busy_us: u64,
+}
+
+impl Totals {
+    pub fn busy(&self) -> Duration {
+        Duration::from_micros(self.busy_us)
+    }
+}
+
+/// Every plugin's totals from earlier runs.
+pub fn load_totals(state: &StateStore) -> BTreeMap<String, Totals> {
+    state.get(TOTALS_KEY).unwrap_or_default()
+}
+
+/// Adds this run's `stats` to the totals in `state`.
+pub fn add_to_totals(state: &mut StateStore, stats: &HashMap<String, ResourceStats>) -> Result<()> {
+    let mut totals = load_totals(state);
+    for (name, run) in stats {
+        let total = totals.entry(name.clone()).or_default();
+        total.calls = total.calls.saturating_add(run.calls);
+        let busy = u64::try_from(run.busy.as_micros()).unwrap_or(u64::MAX);
+        total.busy_us = total.busy_us.saturating_add(busy);
+    }
+    state.set(TOTALS_KEY, &totals)
+}
+
+/// A snapshot taken before a plugin call.
+pub struct Probe {
+    started: Instant,
+    rss: Option<u64>,
+    fds: Option<usize>,
+    allocs: (u64, u64),
+}
+
+impl Probe {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            rss: resident_bytes(),
+            fds: open_fd_count(),
+            allocs: allocation_counters(),
+        }
+    }
+
+    /// Adds everything that changed since `start` to `stats`.
+    pub fn finish(self, stats: &mut ResourceStats) {
+        stats.calls += 1;
+        stats.busy += self.started.elapsed();
+        if let (Some(before), Some(after)) = (self.rss, resident_bytes()) {
+            stats.rss_delta_bytes += after as i64 - before as i64;
+        }
+        if let (Some(before), Some(after)) = (self.fds, open_fd_count()) {
+            stats.open_files_delta += after as i64 - before as i64;
+        }
+        let (bytes, count) = allocation_counters();
+        stats.allocated_bytes += bytes - self.allocs.0;
+        stats.allocations += count - self.allocs.1;
+    }
+}
+
+fn resident_bytes() -> Option<u64> {
+    // statm reports pages (size resident shared ...); 4 KiB pages are close enough here
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let resident: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(resident * 4096)
+}
+
+fn open_fd_count() -> Option<usize> {
+    std::fs::read_dir("/proc/self/fd")
+        .ok()
+        .map(|entries| entries.count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(stats: &str) -> HashMap<String, toml::Value> {
+        toml::from_str(&format!("[stats]\n{}", stats)).unwrap()
+    }
+
+    #[test]
+    fn slow_share_is_a_percentage() {
+        assert_eq!(slow_share(&HashMap::new()).unwrap(), 50.0);
+        assert_eq!(slow_share(&config("slow_share = 0")).unwrap(), 0.0);
+        assert_eq!(slow_share(&config("slow_share = 12.5")).unwrap(), 12.5);
+        assert_eq!(slow_share(&config("slow_share = 100")).unwrap(), 100.0);
+        assert!(slow_share(&config("slow_share = 101")).is_err());
+        assert!(slow_share(&config("slow_share = -1")).is_err());
+        assert!(slow_share(&config("slow_share = \"half\"")).is_err());
+    }
+
+    #[test]
+    fn totals_add_up_across_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = StateStore::open(dir.path().join("state.json")).unwrap();
+        let run = HashMap::from([(
+            "nix".to_string(),
+            ResourceStats {
+                calls: 2,
+                busy: Duration::from_millis(30),
+                ..Default::default()
+            },
+        )]);
+        add_to_totals(&mut state, &run).unwrap();
+        add_to_totals(&mut state, &run).unwrap();
+        let totals = load_totals(&state);
+        assert_eq!(totals["nix"].calls, 4);
+        assert_eq!(totals["nix"].busy(), Duration::from_millis(60));
+    }
+}
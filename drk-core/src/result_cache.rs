@@ -0,0 +1,140 @@
+//! Saved output of commands that declare themselves `cacheable`.
+//!
+//! Each result is a JSON file in `<data dir>/cache/`, named after a hash of
+//! everything the output may depend on: the plugin and its version, the
+//! command, its args and its declared [`CacheInput`]s.
+//!
+//! ```toml
+//! [cache]
+//! max_age_secs = 600
+//! ```
+
+use crate::paths;
+use anyhow::{Context as _, Result};
+use drk_api::{CacheInput, CommandMatches, PluginCommand};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How long results stay fresh when `[cache] max_age_secs` isn't set.
+pub const DEFAULT_MAX_AGE_SECS: u64 = 600;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// Seconds since the Unix epoch when the command ran.
+    pub created: u64,
+    pub output: String,
+}
+
+impl Entry {
+    /// Seconds since the result was produced.
+    pub fn age(&self) -> u64 {
+        now().saturating_sub(self.created)
+    }
+}
+
+/// Where results are kept.
+pub fn default_dir() -> PathBuf {
+    paths::data_dir().join("cache")
+}
+
+/// `[cache] max_age_secs`, or the default.
+pub fn max_age(config: &HashMap<String, toml::Value>) -> u64 {
+    config
+        .get("cache")
+        .and_then(|c| c.get("max_age_secs"))
+        .and_then(|v| v.as_integer())
+        .filter(|v| *v >= 0)
+        .map_or(DEFAULT_MAX_AGE_SECS, |v| v as u64)
+}
+
+/// The cache key of one invocation of `command`, the command at the end of
+/// `matches.path`, with file inputs resolved against `cwd`.
+pub fn key(
+    plugin: &str,
+    plugin_version: &str,
+    command: &PluginCommand,
+    matches: &CommandMatches,
+    cwd: &Path,
+) -> String {
+    let args: BTreeMap<&String, &String> = matches.args.iter().collect();
+    // Keys of commands without subcommands stay what they were before nesting
+    let name = match matches.path.len() {
+        0 | 1 => command.name.clone(),
+        _ => matches.path.join(" "),
+    };
+    let inputs: Vec<String> = command
+        .cache_inputs
+        .iter()
+        .map(|input| match input {
+            CacheInput::File(path) => match fs::metadata(cwd.join(path)) {
+                Ok(meta) => {
+                    let modified = meta
+                        .modified()
+                        .ok()
+                        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                        .map_or(0, |d| d.as_nanos());
+                    format!("file:{}:{}:{}", path, meta.len(), modified)
+                }
+                Err(_) => format!("file:{}:missing", path),
+            },
+            CacheInput::Env(name) => format!("env:{}={:?}", name, std::env::var(name).ok()),
+            CacheInput::WorkingDir => format!("cwd:{}", cwd.display()),
+        })
+        .collect();
+    let material = serde_json::json!([plugin, plugin_version, name, args, inputs]);
+    Sha256::digest(material.to_string().as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The stored result for `key`, if it's younger than `max_age` seconds.
+pub fn lookup(dir: &Path, key: &str, max_age: u64) -> Option<Entry> {
+    let text = fs::read_to_string(entry_path(dir, key)).ok()?;
+    let entry: Entry = serde_json::from_str(&text).ok()?;
+    (entry.age() <= max_age).then_some(entry)
+}
+
+/// Saves `output` under `key`, dropping results older than `max_age` on
+/// the way so the directory doesn't grow without bound.
+pub fn store(dir: &Path, key: &str, output: &str, max_age: u64) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Could not create cache at {:?}", dir))?;
+    prune(dir, max_age);
+    let entry = Entry {
+        created: now(),
+        output: output.to_string(),
+    };
+    let path = entry_path(dir, key);
+    fs::write(&path, serde_json::to_string(&entry)?)
+        .with_context(|| format!("Could not write cached result to {:?}", path))
+}
+
+fn prune(dir: &Path, max_age: u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        let expired = fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Entry>(&text).ok())
+            .is_none_or(|entry| entry.age() > max_age);
+        if expired {
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+fn entry_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{}.json", key))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,205 @@
+//! Role-based command restrictions.
+//!
+//! Roles are declared in config and limit which plugin commands may run:
+//!
+//! ```toml
+//! [roles.ci]
+//! allow = ["nix.*", "tasks.*"]
+//! deny = ["nix.gc"]
+//! ```
+//!
+//! Patterns match against `<plugin>.<command>` (case-insensitive) and support
+//! `*` as a wildcard. `deny` always wins over `allow`.
+//!
+//! Most built-in commands only read, and every role may run them. The ones in
+//! [`GATED_BUILTINS`] run other commands or change which plugins load and how
+//! drk is configured, so a role needs to allow them like plugin commands,
+//! as `drk.<command>`: `drk.batch`, `drk.plugin.install`, `drk.config.edit`.
+//!
+//! Whoever sets `DRK_ROLE` is restricting drk, so a `--role` flag naming a
+//! different role is refused rather than taking its place.
+
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+/// Built-in commands a role must allow, by their path under `drk`.
+pub const GATED_BUILTINS: &[&str] = &[
+    "batch",
+    "rerun",
+    "save-as",
+    "config edit",
+    "state gc",
+    "plugin install",
+    "plugin update-index",
+    "plugin bundle",
+    "plugin uninstall",
+    "plugin upgrade",
+    "plugin rebuild",
+    "plugin enable",
+    "plugin disable",
+    "plugin reset",
+    "plugin clean",
+];
+
+#[derive(Debug, Clone)]
+pub struct Role {
+    pub name: String,
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl Role {
+    /// Looks up `[roles.<name>]` in the config store.
+    pub fn from_config(config: &HashMap<String, toml::Value>, name: &str) -> Result<Self> {
+        let Some(section) = config.get("roles").and_then(|r| r.get(name)) else {
+            bail!(
+                "Role '{}' is not defined in config ([roles.{}])",
+                name,
+                name
+            );
+        };
+
+        let patterns = |key: &str| -> Vec<String> {
+            section
+                .get(key)
+                .and_then(|v| v.as_array())
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_str())
+                        .map(|s| s.to_lowercase())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            allow: patterns("allow"),
+            deny: patterns("deny"),
+        })
+    }
+
+    /// Whether this role may run `command` from `plugin`.
+    pub fn allows(&self, plugin: &str, command: &str) -> bool {
+        let qualified = format!("{}.{}", plugin, command).to_lowercase();
+        if self.deny.iter().any(|p| matches_pattern(p, &qualified)) {
+            return false;
+        }
+        self.allow.iter().any(|p| matches_pattern(p, &qualified))
+    }
+
+    /// Whether this role may run the built-in command at `path`, like
+    /// `plugin install`. Only [`GATED_BUILTINS`] are restricted.
+    pub fn allows_builtin(&self, path: &str) -> bool {
+        !GATED_BUILTINS.contains(&path) || self.allows("drk", &path.replace(' ', "."))
+    }
+}
+
+/// Matches `value` against a pattern where `*` stands for any run of characters.
+///
+/// # Example
+/// ```
+/// # use drk_core::roles::matches_pattern;
+/// assert!(matches_pattern("nix.*", "nix.init"));
+/// assert!(matches_pattern("*.list", "plugin.list"));
+/// assert!(!matches_pattern("nix.*", "basic.greet"));
+/// ```
+pub fn matches_pattern(pattern: &str, value: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == value;
+    }
+
+    let mut rest = value;
+    for (i, part) in parts.iter().enumerate() {
+        if i == 0 {
+            match rest.strip_prefix(part) {
+                Some(r) => rest = r,
+                None => return false,
+            }
+        } else if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        } else {
+            match rest.find(part) {
+                Some(idx) => rest = &rest[idx + part.len()..],
+                None => return false,
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role_with(section: &str) -> Role {
+        let config: HashMap<String, toml::Value> =
+            toml::from_str(&format!("[roles.test]\n{}", section)).unwrap();
+        Role::from_config(&config, "test").unwrap()
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let role = role_with("allow = [\"nix.*\"]\ndeny = [\"nix.gc\"]");
+        assert!(role.allows("nix", "init"));
+        assert!(!role.allows("nix", "gc"));
+        let role = role_with("allow = [\"*\"]\ndeny = [\"*.gc\"]");
+        assert!(!role.allows("store", "gc"));
+        assert!(role.allows("store", "list"));
+    }
+
+    #[test]
+    fn nothing_is_allowed_unless_listed() {
+        assert!(!role_with("").allows("basic", "greet"));
+        assert!(!role_with("deny = [\"nix.*\"]").allows("basic", "greet"));
+    }
+
+    #[test]
+    fn matching_ignores_case() {
+        let role = role_with("allow = [\"Nix.*\"]\ndeny = [\"NIX.GC\"]");
+        assert!(role.allows("nix", "Init"));
+        assert!(role.allows("NIX", "init"));
+        assert!(!role.allows("nix", "gc"));
+        assert!(!role.allows("Nix", "Gc"));
+    }
+
+    #[test]
+    fn wildcards_match_any_run_of_characters() {
+        assert!(matches_pattern("*", "basic.greet"));
+        assert!(matches_pattern("n*.i*t", "nix.init"));
+        assert!(matches_pattern("*.*", "a.b"));
+        assert!(!matches_pattern("nix.*", "nixos.init"));
+        assert!(!matches_pattern("nix.init", "nix.init2"));
+        assert!(!matches_pattern("*.list", "plugin.listing"));
+    }
+
+    #[test]
+    fn only_gated_builtins_need_allowing() {
+        let role = role_with("allow = [\"drk.plugin.*\"]\ndeny = [\"drk.plugin.reset\"]");
+        assert!(role.allows_builtin("history"));
+        assert!(role.allows_builtin("plugin list"));
+        assert!(role.allows_builtin("plugin install"));
+        assert!(!role.allows_builtin("plugin reset"));
+        assert!(!role.allows_builtin("batch"));
+        assert!(!role.allows_builtin("config edit"));
+    }
+
+    #[test]
+    fn builtins_that_change_setup_are_gated() {
+        let role = role_with("allow = [\"basic.*\"]");
+        for path in [
+            "save-as",
+            "state gc",
+            "plugin clean",
+            "plugin bundle",
+            "plugin update-index",
+        ] {
+            assert!(!role.allows_builtin(path), "{} should be gated", path);
+        }
+        let role = role_with("allow = [\"drk.save-as\", \"drk.state.gc\", \"drk.plugin.*\"]");
+        assert!(role.allows_builtin("save-as"));
+        assert!(role.allows_builtin("state gc"));
+        assert!(role.allows_builtin("plugin clean"));
+    }
+}
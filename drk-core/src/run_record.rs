@@ -0,0 +1,180 @@
+//! Run manifests for reproducing an invocation: `drk --record run.json ...`
+//! writes one, `drk rerun run.json` replays it.
+//!
+//! A record holds everything the outcome may depend on that can be written
+//! down: drk's version, each plugin's version and library hash, the config,
+//! the command line, the working directory and the environment drk reads.
+//! Replaying compares all of it with the current setup first, so a
+//! different result can be traced to what changed.
+
+use crate::lockfile::sha256_file;
+use crate::manager::PluginManager;
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Variables recorded besides every `DRK_*` one.
+const RECORDED_ENV: &[&str] = &["PATH", "SHELL", "TERM", "LANG", "CLICOLOR", "NO_COLOR"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub drk_version: String,
+    /// Seconds since the Unix epoch.
+    pub recorded_at: u64,
+    /// The command line after `drk`, without `--record`.
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    pub os: String,
+    pub arch: String,
+    pub plugins: BTreeMap<String, RecordedPlugin>,
+    pub config: toml::Table,
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedPlugin {
+    pub version: String,
+    pub enabled: bool,
+    /// Of the library file; absent for plugins built into drk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl RunRecord {
+    /// Describes running `args` now, with the plugins and config `manager` has.
+    pub fn capture(drk_version: &str, args: &[String], manager: &PluginManager) -> Self {
+        let plugins = manager
+            .plugin_names()
+            .into_iter()
+            .filter_map(|name| {
+                let meta = manager.metadata(&name)?;
+                let plugin = RecordedPlugin {
+                    version: meta.version.clone(),
+                    enabled: manager.is_enabled(&name).unwrap_or(false),
+                    sha256: manager.library(&name).and_then(|lib| sha256_file(lib).ok()),
+                };
+                Some((name, plugin))
+            })
+            .collect();
+        let env = std::env::vars()
+            .filter(|(key, _)| key.starts_with("DRK_") || RECORDED_ENV.contains(&key.as_str()))
+            .collect();
+
+        Self {
+            drk_version: drk_version.to_string(),
+            recorded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            args: args.to_vec(),
+            cwd: std::env::current_dir().unwrap_or_default(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            plugins,
+            config: manager
+                .config()
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            env,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Could not read {:?}", path))?;
+        serde_json::from_str(&text).with_context(|| format!("{:?} is not a run record", path))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Could not write run record to {:?}", path))
+    }
+
+    /// How `now` differs from this record, one line per difference.
+    pub fn divergences(&self, now: &RunRecord) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.drk_version != now.drk_version {
+            out.push(format!(
+                "drk is {}, the run used {}",
+                now.drk_version, self.drk_version
+            ));
+        }
+        if (&self.os, &self.arch) != (&now.os, &now.arch) {
+            out.push(format!(
+                "this is {} {}, the run was on {} {}",
+                now.os, now.arch, self.os, self.arch
+            ));
+        }
+        if !self.cwd.is_dir() {
+            out.push(format!(
+                "the run's directory {} doesn't exist here",
+                self.cwd.display()
+            ));
+        }
+
+        for (name, then) in &self.plugins {
+            match now.plugins.get(name) {
+                None => out.push(format!("plugin {} (v{}) isn't loaded", name, then.version)),
+                Some(current) if current.version != then.version => out.push(format!(
+                    "plugin {} is v{}, the run used v{}",
+                    name, current.version, then.version
+                )),
+                Some(current) if current.sha256 != then.sha256 => out.push(format!(
+                    "plugin {} is a different build of v{}",
+                    name, current.version
+                )),
+                Some(current) if current.enabled != then.enabled => out.push(format!(
+                    "plugin {} is {}",
+                    name,
+                    if current.enabled {
+                        "enabled now"
+                    } else {
+                        "disabled now"
+                    }
+                )),
+                Some(_) => {}
+            }
+        }
+        for (name, current) in &now.plugins {
+            if !self.plugins.contains_key(name) {
+                out.push(format!(
+                    "plugin {} (v{}) wasn't loaded in the run",
+                    name, current.version
+                ));
+            }
+        }
+
+        let mut sections: Vec<&String> = self.config.keys().chain(now.config.keys()).collect();
+        sections.sort();
+        sections.dedup();
+        for section in sections {
+            match (self.config.get(section), now.config.get(section)) {
+                (Some(_), None) => out.push(format!("config [{}] is gone", section)),
+                (None, Some(_)) => out.push(format!("config [{}] is new", section)),
+                (then, current) if then != current => {
+                    out.push(format!("config [{}] differs", section))
+                }
+                _ => {}
+            }
+        }
+
+        let mut vars: Vec<&String> = self.env.keys().chain(now.env.keys()).collect();
+        vars.sort();
+        vars.dedup();
+        for var in vars {
+            if self.env.get(var) != now.env.get(var) {
+                let restored = if var.starts_with("DRK_") {
+                    " (the replay uses the recorded value)"
+                } else {
+                    ""
+                };
+                out.push(format!("environment variable {} differs{}", var, restored));
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,134 @@
+//! Saved command templates.
+//!
+//! `drk save-as greet-team -- greet --name {who}` stores the invocation under
+//! `[saved]` in config; `drk run-saved greet-team who=Ops` fills the
+//! placeholders and runs it. `{config.*}` and `{env.*}` work here too.
+
+use crate::interpolate::interpolate_with;
+use anyhow::{bail, Context as _, Result};
+use std::collections::{BTreeMap, HashMap};
+
+/// Reads the `[saved]` section.
+pub fn from_config(config: &HashMap<String, toml::Value>) -> BTreeMap<String, String> {
+    config
+        .get("saved")
+        .and_then(|s| s.as_table())
+        .map(|table| {
+            table
+                .iter()
+                .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Stores `words` as the template called `name`, replacing any previous one.
+pub fn save(config: &mut HashMap<String, toml::Value>, name: &str, words: &[String]) {
+    let section = config
+        .entry("saved".to_string())
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    if let Some(table) = section.as_table_mut() {
+        table.insert(
+            name.to_string(),
+            toml::Value::String(shell_words::join(words)),
+        );
+    }
+}
+
+/// Rewrites `drk run-saved <name> key=value...` into the stored invocation.
+pub fn expand_invocation(
+    argv: Vec<String>,
+    config: &HashMap<String, toml::Value>,
+) -> Result<Vec<String>> {
+    let Some(name) = argv.get(2) else {
+        bail!("Usage: drk run-saved <name> [key=value...]");
+    };
+    let saved = from_config(config);
+    let Some(template) = saved.get(name) else {
+        bail!("No saved command named '{}' (see [saved] in config)", name);
+    };
+
+    let mut vars = HashMap::new();
+    for pair in &argv[3..] {
+        let Some((key, value)) = pair.split_once('=') else {
+            bail!("Expected key=value, got '{}'", pair);
+        };
+        vars.insert(key.to_string(), value.to_string());
+    }
+
+    let words = shell_words::split(template)
+        .with_context(|| format!("Saved command '{}' is not valid shell syntax", name))?;
+
+    let mut expanded = vec![argv[0].clone()];
+    for word in words {
+        expanded.push(
+            interpolate_with(&word, config, &vars)
+                .with_context(|| format!("In saved command '{}'", name))?,
+        );
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    fn saved(name: &str, words: &[&str]) -> HashMap<String, toml::Value> {
+        let mut config = HashMap::new();
+        save(&mut config, name, &args(words));
+        config
+    }
+
+    #[test]
+    fn saved_commands_round_trip_through_config() {
+        let config = saved("greet-team", &["greet", "--name", "{who} and co"]);
+        assert_eq!(
+            from_config(&config)["greet-team"],
+            "greet --name '{who} and co'"
+        );
+
+        let expanded = expand_invocation(
+            args(&["drk", "run-saved", "greet-team", "who=Ops"]),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(expanded, ["drk", "greet", "--name", "Ops and co"]);
+    }
+
+    #[test]
+    fn saving_again_replaces_the_template() {
+        let mut config = saved("build", &["nix", "build"]);
+        save(&mut config, "build", &args(&["nix", "build", "--release"]));
+        assert_eq!(from_config(&config)["build"], "nix build --release");
+    }
+
+    #[test]
+    fn values_may_contain_equals_signs() {
+        let config = saved("env", &["exec", "{pair}"]);
+        let expanded =
+            expand_invocation(args(&["drk", "run-saved", "env", "pair=A=1"]), &config).unwrap();
+        assert_eq!(expanded, ["drk", "exec", "A=1"]);
+    }
+
+    #[test]
+    fn bad_invocations_are_refused() {
+        let config = saved("greet-team", &["greet", "--name", "{who}"]);
+        let run = |words: &[&str]| expand_invocation(args(words), &config).unwrap_err();
+
+        assert!(run(&["drk", "run-saved"]).to_string().starts_with("Usage"));
+        assert!(run(&["drk", "run-saved", "nope"])
+            .to_string()
+            .contains("No saved command named 'nope'"));
+        assert!(run(&["drk", "run-saved", "greet-team", "who"])
+            .to_string()
+            .contains("Expected key=value"));
+        assert_eq!(
+            run(&["drk", "run-saved", "greet-team"]).to_string(),
+            "In saved command 'greet-team'"
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Copies of a directory tree for undoing a group of commands' file changes
+//! together (`drk batch --atomic`).
+//!
+//! Plugins write files directly and through the programs they run, so there
+//! is no single place to intercept writes. Instead the tree is copied before
+//! the group runs; rolling back makes it match the copy again: files created
+//! since are removed, changed and deleted ones are put back. Committing just
+//! drops the copy.
+//!
+//! `.git` is left out, and a tree larger than `[batch] atomic_max_mb`
+//! (default 200) is refused rather than copied:
+//!
+//! ```toml
+//! [batch]
+//! atomic_max_mb = 500
+//! ```
+
+use anyhow::{bail, Context as _, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+const DEFAULT_MAX_MB: u64 = 200;
+
+/// The largest tree `config` allows copying, in bytes.
+pub fn max_bytes(config: &HashMap<String, toml::Value>) -> u64 {
+    config
+        .get("batch")
+        .and_then(|b| b.get("atomic_max_mb"))
+        .and_then(|v| v.as_integer())
+        .filter(|v| *v >= 0)
+        .map_or(DEFAULT_MAX_MB, |v| v as u64)
+        .saturating_mul(1024 * 1024)
+}
+
+#[derive(Debug)]
+enum Entry {
+    Dir,
+    File { len: u64, modified: SystemTime },
+    Symlink(PathBuf),
+}
+
+/// A directory tree as it was when [`Snapshot::take`] ran.
+pub struct Snapshot {
+    root: PathBuf,
+    copy: tempfile::TempDir,
+    /// By path relative to `root`
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+impl Snapshot {
+    /// Copies the tree under `root`, failing if it holds more than
+    /// `max_bytes` of files.
+    pub fn take(root: &Path, max_bytes: u64) -> Result<Self> {
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("Could not resolve {:?}", root))?;
+        let copy = tempfile::Builder::new().prefix("drk-snapshot-").tempdir()?;
+        let mut entries = BTreeMap::new();
+        let mut total = 0;
+
+        for entry in walk(&root) {
+            let entry = entry?;
+            let relative = entry.path().strip_prefix(&root)?.to_path_buf();
+            let kind = entry.file_type();
+            if kind.is_symlink() {
+                entries.insert(relative, Entry::Symlink(fs::read_link(entry.path())?));
+            } else if kind.is_dir() {
+                fs::create_dir_all(copy.path().join(&relative))?;
+                entries.insert(relative, Entry::Dir);
+            } else {
+                let meta = entry.metadata()?;
+                total = meta.len().saturating_add(total);
+                if total > max_bytes {
+                    bail!(
+                        "{} holds more than {} MiB of files, over the [batch] atomic_max_mb limit",
+                        root.display(),
+                        max_bytes / 1024 / 1024
+                    );
+                }
+                fs::copy(entry.path(), copy.path().join(&relative))
+                    .with_context(|| format!("Could not copy {:?}", entry.path()))?;
+                entries.insert(
+                    relative,
+                    Entry::File {
+                        len: meta.len(),
+                        modified: meta.modified()?,
+                    },
+                );
+            }
+        }
+        Ok(Self {
+            root,
+            copy,
+            entries,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Puts the tree back as it was. Returns how many paths were removed,
+    /// restored or recreated.
+    pub fn restore(self) -> Result<usize> {
+        let mut changed = 0;
+
+        // Whatever is new goes, deepest first so directories are empty by then
+        let current: Vec<(PathBuf, bool)> = walk(&self.root)
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let relative = e.path().strip_prefix(&self.root).ok()?.to_path_buf();
+                Some((relative, e.file_type().is_dir()))
+            })
+            .collect();
+        let mut kept = BTreeSet::new();
+        for (relative, is_dir) in current.into_iter().rev() {
+            let path = self.root.join(&relative);
+            let same_kind = match self.entries.get(&relative) {
+                Some(Entry::Dir) => is_dir && !path.is_symlink(),
+                Some(Entry::Symlink(_)) => path.is_symlink(),
+                Some(Entry::File { .. }) => !is_dir && !path.is_symlink(),
+                None => false,
+            };
+            if same_kind {
+                kept.insert(relative);
+                continue;
+            }
+            if is_dir && !path.is_symlink() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+            changed += 1;
+        }
+
+        for (relative, entry) in &self.entries {
+            let path = self.root.join(relative);
+            match entry {
+                Entry::Dir => {
+                    if !kept.contains(relative) {
+                        fs::create_dir_all(&path)?;
+                        changed += 1;
+                    }
+                }
+                Entry::Symlink(target) => {
+                    if kept.contains(relative) && fs::read_link(&path).ok().as_ref() == Some(target)
+                    {
+                        continue;
+                    }
+                    let _ = fs::remove_file(&path);
+                    symlink(target, &path)?;
+                    changed += 1;
+                }
+                Entry::File { len, modified } => {
+                    let unchanged = kept.contains(relative)
+                        && fs::symlink_metadata(&path).is_ok_and(|meta| {
+                            meta.len() == *len && meta.modified().ok() == Some(*modified)
+                        });
+                    if unchanged {
+                        continue;
+                    }
+                    fs::copy(self.copy.path().join(relative), &path)
+                        .with_context(|| format!("Could not restore {:?}", path))?;
+                    changed += 1;
+                }
+            }
+        }
+        Ok(changed)
+    }
+}
+
+/// Everything under `root` except `root` itself and `.git`.
+fn walk(root: &Path) -> impl Iterator<Item = walkdir::Result<walkdir::DirEntry>> {
+    WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| e.depth() != 1 || e.file_name() != ".git")
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, path: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, path: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_undoes_creates_modifies_and_deletes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("README.md"), "readme").unwrap();
+
+        let snapshot = Snapshot::take(root, max_bytes(&HashMap::new())).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() { todo!() }").unwrap();
+        fs::remove_file(root.join("README.md")).unwrap();
+        fs::create_dir(root.join("target")).unwrap();
+        fs::write(root.join("target/out"), "built").unwrap();
+        fs::write(root.join("notes.txt"), "new").unwrap();
+
+        assert_eq!(snapshot.restore().unwrap(), 5);
+        assert_eq!(
+            fs::read_to_string(root.join("src/main.rs")).unwrap(),
+            "fn main() {}"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("README.md")).unwrap(),
+            "readme"
+        );
+        assert!(!root.join("target").exists());
+        assert!(!root.join("notes.txt").exists());
+    }
+
+    #[test]
+    fn trees_over_the_limit_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("big"), vec![0u8; 2048]).unwrap();
+
+        assert!(Snapshot::take(dir.path(), 1024).is_err());
+        assert!(Snapshot::take(dir.path(), 4096).is_ok());
+    }
+
+    #[test]
+    fn huge_limits_do_not_overflow() {
+        let config = toml::from_str(&format!("[batch]\natomic_max_mb = {}", i64::MAX)).unwrap();
+        assert_eq!(max_bytes(&config), u64::MAX);
+        assert_eq!(max_bytes(&HashMap::new()), DEFAULT_MAX_MB * 1024 * 1024);
+    }
+}
@@ -0,0 +1,344 @@
+//! Host side of the compiler-independent plugin interface ([`drk_api::abi`]).
+//!
+//! [`StablePlugin`] wraps an exported [`PluginVTable`] in the ordinary
+//! [`Plugin`] trait, so the rest of the manager doesn't care which entry
+//! point a plugin was loaded through.
+
+use anyhow::{anyhow, bail, Result};
+use drk_api::abi::{
+    decode_event, encode_event, EventOutcome, ExitCallback, HostCall, PluginVTable, RBuf, RStr,
+    ABI_VERSION, MIN_ABI_VERSION,
+};
+use drk_api::{
+    CancellationToken, Capability, ConfigKey, Context, ControlFlow, EventKind, Plugin,
+    PluginCommand, PluginMetadata, SystemEvent, Tip,
+};
+use std::ffi::c_void;
+
+pub struct StablePlugin {
+    vtable: *const PluginVTable,
+    instance: *mut c_void,
+    // Read once up front; a plugin whose answers don't parse isn't loaded
+    metadata: PluginMetadata,
+    commands: Vec<PluginCommand>,
+}
+
+// SAFETY: `Plugin` requires implementors to be `Send + Sync`, and the table's
+// functions only touch the instance they're given
+unsafe impl Send for StablePlugin {}
+unsafe impl Sync for StablePlugin {}
+
+impl StablePlugin {
+    /// Creates an instance through `vtable`.
+    ///
+    /// # Safety
+    /// `vtable` must be a `_plugin_vtable` export from a library that stays
+    /// loaded for as long as the result lives.
+    pub unsafe fn new(vtable: *const PluginVTable) -> Result<Self> {
+        // Only the version is known to be laid out as expected until it's checked
+        let abi_version = (*vtable).abi_version;
+        if !(MIN_ABI_VERSION..=ABI_VERSION).contains(&abi_version) {
+            bail!(
+                "Plugin was built against drk-api ABI v{}, but this drk speaks v{}-v{}; rebuild the plugin",
+                abi_version,
+                MIN_ABI_VERSION,
+                ABI_VERSION
+            );
+        }
+
+        let table = &*vtable;
+        let instance = (table.create)();
+        if instance.is_null() {
+            bail!("Plugin panicked while being created");
+        }
+        let parsed = (|| -> Result<_> {
+            let metadata = serde_json::from_str(&text((table.metadata)(instance))?)?;
+            let commands = serde_json::from_str(&text((table.commands)(instance))?)?;
+            Ok((metadata, commands))
+        })();
+        match parsed {
+            Ok((metadata, commands)) => Ok(Self {
+                vtable,
+                instance,
+                metadata,
+                commands,
+            }),
+            Err(e) => {
+                (table.destroy)(instance);
+                Err(e.context("Plugin returned unreadable metadata"))
+            }
+        }
+    }
+
+    fn table(&self) -> &PluginVTable {
+        // SAFETY: the library outlives the plugin, see `new`
+        unsafe { &*self.vtable }
+    }
+}
+
+fn text(buf: RBuf) -> Result<String> {
+    buf.into_string()
+        .ok_or_else(|| anyhow!("plugin returned nothing"))
+}
+
+/// An absent buffer is success; anything else is the plugin's error message.
+fn status(buf: RBuf) -> Result<()> {
+    match buf.into_string() {
+        None => Ok(()),
+        Some(message) => Err(anyhow!(message)),
+    }
+}
+
+impl Drop for StablePlugin {
+    fn drop(&mut self) {
+        // SAFETY: `instance` came from this table's `create` and is dropped once
+        unsafe { (self.table().destroy)(self.instance) }
+    }
+}
+
+impl Plugin for StablePlugin {
+    fn metadata(&self) -> PluginMetadata {
+        self.metadata.clone()
+    }
+
+    fn get_commands(&self) -> Vec<PluginCommand> {
+        self.commands.clone()
+    }
+
+    fn config_schema(&self) -> Vec<ConfigKey> {
+        // The entry doesn't exist before v5
+        if self.table().abi_version < 5 {
+            return Vec::new();
+        }
+        // SAFETY: see `table`
+        let json = unsafe { (self.table().config_schema)(self.instance) };
+        match text(json).and_then(|json| Ok(serde_json::from_str(&json)?)) {
+            Ok(schema) => schema,
+            Err(e) => {
+                eprintln!(
+                    "Plugin '{}' returned an unreadable config schema: {:#}",
+                    self.metadata.name, e
+                );
+                Vec::new()
+            }
+        }
+    }
+
+    fn tips(&self) -> Vec<Tip> {
+        // The entry doesn't exist before v7
+        if self.table().abi_version < 7 {
+            return Vec::new();
+        }
+        // SAFETY: see `table`
+        let json = unsafe { (self.table().tips)(self.instance) };
+        match text(json).and_then(|json| Ok(serde_json::from_str(&json)?)) {
+            Ok(tips) => tips,
+            Err(e) => {
+                eprintln!(
+                    "Plugin '{}' returned unreadable tips: {:#}",
+                    self.metadata.name, e
+                );
+                Vec::new()
+            }
+        }
+    }
+
+    fn subscriptions(&self) -> Vec<EventKind> {
+        // The entry doesn't exist before v9, when plugins got everything
+        if self.table().abi_version < 9 {
+            return vec![EventKind::All];
+        }
+        // SAFETY: see `table`
+        let json = unsafe { (self.table().subscriptions)(self.instance) };
+        match text(json).and_then(|json| Ok(serde_json::from_str(&json)?)) {
+            Ok(kinds) => kinds,
+            Err(e) => {
+                eprintln!(
+                    "Plugin '{}' returned unreadable subscriptions: {:#}",
+                    self.metadata.name, e
+                );
+                vec![EventKind::All]
+            }
+        }
+    }
+
+    fn config_version(&self) -> u32 {
+        // SAFETY: see `table`
+        unsafe { (self.table().config_version)(self.instance) }
+    }
+
+    fn migrate_config(&self, from: u32, section: &mut toml::Table) -> Result<()> {
+        let input = toml::to_string(section)?;
+        // SAFETY: see `table`; `input` outlives the call
+        let outcome =
+            unsafe { (self.table().migrate_config)(self.instance, from, RStr::new(&input)) };
+        status(outcome.error)?;
+        *section = toml::from_str(&text(outcome.section)?)?;
+        Ok(())
+    }
+
+    fn on_load(&mut self) -> Result<()> {
+        // SAFETY: see `table`
+        status(unsafe { (self.table().on_load)(self.instance) })
+    }
+
+    fn on_unload(&mut self) -> Result<()> {
+        // SAFETY: see `table`
+        status(unsafe { (self.table().on_unload)(self.instance) })
+    }
+
+    fn on_cancel(&mut self) -> Result<()> {
+        // SAFETY: see `table`
+        status(unsafe { (self.table().on_cancel)(self.instance) })
+    }
+
+    fn contribute_commands(&mut self, ctx: &mut Context) -> Result<Vec<PluginCommand>> {
+        // The entry doesn't exist in v1 tables
+        if self.table().abi_version < 2 {
+            return Ok(Vec::new());
+        }
+        let mut commands = RBuf::none();
+        self.call_with_context(ctx, |table, instance, call| {
+            // SAFETY: see `table`; `commands` outlives the call
+            unsafe { (table.contribute_commands)(instance, call, &mut commands) }
+        })?;
+        match commands.into_string() {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn on_install(&mut self, ctx: &mut Context) -> Result<()> {
+        // The entry doesn't exist before v3
+        if self.table().abi_version < 3 {
+            return Ok(());
+        }
+        self.call_with_context(ctx, |table, instance, call| {
+            // SAFETY: see `table`
+            unsafe { (table.on_install)(instance, call) }
+        })
+    }
+
+    fn handle_unknown_command(&mut self, argv: &[String], ctx: &mut Context) -> Result<bool> {
+        // The entry doesn't exist before v4
+        if self.table().abi_version < 4 {
+            return Ok(false);
+        }
+        let argv = serde_json::to_string(argv)?;
+        let mut handled = false;
+        self.call_with_context(ctx, |table, instance, call| {
+            // SAFETY: see `table`; `argv` and `handled` outlive the call
+            unsafe {
+                (table.handle_unknown_command)(instance, RStr::new(&argv), call, &mut handled)
+            }
+        })?;
+        Ok(handled)
+    }
+
+    fn on_pre_command(
+        &mut self,
+        name: &str,
+        args: &[String],
+        ctx: &mut Context,
+    ) -> Result<ControlFlow> {
+        // Before v8 plugins only see the event, and can't cancel
+        if self.table().abi_version < 8 {
+            let event = SystemEvent::PreCommand {
+                name: name.to_string(),
+                args: args.to_vec(),
+            };
+            self.handle_event(&event, ctx)?;
+            return Ok(ControlFlow::Continue);
+        }
+        let command = serde_json::to_string(&(name, args))?;
+        let mut reason = RBuf::none();
+        self.call_with_context(ctx, |table, instance, call| {
+            // SAFETY: see `table`; `command` and `reason` outlive the call
+            unsafe { (table.on_pre_command)(instance, RStr::new(&command), call, &mut reason) }
+        })?;
+        Ok(match reason.into_string() {
+            Some(reason) => ControlFlow::Cancel(reason),
+            None => ControlFlow::Continue,
+        })
+    }
+
+    fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> Result<()> {
+        let event = encode_event(event);
+        self.call_with_context(ctx, |table, instance, call| {
+            // SAFETY: see `table`; `event` outlives the call
+            unsafe { (table.handle_event)(instance, RStr::new(&event), call) }
+        })
+    }
+}
+
+impl StablePlugin {
+    /// Sends `ctx` across as a [`HostCall`] for `f`, then applies the config
+    /// and state changes the plugin reports.
+    fn call_with_context(
+        &self,
+        ctx: &mut Context,
+        f: impl FnOnce(&PluginVTable, *mut c_void, &HostCall) -> EventOutcome,
+    ) -> Result<()> {
+        let config: toml::Table = ctx
+            .config
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        let config = toml::to_string(&config)?;
+        let state = toml::to_string(&*ctx.state)?;
+        let data_dir = ctx.data_dir.map(|dir| dir.to_string_lossy().into_owned());
+        let cancel = ctx.cancellation();
+
+        let outcome = {
+            let mut host: &mut Context = ctx;
+            let call = HostCall {
+                host: &mut host as *mut &mut Context as *mut c_void,
+                config: RStr::new(&config),
+                state: RStr::new(&state),
+                data_dir: data_dir.as_deref().map_or(RStr::none(), RStr::new),
+                emit,
+                cancel: &cancel as *const CancellationToken as *const c_void,
+                cancelled,
+                on_exit,
+                request_permission,
+            };
+            // Everything `call` points to outlives the call
+            f(self.table(), self.instance, &call)
+        };
+
+        if let Some(config) = outcome.config.into_string() {
+            let config: toml::Table = toml::from_str(&config)?;
+            *ctx.config = config.into_iter().collect();
+        }
+        if let Some(state) = outcome.state.into_string() {
+            *ctx.state = toml::from_str(&state)?;
+        }
+        status(outcome.error)
+    }
+}
+
+unsafe extern "C" fn emit(host: *mut c_void, event: RStr) {
+    let ctx = &mut **(host as *mut &mut Context);
+    match event.get().map(decode_event) {
+        Some(Ok(Some(event))) => (ctx.event_sender)(event),
+        Some(Err(e)) => eprintln!("Dropped unreadable event from plugin: {:#}", e),
+        Some(Ok(None)) | None => {}
+    }
+}
+
+unsafe extern "C" fn cancelled(cancel: *const c_void) -> bool {
+    (*(cancel as *const CancellationToken)).is_cancelled()
+}
+
+unsafe extern "C" fn request_permission(host: *mut c_void, capability: RStr) -> bool {
+    let ctx = &mut **(host as *mut &mut Context);
+    match capability.get().and_then(Capability::from_key) {
+        Some(capability) => (ctx.permit)(capability),
+        None => false,
+    }
+}
+
+unsafe extern "C" fn on_exit(host: *mut c_void, cleanup: ExitCallback) {
+    let ctx = &mut **(host as *mut &mut Context);
+    ctx.on_abnormal_exit(move || cleanup.run());
+}
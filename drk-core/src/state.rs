@@ -0,0 +1,211 @@
+//! Persistent key-value state shared by host features and plugins.
+//!
+//! Unlike config, state is written by drk itself (usage stats, caches of
+//! decisions) and isn't meant to be edited by hand. It's a single JSON file in
+//! the data directory; each feature owns one top-level key, and each plugin
+//! owns `plugin:<name>`.
+//!
+//! The file records its format version so older layouts can be upgraded on
+//! open. Plugin entries are capped by a quota:
+//!
+//! ```toml
+//! [state]
+//! plugin_quota_bytes = 262144  # per plugin, serialized size
+//! usage_max_age_days = 180     # `drk state gc` forgets older command usage
+//! ```
+
+use anyhow::{bail, Context as _, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Layout version written by this build.
+pub const FORMAT_VERSION: u64 = 1;
+
+/// Prefix of the keys owned by plugins.
+pub const PLUGIN_PREFIX: &str = "plugin:";
+
+/// The key holding `plugin`'s state.
+pub fn plugin_key(plugin: &str) -> String {
+    format!("{}{}", PLUGIN_PREFIX, plugin)
+}
+
+#[derive(Debug, Clone)]
+pub struct StateLimits {
+    pub plugin_quota_bytes: usize,
+    pub usage_max_age_days: u64,
+}
+
+impl Default for StateLimits {
+    fn default() -> Self {
+        Self {
+            plugin_quota_bytes: 256 * 1024,
+            usage_max_age_days: 180,
+        }
+    }
+}
+
+impl StateLimits {
+    /// Reads the `[state]` section, keeping defaults for anything unset.
+    pub fn from_config(config: &HashMap<String, toml::Value>) -> Self {
+        let mut limits = Self::default();
+        let Some(section) = config.get("state") else {
+            return limits;
+        };
+        let get = |key: &str| {
+            section
+                .get(key)
+                .and_then(|v| v.as_integer())
+                .filter(|v| *v > 0)
+        };
+        if let Some(v) = get("plugin_quota_bytes") {
+            limits.plugin_quota_bytes = v as usize;
+        }
+        if let Some(v) = get("usage_max_age_days") {
+            limits.usage_max_age_days = v as u64;
+        }
+        limits
+    }
+}
+
+pub struct StateStore {
+    path: PathBuf,
+    data: BTreeMap<String, serde_json::Value>,
+}
+
+impl StateStore {
+    /// Opens the store at `path`, upgrading older layouts. A missing file
+    /// gives an empty store.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let data = if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Could not read state at {:?}", path))?;
+            let document: serde_json::Value = serde_json::from_str(&content)
+                .with_context(|| format!("Corrupt state file at {:?}", path))?;
+            Self::upgrade(document).with_context(|| format!("Unusable state file at {:?}", path))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self { path, data })
+    }
+
+    /// Turns any known on-disk layout into the current in-memory one.
+    fn upgrade(document: serde_json::Value) -> Result<BTreeMap<String, serde_json::Value>> {
+        let version = document.get("version").and_then(|v| v.as_u64());
+        let entries = document.get("entries").filter(|e| e.is_object());
+        match (version, entries) {
+            (Some(v), _) if v > FORMAT_VERSION => bail!(
+                "State was written by a newer drk (format v{}, this build reads up to v{})",
+                v,
+                FORMAT_VERSION
+            ),
+            (Some(_), Some(entries)) => Ok(serde_json::from_value(entries.clone())?),
+            // v0: the entries map was the whole file
+            _ => Ok(serde_json::from_value(document)?),
+        }
+    }
+
+    /// The default store inside the drk data directory.
+    pub fn default_path() -> PathBuf {
+        crate::paths::data_dir().join("state.json")
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads `key`, or `None` if it's missing or no longer matches `T`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.data
+            .get(key)
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    pub fn set<T: Serialize>(&mut self, key: &str, value: &T) -> Result<()> {
+        self.data
+            .insert(key.to_string(), serde_json::to_value(value)?);
+        Ok(())
+    }
+
+    /// Stores `plugin`'s state, refusing it if it exceeds `quota` bytes.
+    pub fn set_plugin<T: Serialize>(
+        &mut self,
+        plugin: &str,
+        value: &T,
+        quota: usize,
+    ) -> Result<()> {
+        let value = serde_json::to_value(value)?;
+        let size = serde_json::to_string(&value)?.len();
+        if size > quota {
+            bail!(
+                "state for plugin '{}' is {} bytes, over its {} byte quota",
+                plugin,
+                size,
+                quota
+            );
+        }
+        self.data.insert(plugin_key(plugin), value);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<serde_json::Value> {
+        self.data.remove(key)
+    }
+
+    /// Every key with the serialized size of its value, in key order.
+    pub fn sizes(&self) -> Vec<(String, usize)> {
+        self.data
+            .iter()
+            .map(|(k, v)| (k.clone(), serde_json::to_string(v).map_or(0, |s| s.len())))
+            .collect()
+    }
+
+    /// Drops empty entries and those `keep` rejects. Returns the removed keys.
+    pub fn compact(&mut self, keep: impl Fn(&str) -> bool) -> Vec<String> {
+        let removed: Vec<String> = self
+            .data
+            .iter()
+            .filter(|(k, v)| !keep(k) || is_empty(v))
+            .map(|(k, _)| k.clone())
+            .collect();
+        for key in &removed {
+            self.data.remove(key);
+        }
+        removed
+    }
+
+    /// The whole store in its on-disk layout.
+    pub fn export(&self) -> serde_json::Value {
+        serde_json::json!({
+            "version": FORMAT_VERSION,
+            "entries": self.data,
+        })
+    }
+
+    /// Writes the store, replacing the old file atomically so a crash never
+    /// leaves truncated JSON behind.
+    pub fn save(&self) -> Result<()> {
+        let dir = self.path.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir)?;
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        std::io::Write::write_all(
+            &mut tmp,
+            serde_json::to_string_pretty(&self.export())?.as_bytes(),
+        )?;
+        tmp.persist(&self.path)
+            .with_context(|| format!("Could not write state at {:?}", self.path))?;
+        Ok(())
+    }
+}
+
+fn is_empty(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => true,
+        serde_json::Value::Object(map) => map.is_empty(),
+        serde_json::Value::Array(items) => items.is_empty(),
+        _ => false,
+    }
+}
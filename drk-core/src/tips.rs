@@ -0,0 +1,125 @@
+//! Occasional hints after commands, declared by plugins (`Plugin::tips`).
+//!
+//! A tip becomes due once its command has run `min_runs` times. At most one
+//! is shown per `interval_hours`, and the same one again only after
+//! `repeat_days`, until `drk tips dismiss` hides it for good:
+//!
+//! ```toml
+//! [tips]
+//! enabled = true
+//! interval_hours = 24
+//! repeat_days = 14
+//! ```
+
+use crate::state::StateStore;
+use drk_api::Tip;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The state store key holding which tips were shown and dismissed.
+pub const STATE_KEY: &str = "tips";
+
+const HOUR: u64 = 60 * 60;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    pub enabled: bool,
+    /// Seconds between any two tips
+    pub interval: u64,
+    /// Seconds before the same tip is shown again
+    pub repeat: u64,
+}
+
+impl Settings {
+    pub fn from_config(config: &HashMap<String, toml::Value>) -> Self {
+        let section = config.get("tips");
+        let number = |key: &str, default: i64| {
+            section
+                .and_then(|s| s.get(key))
+                .and_then(|v| v.as_integer())
+                .unwrap_or(default)
+                .max(0) as u64
+        };
+        Self {
+            enabled: section
+                .and_then(|s| s.get("enabled"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true),
+            interval: number("interval_hours", 24) * HOUR,
+            repeat: number("repeat_days", 14) * 24 * HOUR,
+        }
+    }
+}
+
+/// What a tip is called in `drk tips` and the state store.
+pub fn key(plugin: &str, tip: &Tip) -> String {
+    format!("{}.{}", plugin, tip.id)
+}
+
+/// The tip's message for a command run `count` times.
+pub fn render(tip: &Tip, count: u64) -> String {
+    tip.message.replace("{count}", &count.to_string())
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TipState {
+    /// Seconds since the Unix epoch when any tip was last shown
+    #[serde(default)]
+    pub last_shown: u64,
+    /// When each tip was last shown, by [`key`]
+    #[serde(default)]
+    pub shown: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub dismissed: BTreeSet<String>,
+}
+
+impl TipState {
+    pub fn load(state: &StateStore) -> Self {
+        state.get(STATE_KEY).unwrap_or_default()
+    }
+
+    pub fn store(&self, state: &mut StateStore) -> anyhow::Result<()> {
+        state.set(STATE_KEY, self)
+    }
+
+    /// The tip to show after `command`, which has now run `count` times, if
+    /// one is due and the rate limit allows it.
+    pub fn due<'a>(
+        &self,
+        tips: &'a [(String, Tip)],
+        command: &str,
+        count: u64,
+        settings: &Settings,
+    ) -> Option<&'a (String, Tip)> {
+        let now = now();
+        if !settings.enabled || now.saturating_sub(self.last_shown) < settings.interval {
+            return None;
+        }
+        tips.iter()
+            .filter(|(_, tip)| tip.command == command && count >= tip.min_runs)
+            .filter(|(plugin, tip)| {
+                let key = key(plugin, tip);
+                !self.dismissed.contains(&key)
+                    && self
+                        .shown
+                        .get(&key)
+                        .is_none_or(|at| now.saturating_sub(*at) >= settings.repeat)
+            })
+            // The one seen longest ago, so several tips take turns
+            .min_by_key(|(plugin, tip)| self.shown.get(&key(plugin, tip)).copied())
+    }
+
+    pub fn mark_shown(&mut self, key: &str) {
+        let now = now();
+        self.last_shown = now;
+        self.shown.insert(key.to_string(), now);
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
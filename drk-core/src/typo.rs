@@ -0,0 +1,40 @@
+//! "Did you mean ...?" matching for mistyped subcommands.
+//!
+//! Auto-running the correction is opt-in, like git's `help.autocorrect`:
+//!
+//! ```toml
+//! [help]
+//! autocorrect = true
+//! ```
+
+use std::collections::HashMap;
+
+/// Minimum Jaro-Winkler similarity for a suggestion to be worth showing.
+pub const THRESHOLD: f64 = 0.8;
+
+/// The candidate most similar to `typo`, if any clears [`THRESHOLD`].
+///
+/// # Example
+/// ```
+/// # use drk_core::typo::closest;
+/// let commands = vec!["greet".to_string(), "echo".to_string()];
+/// assert_eq!(closest("gret", &commands).as_deref(), Some("greet"));
+/// assert_eq!(closest("xyz", &commands), None);
+/// ```
+pub fn closest(typo: &str, candidates: &[String]) -> Option<String> {
+    candidates
+        .iter()
+        .map(|c| (c, strsim::jaro_winkler(typo, c)))
+        .filter(|(_, score)| *score >= THRESHOLD)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(c, _)| c.clone())
+}
+
+/// Whether `[help] autocorrect` is switched on.
+pub fn autocorrect_enabled(config: &HashMap<String, toml::Value>) -> bool {
+    config
+        .get("help")
+        .and_then(|h| h.get("autocorrect"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
@@ -0,0 +1,129 @@
+//! Command usage tracking for suggestions.
+//!
+//! Every executed command bumps a counter (overall and per project) in the
+//! state store. Counts are weighted by recency ("frecency") so a command used
+//! a lot last year doesn't outrank the one used all week.
+
+use crate::state::StateStore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The state store key holding usage stats.
+pub const STATE_KEY: &str = "usage";
+
+const DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandUsage {
+    pub count: u64,
+    /// Seconds since the Unix epoch.
+    pub last_used: u64,
+    /// Per project root: how often the command ran there.
+    #[serde(default)]
+    pub projects: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageStats {
+    #[serde(default)]
+    pub commands: BTreeMap<String, CommandUsage>,
+}
+
+impl UsageStats {
+    pub fn load(state: &StateStore) -> Self {
+        state.get(STATE_KEY).unwrap_or_default()
+    }
+
+    pub fn store(&self, state: &mut StateStore) -> anyhow::Result<()> {
+        state.set(STATE_KEY, self)
+    }
+
+    /// Records one run of `command` inside `project`.
+    pub fn record(&mut self, command: &str, project: Option<&Path>) {
+        let entry = self.commands.entry(command.to_string()).or_default();
+        entry.count += 1;
+        entry.last_used = now();
+        if let Some(project) = project {
+            *entry
+                .projects
+                .entry(project.display().to_string())
+                .or_default() += 1;
+        }
+    }
+
+    /// Recency-weighted score. Restricted to `project` when given.
+    pub fn frecency(&self, command: &str, project: Option<&Path>) -> f64 {
+        let Some(usage) = self.commands.get(command) else {
+            return 0.0;
+        };
+        let count = match project {
+            Some(p) => usage
+                .projects
+                .get(&p.display().to_string())
+                .copied()
+                .unwrap_or(0),
+            None => usage.count,
+        };
+
+        let age = now().saturating_sub(usage.last_used);
+        let weight = if age < DAY {
+            4.0
+        } else if age < 7 * DAY {
+            2.0
+        } else if age < 30 * DAY {
+            1.0
+        } else {
+            0.5
+        };
+        count as f64 * weight
+    }
+
+    /// Commands ranked by frecency, highest first, skipping unused ones.
+    pub fn ranked(&self, project: Option<&Path>) -> Vec<(String, f64)> {
+        let mut ranked: Vec<(String, f64)> = self
+            .commands
+            .keys()
+            .map(|name| (name.clone(), self.frecency(name, project)))
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked
+    }
+
+    /// Forgets commands not used for `max_age_days`. Returns how many went.
+    pub fn prune(&mut self, max_age_days: u64) -> usize {
+        let cutoff = now().saturating_sub(max_age_days * DAY);
+        let before = self.commands.len();
+        self.commands.retain(|_, usage| usage.last_used >= cutoff);
+        before - self.commands.len()
+    }
+
+    /// The used command closest to `typo`, favouring frequently used ones.
+    ///
+    /// Only commands in `available` are considered, so suggestions never point
+    /// at a plugin that has since been removed.
+    pub fn suggest_for_typo(&self, typo: &str, available: &[String]) -> Option<String> {
+        available
+            .iter()
+            .filter(|name| self.commands.contains_key(*name))
+            .filter_map(|name| {
+                let similarity = strsim::jaro_winkler(typo, name);
+                if similarity < 0.75 {
+                    return None;
+                }
+                let boost = 0.05 * (1.0 + self.frecency(name, None)).ln();
+                Some((name, similarity + boost))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(name, _)| name.clone())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,361 @@
+//! WebAssembly plugins: `.wasm` modules in a plugin directory, run in a
+//! sandbox by an interpreter instead of loaded into the process.
+//!
+//! They don't depend on the Rust ABI at all, and get no file system, network
+//! or environment access; all they can do is answer the calls below and
+//! print. Data crosses the boundary as JSON (the same wire format
+//! [`drk_api::abi`] uses) in the module's linear memory. A call that returns
+//! a buffer packs it into an `i64` as `ptr << 32 | len`, where `0` means
+//! "nothing" or, for status calls, success; any other status is a UTF-8
+//! error message.
+//!
+//! A module exports:
+//!
+//! | export | signature | |
+//! |---|---|---|
+//! | `memory` | memory | |
+//! | `drk_abi_version` | `() -> i32` | must return [`WASM_ABI_VERSION`] |
+//! | `drk_alloc` | `(len: i32) -> i32` | a buffer the host fills for the next call; the plugin frees it |
+//! | `drk_metadata` | `() -> i64` | JSON [`PluginMetadata`] |
+//! | `drk_commands` | `() -> i64` | optional, JSON list of [`PluginCommand`] |
+//! | `drk_handle_event` | `(ptr: i32, len: i32) -> i64` | optional, takes a JSON event, returns a status |
+//! | `drk_on_load`, `drk_on_unload` | `() -> i64` | optional, return a status |
+//!
+//! and may import `print(ptr: i32, len: i32)` and `eprint(ptr: i32, len: i32)`
+//! from the `drk` module to write text to stdout and stderr.
+//!
+//! Each call gets a fixed amount of fuel, so a plugin stuck in a loop fails
+//! with an error instead of hanging drk.
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use drk_api::abi::encode_event;
+use drk_api::{Context, Plugin, PluginCommand, PluginMetadata, SystemEvent};
+use serde::de::DeserializeOwned;
+use std::io::Write;
+use std::path::Path;
+use wasmi::{
+    AsContext, Caller, Config, Engine, Instance, Linker, Memory, Module, Store, TypedFunc,
+};
+
+/// The version of the interface described above.
+pub const WASM_ABI_VERSION: i32 = 1;
+
+/// Roughly how many instructions one call may run.
+const FUEL_PER_CALL: u64 = 1_000_000_000;
+
+pub fn is_wasm_module(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "wasm")
+}
+
+pub struct WasmPlugin {
+    guest: Guest,
+    // Read once up front; a plugin whose answers don't parse isn't loaded
+    metadata: PluginMetadata,
+    commands: Vec<PluginCommand>,
+}
+
+/// An instantiated module and the exports every call needs.
+struct Guest {
+    store: Store<()>,
+    instance: Instance,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+}
+
+impl WasmPlugin {
+    /// Compiles and instantiates the module at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path).with_context(|| format!("Could not read {:?}", path))?;
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, &bytes[..])
+            .map_err(|e| anyhow!("Not a valid WebAssembly module: {}", e))?;
+
+        let mut store = Store::new(&engine, ());
+        let mut linker = <Linker<()>>::new(&engine);
+        linker.func_wrap(
+            "drk",
+            "print",
+            |caller: Caller<'_, ()>, ptr: i32, len: i32| {
+                if let Some(text) = read_caller(&caller, ptr, len) {
+                    print!("{}", text);
+                    let _ = std::io::stdout().flush();
+                }
+            },
+        )?;
+        linker.func_wrap(
+            "drk",
+            "eprint",
+            |caller: Caller<'_, ()>, ptr: i32, len: i32| {
+                if let Some(text) = read_caller(&caller, ptr, len) {
+                    eprint!("{}", text);
+                }
+            },
+        )?;
+        refuel(&mut store);
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| anyhow!("Could not instantiate the module: {}", e))?;
+
+        let version = instance
+            .get_typed_func::<(), i32>(&store, "drk_abi_version")
+            .map_err(|_| anyhow!("Missing export drk_abi_version, is this a drk plugin?"))?
+            .call(&mut store, ())?;
+        if version != WASM_ABI_VERSION {
+            bail!(
+                "Plugin was built for WebAssembly interface v{}, this drk supports v{}",
+                version,
+                WASM_ABI_VERSION
+            );
+        }
+        let memory = instance
+            .get_memory(&store, "memory")
+            .context("Missing export memory")?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, "drk_alloc")
+            .map_err(|_| anyhow!("Missing export drk_alloc"))?;
+
+        let mut guest = Guest {
+            store,
+            instance,
+            memory,
+            alloc,
+        };
+        let metadata = guest
+            .call_json("drk_metadata")?
+            .context("Missing export drk_metadata")?;
+        let commands = guest.call_json("drk_commands")?.unwrap_or_default();
+        Ok(Self {
+            guest,
+            metadata,
+            commands,
+        })
+    }
+}
+
+impl Guest {
+    /// Calls a `() -> i64` export returning JSON. `None` if it isn't exported.
+    fn call_json<T: DeserializeOwned>(&mut self, export: &str) -> Result<Option<T>> {
+        let Some(packed) = self.call_packed(export)? else {
+            return Ok(None);
+        };
+        let bytes = self.read(packed)?;
+        let value = serde_json::from_slice(&bytes)
+            .with_context(|| format!("{} returned invalid JSON", export))?;
+        Ok(Some(value))
+    }
+
+    /// Calls a `() -> i64` export returning a status. Succeeds if it isn't exported.
+    fn call_status(&mut self, export: &str) -> Result<()> {
+        match self.call_packed(export)? {
+            Some(packed) => self.status(packed),
+            None => Ok(()),
+        }
+    }
+
+    fn call_packed(&mut self, export: &str) -> Result<Option<i64>> {
+        let Ok(func) = self.instance.get_typed_func::<(), i64>(&self.store, export) else {
+            return Ok(None);
+        };
+        refuel(&mut self.store);
+        let packed = func
+            .call(&mut self.store, ())
+            .map_err(|e| anyhow!("{} failed: {}", export, e))?;
+        Ok(Some(packed))
+    }
+
+    fn status(&self, packed: i64) -> Result<()> {
+        if packed == 0 {
+            return Ok(());
+        }
+        let message = self.read(packed)?;
+        bail!("{}", String::from_utf8_lossy(&message))
+    }
+
+    /// Copies the buffer a call returned out of the module's memory.
+    fn read(&self, packed: i64) -> Result<Vec<u8>> {
+        let (ptr, len) = ((packed >> 32) as u32 as usize, packed as u32 as usize);
+        self.memory
+            .data(&self.store)
+            .get(ptr..ptr + len)
+            .map(<[u8]>::to_vec)
+            .context("Plugin returned a buffer outside its memory")
+    }
+
+    /// Copies `bytes` into a buffer from `drk_alloc`.
+    fn write(&mut self, bytes: &[u8]) -> Result<(i32, i32)> {
+        let len = i32::try_from(bytes.len()).context("Too much data for the plugin")?;
+        refuel(&mut self.store);
+        let ptr = self
+            .alloc
+            .call(&mut self.store, len)
+            .map_err(|e| anyhow!("drk_alloc failed: {}", e))?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, bytes)
+            .map_err(|_| anyhow!("drk_alloc returned a buffer outside its memory"))?;
+        Ok((ptr, len))
+    }
+}
+
+fn refuel(store: &mut Store<()>) {
+    store
+        .set_fuel(FUEL_PER_CALL)
+        .expect("the engine meters fuel");
+}
+
+fn read_caller(caller: &Caller<'_, ()>, ptr: i32, len: i32) -> Option<String> {
+    let memory = caller.get_export("memory")?.into_memory()?;
+    let (ptr, len) = (ptr as u32 as usize, len as u32 as usize);
+    let bytes = memory.data(caller.as_context()).get(ptr..ptr + len)?;
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+impl Plugin for WasmPlugin {
+    fn metadata(&self) -> PluginMetadata {
+        self.metadata.clone()
+    }
+
+    fn get_commands(&self) -> Vec<PluginCommand> {
+        self.commands.clone()
+    }
+
+    fn on_load(&mut self) -> Result<()> {
+        self.guest.call_status("drk_on_load")
+    }
+
+    fn on_unload(&mut self) -> Result<()> {
+        self.guest.call_status("drk_on_unload")
+    }
+
+    fn handle_event(&mut self, event: &SystemEvent, _ctx: &mut Context) -> Result<()> {
+        let guest = &mut self.guest;
+        let Ok(handle) = guest
+            .instance
+            .get_typed_func::<(i32, i32), i64>(&guest.store, "drk_handle_event")
+        else {
+            return Ok(());
+        };
+        let (ptr, len) = guest.write(encode_event(event).as_bytes())?;
+        refuel(&mut guest.store);
+        let packed = handle
+            .call(&mut guest.store, (ptr, len))
+            .map_err(|e| anyhow!("drk_handle_event failed: {}", e))?;
+        guest.status(packed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use drk_api::CancellationToken;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    const METADATA: &str = r#"{"name":"echo","version":"0.1.0","author":"drk","description":"Echoes","essential":false}"#;
+    const COMMANDS: &str = r#"[{"name":"echo","description":"Echo it","args":[]}]"#;
+    const REFUSAL: &str = "not today";
+
+    fn packed(ptr: i64, text: &str) -> i64 {
+        ptr << 32 | text.len() as i64
+    }
+
+    /// Builds a module speaking interface `version` whose `drk_on_load`
+    /// runs `on_load`, and writes it to `dir`.
+    fn module(dir: &Path, version: i32, on_load: &str) -> PathBuf {
+        let text = format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "{metadata}")
+                (data (i32.const 256) "{commands}")
+                (data (i32.const 512) "{refusal}")
+                (func (export "drk_abi_version") (result i32) (i32.const {version}))
+                (func (export "drk_alloc") (param i32) (result i32) (i32.const 1024))
+                (func (export "drk_metadata") (result i64) (i64.const {metadata_at}))
+                (func (export "drk_commands") (result i64) (i64.const {commands_at}))
+                (func (export "drk_on_load") (result i64) {on_load})
+                (func (export "drk_handle_event") (param i32 i32) (result i64)
+                    (if (result i64) (i32.eq (i32.load8_u (local.get 0)) (i32.const 123))
+                        (then (i64.const 0))
+                        (else (i64.const {refusal_at})))))"#,
+            metadata = METADATA.replace('"', "\\\""),
+            commands = COMMANDS.replace('"', "\\\""),
+            refusal = REFUSAL,
+            metadata_at = packed(0, METADATA),
+            commands_at = packed(256, COMMANDS),
+            refusal_at = packed(512, REFUSAL),
+        );
+        let path = dir.join("echo.wasm");
+        std::fs::write(&path, wat::parse_str(text).unwrap()).unwrap();
+        path
+    }
+
+    #[test]
+    fn modules_answer_through_their_exports() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut plugin = WasmPlugin::load(&module(dir.path(), 1, "(i64.const 0)")).unwrap();
+
+        assert_eq!(plugin.metadata().name, "echo");
+        assert_eq!(plugin.get_commands()[0].description, "Echo it");
+        plugin.on_load().unwrap();
+        plugin.on_unload().unwrap();
+
+        let mut config = HashMap::new();
+        let mut state = toml::Table::new();
+        let cancel = CancellationToken::default();
+        let mut ctx = Context {
+            config: &mut config,
+            event_sender: &mut |_| {},
+            state: &mut state,
+            data_dir: None,
+            cancel: &cancel,
+            on_exit: &mut |_| {},
+            permit: &mut |_| false,
+        };
+        plugin
+            .handle_event(&SystemEvent::Startup, &mut ctx)
+            .unwrap();
+    }
+
+    #[test]
+    fn a_failing_status_carries_the_plugins_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let on_load = format!("(i64.const {})", packed(512, REFUSAL));
+        let mut plugin = WasmPlugin::load(&module(dir.path(), 1, &on_load)).unwrap();
+        assert_eq!(plugin.on_load().unwrap_err().to_string(), REFUSAL);
+    }
+
+    #[test]
+    fn a_trap_fails_the_call_instead_of_drk() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut plugin = WasmPlugin::load(&module(dir.path(), 1, "(unreachable)")).unwrap();
+        assert!(plugin
+            .on_load()
+            .unwrap_err()
+            .to_string()
+            .starts_with("drk_on_load failed"));
+    }
+
+    #[test]
+    fn other_interface_versions_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = WasmPlugin::load(&module(dir.path(), 2, "(i64.const 0)"))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("interface v2"));
+    }
+
+    #[test]
+    fn files_that_are_not_modules_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fake.wasm");
+        std::fs::write(&path, "not wasm").unwrap();
+        let err = WasmPlugin::load(&path).err().unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("Not a valid WebAssembly module"));
+        assert!(is_wasm_module(&path));
+        assert!(!is_wasm_module(Path::new("libecho.so")));
+    }
+}
@@ -1,7 +1,8 @@
 use anyhow::Result;
 use drk_api::{
-    declare_plugin, icon_error, icon_info, icon_success, style_error, style_primary, style_success,
-    ArgType, CommandArg, CommandMatches, Context, Plugin, PluginCommand, PluginMetadata,
+    declare_plugin, icon_error, icon_info, icon_success, serde_json::json, style_error,
+    style_primary, style_success, ArgType, CommandArg, CommandMatches, CommandOutput, ConfigKey,
+    ConfigType, Context, ControlFlow, EventKind, Plugin, PluginCommand, PluginMetadata,
     SystemEvent,
 };
 
@@ -26,12 +27,17 @@ impl Plugin for BasicPlugin {
             PluginCommand {
                 name: "greet".to_string(),
                 description: "Greet someone by name".to_string(),
+                aliases: vec!["g".to_string()],
                 args: vec![CommandArg {
                     name: "name".to_string(),
                     description: "The name to greet".to_string(),
                     required: false,
                     arg_type: ArgType::String,
+                    default: Some("World".to_string()),
+                    env: Some("DRK_GREET_NAME".to_string()),
+                    ..Default::default()
                 }],
+                ..Default::default()
             },
             // Echo command
             PluginCommand {
@@ -42,16 +48,99 @@ impl Plugin for BasicPlugin {
                     description: "The message to echo".to_string(),
                     required: true,
                     arg_type: ArgType::String,
+                    ..Default::default()
                 }],
+                cacheable: true,
+                ..Default::default()
             },
         ]
     }
 
+    fn config_version(&self) -> u32 {
+        1
+    }
+
+    fn config_schema(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey {
+                name: "greeting_prefix".to_string(),
+                description: "The word `drk greet` puts before the name".to_string(),
+                value_type: ConfigType::String,
+                default: Some("Hello".into()),
+            },
+            ConfigKey {
+                name: "blocked_commands".to_string(),
+                description: "Commands this plugin stops from running".to_string(),
+                value_type: ConfigType::Array,
+                default: None,
+            },
+        ]
+    }
+
+    fn migrate_config(&self, from: u32, section: &mut drk_api::toml::Table) -> Result<()> {
+        // v0 called the greeting prefix `greeting`
+        if from == 0 {
+            if let Some(prefix) = section.remove("greeting") {
+                section.insert("greeting_prefix".to_string(), prefix);
+            }
+        }
+        Ok(())
+    }
+
     fn on_load(&mut self) -> Result<()> {
         println!("[BasicPlugin] Loaded and ready!");
         Ok(())
     }
 
+    fn on_install(&mut self, ctx: &mut Context) -> Result<()> {
+        // Write the default greeting so users can find and change it
+        let section = ctx
+            .config
+            .entry("basic".to_string())
+            .or_insert_with(|| drk_api::toml::Value::Table(Default::default()));
+        if let Some(table) = section.as_table_mut() {
+            table
+                .entry("greeting_prefix")
+                .or_insert_with(|| "Hello".into());
+        }
+
+        println!(
+            "{} {} try {} or {}",
+            style_primary(icon_info()),
+            style_primary("basic is set up:"),
+            style_success("drk greet --name you"),
+            style_success("drk echo --message hi")
+        );
+        Ok(())
+    }
+
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![EventKind::Startup, EventKind::PreCommand]
+    }
+
+    // Runs before every command, and may stop it from running
+    fn on_pre_command(
+        &mut self,
+        name: &str,
+        _args: &[String],
+        ctx: &mut Context,
+    ) -> Result<ControlFlow> {
+        println!("[BasicPlugin] PreCommand hook: {}", name);
+        let blocked = ctx
+            .config
+            .get("basic")
+            .and_then(|cfg| cfg.get("blocked_commands"))
+            .and_then(|list| list.as_array())
+            .is_some_and(|list| list.iter().any(|v| v.as_str() == Some(name)));
+        if blocked {
+            return Ok(ControlFlow::Cancel(format!(
+                "'{}' is in [basic] blocked_commands",
+                name
+            )));
+        }
+        Ok(ControlFlow::Continue)
+    }
+
     fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> Result<()> {
         match event {
             // Hook into the application startup
@@ -60,19 +149,12 @@ impl Plugin for BasicPlugin {
             }
 
             // Handle command execution
+            // Only handle commands meant for this plugin
             SystemEvent::ExecuteCommand {
                 plugin_name,
                 matches,
-            } => {
-                // Only handle commands meant for this plugin
-                if plugin_name == "basic" {
-                    self.execute_command(matches, ctx)?;
-                }
-            }
-
-            // Legacy hook for PreCommand (for backward compatibility)
-            SystemEvent::PreCommand { name, .. } => {
-                println!("[BasicPlugin] PreCommand hook: {}", name);
+            } if plugin_name == "basic" => {
+                self.execute_command(matches, ctx)?;
             }
 
             _ => {}
@@ -85,11 +167,8 @@ impl BasicPlugin {
     fn execute_command(&self, matches: &CommandMatches, ctx: &mut Context) -> Result<()> {
         match matches.command_name.as_str() {
             "greet" => {
-                let name = matches
-                    .args
-                    .get("name")
-                    .map(|s| s.as_str())
-                    .unwrap_or("World");
+                // Always there: drk fills in $DRK_GREET_NAME or the default
+                let name = matches.args["name"].as_str();
 
                 // Access config safely for greeting prefix
                 let mut prefix = "Hello".to_string();
@@ -99,20 +178,30 @@ impl BasicPlugin {
                     }
                 }
 
-                println!(
+                // State persists between runs, unlike locals
+                let greeted = ctx
+                    .state
+                    .get("greeted")
+                    .and_then(|v| v.as_integer())
+                    .unwrap_or(0)
+                    + 1;
+                ctx.state.insert("greeted".to_string(), greeted.into());
+
+                // drk prints the text, or the data with `--output json`
+                let text = format!(
                     "{} {} {}{}",
                     style_success(icon_success()),
                     style_success(&prefix),
                     style_primary(name),
                     style_success("!")
                 );
+                let data = json!({ "greeting": prefix, "name": name, "greeted": greeted });
+                ctx.output(CommandOutput::new(data).with_text(text));
 
                 // Fire a custom event back to the system
-                (ctx.event_sender)(SystemEvent::Custom {
-                    source: "basic".into(),
-                    event: "greeted".into(),
-                    payload: None,
-                });
+                let event = SystemEvent::custom("basic", "greeted")
+                    .with_data(&json!({ "name": name, "greeted": greeted }))?;
+                (ctx.event_sender)(event);
             }
 
             "echo" => {
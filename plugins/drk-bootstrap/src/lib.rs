@@ -0,0 +1,533 @@
+//! `drk bootstrap apply`: brings a machine in line with the `[bootstrap]`
+//! section of the config, like a Brewfile that also covers drk plugins and
+//! dotfiles.
+//!
+//! ```toml
+//! [bootstrap]
+//! packages = ["git", "ripgrep"]
+//! # A registry name, or a name with where to install it from
+//! plugins = ["go", { name = "rust", git = "https://github.com/me/drk-rust" }]
+//! dotfiles_dir = "~/dotfiles"
+//!
+//! [bootstrap.dotfiles]
+//! "~/.gitconfig" = "gitconfig"
+//! ```
+//!
+//! Everything already in place is left alone. `--dry-run` prints what would
+//! change, with a diff for every dotfile, and changes nothing.
+
+use anyhow::{anyhow, bail, Result};
+use drk_api::diff;
+use drk_api::fs::Transaction;
+use drk_api::tools::which;
+use drk_api::{
+    declare_plugin, icon_info, icon_success, serde_json::json, style_dim, style_primary,
+    style_success, style_warning, toml, ArgType, Capability, CommandArg, CommandMatches,
+    CommandOutput, ConfigKey, ConfigType, Context, EventKind, Plugin, PluginCommand,
+    PluginMetadata, SystemEvent,
+};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Where dotfile sources are looked up unless `dotfiles_dir` says otherwise
+const DEFAULT_DOTFILES_DIR: &str = "~/dotfiles";
+
+/// What an overwritten dotfile is kept as, next to it
+const BACKUP_SUFFIX: &str = ".drk-backup";
+
+#[derive(Default)]
+struct BootstrapPlugin {
+    /// The plugins that loaded, by name, from `PluginsLoaded`
+    loaded: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PackageManager {
+    Brew,
+    Apt,
+    Dnf,
+    Pacman,
+}
+
+impl PackageManager {
+    const ALL: [PackageManager; 4] = [
+        PackageManager::Brew,
+        PackageManager::Apt,
+        PackageManager::Dnf,
+        PackageManager::Pacman,
+    ];
+
+    fn key(self) -> &'static str {
+        match self {
+            PackageManager::Brew => "brew",
+            PackageManager::Apt => "apt",
+            PackageManager::Dnf => "dnf",
+            PackageManager::Pacman => "pacman",
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            PackageManager::Apt => "apt-get",
+            other => other.key(),
+        }
+    }
+
+    /// `[bootstrap] package_manager`, or the first one on `PATH` for `auto`
+    fn configured(ctx: &Context) -> Result<Self> {
+        let key = setting(ctx, "package_manager")
+            .and_then(|v| v.as_str())
+            .unwrap_or("auto");
+        if key == "auto" {
+            return Self::ALL
+                .into_iter()
+                .find(|pm| which(pm.program()).is_some())
+                .ok_or_else(|| {
+                    anyhow!("No supported package manager (brew, apt, dnf, pacman) is installed")
+                });
+        }
+        Self::ALL
+            .into_iter()
+            .find(|pm| pm.key() == key)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown package manager '{}' in [bootstrap] package_manager; use auto, brew, apt, dnf or pacman",
+                    key
+                )
+            })
+    }
+
+    fn is_installed(self, package: &str) -> bool {
+        let (program, args): (&str, &[&str]) = match self {
+            PackageManager::Brew => ("brew", &["list", "--versions"]),
+            PackageManager::Apt => ("dpkg", &["-s"]),
+            PackageManager::Dnf => ("rpm", &["-q"]),
+            PackageManager::Pacman => ("pacman", &["-Q"]),
+        };
+        Command::new(program)
+            .args(args)
+            .arg(package)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+
+    fn install(self, packages: &[String]) -> Command {
+        let mut command = match self {
+            PackageManager::Brew => Command::new("brew"),
+            // System package managers want root
+            _ if which("sudo").is_some() => {
+                let mut sudo = Command::new("sudo");
+                sudo.arg(self.program());
+                sudo
+            }
+            _ => Command::new(self.program()),
+        };
+        match self {
+            PackageManager::Brew => command.arg("install"),
+            PackageManager::Apt | PackageManager::Dnf => command.args(["install", "-y"]),
+            PackageManager::Pacman => command.args(["-S", "--needed", "--noconfirm"]),
+        };
+        command.args(packages);
+        command
+    }
+}
+
+/// A plugin to install, and the `drk plugin install` arguments that do it
+#[derive(Debug, Clone)]
+struct PluginSpec {
+    name: String,
+    install_args: Vec<String>,
+}
+
+impl PluginSpec {
+    fn parse(value: &toml::Value) -> Result<Self> {
+        if let Some(name) = value.as_str() {
+            return Ok(Self {
+                name: name.to_string(),
+                install_args: vec![name.to_string()],
+            });
+        }
+        let table = value
+            .as_table()
+            .ok_or_else(|| anyhow!("[bootstrap] plugins takes names or tables, not {}", value))?;
+        let name = table
+            .get("name")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("A [bootstrap] plugins entry has no name"))?;
+        let source = ["git", "url", "path"]
+            .into_iter()
+            .find_map(|key| Some((key, table.get(key)?.as_str()?)));
+        let install_args = match source {
+            Some((key, from)) => vec![format!("--{}", key), from.to_string()],
+            None => vec![name.to_string()],
+        };
+        Ok(Self {
+            name: name.to_string(),
+            install_args,
+        })
+    }
+}
+
+/// A dotfile that's missing or differs from its source
+#[derive(Debug, Clone)]
+struct DotfileChange {
+    target: PathBuf,
+    /// What it holds now; `None` if it doesn't exist
+    current: Option<String>,
+    desired: String,
+}
+
+/// Everything `apply` would do
+#[derive(Debug, Default)]
+struct Plan {
+    package_manager: Option<PackageManager>,
+    packages: Vec<String>,
+    plugins: Vec<PluginSpec>,
+    dotfiles: Vec<DotfileChange>,
+}
+
+impl Plan {
+    fn is_empty(&self) -> bool {
+        self.packages.is_empty() && self.plugins.is_empty() && self.dotfiles.is_empty()
+    }
+
+    fn render(&self) -> String {
+        let mut lines = Vec::new();
+        for package in &self.packages {
+            let via = self.package_manager.map(PackageManager::key).unwrap_or("?");
+            lines.push(format!(
+                "{} package {} {}",
+                style_success("+"),
+                style_primary(package),
+                style_dim(&format!("({})", via))
+            ));
+        }
+        for plugin in &self.plugins {
+            lines.push(format!(
+                "{} plugin {} {}",
+                style_success("+"),
+                style_primary(&plugin.name),
+                style_dim(&format!(
+                    "(drk plugin install {})",
+                    plugin.install_args.join(" ")
+                ))
+            ));
+        }
+        for change in &self.dotfiles {
+            let target = change.target.display().to_string();
+            match &change.current {
+                None => lines.push(format!(
+                    "{} dotfile {}",
+                    style_success("+"),
+                    style_primary(&target)
+                )),
+                Some(current) => {
+                    lines.push(format!(
+                        "{} dotfile {}",
+                        style_warning("~"),
+                        style_primary(&target)
+                    ));
+                    lines.extend(
+                        diff::render(current, &change.desired)
+                            .lines()
+                            .map(|l| format!("    {}", l)),
+                    );
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn to_json(&self) -> drk_api::serde_json::Value {
+        json!({
+            "package_manager": self.package_manager.map(PackageManager::key),
+            "packages": self.packages,
+            "plugins": self.plugins.iter().map(|p| &p.name).collect::<Vec<_>>(),
+            "dotfiles": self.dotfiles.iter().map(|d| json!({
+                "target": d.target.display().to_string(),
+                "exists": d.current.is_some(),
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl Plugin for BootstrapPlugin {
+    fn metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: "bootstrap".to_string(),
+            version: "0.1.0".to_string(),
+            author: "dark1zinn".to_string(),
+            description:
+                "Sets up a machine from the packages, plugins and dotfiles listed in [bootstrap]"
+                    .to_string(),
+            essential: false,
+        }
+    }
+
+    fn get_commands(&self) -> Vec<PluginCommand> {
+        vec![PluginCommand {
+            name: "bootstrap".to_string(),
+            description: "Converge this machine on the [bootstrap] config section".to_string(),
+            subcommands: vec![PluginCommand {
+                name: "apply".to_string(),
+                description: "Install missing packages and plugins and write dotfiles that differ"
+                    .to_string(),
+                args: vec![CommandArg {
+                    name: "dry-run".to_string(),
+                    description:
+                        "Print what would change, with dotfile diffs, without changing anything"
+                            .to_string(),
+                    required: false,
+                    arg_type: ArgType::Boolean,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }]
+    }
+
+    fn config_schema(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey {
+                name: "packages".to_string(),
+                description: "System packages to install".to_string(),
+                value_type: ConfigType::Array,
+                default: None,
+            },
+            ConfigKey {
+                name: "package_manager".to_string(),
+                description: "auto (the first of brew, apt, dnf, pacman on PATH), or one of them"
+                    .to_string(),
+                value_type: ConfigType::String,
+                default: Some("auto".into()),
+            },
+            ConfigKey {
+                name: "plugins".to_string(),
+                description:
+                    "drk plugins to install: registry names, or { name, git | url | path } tables"
+                        .to_string(),
+                value_type: ConfigType::Array,
+                default: None,
+            },
+            ConfigKey {
+                name: "dotfiles".to_string(),
+                description: "Target path = source file, relative to dotfiles_dir".to_string(),
+                value_type: ConfigType::Table,
+                default: None,
+            },
+            ConfigKey {
+                name: "dotfiles_dir".to_string(),
+                description: "Where dotfile sources are read from".to_string(),
+                value_type: ConfigType::String,
+                default: Some(DEFAULT_DOTFILES_DIR.into()),
+            },
+        ]
+    }
+
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![EventKind::PluginsLoaded]
+    }
+
+    fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> Result<()> {
+        match event {
+            SystemEvent::PluginsLoaded { plugins } => self.loaded = plugins.clone(),
+            SystemEvent::ExecuteCommand {
+                plugin_name,
+                matches,
+            } if plugin_name == "bootstrap" => self.execute_command(matches, ctx)?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl BootstrapPlugin {
+    fn execute_command(&self, matches: &CommandMatches, ctx: &mut Context) -> Result<()> {
+        if matches.subcommands().first().map(String::as_str) != Some("apply") {
+            println!(
+                "{} {}",
+                style_primary(icon_info()),
+                style_primary("Try `drk bootstrap apply --dry-run` to see what would change")
+            );
+            return Ok(());
+        }
+        if ctx.config.get("bootstrap").is_none() {
+            bail!(
+                "There is no [bootstrap] section in the config; `drk config docs` lists its keys"
+            );
+        }
+
+        // Checking which packages are installed runs the package manager
+        ctx.request_permission(Capability::ProcessSpawn)?;
+        let plan = self.plan(ctx)?;
+        let dry_run = matches.args.contains_key("dry-run");
+
+        let text = if plan.is_empty() {
+            format!(
+                "{} {}",
+                style_success(icon_success()),
+                style_success("This machine already matches [bootstrap]")
+            )
+        } else if dry_run {
+            format!(
+                "{}\n{}",
+                plan.render(),
+                style_dim("Dry run: nothing was changed")
+            )
+        } else {
+            // The output is printed after the command; the plan goes before the work
+            println!("{}", plan.render());
+            self.apply(&plan, ctx)?;
+            format!(
+                "{} {}",
+                style_success(icon_success()),
+                style_success("This machine now matches [bootstrap]")
+            )
+        };
+        ctx.output(CommandOutput::new(plan.to_json()).with_text(text));
+        Ok(())
+    }
+
+    fn plan(&self, ctx: &Context) -> Result<Plan> {
+        let mut plan = Plan::default();
+
+        let packages = strings(setting(ctx, "packages"), "packages")?;
+        if !packages.is_empty() {
+            let pm = PackageManager::configured(ctx)?;
+            plan.package_manager = Some(pm);
+            plan.packages = packages
+                .into_iter()
+                .filter(|p| !pm.is_installed(p))
+                .collect();
+        }
+
+        if let Some(entries) = setting(ctx, "plugins") {
+            let entries = entries
+                .as_array()
+                .ok_or_else(|| anyhow!("[bootstrap] plugins must be an array"))?;
+            for entry in entries {
+                let spec = PluginSpec::parse(entry)?;
+                if !self
+                    .loaded
+                    .iter()
+                    .any(|l| l.eq_ignore_ascii_case(&spec.name))
+                {
+                    plan.plugins.push(spec);
+                }
+            }
+        }
+
+        if let Some(dotfiles) = setting(ctx, "dotfiles") {
+            let dotfiles = dotfiles.as_table().ok_or_else(|| {
+                anyhow!("[bootstrap] dotfiles must be a table of target = source")
+            })?;
+            let dir = expand_home(
+                setting(ctx, "dotfiles_dir")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(DEFAULT_DOTFILES_DIR),
+            );
+            for (target, source) in dotfiles {
+                let source = source.as_str().ok_or_else(|| {
+                    anyhow!("[bootstrap] dotfiles \"{}\" must be a file path", target)
+                })?;
+                let source = dir.join(expand_home(source));
+                let desired = std::fs::read_to_string(&source)
+                    .map_err(|e| anyhow!("Could not read dotfile source {:?}: {}", source, e))?;
+                let target = expand_home(target);
+                let current = std::fs::read_to_string(&target).ok();
+                if current.as_deref() != Some(desired.as_str()) {
+                    plan.dotfiles.push(DotfileChange {
+                        target,
+                        current,
+                        desired,
+                    });
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    fn apply(&self, plan: &Plan, ctx: &mut Context) -> Result<()> {
+        if !plan.dotfiles.is_empty() {
+            ctx.request_permission(Capability::FsWrite)?;
+        }
+
+        let mut report = ctx.reporter();
+        if let (Some(pm), false) = (plan.package_manager, plan.packages.is_empty()) {
+            report.step(&format!(
+                "Installing {} with {}",
+                plan.packages.join(", "),
+                pm.key()
+            ));
+            report.exec(&mut pm.install(&plan.packages))?;
+        }
+        if !plan.plugins.is_empty() {
+            // The running drk installs them, wherever it lives
+            let drk = std::env::current_exe()?;
+            for plugin in &plan.plugins {
+                report.step(&format!("Installing plugin {}", plugin.name));
+                report.exec(
+                    Command::new(&drk)
+                        .args(["plugin", "install"])
+                        .args(&plugin.install_args),
+                )?;
+            }
+        }
+        if !plan.dotfiles.is_empty() {
+            report.step(&format!("Writing {} dotfiles", plan.dotfiles.len()));
+            // All of them or none, keeping what they replace
+            let mut txn = Transaction::new().keep_backups(BACKUP_SUFFIX);
+            for change in &plan.dotfiles {
+                txn.write(&change.target, change.desired.as_str());
+                if change.current.is_some() {
+                    report.log(&format!(
+                        "Keeping the old {} as {}{}",
+                        change.target.display(),
+                        change.target.display(),
+                        BACKUP_SUFFIX
+                    ));
+                }
+            }
+            txn.commit()?;
+        }
+        report.finish();
+        Ok(())
+    }
+}
+
+/// A key of the `[bootstrap]` section
+fn setting<'a>(ctx: &'a Context, key: &str) -> Option<&'a toml::Value> {
+    ctx.config.get("bootstrap")?.get(key)
+}
+
+fn strings(value: Option<&toml::Value>, key: &str) -> Result<Vec<String>> {
+    let Some(value) = value else {
+        return Ok(Vec::new());
+    };
+    value
+        .as_array()
+        .and_then(|items| {
+            items
+                .iter()
+                .map(|v| v.as_str().map(str::to_string))
+                .collect()
+        })
+        .ok_or_else(|| anyhow!("[bootstrap] {} must be an array of strings", key))
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    match (path.strip_prefix("~/"), home) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn constructor() -> BootstrapPlugin {
+    BootstrapPlugin::default()
+}
+
+declare_plugin!(BootstrapPlugin, constructor);
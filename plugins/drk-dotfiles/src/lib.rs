@@ -0,0 +1,422 @@
+//! `drk dotfiles`: links the files of a dotfiles repository into the home
+//! directory, with per-host overrides and templates.
+//!
+//! The repository mirrors the home directory under `home/`, and under
+//! `hosts/<host>/` for files that differ on one machine:
+//!
+//! ```text
+//! ~/dotfiles/home/.gitconfig.tmpl      -> ~/.gitconfig, rendered
+//! ~/dotfiles/home/.config/nvim/init.lua -> ~/.config/nvim/init.lua
+//! ~/dotfiles/hosts/work-laptop/.ssh/config -> ~/.ssh/config, on work-laptop only
+//! ```
+//!
+//! Files ending in `.tmpl` are rendered with drk's placeholders (`{env.USER}`,
+//! `{config.section.key}`, plus `{host}` and `{home}`) and written as copies;
+//! the others are symlinked, or copied with `mode = "copy"`.
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use drk_api::diff;
+use drk_api::fs::Transaction;
+use drk_api::interpolate::interpolate_with;
+use drk_api::{
+    declare_plugin, icon_info, icon_success, icon_warning, serde_json::json, style_dim,
+    style_primary, style_success, style_warning, Capability, CommandMatches, CommandOutput,
+    ConfigKey, ConfigType, Context, EventKind, Plugin, PluginCommand, PluginMetadata, SystemEvent,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_REPO: &str = "~/dotfiles";
+
+/// What a replaced file is kept as, next to it
+const BACKUP_SUFFIX: &str = ".drk-backup";
+
+/// Marks a file to render rather than link
+const TEMPLATE_EXTENSION: &str = ".tmpl";
+
+struct DotfilesPlugin;
+
+/// One file of the repository and where it goes
+#[derive(Debug)]
+struct Entry {
+    target: PathBuf,
+    source: PathBuf,
+    desired: Desired,
+    state: State,
+}
+
+#[derive(Debug)]
+enum Desired {
+    Link,
+    Contents(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    UpToDate,
+    Missing,
+    Differs,
+}
+
+impl State {
+    fn key(self) -> &'static str {
+        match self {
+            State::UpToDate => "up-to-date",
+            State::Missing => "missing",
+            State::Differs => "differs",
+        }
+    }
+}
+
+impl Entry {
+    /// What the target should hold, for diffs
+    fn desired_contents(&self) -> String {
+        match &self.desired {
+            Desired::Contents(contents) => contents.clone(),
+            Desired::Link => std::fs::read_to_string(&self.source).unwrap_or_default(),
+        }
+    }
+}
+
+impl Plugin for DotfilesPlugin {
+    fn metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: "dotfiles".to_string(),
+            version: "0.1.0".to_string(),
+            author: "dark1zinn".to_string(),
+            description: "Links and renders dotfiles from a repository into the home directory"
+                .to_string(),
+            essential: false,
+        }
+    }
+
+    fn get_commands(&self) -> Vec<PluginCommand> {
+        let nested = |name: &str, description: &str| PluginCommand {
+            name: name.to_string(),
+            description: description.to_string(),
+            ..Default::default()
+        };
+        vec![PluginCommand {
+            name: "dotfiles".to_string(),
+            description: "Dotfiles from the repository in [dotfiles] repo".to_string(),
+            subcommands: vec![
+                nested(
+                    "status",
+                    "Show which dotfiles are in place, missing or different",
+                ),
+                nested("diff", "Show what apply would change in each file"),
+                nested(
+                    "apply",
+                    "Link and render every dotfile that isn't in place, keeping what it replaces",
+                ),
+            ],
+            ..Default::default()
+        }]
+    }
+
+    fn config_schema(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey {
+                name: "repo".to_string(),
+                description: "The dotfiles repository, with home/ and hosts/<host>/ inside"
+                    .to_string(),
+                value_type: ConfigType::String,
+                default: Some(DEFAULT_REPO.into()),
+            },
+            ConfigKey {
+                name: "mode".to_string(),
+                description: "symlink, or copy for systems where links don't work out".to_string(),
+                value_type: ConfigType::String,
+                default: Some("symlink".into()),
+            },
+            ConfigKey {
+                name: "host".to_string(),
+                description: "Which hosts/ directory applies; this machine's hostname if unset"
+                    .to_string(),
+                value_type: ConfigType::String,
+                default: None,
+            },
+        ]
+    }
+
+    // Only its own commands
+    fn subscriptions(&self) -> Vec<EventKind> {
+        Vec::new()
+    }
+
+    fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> Result<()> {
+        if let SystemEvent::ExecuteCommand {
+            plugin_name,
+            matches,
+        } = event
+        {
+            if plugin_name == "dotfiles" {
+                self.execute_command(matches, ctx)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DotfilesPlugin {
+    fn execute_command(&self, matches: &CommandMatches, ctx: &mut Context) -> Result<()> {
+        let subcommand = matches.subcommands().first().map(String::as_str);
+        let Some(subcommand @ ("status" | "diff" | "apply")) = subcommand else {
+            println!(
+                "{} {}",
+                style_primary(icon_info()),
+                style_primary(
+                    "Try `drk dotfiles status`, `drk dotfiles diff` or `drk dotfiles apply`"
+                )
+            );
+            return Ok(());
+        };
+        let entries = entries(ctx)?;
+        match subcommand {
+            "status" => status(&entries, ctx),
+            "diff" => {
+                let changed: Vec<&Entry> = entries
+                    .iter()
+                    .filter(|e| e.state != State::UpToDate)
+                    .collect();
+                if changed.is_empty() {
+                    println!("{}", style_dim("Every dotfile is in place"));
+                }
+                for entry in changed {
+                    let current = std::fs::read_to_string(&entry.target).unwrap_or_default();
+                    let mark = match entry.state {
+                        State::Missing => style_success("+"),
+                        _ => style_warning("~"),
+                    };
+                    println!("{} {}", mark, style_primary(&tilde(&entry.target)));
+                    match diff::render(&current, &entry.desired_contents()) {
+                        text if text.is_empty() => println!(
+                            "    {}",
+                            style_dim("same contents; it becomes a link to the repository")
+                        ),
+                        text => text.lines().for_each(|line| println!("    {}", line)),
+                    }
+                }
+            }
+            _ => apply(&entries, ctx)?,
+        }
+        Ok(())
+    }
+}
+
+fn status(entries: &[Entry], ctx: &mut Context) {
+    let lines: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let target = tilde(&entry.target);
+            match entry.state {
+                State::UpToDate => format!("{} {}", style_success(icon_success()), target),
+                State::Missing => format!(
+                    "{} {} {}",
+                    style_warning(icon_warning()),
+                    target,
+                    style_warning("missing")
+                ),
+                State::Differs => format!(
+                    "{} {} {}",
+                    style_warning(icon_warning()),
+                    target,
+                    style_warning("differs; `drk dotfiles diff` shows how")
+                ),
+            }
+        })
+        .collect();
+    let text = if lines.is_empty() {
+        style_dim("The repository has no dotfiles for this host").to_string()
+    } else {
+        lines.join("\n")
+    };
+    let data: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            json!({
+                "target": entry.target.display().to_string(),
+                "source": entry.source.display().to_string(),
+                "state": entry.state.key(),
+            })
+        })
+        .collect();
+    ctx.output(CommandOutput::new(data).with_text(text));
+}
+
+fn apply(entries: &[Entry], ctx: &mut Context) -> Result<()> {
+    let mut txn = Transaction::new().keep_backups(BACKUP_SUFFIX);
+    let mut replaced = 0;
+    for entry in entries.iter().filter(|e| e.state != State::UpToDate) {
+        match &entry.desired {
+            Desired::Link => txn.symlink(&entry.source, &entry.target),
+            Desired::Contents(contents) => txn.write(&entry.target, contents.as_str()),
+        }
+        if entry.state == State::Differs {
+            replaced += 1;
+        }
+    }
+    if txn.is_empty() {
+        println!(
+            "{} {}",
+            style_success(icon_success()),
+            style_success("Every dotfile is in place")
+        );
+        return Ok(());
+    }
+
+    ctx.request_permission(Capability::FsWrite)?;
+    let changed: Vec<String> = txn.paths().map(tilde).collect();
+    txn.commit()?;
+    for path in &changed {
+        println!("{} {}", style_success(icon_success()), path);
+    }
+    if replaced > 0 {
+        println!(
+            "  {}",
+            style_dim(&format!(
+                "{} replaced files were kept with a {} suffix",
+                replaced, BACKUP_SUFFIX
+            ))
+        );
+    }
+    Ok(())
+}
+
+/// Every dotfile for this host, host overrides winning, with its state
+fn entries(ctx: &Context) -> Result<Vec<Entry>> {
+    let section = ctx.config.get("dotfiles");
+    let setting = |key: &str| section.and_then(|s| s.get(key)).and_then(|v| v.as_str());
+
+    let repo = expand_home(setting("repo").unwrap_or(DEFAULT_REPO));
+    if !repo.is_dir() {
+        bail!(
+            "No dotfiles repository at {}; clone one there or set [dotfiles] repo",
+            repo.display()
+        );
+    }
+    let repo = repo.canonicalize()?;
+    let copy = match setting("mode").unwrap_or("symlink") {
+        "symlink" => false,
+        "copy" => true,
+        other => bail!("Unknown [dotfiles] mode '{}'; use symlink or copy", other),
+    };
+    let host = setting("host").map(str::to_string).unwrap_or_else(hostname);
+    let home = home_dir().ok_or_else(|| anyhow!("Could not tell where the home directory is"))?;
+
+    // Relative path in the home directory -> source file
+    let mut sources: BTreeMap<PathBuf, PathBuf> = BTreeMap::new();
+    for root in [repo.join("home"), repo.join("hosts").join(&host)] {
+        for source in files(&root)? {
+            let relative = source
+                .strip_prefix(&root)
+                .expect("found under root")
+                .to_path_buf();
+            sources.insert(relative, source);
+        }
+    }
+
+    let vars = HashMap::from([
+        ("host".to_string(), host.clone()),
+        ("home".to_string(), home.display().to_string()),
+    ]);
+    sources
+        .into_iter()
+        .map(|(relative, source)| {
+            let name = relative.to_string_lossy();
+            let template = name.strip_suffix(TEMPLATE_EXTENSION);
+            let target = home.join(template.unwrap_or(&name));
+            let desired = match template {
+                Some(_) => {
+                    let text = std::fs::read_to_string(&source)?;
+                    let rendered = interpolate_with(&text, ctx.config, &vars)
+                        .with_context(|| format!("In template {}", source.display()))?;
+                    Desired::Contents(rendered)
+                }
+                None if copy => Desired::Contents(std::fs::read_to_string(&source)?),
+                None => Desired::Link,
+            };
+            let state = state(&target, &source, &desired);
+            Ok(Entry {
+                target,
+                source,
+                desired,
+                state,
+            })
+        })
+        .collect()
+}
+
+fn state(target: &Path, source: &Path, desired: &Desired) -> State {
+    if target.symlink_metadata().is_err() {
+        return State::Missing;
+    }
+    let in_place = match desired {
+        Desired::Link => std::fs::read_link(target).is_ok_and(|link| link == source),
+        Desired::Contents(contents) => {
+            // A link into the repository isn't a copy, even with the same contents
+            target.read_link().is_err()
+                && std::fs::read_to_string(target).is_ok_and(|current| &current == contents)
+        }
+    };
+    if in_place {
+        State::UpToDate
+    } else {
+        State::Differs
+    }
+}
+
+/// The files under `dir`, recursively; none if it doesn't exist
+fn files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    if !dir.is_dir() {
+        return Ok(found);
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            found.extend(files(&path)?);
+        } else {
+            found.push(path);
+        }
+    }
+    Ok(found)
+}
+
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// `path` with the home directory shown as `~`
+fn tilde(path: impl AsRef<Path>) -> String {
+    let path = path.as_ref();
+    match home_dir().and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+fn constructor() -> DotfilesPlugin {
+    DotfilesPlugin
+}
+
+declare_plugin!(DotfilesPlugin, constructor);
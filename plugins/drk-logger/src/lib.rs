@@ -1,6 +1,6 @@
 use drk_api::{
-    declare_plugin, style_dim, style_primary, style_success, style_warning, Context, Plugin,
-    PluginMetadata, SystemEvent,
+    declare_plugin, fit_to_terminal, style_dim, style_primary, style_success, style_warning,
+    Context, Plugin, PluginMetadata, SystemEvent,
 };
 
 struct LoggerPlugin;
@@ -17,9 +17,27 @@ impl Plugin for LoggerPlugin {
     }
 
     fn handle_event(&mut self, event: &SystemEvent, _ctx: &mut Context) -> anyhow::Result<()> {
-        match event {
-            SystemEvent::Startup => println!("{} System is starting up...", style_dim("[Logger]")),
-            SystemEvent::PreCommand { name, .. } => println!(
+        // Long command names or event payload sources shouldn't wrap the log
+        let line = match event {
+            SystemEvent::ConfigLoaded => format!("{} Config loaded", style_dim("[Logger]")),
+            SystemEvent::PluginsDiscovered { libraries } => format!(
+                "{} Found {} plugin libraries",
+                style_dim("[Logger]"),
+                libraries.len()
+            ),
+            SystemEvent::PluginsLoaded { plugins } => format!(
+                "{} Loaded plugins: {}",
+                style_dim("[Logger]"),
+                plugins.join(", ")
+            ),
+            SystemEvent::Startup => format!("{} System is starting up...", style_dim("[Logger]")),
+            SystemEvent::BuildingCli => format!("{} Building command line", style_dim("[Logger]")),
+            SystemEvent::CliBuilt { commands } => format!(
+                "{} Command line ready with {} commands",
+                style_dim("[Logger]"),
+                commands.len()
+            ),
+            SystemEvent::PreCommand { name, .. } => format!(
                 "{} About to run: {}",
                 style_dim("[Logger]"),
                 style_primary(name)
@@ -30,33 +48,39 @@ impl Plugin for LoggerPlugin {
                 } else {
                     style_warning("failed")
                 };
-                println!(
+                format!(
                     "{} Command '{}' completed with status: {}",
                     style_dim("[Logger]"),
                     style_primary(name),
                     status
-                );
+                )
             }
             SystemEvent::ExecuteCommand {
                 plugin_name,
                 matches,
-            } => {
-                println!(
-                    "{} Executing command '{}' from plugin '{}'",
-                    style_dim("[Logger]"),
-                    style_primary(&matches.command_name),
-                    style_warning(plugin_name)
-                );
-            }
-            SystemEvent::Custom { source, event, .. } => {
-                println!(
-                    "{} Intercepted event '{}' from '{}'",
-                    style_dim("[Logger]"),
-                    style_primary(event),
-                    style_warning(source)
-                );
-            }
-        }
+            } => format!(
+                "{} Executing command '{}' from plugin '{}'",
+                style_dim("[Logger]"),
+                style_primary(&matches.command_name),
+                style_warning(plugin_name)
+            ),
+            SystemEvent::Custom {
+                source,
+                event,
+                data,
+                ..
+            } => format!(
+                "{} Intercepted event '{}' from '{}'{}",
+                style_dim("[Logger]"),
+                style_primary(event),
+                style_warning(source),
+                data.as_ref()
+                    .map(|data| format!(" {}", style_dim(&data.to_string())))
+                    .unwrap_or_default()
+            ),
+            SystemEvent::Shutdown => format!("{} Shutting down", style_dim("[Logger]")),
+        };
+        println!("{}", fit_to_terminal(&line));
         Ok(())
     }
 }
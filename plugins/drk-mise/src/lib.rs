@@ -0,0 +1,467 @@
+//! `drk tools`: the runtimes a project pins for mise or asdf, and whether
+//! they're installed.
+//!
+//! Pins come from the nearest directory holding a `.tool-versions` or a mise
+//! config (`mise.toml`, `.mise.toml`, `.config/mise.toml`,
+//! `.config/mise/config.toml`); mise config wins where both pin a tool.
+//!
+//! The first drk command run in a project checks its pins. If some aren't
+//! installed, it says so and fires `tools:missing` from `mise`, with the
+//! missing `tool@version`s as a `Vec<String>` payload.
+
+use anyhow::{bail, Result};
+use drk_api::tools::{install_hint, which};
+use drk_api::{
+    declare_plugin, icon_info, icon_success, icon_warning, pad, serde_json::json, style_dim,
+    style_error, style_primary, style_success, style_warning, Align, ArgType, Capability,
+    CommandArg, CommandMatches, CommandOutput, ConfigKey, ConfigType, Context, EventKind, Plugin,
+    PluginCommand, PluginMetadata, SystemEvent,
+};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The event fired on entering a project with missing tools
+const MISSING_EVENT: &str = "tools:missing";
+
+/// Files pinning tool versions, least specific first: later ones win
+const PIN_FILES: [&str; 5] = [
+    ".tool-versions",
+    ".config/mise/config.toml",
+    ".config/mise.toml",
+    "mise.toml",
+    ".mise.toml",
+];
+
+struct MisePlugin;
+
+/// A tool version a project pins, e.g. `node 20.11.0` from `.tool-versions`
+#[derive(Debug, Clone)]
+struct Pin {
+    tool: String,
+    version: String,
+    /// The file it came from, relative to the project
+    source: &'static str,
+}
+
+impl Pin {
+    fn spec(&self) -> String {
+        format!("{}@{}", self.tool, self.version)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Manager {
+    Mise,
+    Asdf,
+}
+
+impl Manager {
+    /// The manager `[mise] manager` names; `auto` takes mise over asdf,
+    /// whichever is on `PATH`.
+    fn configured(ctx: &Context) -> Result<Option<Self>> {
+        let name = ctx
+            .config
+            .get("mise")
+            .and_then(|cfg| cfg.get("manager"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("auto");
+        Ok(match name {
+            "mise" => Some(Manager::Mise),
+            "asdf" => Some(Manager::Asdf),
+            "auto" if which("mise").is_some() => Some(Manager::Mise),
+            "auto" if which("asdf").is_some() => Some(Manager::Asdf),
+            "auto" => None,
+            other => bail!(
+                "Unknown manager '{}' in [mise] manager; use auto, mise or asdf",
+                other
+            ),
+        })
+    }
+
+    /// Like `configured`, but an error naming how to get one if there's none
+    fn required(ctx: &Context) -> Result<Self> {
+        let manager = Self::configured(ctx)?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "Neither mise nor asdf is installed. {}",
+                install_hint("mise")
+            )
+        })?;
+        if which(manager.program()).is_none() {
+            bail!(
+                "{} is not installed. {}",
+                manager.program(),
+                install_hint(manager.program())
+            );
+        }
+        Ok(manager)
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Manager::Mise => "mise",
+            Manager::Asdf => "asdf",
+        }
+    }
+
+    /// Where the manager keeps installed versions, as `<dir>/<tool>/<version>`
+    fn installs_dir(self) -> Option<PathBuf> {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let data = match self {
+            Manager::Mise => std::env::var_os("MISE_DATA_DIR")
+                .map(PathBuf::from)
+                .or_else(|| {
+                    std::env::var_os("XDG_DATA_HOME").map(|d| PathBuf::from(d).join("mise"))
+                })
+                .or_else(|| home.map(|h| h.join(".local/share/mise")))?,
+            Manager::Asdf => std::env::var_os("ASDF_DATA_DIR")
+                .map(PathBuf::from)
+                .or_else(|| home.map(|h| h.join(".asdf")))?,
+        };
+        Some(data.join("installs"))
+    }
+
+    /// Checked on disk rather than by running the manager, so entering a
+    /// project costs no process spawns. mise links fuzzy versions (`20`,
+    /// `latest`) to the install they resolve to.
+    fn is_installed(self, pin: &Pin) -> bool {
+        pin.version == "system"
+            || self
+                .installs_dir()
+                .is_some_and(|dir| dir.join(&pin.tool).join(&pin.version).exists())
+    }
+
+    fn install(self, pin: &Pin) -> Command {
+        let mut command = Command::new(self.program());
+        match self {
+            Manager::Mise => command.args(["install", &pin.spec()]),
+            Manager::Asdf => command.args(["install", &pin.tool, &pin.version]),
+        };
+        command
+    }
+}
+
+impl Plugin for MisePlugin {
+    fn metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: "mise".to_string(),
+            version: "0.1.0".to_string(),
+            author: "dark1zinn".to_string(),
+            description:
+                "Project runtimes from .tool-versions and mise config, through mise or asdf"
+                    .to_string(),
+            essential: false,
+        }
+    }
+
+    fn get_commands(&self) -> Vec<PluginCommand> {
+        vec![PluginCommand {
+            name: "tools".to_string(),
+            description: "Runtimes the project pins for mise or asdf".to_string(),
+            subcommands: vec![
+                PluginCommand {
+                    name: "list".to_string(),
+                    description:
+                        "List the pinned tools, where each is pinned and whether it's installed"
+                            .to_string(),
+                    ..Default::default()
+                },
+                PluginCommand {
+                    name: "current".to_string(),
+                    description:
+                        "Show the versions active in this directory, as the manager resolves them"
+                            .to_string(),
+                    ..Default::default()
+                },
+                PluginCommand {
+                    name: "install".to_string(),
+                    description: "Install the pinned tools that are missing".to_string(),
+                    args: vec![CommandArg {
+                        name: "tool".to_string(),
+                        description: "Only install these tools' pinned versions".to_string(),
+                        required: false,
+                        arg_type: ArgType::String,
+                        multiple: true,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }]
+    }
+
+    fn config_schema(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey {
+                name: "manager".to_string(),
+                description:
+                    "Which version manager installs tools: auto (mise, else asdf), mise or asdf"
+                        .to_string(),
+                value_type: ConfigType::String,
+                default: Some("auto".into()),
+            },
+            ConfigKey {
+                name: "check_on_enter".to_string(),
+                description: "Warn about missing tools the first time drk runs in a project"
+                    .to_string(),
+                value_type: ConfigType::Boolean,
+                default: Some(true.into()),
+            },
+        ]
+    }
+
+    fn subscriptions(&self) -> Vec<EventKind> {
+        vec![EventKind::Startup]
+    }
+
+    fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> Result<()> {
+        match event {
+            SystemEvent::Startup => self.check_on_enter(ctx),
+            SystemEvent::ExecuteCommand {
+                plugin_name,
+                matches,
+            } if plugin_name == "mise" => self.execute_command(matches, ctx),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl MisePlugin {
+    fn execute_command(&self, matches: &CommandMatches, ctx: &mut Context) -> Result<()> {
+        let cwd = std::env::current_dir()?;
+        match matches.subcommands().first().map(String::as_str) {
+            Some("list") => {
+                let pins = project_pins(&cwd)?
+                    .map(|(_, pins)| pins)
+                    .unwrap_or_default();
+                let manager = Manager::configured(ctx)?;
+                let installed =
+                    |pin: &Pin| manager.is_some_and(|manager| manager.is_installed(pin));
+
+                let width = pins.iter().map(|p| p.tool.len()).max().unwrap_or(0);
+                let lines: Vec<String> = pins
+                    .iter()
+                    .map(|pin| {
+                        let (icon, state) = if installed(pin) {
+                            (style_success(icon_success()).to_string(), String::new())
+                        } else {
+                            (
+                                style_warning(icon_warning()).to_string(),
+                                style_warning("not installed").to_string(),
+                            )
+                        };
+                        format!(
+                            "{} {} {} {} {}",
+                            icon,
+                            pad(&pin.tool, width, Align::Left),
+                            style_primary(&pin.version),
+                            style_dim(pin.source),
+                            state
+                        )
+                        .trim_end()
+                        .to_string()
+                    })
+                    .collect();
+                let text = if pins.is_empty() {
+                    style_dim("No .tool-versions or mise config here").to_string()
+                } else {
+                    lines.join("\n")
+                };
+                let data: Vec<_> = pins
+                    .iter()
+                    .map(|pin| {
+                        json!({
+                            "tool": pin.tool,
+                            "version": pin.version,
+                            "source": pin.source,
+                            "installed": installed(pin),
+                        })
+                    })
+                    .collect();
+                ctx.output(CommandOutput::new(data).with_text(text));
+            }
+            Some("current") => {
+                let manager = Manager::required(ctx)?;
+                ctx.request_permission(Capability::ProcessSpawn)?;
+                let status = Command::new(manager.program()).arg("current").status()?;
+                if !status.success() {
+                    bail!("`{} current` failed with {}", manager.program(), status);
+                }
+            }
+            Some("install") => {
+                let Some((_, pins)) = project_pins(&cwd)? else {
+                    bail!("No .tool-versions or mise config here, so there's nothing to install");
+                };
+                let tools = matches.values("tool");
+                if let Some(tool) = tools.iter().find(|t| !pins.iter().any(|p| &p.tool == *t)) {
+                    bail!("This project doesn't pin a version of {}", tool);
+                }
+                let pins: Vec<Pin> = pins
+                    .into_iter()
+                    .filter(|pin| tools.is_empty() || tools.contains(&pin.tool.as_str()))
+                    .collect();
+                let manager = Manager::required(ctx)?;
+                let missing: Vec<&Pin> = pins.iter().filter(|p| !manager.is_installed(p)).collect();
+                if missing.is_empty() {
+                    println!(
+                        "{} {}",
+                        style_success(icon_success()),
+                        style_success("Every pinned tool is installed")
+                    );
+                    return Ok(());
+                }
+
+                ctx.request_permission(Capability::ProcessSpawn)?;
+                let mut report = ctx.reporter();
+                for pin in missing {
+                    report.step(&format!(
+                        "Installing {} with {}",
+                        pin.spec(),
+                        manager.program()
+                    ));
+                    report.exec(&mut manager.install(pin))?;
+                }
+                report.finish();
+            }
+            _ => println!(
+                "{} {}",
+                style_primary(icon_info()),
+                style_primary("Try `drk tools list`, `drk tools current` or `drk tools install`")
+            ),
+        }
+        Ok(())
+    }
+
+    /// Warns once per project entered, remembering the last project in state
+    fn check_on_enter(&self, ctx: &mut Context) -> Result<()> {
+        let enabled = ctx
+            .config
+            .get("mise")
+            .and_then(|cfg| cfg.get("check_on_enter"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        if !enabled {
+            return Ok(());
+        }
+
+        let Some((root, pins)) = project_pins(&std::env::current_dir()?)? else {
+            ctx.state.remove("project");
+            return Ok(());
+        };
+        let root = root.display().to_string();
+        if ctx.state.get("project").and_then(|v| v.as_str()) == Some(root.as_str()) {
+            return Ok(());
+        }
+        ctx.state.insert("project".to_string(), root.clone().into());
+
+        // Without a manager there is nothing to offer but the hint below
+        let manager = Manager::configured(ctx)?;
+        let missing: Vec<String> = pins
+            .iter()
+            .filter(|pin| !manager.is_some_and(|m| m.is_installed(pin)))
+            .map(Pin::spec)
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let fix = match manager {
+            Some(_) => "run `drk tools install`".to_string(),
+            None => install_hint("mise"),
+        };
+        println!(
+            "{} {} {}",
+            style_warning(icon_warning()),
+            style_warning(&format!(
+                "This project needs {} tools that aren't installed:",
+                missing.len()
+            )),
+            style_error(&missing.join(", "))
+        );
+        println!("  {}", style_dim(&fix));
+        (ctx.event_sender)(SystemEvent::Custom {
+            source: "mise".into(),
+            event: MISSING_EVENT.into(),
+            payload: Some(std::sync::Arc::new(missing)),
+            data: None,
+        });
+        Ok(())
+    }
+}
+
+/// The nearest directory from `start` up with pin files, and its pins
+fn project_pins(start: &Path) -> Result<Option<(PathBuf, Vec<Pin>)>> {
+    let Some(root) = start
+        .ancestors()
+        .find(|dir| PIN_FILES.iter().any(|file| dir.join(file).is_file()))
+    else {
+        return Ok(None);
+    };
+
+    let mut pins: Vec<Pin> = Vec::new();
+    for source in PIN_FILES {
+        let path = root.join(source);
+        if !path.is_file() {
+            continue;
+        }
+        let text = std::fs::read_to_string(&path)?;
+        let found = if source == ".tool-versions" {
+            parse_tool_versions(&text)
+        } else {
+            parse_mise_toml(&text)
+                .map_err(|e| anyhow::anyhow!("Invalid mise config at {:?}: {}", path, e))?
+        };
+        for (tool, version) in found {
+            pins.retain(|p| p.tool != tool);
+            pins.push(Pin {
+                tool,
+                version,
+                source,
+            });
+        }
+    }
+    pins.sort_by(|a, b| a.tool.cmp(&b.tool));
+    Ok(Some((root.to_path_buf(), pins)))
+}
+
+/// `node 20.11.0` lines; of several versions on a line, the first is the
+/// one in use
+fn parse_tool_versions(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            Some((words.next()?.to_string(), words.next()?.to_string()))
+        })
+        .collect()
+}
+
+/// The `[tools]` table, whose values are a version, a list of versions or
+/// a table with a `version`
+fn parse_mise_toml(text: &str) -> Result<Vec<(String, String)>> {
+    let table: drk_api::toml::Table = drk_api::toml::from_str(text)?;
+    let Some(tools) = table.get("tools").and_then(|t| t.as_table()) else {
+        return Ok(Vec::new());
+    };
+    Ok(tools
+        .iter()
+        .filter_map(|(tool, value)| {
+            let version = match value {
+                drk_api::toml::Value::Array(versions) => versions.first()?,
+                drk_api::toml::Value::Table(options) => options.get("version")?,
+                other => other,
+            };
+            let version = match version {
+                drk_api::toml::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            Some((tool.clone(), version))
+        })
+        .collect())
+}
+
+fn constructor() -> MisePlugin {
+    MisePlugin
+}
+
+declare_plugin!(MisePlugin, constructor);
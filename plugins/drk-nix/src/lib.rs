@@ -1,15 +1,28 @@
 use drk_api::{
-    ArgType, CommandArg, CommandMatches, Context, Plugin, PluginCommand, PluginMetadata, SystemEvent, declare_plugin, icon_error, icon_info, icon_warning, style_error, style_primary, style_warning
+    ArgType, Async, AsyncPlugin, Capability, CommandArg, CommandMatches, ConfigKey, ConfigType, Context, EventKind, PluginCommand, PluginMetadata, Requirement, SystemEvent, Tip, ToolStatus, declare_plugin, icon_error, icon_info, icon_warning, style_error, style_primary, style_warning
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How long a fetched template list is reused, unless `templates_ttl_hours` says otherwise
+const DEFAULT_TTL_HOURS: i64 = 24;
 
 struct NixPlugin;
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 struct Template {
     name: String,
 }
 
+/// The template list as last fetched, kept in the plugin's data directory
+#[derive(Debug, Serialize, Deserialize)]
+struct TemplateCache {
+    /// Seconds since the Unix epoch
+    fetched_at: u64,
+    templates: Vec<Template>,
+}
+
 #[derive(Debug, Deserialize)]
 struct GithubItem {
     name: String,
@@ -17,7 +30,7 @@ struct GithubItem {
     item_type: String,
 }
 
-impl Plugin for NixPlugin {
+impl AsyncPlugin for NixPlugin {
     fn metadata(&self) -> PluginMetadata {
         PluginMetadata {
             name: "Nix".to_string(),
@@ -29,18 +42,36 @@ impl Plugin for NixPlugin {
     }
 
     fn get_commands(&self) -> Vec<PluginCommand> {
-        vec![
-            PluginCommand {
-                name: "nix".to_string(),
-                description: "Nix command".to_string(),
-                args: vec![CommandArg {
-                    name: "template".to_string(),
-                    description: "Initialize a nix flake dev environment template, pass the name of the template from the-nix-way/dev-templates".to_string(),
-                    required: true,
-                    arg_type: ArgType::String,
-                }],
-            },
-        ]
+        vec![nix_command(ArgType::String)]
+    }
+
+    // With a fresh template list the names are known, so the shell can complete them
+    fn contribute_commands(&mut self, ctx: &mut Context) -> anyhow::Result<Vec<PluginCommand>> {
+        Ok(self
+            .cached_templates(ctx)
+            .map(|templates| {
+                let names = templates.into_iter().map(|t| t.name).collect();
+                vec![nix_command(ArgType::Choice(names))]
+            })
+            .unwrap_or_default())
+    }
+
+    fn config_schema(&self) -> Vec<ConfigKey> {
+        vec![ConfigKey {
+            name: "templates_ttl_hours".to_string(),
+            description: "How long the template list fetched from GitHub is reused before `drk nix` fetches it again".to_string(),
+            value_type: ConfigType::Integer,
+            default: Some(DEFAULT_TTL_HOURS.into()),
+        }]
+    }
+
+    fn tips(&self) -> Vec<Tip> {
+        vec![Tip {
+            id: "template-alias".to_string(),
+            command: "nix".to_string(),
+            min_runs: 5,
+            message: "You've run `drk nix` {count} times. Keep your usual template in config and alias it: tpl = \"nix --template {config.nix.default_template}\" under [aliases]".to_string(),
+        }]
     }
 
     fn on_load(&mut self) -> anyhow::Result<()> {
@@ -53,28 +84,31 @@ impl Plugin for NixPlugin {
         Ok(())
     }
 
-    fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> anyhow::Result<()> {
-        match event {
-            SystemEvent::ExecuteCommand {
-                plugin_name,
-                matches,
-            } => {
-                // Only handle commands meant for this plugin
-                if plugin_name == "Nix" {
-                    self.execute_command(matches, ctx)?;
-                }
+    // Only its own commands
+    fn subscriptions(&self) -> Vec<EventKind> {
+        Vec::new()
+    }
+
+    async fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context<'_>) -> anyhow::Result<()> {
+        if let SystemEvent::ExecuteCommand {
+            plugin_name,
+            matches,
+        } = event
+        {
+            // Only handle commands meant for this plugin
+            if plugin_name == "Nix" {
+                self.execute_command(matches, ctx).await?;
             }
-            _ => {}
         }
         Ok(())
     }
 }
 
 impl NixPlugin {
-    fn execute_command(
+    async fn execute_command(
         &mut self,
         matches: &CommandMatches,
-        #[allow(unused_variables)] ctx: &mut Context,
+        ctx: &mut Context<'_>,
     ) -> anyhow::Result<()> {
         match matches.command_name.as_str() {
             "nix" => {
@@ -84,19 +118,72 @@ impl NixPlugin {
                     .map(|s| Template { name: s.to_string() })
                     .unwrap_or(Template { name: "empty".to_string() });
                 
-                let gh_templates = self.fetch_gh_templates()?;
-                
-                if !gh_templates.contains(&template) {
-                    println!("{} {}", style_warning(icon_warning()), style_warning("Template not found!"));
-                    println!("{} {}", style_primary(icon_info()), style_primary("You may wanne check out the available templates at https://github.com/the-nix-way/dev-templates"));
-                    return Ok(())
+                let mut report = ctx.reporter();
+
+                // Flake templates need flakes, which arrived in Nix 2.4
+                report.step("Checking nix");
+                let version = ctx.tool_version("nix", ">=2.4")?;
+                report.log(&format!("nix {}", version));
+                let catalog = ctx.tool_catalog().unwrap_or_default();
+                if let Some(ToolStatus::Drift(_)) = catalog.check("nix") {
+                    report.log(&format!(
+                        "This project pins nix {}; `drk check-tools` lists every drift",
+                        catalog.requirement("nix").unwrap_or_default()
+                    ));
                 }
+
+                let refresh = matches.args.contains_key("refresh");
+                let gh_templates = match self.cached_templates(ctx).filter(|_| !refresh) {
+                    Some(templates) => {
+                        report.step("Using cached templates from the-nix-way/dev-templates");
+                        templates
+                    }
+                    None => {
+                        report.step("Fetching templates from the-nix-way/dev-templates");
+                        ctx.request_permission(Capability::Network)?;
+                        // The step line stays still while the request is out; this keeps turning
+                        let spinner = ctx.spinner("GET api.github.com/repos/the-nix-way/dev-templates");
+                        let templates = self.fetch_gh_templates().await;
+                        spinner.clear();
+                        let templates = templates?;
+                        if let Err(e) = self.cache_templates(ctx, &templates) {
+                            report.log(&format!("Could not cache the template list: {}", e));
+                        }
+                        templates
+                    }
+                };
+                report.log(&format!("{} templates available", gh_templates.len()));
                 
-                println!(
-                    "{} Initializing nix flake dev environment template: {}",
-                    style_warning(icon_info()),
-                    style_primary(&template.name)
-                );
+                // On a terminal a wrong name gets a pick from the list instead of an error
+                let template = if gh_templates.contains(&template) {
+                    template
+                } else {
+                    let ui = ctx.ui();
+                    let picked = if ui.is_interactive() {
+                        let names: Vec<&str> = gh_templates.iter().map(|t| t.name.as_str()).collect();
+                        ui.select_from_list(&format!("No template named '{}'; pick one", template.name), &names)?
+                    } else {
+                        None
+                    };
+                    match picked {
+                        Some(i) => gh_templates[i].clone(),
+                        None => {
+                            report.fail("Template not found!");
+                            println!("{} {}", style_primary(icon_info()), style_primary("You may wanne check out the available templates at https://github.com/the-nix-way/dev-templates"));
+                            anyhow::bail!("No template named '{}' in the-nix-way/dev-templates", template.name);
+                        }
+                    }
+                };
+                
+                report.step(&format!("Initializing nix flake dev environment template: {}", template.name));
+                ctx.request_permission(Capability::ProcessSpawn)?;
+                report.exec(Command::new("nix").args([
+                    "flake",
+                    "init",
+                    "--template",
+                    &format!("github:the-nix-way/dev-templates#{}", template.name),
+                ]))?;
+                report.finish();
             }
             _ => println!(
                 "{} Unknown command: {}",
@@ -107,26 +194,48 @@ impl NixPlugin {
         Ok(())
     }
 
+    /// The cached template list, unless it's missing or older than `templates_ttl_hours`
+    fn cached_templates(&self, ctx: &Context) -> Option<Vec<Template>> {
+        let ttl_hours = ctx
+            .config
+            .get("Nix")
+            .and_then(|cfg| cfg.get("templates_ttl_hours"))
+            .and_then(|v| v.as_integer())
+            .unwrap_or(DEFAULT_TTL_HOURS)
+            .max(0) as u64;
+        let path = ctx.plugin_data_dir().ok()?.join("templates.json");
+        let cache: TemplateCache = serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+        let age = now().saturating_sub(cache.fetched_at);
+        (age < ttl_hours * 60 * 60).then_some(cache.templates)
+    }
+
+    fn cache_templates(&self, ctx: &Context, templates: &[Template]) -> anyhow::Result<()> {
+        let path = ctx.plugin_data_dir()?.join("templates.json");
+        let cache = TemplateCache { fetched_at: now(), templates: templates.to_vec() };
+        std::fs::write(path, serde_json::to_string_pretty(&cache)?)?;
+        Ok(())
+    }
+
     /// Fetches the list of directories from a GitHub repository
     /// # Returns
     /// * `Result<Vec<Template>, anyhow::Error>` - List of directory names or error
-    fn fetch_gh_templates(&self) -> Result<Vec<Template>, anyhow::Error> {
+    async fn fetch_gh_templates(&self) -> Result<Vec<Template>, anyhow::Error> {
         // GH api URL pointing to flake templates provided by the-nix-way/dev-templates
-        let tnw_templates_url =
-            format!("https://api.github.com/repos/the-nix-way/dev-templates/contents");
+        let tnw_templates_url = "https://api.github.com/repos/the-nix-way/dev-templates/contents";
         
-        let client = reqwest::blocking::Client::new();
+        let client = drk_api::http::Client::new()?;
         let response = client
-            .get(&tnw_templates_url)
+            .get(tnw_templates_url)
             .header("User-Agent", "drk-nix-plugin")
-            .send()?;
+            .send_async()
+            .await?;
         
-        if !response.status().is_success() {
+        if !response.is_success() {
             anyhow::bail!(
                 "{} {}{}",
                 style_error(icon_error()),
                 style_warning("Failed to fetch templates from Github.\n"),
-                style_error(response.status().as_str())
+                style_error(&response.status().to_string())
             );
         }
         
@@ -142,9 +251,40 @@ impl NixPlugin {
     }
 }
 
+/// The `nix` command, its `template` argument taking `template_type`
+fn nix_command(template_type: ArgType) -> PluginCommand {
+    PluginCommand {
+        name: "nix".to_string(),
+        description: "Nix command".to_string(),
+        args: vec![CommandArg {
+            name: "template".to_string(),
+            description: "Initialize a nix flake dev environment template, pass the name of the template from the-nix-way/dev-templates".to_string(),
+            required: true,
+            arg_type: template_type,
+            ..Default::default()
+        }, CommandArg {
+            name: "refresh".to_string(),
+            description: "Fetch the template list again instead of using the cached one".to_string(),
+            required: false,
+            arg_type: ArgType::Boolean,
+            ..Default::default()
+        }],
+        requires: vec![Requirement::Binary("nix".to_string())],
+        ..Default::default()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 // Helper to create the plugin instance
-fn constructor() -> NixPlugin {
-    NixPlugin
+// The template fetch awaits instead of blocking, on the plugin's own runtime
+fn constructor() -> Async<NixPlugin> {
+    Async::new(NixPlugin)
 }
 
-declare_plugin!(NixPlugin, constructor);
+declare_plugin!(Async<NixPlugin>, constructor);
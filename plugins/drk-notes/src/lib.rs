@@ -0,0 +1,452 @@
+//! `drk note`: notes kept per project, encrypted at rest.
+//!
+//! ```text
+//! $ drk note add "Ask about the release branch"
+//! $ drk note add            # opens $EDITOR
+//! $ drk note list
+//! $ drk note edit 2
+//! ```
+//!
+//! A note belongs to the project it was written in: the nearest directory
+//! up from where drk runs holding a `drk.toml` or `.git`, or to no project
+//! outside one. Each note is a file in the plugin's data directory,
+//! encrypted with ChaCha20-Poly1305 under a key kept in the system keychain
+//! (`ctx.secrets()`), so the files alone don't give the notes away. Where
+//! there's no keychain, secrets fall back to a file in that same directory,
+//! which would leave the key next to the notes it opens, so `drk note`
+//! refuses to run without a keychain. A project's directory is named after a hash
+//! of its path, and each note is sealed with its project and number as
+//! associated data, so a note file renamed, or copied into another project,
+//! doesn't decrypt.
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use drk_api::toml::{self, Table};
+use drk_api::{
+    declare_plugin, icon_info, icon_success, pad, serde_json::json, style_dim, style_primary,
+    style_success, truncate, Align, ArgType, Capability, CommandArg, CommandMatches, CommandOutput,
+    Context, EventKind, Plugin, PluginCommand, PluginMetadata, SystemEvent,
+};
+use sha2::{Digest, Sha256};
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What the encryption key is saved as in the secrets store
+const KEY_SECRET: &str = "notes-key";
+
+/// The directory notes written outside any project go in
+const NO_PROJECT: &str = "_global";
+
+const NONCE_LEN: usize = 12;
+
+/// How much of a note `list` shows
+const PREVIEW_WIDTH: usize = 60;
+
+struct NotesPlugin;
+
+/// A decrypted note
+struct Note {
+    id: u32,
+    text: String,
+    /// Unix seconds
+    created: u64,
+    updated: u64,
+}
+
+impl Plugin for NotesPlugin {
+    fn metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: "notes".to_string(),
+            version: "0.1.0".to_string(),
+            author: "dark1zinn".to_string(),
+            description: "Encrypted notes kept per project".to_string(),
+            essential: false,
+        }
+    }
+
+    fn get_commands(&self) -> Vec<PluginCommand> {
+        let id = CommandArg {
+            name: "id".to_string(),
+            description: "The note's number, from `drk note list`".to_string(),
+            arg_type: ArgType::Positional,
+            required: true,
+            ..Default::default()
+        };
+        let nested = |command: &str, description: &str, args: Vec<CommandArg>| PluginCommand {
+            name: command.to_string(),
+            description: description.to_string(),
+            args,
+            ..Default::default()
+        };
+        vec![PluginCommand {
+            name: "note".to_string(),
+            description: "Encrypted notes for the current project".to_string(),
+            subcommands: vec![
+                nested(
+                    "add",
+                    "Write a note; without text, what's piped in or what you write in $EDITOR",
+                    vec![CommandArg {
+                        name: "text".to_string(),
+                        description: "The note".to_string(),
+                        arg_type: ArgType::Variadic,
+                        ..Default::default()
+                    }],
+                ),
+                nested("list", "Show this project's notes", Vec::new()),
+                nested("show", "Print a note", vec![id.clone()]),
+                nested("edit", "Change a note in $EDITOR", vec![id.clone()]),
+                nested("remove", "Delete a note", vec![id]),
+            ],
+            ..Default::default()
+        }]
+    }
+
+    // Only its own commands
+    fn subscriptions(&self) -> Vec<EventKind> {
+        Vec::new()
+    }
+
+    fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> Result<()> {
+        if let SystemEvent::ExecuteCommand {
+            plugin_name,
+            matches,
+        } = event
+        {
+            if plugin_name == "notes" {
+                self.execute_command(matches, ctx)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl NotesPlugin {
+    fn execute_command(&self, matches: &CommandMatches, ctx: &mut Context) -> Result<()> {
+        let id = || -> Result<u32> {
+            let id = matches
+                .args
+                .get("id")
+                .map(String::as_str)
+                .unwrap_or_default();
+            id.trim_start_matches('#')
+                .parse()
+                .map_err(|_| anyhow!("Expected a note number like 3, got '{}'", id))
+        };
+        match matches.subcommands().first().map(String::as_str) {
+            Some("add") => add(ctx, matches.values("text")),
+            Some("list") => list(ctx),
+            Some("show") => {
+                let note = Notes::open(ctx)?.load(id()?)?;
+                let data = json!({ "id": note.id, "text": note.text, "created": note.created, "updated": note.updated });
+                ctx.output(CommandOutput::new(data).with_text(note.text));
+                Ok(())
+            }
+            Some("edit") => edit_note(ctx, id()?),
+            Some("remove") => {
+                let notes = Notes::open(ctx)?;
+                let id = id()?;
+                std::fs::remove_file(notes.path(id))
+                    .map_err(|_| anyhow!("No note #{}; `drk note list` shows them", id))?;
+                done(&format!("Removed note #{}", id));
+                Ok(())
+            }
+            _ => {
+                println!(
+                    "{} {}",
+                    style_primary(icon_info()),
+                    style_primary("Try `drk note add <text>` or `drk note list`")
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+fn add(ctx: &mut Context, words: Vec<&str>) -> Result<()> {
+    let text = if !words.is_empty() {
+        words.join(" ")
+    } else if !std::io::stdin().is_terminal() {
+        let mut piped = String::new();
+        std::io::stdin().read_to_string(&mut piped)?;
+        piped
+    } else {
+        ctx.request_permission(Capability::ProcessSpawn)?;
+        ctx.edit("")?
+    };
+    let text = text.trim_end().to_string();
+    if text.trim().is_empty() {
+        bail!("The note is empty; nothing was saved");
+    }
+
+    let notes = Notes::open(ctx)?;
+    let id = notes.ids()?.into_iter().max().unwrap_or(0) + 1;
+    let now = now();
+    notes.save(&Note {
+        id,
+        text,
+        created: now,
+        updated: now,
+    })?;
+    done(&format!("Saved note #{}", id));
+    Ok(())
+}
+
+fn list(ctx: &mut Context) -> Result<()> {
+    let notes = Notes::open(ctx)?;
+    let mut lines = Vec::new();
+    let mut data = Vec::new();
+    let loaded = notes
+        .ids()?
+        .into_iter()
+        .map(|id| notes.load(id))
+        .collect::<Result<Vec<_>>>()?;
+    let width = loaded
+        .iter()
+        .map(|note| note.id.to_string().len() + 1)
+        .max()
+        .unwrap_or(0);
+    for note in &loaded {
+        let first = note.text.lines().next().unwrap_or_default();
+        lines.push(format!(
+            "{}  {}  {}",
+            style_dim(&pad(&format!("#{}", note.id), width, Align::Right)),
+            truncate(first, PREVIEW_WIDTH),
+            style_dim(&ago(now().saturating_sub(note.updated)))
+        ));
+        data.push(json!({ "id": note.id, "title": first, "created": note.created, "updated": note.updated }));
+    }
+    let text = if lines.is_empty() {
+        style_dim(&format!(
+            "No notes {} yet; write one with `drk note add`",
+            notes.scope
+        ))
+        .to_string()
+    } else {
+        lines.join("\n")
+    };
+    ctx.output(CommandOutput::new(data).with_text(text));
+    Ok(())
+}
+
+fn edit_note(ctx: &mut Context, id: u32) -> Result<()> {
+    let notes = Notes::open(ctx)?;
+    let mut note = notes.load(id)?;
+    ctx.request_permission(Capability::ProcessSpawn)?;
+    let text = ctx.edit(&note.text)?.trim_end().to_string();
+    if text == note.text {
+        println!("{}", style_dim(&format!("Note #{} is unchanged", id)));
+        return Ok(());
+    }
+    if text.trim().is_empty() {
+        bail!(
+            "The note came back empty; `drk note remove {}` deletes it",
+            id
+        );
+    }
+    note.text = text;
+    note.updated = now();
+    notes.save(&note)?;
+    done(&format!("Saved note #{}", id));
+    Ok(())
+}
+
+/// The current project's notes and the key they're encrypted with.
+struct Notes {
+    dir: PathBuf,
+    /// The directory's name, which notes are sealed with
+    project: String,
+    cipher: ChaCha20Poly1305,
+    /// Which notes these are, for messages: `for <root>` or `outside projects`
+    scope: String,
+}
+
+impl Notes {
+    fn open(ctx: &Context) -> Result<Self> {
+        let cwd = std::env::current_dir()?;
+        let project = cwd
+            .ancestors()
+            .find(|dir| dir.join("drk.toml").exists() || dir.join(".git").exists());
+        let (name, scope) = match project {
+            Some(root) => (dir_name(root), format!("for {}", root.display())),
+            None => (NO_PROJECT.to_string(), "outside projects".to_string()),
+        };
+        let dir = ctx.plugin_data_dir()?.join(&name);
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            cipher: ChaCha20Poly1305::new(&key(ctx)?),
+            dir,
+            project: name,
+            scope,
+        })
+    }
+
+    fn path(&self, id: u32) -> PathBuf {
+        self.dir.join(format!("{}.note", id))
+    }
+
+    /// The notes there are, in order.
+    fn ids(&self) -> Result<Vec<u32>> {
+        let mut ids: Vec<u32> = std::fs::read_dir(&self.dir)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                (path.extension()? == "note")
+                    .then(|| path.file_stem()?.to_str()?.parse().ok())
+                    .flatten()
+            })
+            .collect();
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    fn load(&self, id: u32) -> Result<Note> {
+        let sealed = std::fs::read(self.path(id))
+            .map_err(|_| anyhow!("No note #{}; `drk note list` shows them", id))?;
+        if sealed.len() < NONCE_LEN {
+            bail!("Note #{} is damaged", id);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let sealed = Payload {
+            msg: ciphertext,
+            aad: &self.aad(id),
+        };
+        let plain = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), sealed)
+            .map_err(|_| {
+                anyhow!(
+                    "Note #{} can't be decrypted; it was written with another key, as another note or in another project, or damaged",
+                    id
+                )
+            })?;
+        let table: Table = String::from_utf8(plain)?
+            .parse()
+            .with_context(|| format!("Note #{} is damaged", id))?;
+        let time = |key: &str| {
+            table
+                .get(key)
+                .and_then(|v| v.as_integer())
+                .and_then(|v| u64::try_from(v).ok())
+                .unwrap_or(0)
+        };
+        Ok(Note {
+            id,
+            text: table
+                .get("text")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            created: time("created"),
+            updated: time("updated"),
+        })
+    }
+
+    fn save(&self, note: &Note) -> Result<()> {
+        let mut table = Table::new();
+        table.insert("text".to_string(), note.text.as_str().into());
+        table.insert("created".to_string(), (note.created as i64).into());
+        table.insert("updated".to_string(), (note.updated as i64).into());
+
+        // A fresh nonce for every write; reusing one under the same key
+        // would give the plaintexts away
+        let mut nonce = [0u8; NONCE_LEN];
+        getrandom::getrandom(&mut nonce).map_err(|e| anyhow!("No randomness: {}", e))?;
+        let plain = toml::to_string(&table)?;
+        let payload = Payload {
+            msg: plain.as_bytes(),
+            aad: &self.aad(note.id),
+        };
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| anyhow!("Could not encrypt note #{}", note.id))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        std::fs::write(self.path(note.id), sealed)?;
+        Ok(())
+    }
+
+    /// What a note is sealed with besides its text: its project and number,
+    /// so it only opens as the note it was written as
+    fn aad(&self, id: u32) -> Vec<u8> {
+        format!("drk-note:{}:{}", self.project, id).into_bytes()
+    }
+}
+
+/// The notes key, made and saved in the keychain the first time it's needed.
+/// Never kept in the secrets file, which sits next to the notes.
+fn key(ctx: &Context) -> Result<Key> {
+    let secrets = ctx.secrets()?;
+    if !secrets.in_keychain() {
+        bail!(
+            "Notes need a system keychain to keep their key in, and there's none here \
+             (the macOS keychain, or a Secret Service keyring with secret-tool); \
+             in {} it would sit next to the notes",
+            secrets.location()
+        );
+    }
+    if let Some(hex) = secrets.get(KEY_SECRET)? {
+        let bytes = from_hex(&hex)
+            .filter(|bytes| bytes.len() == 32)
+            .ok_or_else(|| anyhow!("The notes key in {} is damaged", secrets.location()))?;
+        return Ok(*Key::from_slice(&bytes));
+    }
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| anyhow!("No randomness: {}", e))?;
+    secrets.set(KEY_SECRET, &to_hex(&bytes))?;
+    Ok(*Key::from_slice(&bytes))
+}
+
+/// The directory a project's notes go in, named after a hash of its
+/// canonical path so the data directory doesn't list the projects
+fn dir_name(root: &Path) -> String {
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let hash = Sha256::digest(root.as_os_str().as_encoded_bytes());
+    to_hex(&hash[..16])
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.trim();
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn done(message: &str) {
+    println!(
+        "{} {}",
+        style_success(icon_success()),
+        style_success(message)
+    );
+}
+
+/// `seconds` in the past, roughly
+fn ago(seconds: u64) -> String {
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn constructor() -> NotesPlugin {
+    NotesPlugin
+}
+
+declare_plugin!(NotesPlugin, constructor);
@@ -0,0 +1,404 @@
+//! `drk snip`: named text snippets, kept in the plugin's state.
+//!
+//! Snippets may hold placeholders, filled in when they're used:
+//!
+//! ```text
+//! $ drk snip add pr "Fixes #{issue}, reviewed by {env.USER}"
+//! $ drk snip copy pr issue=42
+//! ```
+//!
+//! A bare `{name}` not given as `name=value` is asked for, and `drk snip get`
+//! or `drk snip copy` without a name offers every snippet to pick from.
+//! `drk snip add <name>` without text saves what's piped in, or else what's
+//! on the clipboard.
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use drk_api::interpolate::{interpolate_with, placeholders};
+use drk_api::{
+    declare_plugin, icon_info, icon_success, pad, serde_json::json, style_dim, style_primary,
+    style_success, truncate, Align, ArgType, Capability, CommandArg, CommandMatches, CommandOutput,
+    Context, EventKind, Plugin, PluginCommand, PluginMetadata, SystemEvent,
+};
+use std::collections::hash_map::{Entry, HashMap};
+use std::io::{IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+
+/// The state key the snippets are kept under, name -> text
+const SNIPPETS_KEY: &str = "snippets";
+
+/// How much of a snippet `list` and the picker show
+const PREVIEW_WIDTH: usize = 50;
+
+/// Commands that put their stdin on the clipboard, tried in turn
+const COPY_COMMANDS: &[&[&str]] = &[
+    &["pbcopy"],
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+    &["clip"],
+];
+
+/// Commands that print the clipboard, tried in turn
+const PASTE_COMMANDS: &[&[&str]] = &[
+    &["pbpaste"],
+    &["wl-paste", "--no-newline"],
+    &["xclip", "-selection", "clipboard", "-o"],
+    &["xsel", "--clipboard", "--output"],
+    &["powershell", "-NoProfile", "-Command", "Get-Clipboard"],
+];
+
+struct SnipPlugin;
+
+impl Plugin for SnipPlugin {
+    fn metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: "snip".to_string(),
+            version: "0.1.0".to_string(),
+            author: "dark1zinn".to_string(),
+            description: "Named text snippets with placeholders, copied to the clipboard"
+                .to_string(),
+            essential: false,
+        }
+    }
+
+    fn get_commands(&self) -> Vec<PluginCommand> {
+        let arg = |name: &str, description: &str, arg_type| CommandArg {
+            name: name.to_string(),
+            description: description.to_string(),
+            arg_type,
+            ..Default::default()
+        };
+        let name = CommandArg {
+            required: true,
+            ..arg("name", "The snippet's name", ArgType::Positional)
+        };
+        let pick = arg(
+            "name",
+            "The snippet's name; pick one if unset",
+            ArgType::Positional,
+        );
+        let vars = arg(
+            "vars",
+            "Placeholder values, as key=value",
+            ArgType::Variadic,
+        );
+        let nested = |command: &str, description: &str, args: Vec<CommandArg>| PluginCommand {
+            name: command.to_string(),
+            description: description.to_string(),
+            args,
+            ..Default::default()
+        };
+        vec![PluginCommand {
+            name: "snip".to_string(),
+            description: "Named text snippets".to_string(),
+            subcommands: vec![
+                nested(
+                    "add",
+                    "Save a snippet; without text, what's piped in or on the clipboard",
+                    vec![
+                        name.clone(),
+                        arg(
+                            "text",
+                            "The snippet, with {placeholders}",
+                            ArgType::Variadic,
+                        ),
+                        arg(
+                            "force",
+                            "Replace a snippet of the same name without asking",
+                            ArgType::Boolean,
+                        ),
+                    ],
+                ),
+                nested(
+                    "get",
+                    "Print a snippet with its placeholders filled in",
+                    vec![pick.clone(), vars.clone()],
+                ),
+                nested(
+                    "copy",
+                    "Copy a snippet to the clipboard with its placeholders filled in",
+                    vec![pick, vars],
+                ),
+                nested("list", "Show the saved snippets", Vec::new()),
+                nested("remove", "Delete a snippet", vec![name]),
+            ],
+            ..Default::default()
+        }]
+    }
+
+    // Only its own commands
+    fn subscriptions(&self) -> Vec<EventKind> {
+        Vec::new()
+    }
+
+    fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> Result<()> {
+        if let SystemEvent::ExecuteCommand {
+            plugin_name,
+            matches,
+        } = event
+        {
+            if plugin_name == "snip" {
+                self.execute_command(matches, ctx)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SnipPlugin {
+    fn execute_command(&self, matches: &CommandMatches, ctx: &mut Context) -> Result<()> {
+        let name = matches.args.get("name").map(String::as_str);
+        match matches.subcommands().first().map(String::as_str) {
+            Some("add") => {
+                let name = name.expect("add requires a name");
+                let force = matches.args.get("force").is_some_and(|v| v == "true");
+                add(ctx, name, matches.values("text"), force)
+            }
+            Some(used @ ("get" | "copy")) => {
+                let (name, text) = render(ctx, name, &matches.values("vars"))?;
+                if used == "get" {
+                    let data = json!({ "name": name, "text": text });
+                    ctx.output(CommandOutput::new(data).with_text(text));
+                    return Ok(());
+                }
+                ctx.request_permission(Capability::ProcessSpawn)?;
+                copy_to_clipboard(&text)?;
+                println!(
+                    "{} {}",
+                    style_success(icon_success()),
+                    style_success(&format!("Copied '{}'", name))
+                );
+                Ok(())
+            }
+            Some("list") => {
+                list(ctx);
+                Ok(())
+            }
+            Some("remove") => {
+                let name = name.expect("remove requires a name");
+                let removed = ctx
+                    .state
+                    .get_mut(SNIPPETS_KEY)
+                    .and_then(|v| v.as_table_mut())
+                    .and_then(|snippets| snippets.remove(name));
+                if removed.is_none() {
+                    bail!("No snippet '{}'; `drk snip list` shows them", name);
+                }
+                println!(
+                    "{} {}",
+                    style_success(icon_success()),
+                    style_success(&format!("Removed '{}'", name))
+                );
+                Ok(())
+            }
+            _ => {
+                println!(
+                    "{} {}",
+                    style_primary(icon_info()),
+                    style_primary("Try `drk snip add <name> <text>` or `drk snip copy`")
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+fn add(ctx: &mut Context, name: &str, words: Vec<&str>, force: bool) -> Result<()> {
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        bail!("Snippet name '{}' can't be empty or have spaces", name);
+    }
+    let text = if !words.is_empty() {
+        words.join(" ")
+    } else if !std::io::stdin().is_terminal() {
+        let mut piped = String::new();
+        std::io::stdin().read_to_string(&mut piped)?;
+        piped.trim_end_matches('\n').to_string()
+    } else {
+        ctx.request_permission(Capability::ProcessSpawn)?;
+        paste_from_clipboard()?
+    };
+    if text.is_empty() {
+        bail!("There's no text to save as '{}'", name);
+    }
+
+    if !force && snippet(ctx, name).is_some() {
+        let question = format!("Replace the snippet '{}'?", name);
+        if !ctx.ui().confirm(&question, false)? {
+            println!("{}", style_dim(&format!("Kept the old '{}'", name)));
+            return Ok(());
+        }
+    }
+    let snippets = ctx
+        .state
+        .entry(SNIPPETS_KEY)
+        .or_insert_with(|| drk_api::toml::Table::new().into())
+        .as_table_mut()
+        .ok_or_else(|| {
+            anyhow!("The saved snippets are damaged; `drk state export` shows what is there")
+        })?;
+    snippets.insert(name.to_string(), text.as_str().into());
+
+    let fills = placeholders(&text);
+    let note = if fills.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", fills.join(", "))
+    };
+    println!(
+        "{} {}{}",
+        style_success(icon_success()),
+        style_success(&format!("Saved '{}'", name)),
+        style_dim(&note)
+    );
+    Ok(())
+}
+
+/// The snippet named `name`, or picked by the user, with its placeholders
+/// filled in from `vars` and, for the rest, by asking.
+fn render(ctx: &Context, name: Option<&str>, vars: &[&str]) -> Result<(String, String)> {
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => pick(ctx)?,
+    };
+    let text = snippet(ctx, &name)
+        .ok_or_else(|| anyhow!("No snippet '{}'; `drk snip list` shows them", name))?;
+
+    let mut values = HashMap::new();
+    for var in vars {
+        let (key, value) = var
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected key=value, got '{}'", var))?;
+        values.insert(key.to_string(), value.to_string());
+    }
+    let ui = ctx.ui();
+    if ui.is_interactive() {
+        for missing in placeholders(&text) {
+            if let Entry::Vacant(entry) = values.entry(missing) {
+                let value = ui.input(entry.key(), None)?;
+                entry.insert(value);
+            }
+        }
+    }
+    let rendered = interpolate_with(&text, ctx.config, &values)
+        .with_context(|| format!("In snippet '{}'", name))?;
+    Ok((name, rendered))
+}
+
+/// A snippet name chosen from the list.
+fn pick(ctx: &Context) -> Result<String> {
+    let names = snippet_names(ctx);
+    if names.is_empty() {
+        bail!("No snippets yet; save one with `drk snip add <name> <text>`");
+    }
+    let ui = ctx.ui();
+    if !ui.is_interactive() {
+        bail!("Name a snippet; `drk snip list` shows them");
+    }
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+    let items: Vec<String> = names
+        .iter()
+        .map(|name| {
+            let text = snippet(ctx, name).unwrap_or_default();
+            format!("{:width$}  {}", name, preview(&text), width = width)
+        })
+        .collect();
+    match ui.select_from_list("Which snippet?", &items)? {
+        Some(i) => Ok(names[i].clone()),
+        None => bail!("No snippet picked"),
+    }
+}
+
+fn list(ctx: &mut Context) {
+    let names = snippet_names(ctx);
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+    let mut lines = Vec::new();
+    let mut data = Vec::new();
+    for name in &names {
+        let text = snippet(ctx, name).unwrap_or_default();
+        lines.push(format!(
+            "{}  {}",
+            style_primary(&pad(name, width, Align::Left)),
+            style_dim(&preview(&text))
+        ));
+        data.push(json!({ "name": name, "text": text, "placeholders": placeholders(&text) }));
+    }
+    let text = if lines.is_empty() {
+        style_dim("No snippets yet; save one with `drk snip add <name> <text>`").to_string()
+    } else {
+        lines.join("\n")
+    };
+    ctx.output(CommandOutput::new(data).with_text(text));
+}
+
+fn snippet(ctx: &Context, name: &str) -> Option<String> {
+    ctx.state
+        .get(SNIPPETS_KEY)
+        .and_then(|snippets| snippets.get(name))
+        .and_then(|text| text.as_str())
+        .map(str::to_string)
+}
+
+fn snippet_names(ctx: &Context) -> Vec<String> {
+    ctx.state
+        .get(SNIPPETS_KEY)
+        .and_then(|v| v.as_table())
+        .map(|snippets| snippets.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// The first line of `text`, cut to fit a listing
+fn preview(text: &str) -> String {
+    let first = text.lines().next().unwrap_or_default();
+    let more = if text.lines().nth(1).is_some() {
+        " ..."
+    } else {
+        ""
+    };
+    truncate(&format!("{}{}", first, more), PREVIEW_WIDTH)
+}
+
+fn copy_to_clipboard(text: &str) -> Result<()> {
+    let command = clipboard_command(COPY_COMMANDS)?;
+    let mut child = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(text.as_bytes())?;
+    if !child.wait()?.success() {
+        bail!("{} could not copy to the clipboard", command[0]);
+    }
+    Ok(())
+}
+
+fn paste_from_clipboard() -> Result<String> {
+    let command = clipboard_command(PASTE_COMMANDS)?;
+    let output = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        bail!("{} could not read the clipboard", command[0]);
+    }
+    let text = String::from_utf8(output.stdout).context("The clipboard doesn't hold text")?;
+    Ok(text.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// The first of `commands` whose program is installed
+fn clipboard_command(commands: &[&'static [&'static str]]) -> Result<&'static [&'static str]> {
+    commands
+        .iter()
+        .find(|command| drk_api::tools::which(command[0]).is_some())
+        .copied()
+        .ok_or_else(|| anyhow!("No clipboard tool found; install wl-clipboard, xclip or xsel"))
+}
+
+fn constructor() -> SnipPlugin {
+    SnipPlugin
+}
+
+declare_plugin!(SnipPlugin, constructor);
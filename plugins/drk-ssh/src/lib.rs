@@ -0,0 +1,648 @@
+//! `drk ssh`: one SSH key per profile, loading keys into the agent, and host
+//! aliases in `~/.ssh/config`.
+//!
+//! `drk ssh keygen --profile work` creates `~/.ssh/id_ed25519_work` and keeps
+//! its passphrase in the secrets store, so `drk ssh use work` can add it to
+//! the agent without asking. Aliases come from the config:
+//!
+//! ```toml
+//! [ssh.hosts.github-work]
+//! hostname = "github.com"
+//! user = "git"
+//! profile = "work"
+//! ```
+//!
+//! and `drk ssh hosts` writes them into a marked block at the top of
+//! `~/.ssh/config`, leaving the rest of the file alone.
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use drk_api::diff;
+use drk_api::fs::Transaction;
+use drk_api::{
+    declare_plugin, icon_info, icon_success, icon_warning, serde_json::json, style_dim,
+    style_primary, style_success, style_warning, ArgType, Capability, CommandArg, CommandMatches,
+    CommandOutput, ConfigKey, ConfigType, Context, EventKind, Plugin, PluginCommand,
+    PluginMetadata, SystemEvent,
+};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// What a replaced key or config is kept as, next to it
+const BACKUP_SUFFIX: &str = ".drk-backup";
+
+const BLOCK_START: &str = "# BEGIN drk ssh hosts (from [ssh.hosts]; changes here are replaced)";
+const BLOCK_END: &str = "# END drk ssh hosts";
+
+const KEY_TYPES: [&str; 3] = ["ed25519", "ecdsa", "rsa"];
+
+struct SshPlugin;
+
+/// One `[ssh.hosts.<alias>]` entry
+struct HostAlias {
+    alias: String,
+    hostname: String,
+    user: Option<String>,
+    port: Option<i64>,
+    identity: Option<String>,
+}
+
+impl Plugin for SshPlugin {
+    fn metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: "ssh".to_string(),
+            version: "0.1.0".to_string(),
+            author: "dark1zinn".to_string(),
+            description: "SSH keys per profile, the agent, and host aliases in ~/.ssh/config"
+                .to_string(),
+            essential: false,
+        }
+    }
+
+    fn get_commands(&self) -> Vec<PluginCommand> {
+        let arg = |name: &str, description: &str, arg_type| CommandArg {
+            name: name.to_string(),
+            description: description.to_string(),
+            arg_type,
+            ..Default::default()
+        };
+        let force = arg(
+            "force",
+            "Replace what's there without asking",
+            ArgType::Boolean,
+        );
+        vec![PluginCommand {
+            name: "ssh".to_string(),
+            description: "SSH keys, the agent and host aliases".to_string(),
+            subcommands: vec![
+                PluginCommand {
+                    name: "keygen".to_string(),
+                    description: if cfg!(unix) {
+                        "Create the key of a profile, ~/.ssh/id_<type>_<profile>".to_string()
+                    } else {
+                        // No askpass script here, so ssh-keygen gets it as an argument
+                        "Create the key of a profile, ~/.ssh/id_<type>_<profile>. The passphrase \
+                         is passed to ssh-keygen on its command line, where other programs \
+                         can see it while it runs"
+                            .to_string()
+                    },
+                    args: vec![
+                        CommandArg {
+                            required: true,
+                            ..arg("profile", "What the key is for, e.g. work", ArgType::String)
+                        },
+                        CommandArg {
+                            default: Some(KEY_TYPES[0].to_string()),
+                            ..arg(
+                                "type",
+                                "The kind of key",
+                                ArgType::Choice(KEY_TYPES.map(String::from).to_vec()),
+                            )
+                        },
+                        arg(
+                            "comment",
+                            "Comment in the public key; user@host if unset",
+                            ArgType::String,
+                        ),
+                        arg(
+                            "no-passphrase",
+                            "Leave the key unencrypted instead of asking for a passphrase",
+                            ArgType::Boolean,
+                        ),
+                        force.clone(),
+                    ],
+                    ..Default::default()
+                },
+                PluginCommand {
+                    name: "use".to_string(),
+                    description: "Add a key to the running ssh-agent, with its saved passphrase"
+                        .to_string(),
+                    args: vec![
+                        CommandArg {
+                            required: true,
+                            ..arg(
+                                "key",
+                                "A profile, or the path of a key",
+                                ArgType::Positional,
+                            )
+                        },
+                        arg(
+                            "only",
+                            "Remove every other key from the agent first",
+                            ArgType::Boolean,
+                        ),
+                    ],
+                    ..Default::default()
+                },
+                PluginCommand {
+                    name: "hosts".to_string(),
+                    description: "Write the aliases in [ssh.hosts] into ~/.ssh/config".to_string(),
+                    args: vec![
+                        arg(
+                            "dry-run",
+                            "Show the change to ~/.ssh/config without making it",
+                            ArgType::Boolean,
+                        ),
+                        force,
+                    ],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }]
+    }
+
+    fn config_schema(&self) -> Vec<ConfigKey> {
+        vec![ConfigKey {
+            name: "hosts".to_string(),
+            description: "Host aliases: [ssh.hosts.<alias>] with hostname, user, port, and profile or identity_file".to_string(),
+            value_type: ConfigType::Table,
+            default: None,
+        }]
+    }
+
+    // Only its own commands
+    fn subscriptions(&self) -> Vec<EventKind> {
+        Vec::new()
+    }
+
+    fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> Result<()> {
+        if let SystemEvent::ExecuteCommand {
+            plugin_name,
+            matches,
+        } = event
+        {
+            if plugin_name == "ssh" {
+                self.execute_command(matches, ctx)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SshPlugin {
+    fn execute_command(&self, matches: &CommandMatches, ctx: &mut Context) -> Result<()> {
+        let flag = |name: &str| matches.args.get(name).is_some_and(|v| v == "true");
+        match matches.subcommands().first().map(String::as_str) {
+            Some("keygen") => keygen(
+                ctx,
+                &matches.args["profile"],
+                &matches.args["type"],
+                matches.args.get("comment").map(String::as_str),
+                flag("no-passphrase"),
+                flag("force"),
+            ),
+            Some("use") => use_key(ctx, &matches.args["key"], flag("only")),
+            Some("hosts") => hosts(ctx, flag("dry-run"), flag("force")),
+            _ => {
+                println!(
+                    "{} {}",
+                    style_primary(icon_info()),
+                    style_primary("Try `drk ssh keygen --profile <name>`, `drk ssh use <key>` or `drk ssh hosts`")
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+fn keygen(
+    ctx: &mut Context,
+    profile: &str,
+    key_type: &str,
+    comment: Option<&str>,
+    no_passphrase: bool,
+    force: bool,
+) -> Result<()> {
+    if profile.is_empty()
+        || !profile
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!(
+            "Profile '{}' can only have letters, digits, - and _",
+            profile
+        );
+    }
+    let ui = ctx.ui();
+    let dir = ssh_dir()?;
+    let name = format!("id_{}_{}", key_type, profile);
+    let key = dir.join(&name);
+    let public = dir.join(format!("{}.pub", name));
+
+    if !force && (key.exists() || public.exists()) {
+        let question = format!(
+            "{} exists; replace it? The old key is kept as {}{}",
+            tilde(&key),
+            name,
+            BACKUP_SUFFIX
+        );
+        if !ui.confirm(&question, false)? {
+            println!("{}", style_dim(&format!("Kept {}", tilde(&key))));
+            return Ok(());
+        }
+    }
+
+    let passphrase = if no_passphrase {
+        String::new()
+    } else if ui.is_interactive() {
+        loop {
+            let first = ui.password("Passphrase for the new key (empty for none)")?;
+            if first.is_empty() || ui.password("The same passphrase again")? == first {
+                break first;
+            }
+            println!(
+                "{} {}",
+                style_warning(icon_warning()),
+                style_warning("The passphrases didn't match")
+            );
+        }
+    } else {
+        bail!("There's no terminal to ask for a passphrase on; pass --no-passphrase for a key without one");
+    };
+
+    ctx.request_permission(Capability::ProcessSpawn)?;
+    ctx.request_permission(Capability::FsWrite)?;
+    create_private_dir(&dir)?;
+
+    // Generated aside and moved into place together, so a failure leaves
+    // the old key as it was
+    let askpass = askpass(ctx)?;
+    let staging = dir.join(format!(".drk-keygen-{}", std::process::id()));
+    create_private_dir(&staging)?;
+    let generated = (|| -> Result<(Vec<u8>, Vec<u8>)> {
+        let staged = staging.join(&name);
+        let mut command = Command::new("ssh-keygen");
+        command.args(["-q", "-t", key_type, "-f"]).arg(&staged);
+        if let Some(comment) = comment {
+            command.args(["-C", comment]);
+        }
+        match &askpass {
+            // Without -N ssh-keygen asks for the passphrase twice, and gets it
+            // from the script, so it never shows in the process list
+            Some(askpass) => command
+                .env("SSH_ASKPASS", askpass)
+                .env("SSH_ASKPASS_REQUIRE", "force")
+                .env("DRK_SSH_PASSPHRASE", &passphrase),
+            None => command.args(["-N", &passphrase]),
+        };
+        let output = command
+            .stdin(Stdio::null())
+            .output()
+            .context("Could not run ssh-keygen; is OpenSSH installed?")?;
+        if !output.status.success() {
+            bail!(
+                "ssh-keygen failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let public = std::fs::read(staged.with_extension("pub"))?;
+        Ok((std::fs::read(&staged)?, public))
+    })();
+    let _ = std::fs::remove_dir_all(&staging);
+    let (private_contents, public_contents) = generated?;
+
+    let mut txn = Transaction::new().keep_backups(BACKUP_SUFFIX);
+    txn.write(&key, private_contents);
+    txn.write(&public, public_contents.clone());
+    txn.commit()?;
+    // ssh refuses private keys others can read
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&key, std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    let secrets = ctx.secrets()?;
+    let saved = !passphrase.is_empty();
+    if saved {
+        secrets.set(&name, &passphrase)?;
+    } else {
+        // An older key's passphrase would no longer fit
+        secrets.remove(&name)?;
+    }
+
+    let public_key = String::from_utf8_lossy(&public_contents).trim().to_string();
+    let mut text = format!(
+        "{} {}\n",
+        style_success(icon_success()),
+        style_success(&format!("Created {}", tilde(&key)))
+    );
+    if saved {
+        text.push_str(&format!(
+            "  {}\n",
+            style_dim(&format!(
+                "Its passphrase is saved in {}",
+                secrets.location()
+            ))
+        ));
+    }
+    text.push_str(&format!(
+        "  {}\n  {}",
+        style_dim(&format!("Public key, {}:", tilde(&public))),
+        public_key
+    ));
+    let data = json!({
+        "key": key.display().to_string(),
+        "public_key": public_key,
+        "passphrase_saved": saved,
+    });
+    ctx.output(CommandOutput::new(data).with_text(text));
+    Ok(())
+}
+
+fn use_key(ctx: &mut Context, key: &str, only: bool) -> Result<()> {
+    let path = resolve_key(key)?;
+    if std::env::var_os("SSH_AUTH_SOCK").is_none() {
+        bail!("No ssh-agent is running in this shell; start one with `eval \"$(ssh-agent)\"`");
+    }
+    ctx.request_permission(Capability::ProcessSpawn)?;
+
+    if only {
+        let status = Command::new("ssh-add")
+            .arg("-D")
+            .stdout(Stdio::null())
+            .status()
+            .context("Could not run ssh-add; is OpenSSH installed?")?;
+        if !status.success() {
+            bail!("ssh-add could not remove the agent's keys");
+        }
+    }
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut command = Command::new("ssh-add");
+    command.arg(&path);
+    if let Some(passphrase) = ctx.secrets()?.get(&name)? {
+        if let Some(askpass) = askpass(ctx)? {
+            // ssh-add asks the script, which answers from the environment
+            command
+                .env("SSH_ASKPASS", askpass)
+                .env("SSH_ASKPASS_REQUIRE", "force")
+                .env("DRK_SSH_PASSPHRASE", passphrase)
+                .stdin(Stdio::null());
+        }
+    }
+    let status = command
+        .status()
+        .context("Could not run ssh-add; is OpenSSH installed?")?;
+    if !status.success() {
+        bail!("ssh-add could not add {}", tilde(&path));
+    }
+    println!(
+        "{} {}",
+        style_success(icon_success()),
+        style_success(&format!("{} is in the agent", tilde(&path)))
+    );
+    Ok(())
+}
+
+fn hosts(ctx: &mut Context, dry_run: bool, force: bool) -> Result<()> {
+    let aliases = host_aliases(ctx)?;
+    if aliases.is_empty() {
+        bail!(
+            "There are no aliases in [ssh.hosts]; add one per host, e.g. [ssh.hosts.github-work]"
+        );
+    }
+    let dir = ssh_dir()?;
+    let config = dir.join("config");
+    let current = match std::fs::read_to_string(&config) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let (before, after) = split_block(&current);
+    let updated = format!("{}{}{}", block(&aliases), before, after);
+    if updated == current {
+        println!(
+            "{} {}",
+            style_success(icon_success()),
+            style_success(&format!("{} already has these hosts", tilde(&config)))
+        );
+        return Ok(());
+    }
+
+    println!("{} {}", style_warning("~"), style_primary(&tilde(&config)));
+    for line in diff::render(&current, &updated).lines() {
+        println!("    {}", line);
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    // Hosts the user wrote themselves would be shadowed by ours
+    let ours: Vec<&str> = aliases.iter().map(|a| a.alias.as_str()).collect();
+    let shadowed: Vec<String> = host_patterns(&format!("{}{}", before, after))
+        .into_iter()
+        .filter(|pattern| ours.contains(&pattern.as_str()))
+        .collect();
+    if !shadowed.is_empty() && !force {
+        let question = format!(
+            "{} already defines {}; replace them with [ssh.hosts]?",
+            tilde(&config),
+            shadowed.join(", ")
+        );
+        if !ctx.ui().confirm(&question, false)? {
+            println!(
+                "{}",
+                style_dim(&format!("Left {} as it was", tilde(&config)))
+            );
+            return Ok(());
+        }
+    }
+
+    ctx.request_permission(Capability::FsWrite)?;
+    create_private_dir(&dir)?;
+    let mut txn = Transaction::new().keep_backups(BACKUP_SUFFIX);
+    txn.write(&config, updated);
+    txn.commit()?;
+    println!(
+        "{} {}",
+        style_success(icon_success()),
+        style_success(&format!(
+            "Wrote {} hosts to {}",
+            aliases.len(),
+            tilde(&config)
+        ))
+    );
+    Ok(())
+}
+
+fn host_aliases(ctx: &Context) -> Result<Vec<HostAlias>> {
+    let Some(table) = ctx
+        .config
+        .get("ssh")
+        .and_then(|s| s.get("hosts"))
+        .and_then(|h| h.as_table())
+    else {
+        return Ok(Vec::new());
+    };
+    table
+        .iter()
+        .map(|(alias, entry)| {
+            if alias.chars().any(char::is_whitespace) {
+                bail!("Host alias '{}' can't have spaces", alias);
+            }
+            let text = |key: &str| entry.get(key).and_then(|v| v.as_str()).map(str::to_string);
+            let hostname = text("hostname")
+                .ok_or_else(|| anyhow!("[ssh.hosts.{}] needs a hostname", alias))?;
+            let identity = match (text("identity_file"), text("profile")) {
+                (Some(file), _) => Some(file),
+                (None, Some(profile)) => Some(tilde(&resolve_key(&profile)?)),
+                (None, None) => None,
+            };
+            Ok(HostAlias {
+                alias: alias.clone(),
+                hostname,
+                user: text("user"),
+                port: entry.get("port").and_then(|v| v.as_integer()),
+                identity,
+            })
+        })
+        .collect()
+}
+
+/// The marked block of `aliases`, followed by a blank line.
+fn block(aliases: &[HostAlias]) -> String {
+    let mut out = format!("{}\n", BLOCK_START);
+    for (i, host) in aliases.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!("Host {}\n", host.alias));
+        out.push_str(&format!("    HostName {}\n", host.hostname));
+        if let Some(user) = &host.user {
+            out.push_str(&format!("    User {}\n", user));
+        }
+        if let Some(port) = host.port {
+            out.push_str(&format!("    Port {}\n", port));
+        }
+        if let Some(identity) = &host.identity {
+            out.push_str(&format!("    IdentityFile {}\n", identity));
+            out.push_str("    IdentitiesOnly yes\n");
+        }
+    }
+    out.push_str(&format!("{}\n\n", BLOCK_END));
+    out
+}
+
+/// The text before and after the marked block, which is left out along with
+/// the blank line following it. All of it is "before" if there's no block.
+fn split_block(text: &str) -> (&str, &str) {
+    let Some(start) = text.find(BLOCK_START) else {
+        return (text, "");
+    };
+    let Some(end) = text[start..]
+        .find(BLOCK_END)
+        .map(|i| start + i + BLOCK_END.len())
+    else {
+        return (text, "");
+    };
+    let rest = &text[end..];
+    let rest = rest.strip_prefix('\n').unwrap_or(rest);
+    (&text[..start], rest.strip_prefix('\n').unwrap_or(rest))
+}
+
+/// The patterns of every `Host` line in an ssh config.
+fn host_patterns(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            words
+                .next()
+                .is_some_and(|word| word.eq_ignore_ascii_case("host"))
+                .then_some(words)
+        })
+        .flatten()
+        .map(str::to_string)
+        .collect()
+}
+
+/// The key named by `key`: a path, a file in ~/.ssh, or the
+/// `id_<type>_<profile>` key of a profile.
+fn resolve_key(key: &str) -> Result<PathBuf> {
+    let path = expand_home(key);
+    if path.is_file() {
+        return Ok(path);
+    }
+    let dir = ssh_dir()?;
+    if dir.join(key).is_file() {
+        return Ok(dir.join(key));
+    }
+    KEY_TYPES
+        .iter()
+        .map(|key_type| dir.join(format!("id_{}_{}", key_type, key)))
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            anyhow!(
+                "No key '{}'; create it with `drk ssh keygen --profile {}`",
+                key,
+                key
+            )
+        })
+}
+
+/// A script that prints `$DRK_SSH_PASSPHRASE`, for `SSH_ASKPASS`. None
+/// where ssh-add and ssh-keygen can't run one.
+#[cfg(unix)]
+fn askpass(ctx: &Context) -> Result<Option<PathBuf>> {
+    use std::os::unix::fs::PermissionsExt;
+    let script = ctx.plugin_data_dir()?.join("askpass");
+    if !script.exists() {
+        std::fs::write(
+            &script,
+            "#!/bin/sh\nprintf '%s\\n' \"$DRK_SSH_PASSPHRASE\"\n",
+        )?;
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(Some(script))
+}
+
+#[cfg(not(unix))]
+fn askpass(_: &Context) -> Result<Option<PathBuf>> {
+    Ok(None)
+}
+
+fn create_private_dir(dir: &Path) -> Result<()> {
+    if dir.exists() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+fn ssh_dir() -> Result<PathBuf> {
+    home_dir()
+        .map(|home| home.join(".ssh"))
+        .ok_or_else(|| anyhow!("Could not tell where the home directory is"))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// `path` with the home directory shown as `~`
+fn tilde(path: &Path) -> String {
+    match home_dir().and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+fn constructor() -> SshPlugin {
+    SshPlugin
+}
+
+declare_plugin!(SshPlugin, constructor);
@@ -0,0 +1,500 @@
+//! `drk timer`: countdowns and pomodoros that notify when they end.
+//!
+//! ```text
+//! $ drk timer start 25m --label focus
+//! $ drk timer pomodoro --rounds 4
+//! $ drk timer list
+//! ```
+//!
+//! drk has no daemon to schedule work on, so each timer gets a `drk timer
+//! wait <id>` process of its own, started in the background, that sleeps
+//! through it and shows a desktop notification with `ctx.notify()` as each
+//! phase ends. Timers are files in the plugin's data directory: that's how
+//! `list` sees timers other processes run, and deleting one is how `cancel`
+//! tells its waiter to stop.
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use drk_api::time;
+use drk_api::toml::{self, Table};
+use drk_api::{
+    declare_plugin, icon_info, icon_success, pad, serde_json::json, style_dim, style_primary,
+    style_success, Align, ArgType, Capability, CommandArg, CommandMatches, CommandOutput, Context,
+    EventKind, Plugin, PluginCommand, PluginMetadata, SystemEvent,
+};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The directory in the plugin's data dir holding one `<id>.toml` per timer
+const TIMERS_DIR: &str = "timers";
+
+/// How often a waiter checks whether its timer was cancelled
+const POLL: Duration = Duration::from_millis(250);
+
+struct TimerPlugin;
+
+/// A running timer: one phase for `start`, alternating focus and breaks
+/// for `pomodoro`.
+struct Timer {
+    id: u32,
+    label: String,
+    /// Unix seconds
+    started: u64,
+    phases: Vec<Phase>,
+}
+
+struct Phase {
+    label: String,
+    seconds: u64,
+}
+
+impl Plugin for TimerPlugin {
+    fn metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: "timer".to_string(),
+            version: "0.1.0".to_string(),
+            author: "dark1zinn".to_string(),
+            description: "Countdowns and pomodoros with desktop notifications".to_string(),
+            essential: false,
+        }
+    }
+
+    fn get_commands(&self) -> Vec<PluginCommand> {
+        let arg = |name: &str, description: &str, arg_type| CommandArg {
+            name: name.to_string(),
+            description: description.to_string(),
+            arg_type,
+            ..Default::default()
+        };
+        let with_default = |arg: CommandArg, default: &str| CommandArg {
+            default: Some(default.to_string()),
+            ..arg
+        };
+        let id = CommandArg {
+            required: true,
+            ..arg(
+                "id",
+                "The timer's number, from `drk timer list`",
+                ArgType::Positional,
+            )
+        };
+        let nested = |command: &str, description: &str, args: Vec<CommandArg>| PluginCommand {
+            name: command.to_string(),
+            description: description.to_string(),
+            args,
+            ..Default::default()
+        };
+        vec![PluginCommand {
+            name: "timer".to_string(),
+            description: "Countdowns that notify when they end".to_string(),
+            subcommands: vec![
+                nested(
+                    "start",
+                    "Start a timer in the background",
+                    vec![
+                        CommandArg {
+                            required: true,
+                            ..arg(
+                                "duration",
+                                "How long, e.g. 25m, 1h30m or 90s",
+                                ArgType::Positional,
+                            )
+                        },
+                        with_default(
+                            arg("label", "What the timer is for", ArgType::String),
+                            "timer",
+                        ),
+                    ],
+                ),
+                nested(
+                    "pomodoro",
+                    "Start rounds of focus and breaks in the background",
+                    vec![
+                        with_default(
+                            arg("work", "How long each focus round is", ArgType::Duration),
+                            "25m",
+                        ),
+                        with_default(
+                            arg("break", "How long each break is", ArgType::Duration),
+                            "5m",
+                        ),
+                        with_default(
+                            arg("rounds", "How many focus rounds", ArgType::Integer),
+                            "4",
+                        ),
+                    ],
+                ),
+                nested("list", "Show the running timers", Vec::new()),
+                nested("cancel", "Stop a running timer", vec![id.clone()]),
+                nested(
+                    "wait",
+                    "Wait for a timer in this terminal, notifying as it ends",
+                    vec![id],
+                ),
+            ],
+            ..Default::default()
+        }]
+    }
+
+    // Only its own commands
+    fn subscriptions(&self) -> Vec<EventKind> {
+        Vec::new()
+    }
+
+    fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> Result<()> {
+        if let SystemEvent::ExecuteCommand {
+            plugin_name,
+            matches,
+        } = event
+        {
+            if plugin_name == "timer" {
+                self.execute_command(matches, ctx)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TimerPlugin {
+    fn execute_command(&self, matches: &CommandMatches, ctx: &mut Context) -> Result<()> {
+        let arg = |name: &str| {
+            matches
+                .args
+                .get(name)
+                .map(String::as_str)
+                .unwrap_or_default()
+        };
+        match matches.subcommands().first().map(String::as_str) {
+            Some("start") => {
+                let seconds = parse_duration(arg("duration"))?;
+                let label = arg("label");
+                let phases = vec![Phase {
+                    label: label.to_string(),
+                    seconds,
+                }];
+                start(ctx, label, phases)
+            }
+            Some("pomodoro") => {
+                // Both have defaults, so they're always there
+                let work = matches.duration("work")?.unwrap_or_default().as_secs();
+                let rest = matches.duration("break")?.unwrap_or_default().as_secs();
+                if work == 0 {
+                    bail!("A focus round needs a length, like --work 25m");
+                }
+                let rounds: u64 = arg("rounds").parse().unwrap_or(0);
+                if rounds == 0 {
+                    bail!("A pomodoro needs at least one round");
+                }
+                let mut phases = Vec::new();
+                for round in 1..=rounds {
+                    phases.push(Phase {
+                        label: format!("focus {}/{}", round, rounds),
+                        seconds: work,
+                    });
+                    // No break after the last round
+                    if round < rounds {
+                        phases.push(Phase {
+                            label: "break".to_string(),
+                            seconds: rest,
+                        });
+                    }
+                }
+                start(ctx, "pomodoro", phases)
+            }
+            Some("list") => list(ctx),
+            Some("cancel") => {
+                let timer = Timer::load(ctx.plugin_data_dir()?, parse_id(arg("id"))?)?;
+                std::fs::remove_file(timer.path(ctx.plugin_data_dir()?))?;
+                println!(
+                    "{} {}",
+                    style_success(icon_success()),
+                    style_success(&format!("Cancelled #{} {}", timer.id, timer.label))
+                );
+                Ok(())
+            }
+            Some("wait") => wait(ctx, parse_id(arg("id"))?),
+            _ => {
+                println!(
+                    "{} {}",
+                    style_primary(icon_info()),
+                    style_primary("Try `drk timer start 25m --label focus`")
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Saves a timer and starts the background `drk timer wait` that sees it
+/// through.
+fn start(ctx: &mut Context, label: &str, phases: Vec<Phase>) -> Result<()> {
+    ctx.request_permission(Capability::ProcessSpawn)?;
+    let dir = ctx.plugin_data_dir()?;
+    let timers = Timer::all(dir)?;
+    let timer = Timer {
+        id: timers.iter().map(|t| t.id).max().unwrap_or(0) + 1,
+        label: label.to_string(),
+        started: now(),
+        phases,
+    };
+    timer.save(dir)?;
+
+    let mut waiter = Command::new(std::env::current_exe()?);
+    waiter
+        .args(["timer", "wait", &timer.id.to_string()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // Its own process group, so Ctrl-C in this shell doesn't stop it
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut waiter, 0);
+    if let Err(e) = waiter.spawn() {
+        std::fs::remove_file(timer.path(dir))?;
+        return Err(anyhow!(e).context("Could not start the timer in the background"));
+    }
+
+    let total = timer.phases.iter().map(|p| p.seconds).sum();
+    println!(
+        "{} {} {}",
+        style_success(icon_success()),
+        style_success(&format!(
+            "Started #{} {} for {}",
+            timer.id,
+            timer.label,
+            format_duration(total)
+        )),
+        style_dim(&format!("(`drk timer cancel {}` stops it)", timer.id))
+    );
+    Ok(())
+}
+
+fn list(ctx: &mut Context) -> Result<()> {
+    let now = now();
+    let timers = Timer::all(ctx.plugin_data_dir()?)?;
+    let mut rows = Vec::new();
+    let mut data = Vec::new();
+    for timer in &timers {
+        let Some((phase, left)) = timer.current(now) else {
+            // Its waiter is about to remove it
+            continue;
+        };
+        let doing = &timer.phases[phase].label;
+        rows.push((
+            format!("#{}", timer.id),
+            doing.clone(),
+            format_duration(left),
+        ));
+        data.push(json!({
+            "id": timer.id,
+            "label": timer.label,
+            "phase": doing,
+            "remaining_seconds": left,
+        }));
+    }
+    let text = if rows.is_empty() {
+        style_dim("No timers running; start one with `drk timer start 25m`").to_string()
+    } else {
+        let id_width = rows.iter().map(|r| r.0.len()).max().unwrap_or(0);
+        let label_width = rows.iter().map(|r| r.1.len()).max().unwrap_or(0);
+        rows.iter()
+            .map(|(id, label, left)| {
+                format!(
+                    "{}  {}  {}",
+                    style_dim(&pad(id, id_width, Align::Right)),
+                    style_primary(&pad(label, label_width, Align::Left)),
+                    style_dim(&format!("{} left", left))
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    ctx.output(CommandOutput::new(data).with_text(text));
+    Ok(())
+}
+
+/// Sleeps through the timer `id`, notifying as each phase ends. Returns
+/// early when the timer is cancelled, here with Ctrl-C or elsewhere with
+/// `drk timer cancel`.
+fn wait(ctx: &mut Context, id: u32) -> Result<()> {
+    let dir = ctx.plugin_data_dir()?.to_path_buf();
+    let timer = Timer::load(&dir, id)?;
+    let path = timer.path(&dir);
+    let mut end = timer.started;
+    for (i, phase) in timer.phases.iter().enumerate() {
+        let start = end;
+        end += phase.seconds;
+        if end <= now() {
+            // Over before the wait began, e.g. after a sleeping laptop woke
+            continue;
+        }
+        let bar = ctx.progress_bar(phase.seconds, &phase.label);
+        while now() < end {
+            if ctx.cancelled() || !path.exists() {
+                bar.clear();
+                return Ok(());
+            }
+            bar.set_position(now().saturating_sub(start));
+            std::thread::sleep(POLL);
+        }
+        let message = match timer.phases.get(i + 1) {
+            Some(next) => format!(
+                "{} is over; {} for {}",
+                phase.label,
+                next.label,
+                format_duration(next.seconds)
+            ),
+            None => format!("{} is done", phase.label),
+        };
+        bar.finish(&message);
+        ctx.notify(&format!("drk timer #{}", timer.id), &message)?;
+    }
+    // Already gone if it was cancelled at the last moment
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+impl Timer {
+    fn path(&self, dir: &Path) -> PathBuf {
+        dir.join(TIMERS_DIR).join(format!("{}.toml", self.id))
+    }
+
+    /// The timers whose files are in `dir`, by id.
+    fn all(dir: &Path) -> Result<Vec<Timer>> {
+        let entries = match std::fs::read_dir(dir.join(TIMERS_DIR)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut timers = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let id = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse().ok());
+            // Skips stray files and timers cancelled while we looked
+            if let Some(Ok(timer)) = id.map(|id| Timer::load(dir, id)) {
+                timers.push(timer);
+            }
+        }
+        timers.sort_by_key(|t| t.id);
+        Ok(timers)
+    }
+
+    fn load(dir: &Path, id: u32) -> Result<Timer> {
+        let path = dir.join(TIMERS_DIR).join(format!("{}.toml", id));
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                bail!("No timer #{}; `drk timer list` shows them", id)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let table: Table = text
+            .parse()
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        let damaged = || anyhow!("Timer file {} is damaged", path.display());
+        let phases = table
+            .get("phases")
+            .and_then(|v| v.as_array())
+            .ok_or_else(damaged)?
+            .iter()
+            .map(|phase| {
+                Some(Phase {
+                    label: phase.get("label")?.as_str()?.to_string(),
+                    seconds: u64::try_from(phase.get("seconds")?.as_integer()?).ok()?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(damaged)?;
+        Ok(Timer {
+            id,
+            label: table
+                .get("label")
+                .and_then(|v| v.as_str())
+                .ok_or_else(damaged)?
+                .to_string(),
+            started: table
+                .get("started")
+                .and_then(|v| v.as_integer())
+                .and_then(|v| u64::try_from(v).ok())
+                .ok_or_else(damaged)?,
+            phases,
+        })
+    }
+
+    fn save(&self, dir: &Path) -> Result<()> {
+        let mut table = Table::new();
+        table.insert("label".to_string(), self.label.as_str().into());
+        table.insert("started".to_string(), (self.started as i64).into());
+        let phases: Vec<toml::Value> = self
+            .phases
+            .iter()
+            .map(|phase| {
+                let mut entry = Table::new();
+                entry.insert("label".to_string(), phase.label.as_str().into());
+                entry.insert("seconds".to_string(), (phase.seconds as i64).into());
+                entry.into()
+            })
+            .collect();
+        table.insert("phases".to_string(), phases.into());
+        std::fs::create_dir_all(dir.join(TIMERS_DIR))?;
+        std::fs::write(self.path(dir), toml::to_string(&table)?)?;
+        Ok(())
+    }
+
+    /// The phase running at `now` and the seconds it has left, or `None`
+    /// once the timer is over.
+    fn current(&self, now: u64) -> Option<(usize, u64)> {
+        let mut end = self.started;
+        for (i, phase) in self.phases.iter().enumerate() {
+            end += phase.seconds;
+            if now < end {
+                return Some((i, end - now));
+            }
+        }
+        None
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn parse_id(text: &str) -> Result<u32> {
+    text.trim_start_matches('#')
+        .parse()
+        .map_err(|_| anyhow!("Expected a timer number like 3, got '{}'", text))
+}
+
+/// Seconds in `25m`, `1h30m`, `90s` or `1h 5m`, read the way drk reads
+/// `Duration` arguments: a number needs a unit.
+fn parse_duration(text: &str) -> Result<u64> {
+    let seconds = time::parse_duration(text)?.as_secs();
+    if seconds == 0 {
+        bail!("A timer needs a length, like 25m");
+    }
+    Ok(seconds)
+}
+
+/// `1h 05m`, `4m 30s` or `45s`
+fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h {:02}m", hours, minutes)
+    } else if minutes > 0 && seconds > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+fn constructor() -> TimerPlugin {
+    TimerPlugin
+}
+
+declare_plugin!(TimerPlugin, constructor);
@@ -0,0 +1,447 @@
+//! `drk weather`: the current weather and a short forecast, from wttr.in.
+//!
+//! A small example of a plugin that goes to the network the way drk expects:
+//!
+//! - requests go through the shared [`drk_api::http::Client`], so they send
+//!   drk's user agent, replay from a cassette in tests and respect
+//!   `drk --offline`;
+//! - the command is `cacheable`, so asking again within `[cache]
+//!   max_age_secs` prints the last answer without a request;
+//! - each forecast is also saved in the plugin's data directory, and shown,
+//!   marked as stale, when drk is offline or the request fails;
+//! - the result goes through `ctx.output`, so `--output json` gets the data.
+//!
+//! ```text
+//! $ drk weather Lisbon --days 3
+//! $ drk --offline weather Lisbon
+//! ```
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use drk_api::http::{self, Client};
+use drk_api::serde_json::{self, json};
+use drk_api::{
+    declare_plugin, style_dim, style_primary, style_warning, ArgType, CacheInput, Capability,
+    CommandArg, CommandMatches, CommandOutput, ConfigKey, ConfigType, Context, EventKind, Plugin,
+    PluginCommand, PluginMetadata, SystemEvent,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// wttr.in answers `/<place>?format=j1` with JSON; an empty place means
+/// wherever the request comes from
+const API: &str = "https://wttr.in";
+
+/// wttr.in forecasts this many days, today included
+const MAX_DAYS: i64 = 3;
+
+/// The directory in the plugin's data dir the last forecast per place is kept in
+const SAVED_DIR: &str = "forecasts";
+
+struct WeatherPlugin;
+
+/// The parts of wttr.in's `format=j1` answer shown here. It sends every
+/// number as a string.
+#[derive(Debug, Serialize, Deserialize)]
+struct Report {
+    current_condition: Vec<Current>,
+    nearest_area: Vec<Area>,
+    weather: Vec<Day>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Current {
+    #[serde(rename = "temp_C")]
+    temp_c: String,
+    #[serde(rename = "temp_F")]
+    temp_f: String,
+    #[serde(rename = "FeelsLikeC")]
+    feels_like_c: String,
+    #[serde(rename = "FeelsLikeF")]
+    feels_like_f: String,
+    humidity: String,
+    windspeed_kmph: String,
+    windspeed_miles: String,
+    weather_desc: Vec<Text>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Area {
+    area_name: Vec<Text>,
+    country: Vec<Text>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Day {
+    date: String,
+    #[serde(rename = "mintempC")]
+    min_c: String,
+    #[serde(rename = "maxtempC")]
+    max_c: String,
+    #[serde(rename = "mintempF")]
+    min_f: String,
+    #[serde(rename = "maxtempF")]
+    max_f: String,
+    /// Every three hours from midnight
+    hourly: Vec<Hour>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Hour {
+    weather_desc: Vec<Text>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Text {
+    value: String,
+}
+
+/// A report as saved for offline use
+#[derive(Debug, Serialize, Deserialize)]
+struct Saved {
+    /// Seconds since the Unix epoch
+    fetched_at: u64,
+    report: Report,
+}
+
+impl Plugin for WeatherPlugin {
+    fn metadata(&self) -> PluginMetadata {
+        PluginMetadata {
+            name: "weather".to_string(),
+            version: "0.1.0".to_string(),
+            author: "dark1zinn".to_string(),
+            description: "The weather and a short forecast, from wttr.in".to_string(),
+            essential: false,
+        }
+    }
+
+    fn get_commands(&self) -> Vec<PluginCommand> {
+        vec![PluginCommand {
+            name: "weather".to_string(),
+            description: "Show the weather and a short forecast".to_string(),
+            args: vec![
+                CommandArg {
+                    name: "place".to_string(),
+                    description: "A city, airport code or landmark; [weather] place, or where you are, if unset".to_string(),
+                    arg_type: ArgType::Positional,
+                    env: Some("DRK_WEATHER_PLACE".to_string()),
+                    ..Default::default()
+                },
+                CommandArg {
+                    name: "days".to_string(),
+                    description: "How many days of forecast, 1 to 3".to_string(),
+                    arg_type: ArgType::Integer,
+                    default: Some("1".to_string()),
+                    ..Default::default()
+                },
+            ],
+            cacheable: true,
+            // An offline answer may be old; it shouldn't stand in for a fresh one
+            cache_inputs: vec![CacheInput::Env(http::OFFLINE_ENV.to_string())],
+            ..Default::default()
+        }]
+    }
+
+    fn config_schema(&self) -> Vec<ConfigKey> {
+        vec![
+            ConfigKey {
+                name: "place".to_string(),
+                description: "The place `drk weather` shows when none is given".to_string(),
+                value_type: ConfigType::String,
+                default: None,
+            },
+            ConfigKey {
+                name: "units".to_string(),
+                description: "\"metric\" or \"imperial\"".to_string(),
+                value_type: ConfigType::String,
+                default: Some("metric".into()),
+            },
+        ]
+    }
+
+    // Only its own commands
+    fn subscriptions(&self) -> Vec<EventKind> {
+        Vec::new()
+    }
+
+    fn handle_event(&mut self, event: &SystemEvent, ctx: &mut Context) -> Result<()> {
+        if let SystemEvent::ExecuteCommand {
+            plugin_name,
+            matches,
+        } = event
+        {
+            if plugin_name == "weather" {
+                self.execute_command(matches, ctx)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl WeatherPlugin {
+    fn execute_command(&self, matches: &CommandMatches, ctx: &mut Context) -> Result<()> {
+        let setting = |key: &str| {
+            ctx.config
+                .get("weather")
+                .and_then(|cfg| cfg.get(key))
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        let place = matches
+            .args
+            .get("place")
+            .cloned()
+            .or_else(|| setting("place"))
+            .unwrap_or_default();
+        let imperial = setting("units").is_some_and(|units| units == "imperial");
+        let days: i64 = matches.args["days"].parse()?;
+        if !(1..=MAX_DAYS).contains(&days) {
+            bail!("--days must be 1 to {}, not {}", MAX_DAYS, days);
+        }
+
+        let (saved, stale) = report(ctx, &place)?;
+        let output = render(&saved, days as usize, imperial, stale.as_deref())?;
+        ctx.output(output);
+        Ok(())
+    }
+}
+
+/// The report for `place`, fetched now or, offline or when the request
+/// fails, as saved last time along with why it's stale.
+fn report(ctx: &mut Context, place: &str) -> Result<(Saved, Option<String>)> {
+    let path = ctx
+        .plugin_data_dir()?
+        .join(SAVED_DIR)
+        .join(format!("{}.json", file_name(place)));
+
+    let stale = if http::is_offline() {
+        "offline".to_string()
+    } else {
+        ctx.request_permission(Capability::Network)?;
+        let spinner = ctx.spinner(&format!("GET wttr.in/{}", place));
+        let fetched = fetch(place);
+        spinner.clear();
+        match fetched {
+            Ok(report) => {
+                let saved = Saved {
+                    fetched_at: now(),
+                    report,
+                };
+                // Losing the offline copy isn't worth failing the command over
+                let _ = save(&path, &saved);
+                return Ok((saved, None));
+            }
+            Err(_) if path.exists() => "wttr.in couldn't be reached".to_string(),
+            Err(e) => return Err(e),
+        }
+    };
+
+    let text = std::fs::read_to_string(&path).map_err(|_| {
+        anyhow!(
+            "There's no saved forecast for {} to show offline",
+            describe(place)
+        )
+    })?;
+    let saved = serde_json::from_str(&text)
+        .with_context(|| format!("The saved forecast {} is damaged", path.display()))?;
+    Ok((saved, Some(stale)))
+}
+
+fn fetch(place: &str) -> Result<Report> {
+    let url = format!("{}/{}?format=j1", API, encode(place));
+    let response = Client::new()?.get(&url).send()?.error_for_status()?;
+    response
+        .json()
+        .with_context(|| format!("wttr.in sent an unexpected answer for {}", describe(place)))
+}
+
+fn save(path: &PathBuf, saved: &Saved) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_string(saved)?)?;
+    Ok(())
+}
+
+fn render(
+    saved: &Saved,
+    days: usize,
+    imperial: bool,
+    stale: Option<&str>,
+) -> Result<CommandOutput> {
+    let report = &saved.report;
+    let current = report
+        .current_condition
+        .first()
+        .ok_or_else(|| anyhow!("wttr.in sent no current conditions"))?;
+    let place = report
+        .nearest_area
+        .first()
+        .map(|area| format!("{}, {}", first(&area.area_name), first(&area.country)))
+        .unwrap_or_default();
+    let (temp, feels_like, wind) = if imperial {
+        (
+            &current.temp_f,
+            &current.feels_like_f,
+            &current.windspeed_miles,
+        )
+    } else {
+        (
+            &current.temp_c,
+            &current.feels_like_c,
+            &current.windspeed_kmph,
+        )
+    };
+    let (degrees, speed) = if imperial {
+        ("°F", "mph")
+    } else {
+        ("°C", "km/h")
+    };
+
+    let mut lines = vec![style_primary(&place).to_string()];
+    if let Some(reason) = stale {
+        lines.push(
+            style_warning(&format!(
+                "  From {} ({})",
+                ago(now().saturating_sub(saved.fetched_at)),
+                reason
+            ))
+            .to_string(),
+        );
+    }
+    lines.push(format!(
+        "  {}, {}{} (feels like {}{})",
+        first(&current.weather_desc),
+        temp,
+        degrees,
+        feels_like,
+        degrees
+    ));
+    lines.push(
+        style_dim(&format!(
+            "  Humidity {}%, wind {} {}",
+            current.humidity, wind, speed
+        ))
+        .to_string(),
+    );
+
+    let mut forecast = Vec::new();
+    for day in report.weather.iter().take(days) {
+        let (min, max) = if imperial {
+            (&day.min_f, &day.max_f)
+        } else {
+            (&day.min_c, &day.max_c)
+        };
+        // Midday stands for the day
+        let description = day
+            .hourly
+            .get(4)
+            .or(day.hourly.first())
+            .map(|hour| first(&hour.weather_desc))
+            .unwrap_or_default();
+        lines.push(format!(
+            "  {}  {}-{}{}  {}",
+            style_dim(&day.date),
+            min,
+            max,
+            degrees,
+            description
+        ));
+        forecast.push(json!({
+            "date": day.date,
+            "min": number(min),
+            "max": number(max),
+            "description": description,
+        }));
+    }
+
+    let data = json!({
+        "place": place,
+        "units": if imperial { "imperial" } else { "metric" },
+        "fetched_at": saved.fetched_at,
+        "stale": stale,
+        "current": {
+            "description": first(&current.weather_desc),
+            "temperature": number(temp),
+            "feels_like": number(feels_like),
+            "humidity": number(&current.humidity),
+            "wind": number(wind),
+        },
+        "forecast": forecast,
+    });
+    Ok(CommandOutput::new(data).with_text(lines.join("\n")))
+}
+
+fn first(texts: &[Text]) -> &str {
+    texts.first().map_or("", |text| text.value.trim())
+}
+
+/// wttr.in's numbers as JSON numbers, for scripts reading `--output json`
+fn number(text: &str) -> serde_json::Value {
+    text.parse::<i64>().map_or_else(|_| text.into(), Into::into)
+}
+
+/// `place` for messages
+fn describe(place: &str) -> String {
+    if place.is_empty() {
+        "where you are".to_string()
+    } else {
+        format!("'{}'", place)
+    }
+}
+
+/// `place` in a URL path: spaces as `+`, anything unusual percent-encoded
+fn encode(place: &str) -> String {
+    let mut encoded = String::new();
+    for byte in place.bytes() {
+        match byte {
+            b' ' => encoded.push('+'),
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// The file `place`'s forecast is saved in, without the extension
+fn file_name(place: &str) -> String {
+    let name: String = place
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    if name.is_empty() {
+        "here".to_string()
+    } else {
+        name
+    }
+}
+
+/// `seconds` in the past, roughly
+fn ago(seconds: u64) -> String {
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn constructor() -> WeatherPlugin {
+    WeatherPlugin
+}
+
+declare_plugin!(WeatherPlugin, constructor);